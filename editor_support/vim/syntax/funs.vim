@@ -52,6 +52,10 @@ hi def link funsComment Comment
 syn match funsVariable /\v\w+/
 hi def link funsVariable Identifier
 
+" Attributes, e.g. `@test`
+syn match funsAttribute /@\w\+/
+hi def link funsAttribute PreProc
+
 " Numbers
 syn match funsNumber "\v<\d+>"
 syn match funsNumber "\v<\d+\.\d+>"
@@ -0,0 +1,6 @@
+# `#|` starts a block comment that ends at the matching `|#`
+#| It can span
+   several lines #| and nest |#
+|#
+x: int = 1
+#| @error: inside a block comment, not an expectation |#
@@ -0,0 +1,10 @@
+# A comment runs to the end of the line
+
+# Blank lines are ignored
+x: int = 1
+# A comment can follow a declaration
+y: int = x + 1 # or end the line of a statement
+twice: (int) -> int = (n) ->
+    m: int = n * 2 # in a block too
+    m
+;
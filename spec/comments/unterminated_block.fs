@@ -0,0 +1,5 @@
+# A block comment left open runs to the end of the file
+# @error: unterminated block comment
+x: int = 1
+#| opened #| and nested |#
+y: int = 2
@@ -0,0 +1,8 @@
+# `@test` and `@bench` mark the top-level functions run by `funs test` and `funs bench`
+square: (int) -> int = (x) -> x * x ;
+
+@test
+squares: () -> bool = () -> square 3 == 9 ;
+
+@bench @test
+square_twice: () -> int = () -> square (square 12) ;
@@ -0,0 +1,4 @@
+# A record names each of its fields once
+# @error: the field `x` appears more than once
+data Point = { x: int, y: int }
+p: Point = { x = 1, y = 2, x = 3 }
@@ -0,0 +1,10 @@
+# A function is a declaration with a function type, its value lists the parameters
+# and the body, an expression or an indented block, closed by `;`
+# @warning: `?todo` stands for a value of type `(int) -> int`
+id: (int) -> int = (x) -> x ;
+answer: () -> int = () -> 42 ;
+first: (int, str) -> int = (a, b) ->
+  result: int = a
+  result
+;
+later: (int) -> int = ?todo
@@ -0,0 +1,3 @@
+# A constructor starts with an uppercase letter, patterns tell it from a name this way
+# @error: the constructor `green` must start with an uppercase letter
+data Color = Red | green
@@ -0,0 +1,4 @@
+# Only top-level functions have attributes
+# @error: attributes only apply to top-level functions
+@test
+limit: int = 10
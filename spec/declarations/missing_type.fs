@@ -0,0 +1,4 @@
+# A type is required after `:`
+# @exit: 1
+# @error: Expected a type, found `=`
+x: = 1
@@ -0,0 +1,3 @@
+# A declaration always has a value
+# @error: Expected `=`, found end of line
+x: int
@@ -0,0 +1,2 @@
+# The end of the file ends the last line
+x: int = 1
\ No newline at end of file
@@ -0,0 +1,9 @@
+# A record type lists its fields, separated by `,` or new lines
+data Point = { x: int, y: int }
+data Segment = {
+  from: Point
+  to: Point
+}
+origin: Point = { x = 0, y = 0 }
+s: Segment = { from = origin, to = { x = 3, y = 4 } }
+dx: int = s.to.x - s.from.x
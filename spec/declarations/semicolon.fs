@@ -0,0 +1,3 @@
+# `;` ends a declaration, so several can share a line
+x: int = 1; y: int = 2
+z: int = 3;
@@ -0,0 +1,10 @@
+# Recursive calls that are not tail calls are warnings, and errors in a `@tailrec` function
+# @warning: this recursive call is not a tail call and may overflow for large inputs
+# @error: `sum` is marked `@tailrec`, but this recursive call is not a tail call
+fact: (int) -> int = (n) -> if n < 2 then 1 else n * fact (n - 1) ;
+
+@tailrec
+count: (int, int) -> int = (n, acc) -> if n == 0 then acc else count (n - 1) (acc + 1) ;
+
+@tailrec
+sum: (int) -> int = (n) -> if n == 0 then 0 else n + sum (n - 1) ;
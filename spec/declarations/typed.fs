@@ -0,0 +1,5 @@
+# A declaration gives a name, a type and a value
+answer: int = 42
+ratio: float = 0.5
+done: bool = false
+greeting: str = "hello"
@@ -0,0 +1,8 @@
+# A name must be declared before it is used, a function can be used anywhere
+# @exit: 1
+# @error: `y` is not defined
+# @error: `a` is declared more than once, first at spec/declarations/undefined.fs:8:27, again at spec/declarations/undefined.fs:8:30
+x: int = twice y
+y: int = twice 1
+twice: (int) -> int = (n) -> n * 2 ;
+add: (int, int) -> int = (a, a) -> a + a ;
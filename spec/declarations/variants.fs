@@ -0,0 +1,17 @@
+# A variant type lists its cases, each a constructor with the types of its fields
+data Color = Red | Green | Rgb int int int
+data Shape =
+| Dot
+# a case can have a comment before it
+| Circle float
+| Rect (float, float)
+;
+c: Color = Rgb 0 0 255
+colors: [Color] = [Red, Green, c]
+area: (Shape) -> float = (s) ->
+  match s
+  | Dot => 0.0
+  | Circle r => 3.14 * r * r
+  | Rect (w, h) => w * h
+  ;
+;
@@ -0,0 +1,5 @@
+# `True` and `False` are still accepted, with a warning
+# @warning: the boolean `True` is deprecated, use `true` instead
+# @warning: the boolean `False` is deprecated, use `false` instead
+yes: bool = True
+no: bool = False
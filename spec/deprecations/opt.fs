@@ -0,0 +1,6 @@
+# `Opt` is still accepted, with a warning
+# @warning: the type `Opt` is deprecated, use `option` instead
+# @warning: `?todo` stands for a value of type `Opt<int>`
+# @exit: 3
+# @error: reached `?todo`, this code is not written yet
+x: Opt<int> = ?todo
@@ -0,0 +1,7 @@
+# `(expr : type)` states the type of an expression, the parentheses are required
+# @warning: `?todo` stands for a value of type `option<int>`
+# @exit: 3
+# @error: reached `?todo`, this code is not written yet
+x: float = (1.0 : float)
+y: option<int> = (?todo : option<int>)
+z: int = ((1 : int))
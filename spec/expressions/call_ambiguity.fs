@@ -0,0 +1,12 @@
+# A `-` touching its operand, after a space, is the sign of an argument, otherwise it
+# is a subtraction, here from a function
+# @exit: 1
+# @error: `-` can not be applied to `(int) -> int` and `int`
+# @error: `-` can not be applied to `(int) -> int` and `int`
+abs: (int) -> int = (x) -> if x < 0 then -x else x ;
+a: int = abs -3
+b: int = abs - 3
+c: int = abs-3
+# A new line ends a call, so these are two statements
+abs a
+-b
@@ -0,0 +1,10 @@
+# A function is called by writing its arguments after it, on the same line, and a
+# function without parameters by giving it `()`
+# @stdout: done
+add: (int, int) -> int = (a, b) -> a + b ;
+square: (int) -> int = (x) -> x * x ;
+answer: () -> int = () -> 42 ;
+x: int = add 1 2
+y: int = square (add x 1) + 1
+z: int = answer () + 1
+print "done"
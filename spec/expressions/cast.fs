@@ -0,0 +1,4 @@
+# `expr as type` converts a number to another numeric type
+x: float = 1 as float
+y: int = 2.5 as int
+z: int = 1 as float as int
@@ -0,0 +1,4 @@
+# `if` is an expression, its `else` branch extends as far as possible
+x: int = if 1 < 2 then 10 else 20
+y: int = 1 + if x > 5 then x else 0
+z: str = if x == 10 then "ten" else if x == 20 then "twenty" else "other"
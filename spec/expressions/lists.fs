@@ -0,0 +1,8 @@
+# `:` puts an element in front of a list and associates to the right, `++` joins lists
+xs: [int] = [1, 2, 3]
+ys: [int] = 0 : xs ++ [4, 5]
+zs: [int] = -1 : 0 : []
+nested: [[int]] = [xs, [], [6]]
+# Inside parentheses a `:` before a type name is an ascription
+typed: [int] = (ys : [int])
+consed: [int] = (7 : xs)
@@ -0,0 +1,9 @@
+# `match` tries its arms in order, each arm is `| pattern => expr`
+x: int = 2
+y: str = match x
+| 0 => "zero"
+| -1 => "minus one"
+| 2 => "two"
+| _ => "other"
+;
+z: int = match x | n => n + 1 ;
@@ -0,0 +1,4 @@
+# A pattern is a literal, a name, `_`, a tuple of patterns or a constructor of a variant
+# @exit: 1
+# @error: Expected a pattern, found `[`
+x: int = match [1] | [1] => 1 | _ => 0 ;
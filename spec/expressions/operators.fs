@@ -0,0 +1,5 @@
+# `*` and `/` bind tighter than `+` and `-`, which bind tighter than comparisons
+x: int = 1 + 2 * 3
+y: int = (1 + 2) * 3 - -4
+z: bool = x * 2 <= y / 2
+w: float = -x as float / 2.0
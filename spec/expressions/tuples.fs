@@ -0,0 +1,6 @@
+# A tuple is written with a comma between parentheses, `()` is the empty tuple
+t: (int, str) = (1, "a")
+(n, s): (int, str) = t
+u: () = ()
+nested: ((int, int), bool) = ((n, 2), true)
+x: int = match t | (0, _) => 0 | (m, _) => m ;
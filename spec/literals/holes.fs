@@ -0,0 +1,8 @@
+# A hole, `?` followed by a name, stands for a value that is not written yet, it is
+# reported with the type it must have, and running it stops with an error
+# @warning: `?todo` stands for a value of type `int`
+# @warning: `?greeting` stands for a value of type `str`
+# @exit: 3
+# @error: reached `?todo`, this code is not written yet
+x: int = ?todo
+y: str = ?greeting
@@ -0,0 +1,4 @@
+# An `int` is 64 bits wide
+# @error: `9223372036854775808` does not fit in an `int`
+max: int = 9223372036854775807
+too_big: int = 9223372036854775808
@@ -0,0 +1,7 @@
+# Numbers take the suffixes `i`, `_int`, `f` and `_float`
+# @error: Unknown numeric suffix in `1_foo`, expected one of `i`, `_int`, `f` or `_float`
+a: int = 1i
+b: int = 2_int
+c: float = 3f
+d: float = 4_float
+e: int = 1_foo
@@ -0,0 +1,5 @@
+# A string ends on the line it starts, the declarations after it are still checked
+# @exit: 1
+# @error: unterminated string literal
+greeting: str = "hello
+name: str = "funs"
@@ -0,0 +1,9 @@
+# A program prints with `print`, and the value of its last line is printed after it
+# unless it is `()`
+# @stdout: start
+# @stdout: 120
+# @stdout: 7
+fact: (int, int) -> int = (n, acc) -> if n == 0 then acc else fact (n - 1) (n * acc) ;
+print "start"
+print (fact 5 1)
+fact 3 1 + 1
@@ -0,0 +1,8 @@
+# A program that stops with an error exits with 3, after what it printed before
+# @exit: 3
+# @error: division by zero
+# @stdout: before
+x: int = 0
+print "before"
+1 / x
+print "after"
@@ -0,0 +1,7 @@
+# Generic types take their arguments between `<` and `>`
+# @warning: `?todo` stands for a value of type `option<int>`
+# @warning: `?todo` stands for a value of type `option<option<str>>`
+# @exit: 3
+# @error: reached `?todo`, this code is not written yet
+x: option<int> = ?todo
+y: option<option<str>> = ?todo
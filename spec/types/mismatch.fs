@@ -0,0 +1,7 @@
+# Types are checked before the program runs, a mismatch is reported on the expression
+# @exit: 1
+# @error: expected `int`, found `str`
+# @error: expected `str`, found `int`
+x: int = "a"
+double: (int) -> int = (n) -> n * 2 ;
+name: str = double 2
@@ -0,0 +1,9 @@
+# A pattern matches values of the type of the scrutinee, and a constructor pattern has
+# a pattern for each field of its case
+# @exit: 1
+# @error: expected `int`, found `float`
+# @error: the constructor `Rgb` has 3 field(s), the pattern has 2
+data Color = Red | Rgb int int int
+x: str = match 2 | 2.5 => "never an int" | _ => "other" ;
+c: Color = Red
+r: int = match c | Rgb r g => r | _ => 0 ;
@@ -0,0 +1,10 @@
+# A record literal gives a value to each field of its type and to no other, and only the
+# declared fields can be read
+# @exit: 1
+# @error: `Point` needs a value for its field `y`
+# @error: `Point` has no field `z`
+# @error: `Point` has no field `z`
+data Point = { x: int, y: int }
+p: Point = { x = 1 }
+q: Point = { x = 1, y = 2, z = 3 }
+z: int = q.z
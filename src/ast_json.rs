@@ -0,0 +1,58 @@
+use crate::parser::Tree;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// The version of the JSON printed by `funs ast --json`.
+///
+/// It changes whenever a tool reading the output would break, see `dev_doc/ast_json.md`.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The output of `funs ast --json`, the contract for tools working on the syntax tree of a
+/// file without linking against the compiler.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct AstJson {
+    pub version: u32,
+    pub file_path: PathBuf,
+    /// The parse errors, the tree is still complete when there are some
+    pub errors: Vec<String>,
+    pub ast: Tree,
+}
+
+impl AstJson {
+    pub fn new(file_path: PathBuf, errors: Vec<String>, ast: Tree) -> AstJson {
+        AstJson {
+            version: FORMAT_VERSION,
+            file_path,
+            errors,
+            ast,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        // A tree only holds strings and numbers, it always serializes
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer::Lexer, parser::Parser, source::Source};
+
+    #[test]
+    fn test_ast_json_round_trip() {
+        let source = Source::from_snippet("tool", "x: int = ?todo\n".to_string());
+        let (ast, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+        let output = AstJson::new(PathBuf::from("main.fs"), errors, ast);
+
+        let json = serde_json::from_str::<serde_json::Value>(&output.to_json()).unwrap();
+        assert_eq!(json["version"], FORMAT_VERSION);
+        assert_eq!(json["file_path"], "main.fs");
+        assert_eq!(json["errors"], serde_json::json!([]));
+        assert_eq!(json["ast"]["kind"], "File");
+        assert_eq!(json["ast"]["children"][0]["Tree"]["kind"], "StmtVarDecl");
+
+        let read = serde_json::from_str::<AstJson>(&output.to_json()).unwrap();
+        assert_eq!(read, output);
+    }
+}
@@ -0,0 +1,219 @@
+use crate::harness::BenchResult;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// The version of the baselines written by `funs bench --save-baseline`.
+///
+/// It changes whenever a baseline saved before could not be compared, see
+/// `dev_doc/bench_json.md`.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// How much slower than its baseline, in percent, a benchmark can get before it is
+/// reported as a regression by default
+pub const DEFAULT_THRESHOLD: u32 = 10;
+
+/// The results of a `funs bench` run, saved to compare the next runs against
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct BenchJson {
+    pub version: u32,
+    pub benches: Vec<BenchEntry>,
+}
+
+/// A benchmark, named `<file>::<function>` or `<file>` for a whole program
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct BenchEntry {
+    pub name: String,
+    pub iterations: usize,
+    pub mean_ns: u64,
+    pub stddev_ns: u64,
+    pub steps: u64,
+}
+
+impl BenchEntry {
+    pub fn new(name: String, result: &BenchResult) -> BenchEntry {
+        BenchEntry {
+            name,
+            iterations: result.iterations,
+            mean_ns: nanos(result.mean),
+            stddev_ns: nanos(result.stddev),
+            steps: result.steps,
+        }
+    }
+}
+
+fn nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BaselineError {
+    Parse(String),
+    Version(u32),
+}
+
+impl std::fmt::Display for BaselineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BaselineError::Parse(message) => write!(f, "Invalid baseline: {}", message),
+            BaselineError::Version(version) => write!(
+                f,
+                "Baseline version {} is not supported, expected {}",
+                version, FORMAT_VERSION
+            ),
+        }
+    }
+}
+
+/// A benchmark doing worse than in the baseline
+#[derive(Debug, PartialEq)]
+pub enum Regression {
+    /// Its mean time grew by more than the threshold
+    Slower {
+        name: String,
+        baseline: Duration,
+        current: Duration,
+    },
+    /// It evaluates more expressions, which does not depend on the machine
+    MoreSteps {
+        name: String,
+        baseline: u64,
+        current: u64,
+    },
+}
+
+impl std::fmt::Display for Regression {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Regression::Slower {
+                name,
+                baseline,
+                current,
+            } => {
+                let percent = (current.as_secs_f64() / baseline.as_secs_f64() - 1.0) * 100.0;
+                write!(
+                    f,
+                    "{}: mean {:?}, {:.1}% slower than the baseline {:?}",
+                    name, current, percent, baseline
+                )
+            }
+            Regression::MoreSteps {
+                name,
+                baseline,
+                current,
+            } => write!(
+                f,
+                "{}: {} steps, {} in the baseline",
+                name, current, baseline
+            ),
+        }
+    }
+}
+
+impl BenchJson {
+    pub fn new(benches: Vec<BenchEntry>) -> BenchJson {
+        BenchJson {
+            version: FORMAT_VERSION,
+            benches,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        // A baseline only holds strings and numbers, it always serializes
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    pub fn from_json(json: &str) -> Result<BenchJson, BaselineError> {
+        let baseline: BenchJson =
+            serde_json::from_str(json).map_err(|e| BaselineError::Parse(e.to_string()))?;
+        if baseline.version != FORMAT_VERSION {
+            return Err(BaselineError::Version(baseline.version));
+        }
+        Ok(baseline)
+    }
+
+    /// The benchmarks of `current` that take more steps than in this baseline, or whose
+    /// mean time grew by more than `threshold` percent. The benchmarks missing from either
+    /// side are not compared.
+    pub fn compare(&self, current: &[BenchEntry], threshold: u32) -> Vec<Regression> {
+        current
+            .iter()
+            .filter_map(|entry| {
+                let baseline = self.benches.iter().find(|b| b.name == entry.name)?;
+                if entry.steps > baseline.steps {
+                    return Some(Regression::MoreSteps {
+                        name: entry.name.clone(),
+                        baseline: baseline.steps,
+                        current: entry.steps,
+                    });
+                }
+                let limit = baseline.mean_ns as f64 * (1.0 + f64::from(threshold) / 100.0);
+                (entry.mean_ns as f64 > limit).then(|| Regression::Slower {
+                    name: entry.name.clone(),
+                    baseline: Duration::from_nanos(baseline.mean_ns),
+                    current: Duration::from_nanos(entry.mean_ns),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, mean_ns: u64, steps: u64) -> BenchEntry {
+        BenchEntry {
+            name: name.to_string(),
+            iterations: 10,
+            mean_ns,
+            stddev_ns: 0,
+            steps,
+        }
+    }
+
+    #[test]
+    fn test_bench_json_round_trip() {
+        let baseline = BenchJson::new(vec![entry("main.fs::fib", 1500, 1768)]);
+        assert_eq!(BenchJson::from_json(&baseline.to_json()), Ok(baseline));
+
+        assert_eq!(
+            BenchJson::from_json(r#"{"version": 99, "benches": []}"#),
+            Err(BaselineError::Version(99))
+        );
+        assert!(matches!(
+            BenchJson::from_json("{}"),
+            Err(BaselineError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_bench_json_compare() {
+        let baseline = BenchJson::new(vec![
+            entry("a.fs::same", 1000, 50),
+            entry("a.fs::noisy", 1000, 50),
+            entry("a.fs::slower", 1000, 50),
+            entry("a.fs::more_steps", 1000, 50),
+            entry("a.fs::removed", 1000, 50),
+        ]);
+        let current = vec![
+            entry("a.fs::same", 900, 40),
+            entry("a.fs::noisy", 1100, 50),
+            entry("a.fs::slower", 1500, 50),
+            entry("a.fs::more_steps", 1000, 51),
+            entry("a.fs::added", 1000, 50),
+        ];
+        let regressions = baseline
+            .compare(&current, DEFAULT_THRESHOLD)
+            .iter()
+            .map(Regression::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            regressions,
+            vec![
+                "a.fs::slower: mean 1.5µs, 50.0% slower than the baseline 1µs",
+                "a.fs::more_steps: 51 steps, 50 in the baseline",
+            ]
+        );
+        assert_eq!(baseline.compare(&current, 100).len(), 1);
+    }
+}
@@ -0,0 +1,885 @@
+use crate::completions::Shell;
+use crate::diagnostics::messages::Locale;
+use crate::parser;
+use crate::{ast_json, bench_json, harness, index, repl, stats_json};
+use serde::Serialize;
+use std::process::ExitCode;
+
+pub const USAGE: &str = "Usage: \n\
+                         funs [<file.fs>...]\n\
+                         funs check [--exclude <glob>]... [--emit-debug-dir <dir>] [--stats-file <file.json>] [--locale <locale>] <file.fs | dir>...\n\
+                         funs test [--doc | --spec] [--exclude <glob>]... [--locale <locale>] <file.fs | dir>...\n\
+                         funs bench [--iterations <n>] [--warmup <n>] [--program] [--baseline <file.json>] [--save-baseline <file.json>] [--threshold <percent>] [--exclude <glob>]... [--locale <locale>] <file.fs | dir>...\n\
+                         funs diff <before.fs> <after.fs>\n\
+                         funs migrate [--exclude <glob>]... <file.fs | dir>...\n\
+                         funs symbols [--json] [--exclude <glob>]... <file.fs | dir>...\n\
+                         funs index [--exclude <glob>]... <file.fs | dir>...\n\
+                         funs minimize [--error <text>] <file.fs>\n\
+                         funs run [--trace-eval] [--locale <locale>] <file.fs>\n\
+                         funs repl [--locale <locale>]\n\
+                         funs lint [--exclude <glob>]... [--allow <rule>]... [--locale <locale>] <file.fs | dir>...\n\
+                         funs ast --json <file.fs>\n\
+                         funs highlight --scopes <file.fs>\n\
+                         funs config --show [<file.fs | dir>]\n\
+                         funs version [--json]\n\
+                         funs completions <bash | zsh | fish | powershell>\n\
+                         funs --help\n\
+                         funs --version";
+
+pub const SUBCOMMANDS: [&str; 16] = [
+    "check",
+    "test",
+    "bench",
+    "diff",
+    "migrate",
+    "symbols",
+    "index",
+    "minimize",
+    "run",
+    "repl",
+    "lint",
+    "ast",
+    "highlight",
+    "config",
+    "version",
+    "completions",
+];
+
+/// The exit codes of the `funs` binary.
+///
+/// Scripts and CI can rely on these values to tell apart the reason of a failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// Everything went fine
+    Success,
+    /// The input could not be compiled (diagnostics were reported, or it could not be read)
+    CompileError,
+    /// The command line was malformed
+    UsageError,
+    /// The program failed at run time
+    RuntimeError,
+    /// The compiler itself failed (a bug in `funs`)
+    InternalError,
+}
+
+impl ExitStatus {
+    pub fn code(&self) -> u8 {
+        match self {
+            ExitStatus::Success => 0,
+            ExitStatus::CompileError => 1,
+            ExitStatus::UsageError => 2,
+            ExitStatus::RuntimeError => 3,
+            ExitStatus::InternalError => 101,
+        }
+    }
+}
+
+impl From<ExitStatus> for ExitCode {
+    fn from(status: ExitStatus) -> ExitCode {
+        ExitCode::from(status.code())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Help,
+    /// Print the version of `funs`, with `all` the versions of the language and of the
+    /// formats it writes too, and with `json` as JSON
+    Version {
+        all: bool,
+        json: bool,
+    },
+    /// Check the given files, and every `.fs` file found (recursively) in the given directories
+    /// that does not match one of the `excludes` globs; with `emit_debug_dir` the tokens
+    /// and the CST of each file are written to that directory as JSON, with `stats_file`
+    /// the timings and counts of the run are recorded in that file. The diagnostics are
+    /// written in `locale`.
+    Check {
+        paths: Vec<String>,
+        excludes: Vec<String>,
+        emit_debug_dir: Option<String>,
+        stats_file: Option<String>,
+        locale: Locale,
+    },
+    /// Run the tests found in the given files and directories: the `@test` functions,
+    /// with `doc` the code examples in the comments, with `spec` the files themselves
+    /// as specs. The diagnostics of the `@test` functions are written in `locale`.
+    Test {
+        doc: bool,
+        spec: bool,
+        paths: Vec<String>,
+        excludes: Vec<String>,
+        locale: Locale,
+    },
+    /// Time the `@bench` functions of the given files and directories, or with `program`
+    /// the whole files, and compare them with a `baseline` saved by `save_baseline`; the
+    /// diagnostics are written in `locale`
+    Bench {
+        options: harness::BenchOptions,
+        program: bool,
+        baseline: Option<String>,
+        save_baseline: Option<String>,
+        /// The slowdown in percent over which a benchmark regressed
+        threshold: u32,
+        paths: Vec<String>,
+        excludes: Vec<String>,
+        locale: Locale,
+    },
+    /// Report the declarations added, removed or changed between two files
+    Diff {
+        before: String,
+        after: String,
+    },
+    /// Rewrite the deprecated syntax used in the given files and directories
+    Migrate {
+        paths: Vec<String>,
+        excludes: Vec<String>,
+    },
+    /// List the top-level declarations of the given files and directories, as a table
+    /// or with `json` as JSON
+    Symbols {
+        json: bool,
+        paths: Vec<String>,
+        excludes: Vec<String>,
+    },
+    /// Print the definitions of the given files and directories and the references to
+    /// them as JSON, for code search tools
+    Index {
+        paths: Vec<String>,
+        excludes: Vec<String>,
+    },
+    /// Shrink a file to a smaller program that fails the same way, a crash of the
+    /// compiler or with `error` an error containing that text
+    Minimize {
+        error: Option<String>,
+        path: String,
+    },
+    /// Run a program and print its result, the diagnostics are written in `locale`; with
+    /// `trace_eval` every evaluation step is logged to the debug log
+    Run {
+        path: String,
+        locale: Locale,
+        trace_eval: bool,
+    },
+    /// Read statements from stdin and run them as they are completed, the diagnostics
+    /// are written in `locale`
+    Repl {
+        locale: Locale,
+    },
+    /// Print the syntax tree of a file, with `json` in the format read by external tools
+    Ast {
+        json: bool,
+        path: String,
+    },
+    /// Print how to highlight a file, with `scopes` as TextMate scopes
+    Highlight {
+        scopes: bool,
+        path: String,
+    },
+    /// Report the lints of the given files and directories, except for the rules in
+    /// `allow`, written in `locale`
+    Lint {
+        paths: Vec<String>,
+        excludes: Vec<String>,
+        allow: Vec<String>,
+        locale: Locale,
+    },
+    /// Print the configuration that applies to a file or directory (the current
+    /// directory by default), with `show` as a `funs.toml`
+    Config {
+        show: bool,
+        path: String,
+    },
+    /// Print the script completing the subcommands, flags and files of `funs` in `shell`
+    Completions {
+        shell: Shell,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CliError {
+    MissingPath,
+    WrongPathCount { expected: usize, found: usize },
+    MissingFlagValue(String),
+    UnknownFlag(String),
+    UnknownLocale(String),
+    UnknownShell(String),
+    InvalidFlagValue { flag: String, value: String },
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CliError::MissingPath => write!(f, "Missing input file or directory"),
+            CliError::WrongPathCount { expected, found } => {
+                write!(f, "Expected {} files, found {}", expected, found)
+            }
+            CliError::MissingFlagValue(flag) => write!(f, "Missing value for flag \"{}\"", flag),
+            CliError::UnknownFlag(flag) => write!(f, "Unknown flag \"{}\"", flag),
+            CliError::InvalidFlagValue { flag, value } => {
+                write!(f, "Invalid value \"{}\" for flag \"{}\"", value, flag)
+            }
+            CliError::UnknownLocale(name) => {
+                let locales = Locale::ALL.map(|locale| locale.name()).join(", ");
+                write!(
+                    f,
+                    "Unknown locale \"{}\", expected one of {}",
+                    name, locales
+                )
+            }
+            CliError::UnknownShell(name) => {
+                let shells = Shell::ALL.map(|shell| shell.name()).join(", ");
+                write!(f, "Unknown shell \"{}\", expected one of {}", name, shells)
+            }
+        }
+    }
+}
+
+impl Command {
+    pub fn parse(args: &[String]) -> Result<Command, CliError> {
+        // `funs <file.fs>...` is a shorthand for `funs check <file.fs>...`, and `funs`
+        // alone for `funs repl`
+        if args.is_empty() {
+            return Ok(Command::Repl {
+                locale: Locale::default(),
+            });
+        }
+        let (subcommand, args) = match args.first().map(String::as_str) {
+            Some(subcommand) if SUBCOMMANDS.contains(&subcommand) => (subcommand, &args[1..]),
+            _ => ("check", args),
+        };
+
+        let mut paths = Vec::new();
+        let mut excludes = Vec::new();
+        let mut doc = false;
+        let mut spec = false;
+        let mut json = false;
+        let mut trace_eval = false;
+        let mut scopes = false;
+        let mut show = false;
+        let mut allow = Vec::new();
+        let mut emit_debug_dir = None;
+        let mut stats_file = None;
+        let mut error = None;
+        let mut bench_options = harness::BenchOptions::default();
+        let mut program = false;
+        let mut baseline = None;
+        let mut save_baseline = None;
+        let mut threshold = bench_json::DEFAULT_THRESHOLD;
+        let mut locale = Locale::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match (subcommand, arg.as_str()) {
+                (_, "-h" | "--help") => return Ok(Command::Help),
+                (_, "-V" | "--version") => {
+                    return Ok(Command::Version {
+                        all: false,
+                        json: false,
+                    })
+                }
+                (_, "--exclude") => match args.next() {
+                    Some(pattern) => excludes.push(pattern.to_string()),
+                    None => return Err(CliError::MissingFlagValue(arg.to_string())),
+                },
+                ("check", "--emit-debug-dir") => match args.next() {
+                    Some(dir) => emit_debug_dir = Some(dir.to_string()),
+                    None => return Err(CliError::MissingFlagValue(arg.to_string())),
+                },
+                ("check", "--stats-file") => match args.next() {
+                    Some(path) => stats_file = Some(path.to_string()),
+                    None => return Err(CliError::MissingFlagValue(arg.to_string())),
+                },
+                ("check" | "test" | "bench" | "lint" | "run" | "repl", "--locale") => {
+                    match args.next() {
+                        Some(name) => match Locale::from_name(name) {
+                            Some(name) => locale = name,
+                            None => return Err(CliError::UnknownLocale(name.to_string())),
+                        },
+                        None => return Err(CliError::MissingFlagValue(arg.to_string())),
+                    }
+                }
+                ("minimize", "--error") => match args.next() {
+                    Some(text) => error = Some(text.to_string()),
+                    None => return Err(CliError::MissingFlagValue(arg.to_string())),
+                },
+                ("bench", "--iterations") => match number(arg, args.next())? {
+                    0 => {
+                        return Err(CliError::InvalidFlagValue {
+                            flag: arg.to_string(),
+                            value: "0".to_string(),
+                        })
+                    }
+                    count => bench_options.iterations = count,
+                },
+                ("bench", "--warmup") => bench_options.warmup = number(arg, args.next())?,
+                ("bench", "--threshold") => threshold = number(arg, args.next())?,
+                ("bench", "--program") => program = true,
+                ("bench", "--baseline") => match args.next() {
+                    Some(path) => baseline = Some(path.to_string()),
+                    None => return Err(CliError::MissingFlagValue(arg.to_string())),
+                },
+                ("bench", "--save-baseline") => match args.next() {
+                    Some(path) => save_baseline = Some(path.to_string()),
+                    None => return Err(CliError::MissingFlagValue(arg.to_string())),
+                },
+                ("run", "--trace-eval") => trace_eval = true,
+                ("test", "--doc") => doc = true,
+                ("test", "--spec") => spec = true,
+                ("symbols" | "ast" | "version", "--json") => json = true,
+                ("highlight", "--scopes") => scopes = true,
+                ("config", "--show") => show = true,
+                ("lint", "--allow") => match args.next() {
+                    Some(rule) => allow.push(rule.to_string()),
+                    None => return Err(CliError::MissingFlagValue(arg.to_string())),
+                },
+                (_, flag) if flag.starts_with('-') => {
+                    return Err(CliError::UnknownFlag(flag.to_string()))
+                }
+                _ => paths.push(arg.to_string()),
+            }
+        }
+        if subcommand == "repl" {
+            return match paths.len() {
+                0 => Ok(Command::Repl { locale }),
+                found => Err(CliError::WrongPathCount { expected: 0, found }),
+            };
+        }
+        if subcommand == "version" {
+            return match paths.len() {
+                0 => Ok(Command::Version { all: true, json }),
+                found => Err(CliError::WrongPathCount { expected: 0, found }),
+            };
+        }
+        if subcommand == "config" && paths.is_empty() {
+            paths.push(".".to_string());
+        }
+        if paths.is_empty() {
+            return Err(CliError::MissingPath);
+        }
+        match subcommand {
+            "test" => Ok(Command::Test {
+                doc,
+                spec,
+                paths,
+                excludes,
+                locale,
+            }),
+            "diff" => match <[String; 2]>::try_from(paths) {
+                Ok([before, after]) => Ok(Command::Diff { before, after }),
+                Err(paths) => Err(CliError::WrongPathCount {
+                    expected: 2,
+                    found: paths.len(),
+                }),
+            },
+            "ast" => match <[String; 1]>::try_from(paths) {
+                Ok([path]) => Ok(Command::Ast { json, path }),
+                Err(paths) => Err(CliError::WrongPathCount {
+                    expected: 1,
+                    found: paths.len(),
+                }),
+            },
+            "minimize" => match <[String; 1]>::try_from(paths) {
+                Ok([path]) => Ok(Command::Minimize { error, path }),
+                Err(paths) => Err(CliError::WrongPathCount {
+                    expected: 1,
+                    found: paths.len(),
+                }),
+            },
+            "run" => match <[String; 1]>::try_from(paths) {
+                Ok([path]) => Ok(Command::Run {
+                    path,
+                    locale,
+                    trace_eval,
+                }),
+                Err(paths) => Err(CliError::WrongPathCount {
+                    expected: 1,
+                    found: paths.len(),
+                }),
+            },
+            "highlight" => match <[String; 1]>::try_from(paths) {
+                Ok([path]) => Ok(Command::Highlight { scopes, path }),
+                Err(paths) => Err(CliError::WrongPathCount {
+                    expected: 1,
+                    found: paths.len(),
+                }),
+            },
+            "bench" => Ok(Command::Bench {
+                options: bench_options,
+                program,
+                baseline,
+                save_baseline,
+                threshold,
+                paths,
+                excludes,
+                locale,
+            }),
+            "migrate" => Ok(Command::Migrate { paths, excludes }),
+            "lint" => Ok(Command::Lint {
+                paths,
+                excludes,
+                allow,
+                locale,
+            }),
+            "config" => match <[String; 1]>::try_from(paths) {
+                Ok([path]) => Ok(Command::Config { show, path }),
+                Err(paths) => Err(CliError::WrongPathCount {
+                    expected: 1,
+                    found: paths.len(),
+                }),
+            },
+            "symbols" => Ok(Command::Symbols {
+                json,
+                paths,
+                excludes,
+            }),
+            "index" => Ok(Command::Index { paths, excludes }),
+            "completions" => match <[String; 1]>::try_from(paths) {
+                Ok([name]) => match Shell::from_name(&name) {
+                    Some(shell) => Ok(Command::Completions { shell }),
+                    None => Err(CliError::UnknownShell(name)),
+                },
+                Err(paths) => Err(CliError::WrongPathCount {
+                    expected: 1,
+                    found: paths.len(),
+                }),
+            },
+            _ => Ok(Command::Check {
+                paths,
+                excludes,
+                emit_debug_dir,
+                stats_file,
+                locale,
+            }),
+        }
+    }
+}
+
+/// The value of the numeric `flag`
+fn number<T: std::str::FromStr>(flag: &str, value: Option<&String>) -> Result<T, CliError> {
+    let value = value.ok_or_else(|| CliError::MissingFlagValue(flag.to_string()))?;
+    value.parse().map_err(|_| CliError::InvalidFlagValue {
+        flag: flag.to_string(),
+        value: value.to_string(),
+    })
+}
+
+pub fn version() -> String {
+    format!("funs {}", env!("CARGO_PKG_VERSION"))
+}
+
+/// The versions printed by `funs version`, for the tools that check they can read the
+/// files `funs` writes, or that the code they generate is understood by it
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Versions {
+    pub cli: &'static str,
+    /// The version of the grammar accepted by the parser
+    pub language: u32,
+    /// The versions of the formats written by `funs ast --json`, `funs index`,
+    /// `funs bench --save-baseline`, `funs check --stats-file` and `:save` in the REPL
+    pub ast_json: u32,
+    pub index_json: u32,
+    pub bench_json: u32,
+    pub stats_json: u32,
+    pub repl_session: u32,
+}
+
+impl Versions {
+    pub fn current() -> Versions {
+        Versions {
+            cli: env!("CARGO_PKG_VERSION"),
+            language: parser::LANGUAGE_VERSION,
+            ast_json: ast_json::FORMAT_VERSION,
+            index_json: index::FORMAT_VERSION,
+            bench_json: bench_json::FORMAT_VERSION,
+            stats_json: stats_json::FORMAT_VERSION,
+            repl_session: repl::FORMAT_VERSION,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        // Only strings and numbers, it always serializes
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+}
+
+impl std::fmt::Display for Versions {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "funs {}", self.cli)?;
+        writeln!(f, "language: {}", self.language)?;
+        writeln!(f, "ast json: {}", self.ast_json)?;
+        writeln!(f, "index json: {}", self.index_json)?;
+        writeln!(f, "bench json: {}", self.bench_json)?;
+        writeln!(f, "stats json: {}", self.stats_json)?;
+        write!(f, "repl session: {}", self.repl_session)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_cli_exit_codes() {
+        assert_eq!(ExitStatus::Success.code(), 0);
+        assert_eq!(ExitStatus::CompileError.code(), 1);
+        assert_eq!(ExitStatus::UsageError.code(), 2);
+        assert_eq!(ExitStatus::RuntimeError.code(), 3);
+        assert_eq!(ExitStatus::InternalError.code(), 101);
+    }
+
+    #[test]
+    fn test_cli_parse_command() {
+        assert_eq!(
+            Command::parse(&args(&["main.fs"])),
+            Ok(Command::Check {
+                paths: vec!["main.fs".to_string()],
+                excludes: vec![],
+                emit_debug_dir: None,
+                stats_file: None,
+                locale: Locale::En
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["check", "src", "--exclude", "gen/**", "main.fs"])),
+            Ok(Command::Check {
+                paths: vec!["src".to_string(), "main.fs".to_string()],
+                excludes: vec!["gen/**".to_string()],
+                emit_debug_dir: None,
+                stats_file: None,
+                locale: Locale::En
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["check", "--emit-debug-dir", "out", "main.fs"])),
+            Ok(Command::Check {
+                paths: vec!["main.fs".to_string()],
+                excludes: vec![],
+                emit_debug_dir: Some("out".to_string()),
+                stats_file: None,
+                locale: Locale::En
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["--stats-file", "stats.json", "src"])),
+            Ok(Command::Check {
+                paths: vec!["src".to_string()],
+                excludes: vec![],
+                emit_debug_dir: None,
+                stats_file: Some("stats.json".to_string()),
+                locale: Locale::En
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["check", "src", "--stats-file"])),
+            Err(CliError::MissingFlagValue("--stats-file".to_string()))
+        );
+        assert_eq!(
+            Command::parse(&args(&["check", "--locale", "en", "main.fs"])),
+            Ok(Command::Check {
+                paths: vec!["main.fs".to_string()],
+                excludes: vec![],
+                emit_debug_dir: None,
+                stats_file: None,
+                locale: Locale::En
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["test", "--doc", "src"])),
+            Ok(Command::Test {
+                doc: true,
+                spec: false,
+                paths: vec!["src".to_string()],
+                excludes: vec![],
+                locale: Locale::En
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["test", "--spec", "spec"])),
+            Ok(Command::Test {
+                doc: false,
+                spec: true,
+                paths: vec!["spec".to_string()],
+                excludes: vec![],
+                locale: Locale::En
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["bench", "--iterations", "10", "benches"])),
+            Ok(Command::Bench {
+                options: harness::BenchOptions {
+                    iterations: 10,
+                    warmup: harness::DEFAULT_WARMUP
+                },
+                program: false,
+                baseline: None,
+                save_baseline: None,
+                threshold: bench_json::DEFAULT_THRESHOLD,
+                paths: vec!["benches".to_string()],
+                excludes: vec![],
+                locale: Locale::En
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["bench", "main.fs"])),
+            Ok(Command::Bench {
+                options: harness::BenchOptions::default(),
+                program: false,
+                baseline: None,
+                save_baseline: None,
+                threshold: bench_json::DEFAULT_THRESHOLD,
+                paths: vec!["main.fs".to_string()],
+                excludes: vec![],
+                locale: Locale::En
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&[
+                "bench",
+                "--program",
+                "--warmup",
+                "0",
+                "--baseline",
+                "old.json",
+                "--save-baseline",
+                "new.json",
+                "--threshold",
+                "25",
+                "main.fs"
+            ])),
+            Ok(Command::Bench {
+                options: harness::BenchOptions {
+                    iterations: harness::DEFAULT_ITERATIONS,
+                    warmup: 0
+                },
+                program: true,
+                baseline: Some("old.json".to_string()),
+                save_baseline: Some("new.json".to_string()),
+                threshold: 25,
+                paths: vec!["main.fs".to_string()],
+                excludes: vec![],
+                locale: Locale::En
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["diff", "a.fs", "b.fs"])),
+            Ok(Command::Diff {
+                before: "a.fs".to_string(),
+                after: "b.fs".to_string()
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["migrate", "src"])),
+            Ok(Command::Migrate {
+                paths: vec!["src".to_string()],
+                excludes: vec![]
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["symbols", "--json", "src"])),
+            Ok(Command::Symbols {
+                json: true,
+                paths: vec!["src".to_string()],
+                excludes: vec![]
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["index", "--exclude", "gen/**", "src"])),
+            Ok(Command::Index {
+                paths: vec!["src".to_string()],
+                excludes: vec!["gen/**".to_string()]
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["lint", "--exclude", "gen/**", "src"])),
+            Ok(Command::Lint {
+                paths: vec!["src".to_string()],
+                excludes: vec!["gen/**".to_string()],
+                allow: vec![],
+                locale: Locale::En
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["lint", "--allow", "hole", "src"])),
+            Ok(Command::Lint {
+                paths: vec!["src".to_string()],
+                excludes: vec![],
+                allow: vec!["hole".to_string()],
+                locale: Locale::En
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["config", "--show"])),
+            Ok(Command::Config {
+                show: true,
+                path: ".".to_string()
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["ast", "--json", "main.fs"])),
+            Ok(Command::Ast {
+                json: true,
+                path: "main.fs".to_string()
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["highlight", "--scopes", "main.fs"])),
+            Ok(Command::Highlight {
+                scopes: true,
+                path: "main.fs".to_string()
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["minimize", "--error", "Expected", "crash.fs"])),
+            Ok(Command::Minimize {
+                error: Some("Expected".to_string()),
+                path: "crash.fs".to_string()
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["run", "--locale", "en", "main.fs"])),
+            Ok(Command::Run {
+                path: "main.fs".to_string(),
+                locale: Locale::En,
+                trace_eval: false
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["run", "--trace-eval", "main.fs"])),
+            Ok(Command::Run {
+                path: "main.fs".to_string(),
+                locale: Locale::En,
+                trace_eval: true
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["ast", "a.fs", "b.fs"])),
+            Err(CliError::WrongPathCount {
+                expected: 1,
+                found: 2
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["diff", "a.fs"])),
+            Err(CliError::WrongPathCount {
+                expected: 2,
+                found: 1
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["check", "--doc", "src"])),
+            Err(CliError::UnknownFlag("--doc".to_string()))
+        );
+        assert_eq!(
+            Command::parse(&args(&["check", "--emit-debug-dir"])),
+            Err(CliError::MissingFlagValue("--emit-debug-dir".to_string()))
+        );
+        assert_eq!(
+            Command::parse(&args(&["check", "--exclude"])),
+            Err(CliError::MissingFlagValue("--exclude".to_string()))
+        );
+        assert_eq!(
+            Command::parse(&args(&["bench", "--iterations", "0", "main.fs"])),
+            Err(CliError::InvalidFlagValue {
+                flag: "--iterations".to_string(),
+                value: "0".to_string()
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["bench", "--threshold", "-5", "main.fs"])),
+            Err(CliError::InvalidFlagValue {
+                flag: "--threshold".to_string(),
+                value: "-5".to_string()
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["bench", "main.fs", "--warmup"])),
+            Err(CliError::MissingFlagValue("--warmup".to_string()))
+        );
+        assert_eq!(
+            Command::parse(&args(&["check", "--locale", "xx", "src"])),
+            Err(CliError::UnknownLocale("xx".to_string()))
+        );
+        assert_eq!(
+            Command::parse(&args(&["diff", "--locale", "en", "a.fs", "b.fs"])),
+            Err(CliError::UnknownFlag("--locale".to_string()))
+        );
+        assert_eq!(Command::parse(&args(&["--help"])), Ok(Command::Help));
+        assert_eq!(
+            Command::parse(&args(&["-V"])),
+            Ok(Command::Version {
+                all: false,
+                json: false
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["version", "--json"])),
+            Ok(Command::Version {
+                all: true,
+                json: true
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["completions", "fish"])),
+            Ok(Command::Completions { shell: Shell::Fish })
+        );
+        assert_eq!(
+            Command::parse(&args(&["completions", "sh"])),
+            Err(CliError::UnknownShell("sh".to_string()))
+        );
+        assert_eq!(
+            Command::parse(&args(&["completions"])),
+            Err(CliError::MissingPath)
+        );
+        assert_eq!(
+            Command::parse(&args(&["version", "main.fs"])),
+            Err(CliError::WrongPathCount {
+                expected: 0,
+                found: 1
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&[])),
+            Ok(Command::Repl {
+                locale: Locale::default()
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["repl", "--locale", "en"])),
+            Ok(Command::Repl { locale: Locale::En })
+        );
+        assert_eq!(
+            Command::parse(&args(&["repl", "main.fs"])),
+            Err(CliError::WrongPathCount {
+                expected: 0,
+                found: 1
+            })
+        );
+        assert_eq!(
+            Command::parse(&args(&["check"])),
+            Err(CliError::MissingPath)
+        );
+        assert_eq!(
+            Command::parse(&args(&["--nope"])),
+            Err(CliError::UnknownFlag("--nope".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_cli_versions() {
+        let versions = Versions::current();
+        assert_eq!(versions.language, parser::LANGUAGE_VERSION);
+        assert!(versions.to_string().starts_with(&version()));
+
+        let json: serde_json::Value = serde_json::from_str(&versions.to_json()).unwrap();
+        assert_eq!(json["cli"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(json["ast_json"], ast_json::FORMAT_VERSION);
+        assert_eq!(json["index_json"], index::FORMAT_VERSION);
+        assert_eq!(json["bench_json"], bench_json::FORMAT_VERSION);
+        assert_eq!(json["stats_json"], stats_json::FORMAT_VERSION);
+        assert_eq!(json["repl_session"], repl::FORMAT_VERSION);
+    }
+}
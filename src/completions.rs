@@ -0,0 +1,526 @@
+//! The completion scripts printed by `funs completions <shell>`.
+//!
+//! The scripts are written out from `SUBCOMMANDS`, so that they follow the flags that
+//! `Command::parse` accepts. The files are completed by the shell when the script runs:
+//! the `.fs` files and the directories for the paths, the directories for
+//! `--emit-debug-dir` and any file for a baseline or a stats file.
+
+use crate::diagnostics::messages::Locale;
+use crate::lint;
+
+/// The shells a completion script is written for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    PowerShell,
+}
+
+impl Shell {
+    pub const ALL: [Shell; 4] = [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::PowerShell];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+            Shell::PowerShell => "powershell",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Shell> {
+        Shell::ALL.into_iter().find(|shell| shell.name() == name)
+    }
+}
+
+/// What follows a flag on the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value {
+    /// Nothing, the flag is a switch
+    None,
+    /// A value the shell can not guess, e.g. a number
+    Any,
+    Dir,
+    File,
+    Locale,
+    Rule,
+}
+
+/// What the arguments that are not flags are
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Args {
+    Paths,
+    Shells,
+    None,
+}
+
+struct Subcommand {
+    name: &'static str,
+    flags: &'static [(&'static str, Value)],
+    args: Args,
+}
+
+const EXCLUDE: (&str, Value) = ("--exclude", Value::Any);
+const LOCALE: (&str, Value) = ("--locale", Value::Locale);
+
+/// The subcommands and their flags, `check` first as `funs <file.fs>...` checks the files
+const SUBCOMMANDS: [Subcommand; 16] = [
+    Subcommand {
+        name: "check",
+        flags: &[
+            EXCLUDE,
+            ("--emit-debug-dir", Value::Dir),
+            ("--stats-file", Value::File),
+            LOCALE,
+        ],
+        args: Args::Paths,
+    },
+    Subcommand {
+        name: "test",
+        flags: &[
+            ("--doc", Value::None),
+            ("--spec", Value::None),
+            EXCLUDE,
+            LOCALE,
+        ],
+        args: Args::Paths,
+    },
+    Subcommand {
+        name: "bench",
+        flags: &[
+            ("--iterations", Value::Any),
+            ("--warmup", Value::Any),
+            ("--program", Value::None),
+            ("--baseline", Value::File),
+            ("--save-baseline", Value::File),
+            ("--threshold", Value::Any),
+            EXCLUDE,
+            LOCALE,
+        ],
+        args: Args::Paths,
+    },
+    Subcommand {
+        name: "diff",
+        flags: &[],
+        args: Args::Paths,
+    },
+    Subcommand {
+        name: "migrate",
+        flags: &[EXCLUDE],
+        args: Args::Paths,
+    },
+    Subcommand {
+        name: "symbols",
+        flags: &[("--json", Value::None), EXCLUDE],
+        args: Args::Paths,
+    },
+    Subcommand {
+        name: "index",
+        flags: &[EXCLUDE],
+        args: Args::Paths,
+    },
+    Subcommand {
+        name: "minimize",
+        flags: &[("--error", Value::Any)],
+        args: Args::Paths,
+    },
+    Subcommand {
+        name: "run",
+        flags: &[("--trace-eval", Value::None), LOCALE],
+        args: Args::Paths,
+    },
+    Subcommand {
+        name: "repl",
+        flags: &[LOCALE],
+        args: Args::None,
+    },
+    Subcommand {
+        name: "lint",
+        flags: &[EXCLUDE, ("--allow", Value::Rule), LOCALE],
+        args: Args::Paths,
+    },
+    Subcommand {
+        name: "ast",
+        flags: &[("--json", Value::None)],
+        args: Args::Paths,
+    },
+    Subcommand {
+        name: "highlight",
+        flags: &[("--scopes", Value::None)],
+        args: Args::Paths,
+    },
+    Subcommand {
+        name: "config",
+        flags: &[("--show", Value::None)],
+        args: Args::Paths,
+    },
+    Subcommand {
+        name: "version",
+        flags: &[("--json", Value::None)],
+        args: Args::None,
+    },
+    Subcommand {
+        name: "completions",
+        flags: &[],
+        args: Args::Shells,
+    },
+];
+
+/// The flags accepted by every subcommand
+const GLOBAL_FLAGS: [&str; 4] = ["-h", "--help", "-V", "--version"];
+
+/// The completion script for `shell`, to be sourced by it
+pub fn script(shell: Shell) -> String {
+    match shell {
+        Shell::Bash => bash(),
+        Shell::Zsh => zsh(),
+        Shell::Fish => fish(),
+        Shell::PowerShell => powershell(),
+    }
+}
+
+/// The words completing the value of a flag taking `value`, for the values known
+/// before the script runs
+fn words(value: Value) -> Vec<&'static str> {
+    match value {
+        Value::Locale => Locale::ALL.iter().map(Locale::name).collect(),
+        Value::Rule => lint::rules().iter().map(|rule| rule.name()).collect(),
+        Value::None | Value::Any | Value::Dir | Value::File => Vec::new(),
+    }
+}
+
+fn shells() -> Vec<&'static str> {
+    Shell::ALL.iter().map(Shell::name).collect()
+}
+
+fn subcommand_names() -> Vec<&'static str> {
+    SUBCOMMANDS
+        .iter()
+        .map(|subcommand| subcommand.name)
+        .collect()
+}
+
+/// Every flag taking a value, once, with the value it takes
+fn value_flags() -> Vec<(&'static str, Value)> {
+    let mut flags: Vec<(&str, Value)> = Vec::new();
+    for &(flag, value) in SUBCOMMANDS.iter().flat_map(|subcommand| subcommand.flags) {
+        if value != Value::None && !flags.iter().any(|(name, _)| *name == flag) {
+            flags.push((flag, value));
+        }
+    }
+    flags
+}
+
+fn flag_names(subcommand: &Subcommand) -> Vec<&'static str> {
+    subcommand.flags.iter().map(|(flag, _)| *flag).collect()
+}
+
+fn bash() -> String {
+    let mut values = String::new();
+    for (flag, value) in value_flags() {
+        let reply = match value {
+            Value::Dir => "COMPREPLY=($(compgen -d -- \"$cur\"))".to_string(),
+            Value::File => "COMPREPLY=($(compgen -f -- \"$cur\"))".to_string(),
+            Value::Locale | Value::Rule => format!(
+                "COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+                words(value).join(" ")
+            ),
+            Value::None | Value::Any => "COMPREPLY=()".to_string(),
+        };
+        values += &format!("        {}) {}; return ;;\n", flag, reply);
+    }
+    let mut flags = String::new();
+    let mut args = String::new();
+    for subcommand in &SUBCOMMANDS {
+        flags += &format!(
+            "        {}) flags=\"{}\" ;;\n",
+            subcommand.name,
+            flag_names(subcommand).join(" ")
+        );
+        let reply = match subcommand.args {
+            Args::Paths => continue,
+            Args::Shells => format!(
+                "COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+                shells().join(" ")
+            ),
+            Args::None => "COMPREPLY=()".to_string(),
+        };
+        args += &format!("        {}) {}; return ;;\n", subcommand.name, reply);
+    }
+    format!(
+        r#"# The completions of funs for bash, written by `funs completions bash`
+_funs() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    local prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    local subcommand="check"
+    if [[ $COMP_CWORD -gt 1 ]]; then
+        subcommand="${{COMP_WORDS[1]}}"
+    fi
+    case "$prev" in
+{values}    esac
+    local flags
+    case "$subcommand" in
+{flags}        *) flags="{check}" ;;
+    esac
+    if [[ "$cur" == -* ]]; then
+        COMPREPLY=($(compgen -W "$flags {global}" -- "$cur"))
+        return
+    fi
+    case "$subcommand" in
+{args}    esac
+    COMPREPLY=($(compgen -d -- "$cur") $(compgen -f -X '!*.fs' -- "$cur"))
+    if [[ $COMP_CWORD -eq 1 ]]; then
+        COMPREPLY+=($(compgen -W "{subcommands}" -- "$cur"))
+    fi
+}}
+complete -o filenames -F _funs funs
+"#,
+        check = flag_names(&SUBCOMMANDS[0]).join(" "),
+        global = GLOBAL_FLAGS.join(" "),
+        subcommands = subcommand_names().join(" "),
+    )
+}
+
+fn zsh() -> String {
+    let mut values = String::new();
+    for (flag, value) in value_flags() {
+        let reply = match value {
+            Value::Dir => "_directories".to_string(),
+            Value::File => "_files".to_string(),
+            Value::Locale | Value::Rule => format!("compadd -- {}", words(value).join(" ")),
+            Value::None | Value::Any => ":".to_string(),
+        };
+        values += &format!("        {}) {}; return ;;\n", flag, reply);
+    }
+    let mut flags = String::new();
+    let mut args = String::new();
+    for subcommand in &SUBCOMMANDS {
+        flags += &format!(
+            "        {}) flags=({}) ;;\n",
+            subcommand.name,
+            flag_names(subcommand).join(" ")
+        );
+        let reply = match subcommand.args {
+            Args::Paths => continue,
+            Args::Shells => format!("compadd -- {}", shells().join(" ")),
+            Args::None => ":".to_string(),
+        };
+        args += &format!("        {}) {}; return ;;\n", subcommand.name, reply);
+    }
+    format!(
+        r#"#compdef funs
+# The completions of funs for zsh, written by `funs completions zsh`
+_funs() {{
+    local subcommand=check
+    if (( CURRENT > 2 )); then
+        subcommand=${{words[2]}}
+    fi
+    case ${{words[CURRENT-1]}} in
+{values}    esac
+    local -a flags
+    case $subcommand in
+{flags}        *) flags=({check}) ;;
+    esac
+    if [[ $PREFIX == -* ]]; then
+        compadd -- $flags {global}
+        return
+    fi
+    case $subcommand in
+{args}    esac
+    if (( CURRENT == 2 )); then
+        compadd -- {subcommands}
+    fi
+    _files -g '*.fs'
+}}
+compdef _funs funs
+"#,
+        check = flag_names(&SUBCOMMANDS[0]).join(" "),
+        global = GLOBAL_FLAGS.join(" "),
+        subcommands = subcommand_names().join(" "),
+    )
+}
+
+fn fish() -> String {
+    let mut script = String::from(
+        "# The completions of funs for fish, written by `funs completions fish`\n\
+         complete -c funs -f\n\
+         complete -c funs -s h -l help\n\
+         complete -c funs -s V -l version\n",
+    );
+    script += &format!(
+        "complete -c funs -n __fish_use_subcommand -a \"{}\"\n",
+        subcommand_names().join(" ")
+    );
+    for (index, subcommand) in SUBCOMMANDS.iter().enumerate() {
+        let condition = format!("__fish_seen_subcommand_from {}", subcommand.name);
+        // `funs <file.fs>...` takes the flags of `check`
+        let condition = match index {
+            0 => format!(
+                "not __fish_seen_subcommand_from {}",
+                subcommand_names()[1..].join(" ")
+            ),
+            _ => condition,
+        };
+        for &(flag, value) in subcommand.flags {
+            let takes = match value {
+                Value::None => String::new(),
+                Value::Any => " -x".to_string(),
+                Value::Dir => " -x -a \"(__fish_complete_directories)\"".to_string(),
+                Value::File => " -r -F".to_string(),
+                Value::Locale | Value::Rule => format!(" -x -a \"{}\"", words(value).join(" ")),
+            };
+            script += &format!(
+                "complete -c funs -n \"{}\" -l {}{}\n",
+                condition,
+                flag.trim_start_matches('-'),
+                takes
+            );
+        }
+        let args = match subcommand.args {
+            Args::Paths => "\"(__fish_complete_suffix .fs)\"".to_string(),
+            Args::Shells => format!("\"{}\"", shells().join(" ")),
+            Args::None => continue,
+        };
+        script += &format!("complete -c funs -n \"{}\" -a {}\n", condition, args);
+    }
+    script
+}
+
+fn powershell() -> String {
+    let quoted = |words: Vec<&str>| {
+        words
+            .iter()
+            .map(|word| format!("'{}'", word))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let mut values = String::new();
+    for (flag, value) in value_flags() {
+        let reply = match value {
+            Value::Dir => "$candidates = Get-Paths $wordToComplete { $_.PSIsContainer }",
+            Value::File => "$candidates = Get-Paths $wordToComplete { $true }",
+            Value::Locale | Value::Rule => "",
+            Value::None | Value::Any => "return",
+        };
+        let reply = match reply {
+            "" => format!("$candidates = @({})", quoted(words(value))),
+            reply => reply.to_string(),
+        };
+        values += &format!("        '{}' {{ {} }}\n", flag, reply);
+    }
+    let mut flags = String::new();
+    let mut args = String::new();
+    for subcommand in &SUBCOMMANDS {
+        flags += &format!(
+            "            '{}' {{ @({}) }}\n",
+            subcommand.name,
+            quoted(flag_names(subcommand))
+        );
+        let reply = match subcommand.args {
+            Args::Paths => continue,
+            Args::Shells => format!("@({})", quoted(shells())),
+            Args::None => "@()".to_string(),
+        };
+        args += &format!("            '{}' {{ {} }}\n", subcommand.name, reply);
+    }
+    format!(
+        r#"# The completions of funs for PowerShell, written by `funs completions powershell`
+Register-ArgumentCompleter -Native -CommandName funs -ScriptBlock {{
+    param($wordToComplete, $commandAst, $cursorPosition)
+    function Get-Paths($prefix, $filter) {{
+        $dir = Split-Path -Path $prefix
+        Get-ChildItem -Path "$prefix*" -ErrorAction SilentlyContinue |
+            Where-Object $filter |
+            ForEach-Object {{ if ($dir) {{ Join-Path $dir $_.Name }} else {{ $_.Name }} }}
+    }}
+    # The words before the one being completed
+    $words = @($commandAst.CommandElements |
+        Where-Object {{ $_.Extent.EndOffset -lt $cursorPosition -or !$wordToComplete }} |
+        ForEach-Object {{ $_.ToString() }})
+    if ($wordToComplete -and $words.Count -gt 0 -and $words[-1] -eq $wordToComplete) {{
+        $words = @($words | Select-Object -SkipLast 1)
+    }}
+    $subcommand = if ($words.Count -gt 1) {{ $words[1] }} else {{ 'check' }}
+    $candidates = $null
+    switch ($words[-1]) {{
+{values}    }}
+    if ($null -eq $candidates) {{
+        if ($wordToComplete.StartsWith('-')) {{
+            $candidates = switch ($subcommand) {{
+{flags}                default {{ @({check}) }}
+            }}
+            $candidates += @({global})
+        }} else {{
+            $candidates = switch ($subcommand) {{
+{args}                default {{ Get-Paths $wordToComplete {{ $_.PSIsContainer -or $_.Extension -eq '.fs' }} }}
+            }}
+            if ($words.Count -eq 1) {{
+                $candidates = @({subcommands}) + $candidates
+            }}
+        }}
+    }}
+    $candidates | Where-Object {{ $_ -like "$wordToComplete*" }} | ForEach-Object {{
+        [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)
+    }}
+}}
+"#,
+        check = quoted(flag_names(&SUBCOMMANDS[0])),
+        global = quoted(GLOBAL_FLAGS.to_vec()),
+        subcommands = quoted(subcommand_names()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{self, CliError, Command};
+
+    #[test]
+    fn test_completions_flags() {
+        // Every subcommand and flag completed is accepted by the command line
+        assert_eq!(cli::SUBCOMMANDS.len(), SUBCOMMANDS.len());
+        for subcommand in &SUBCOMMANDS {
+            assert!(cli::SUBCOMMANDS.contains(&subcommand.name));
+        }
+        for subcommand in &SUBCOMMANDS {
+            for &(flag, value) in subcommand.flags {
+                let mut args = vec![subcommand.name, flag];
+                if value != Value::None {
+                    args.push(words(value).first().copied().unwrap_or("1"));
+                }
+                args.push("main.fs");
+                let args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+                let parsed = Command::parse(&args);
+                assert_ne!(parsed, Err(CliError::UnknownFlag(flag.to_string())));
+                assert_ne!(parsed, Err(CliError::MissingFlagValue(flag.to_string())));
+            }
+        }
+    }
+
+    #[test]
+    fn test_completions_scripts() {
+        for shell in Shell::ALL {
+            assert_eq!(Shell::from_name(shell.name()), Some(shell));
+            let script = script(shell);
+            for subcommand in subcommand_names() {
+                assert!(
+                    script.contains(subcommand),
+                    "{} in {}",
+                    subcommand,
+                    shell.name()
+                );
+            }
+            assert!(script.contains("emit-debug-dir"));
+        }
+        assert_eq!(Shell::from_name("sh"), None);
+
+        let bash = script(Shell::Bash);
+        assert!(bash.contains("--locale) COMPREPLY=($(compgen -W \"en\" -- \"$cur\")); return ;;"));
+        assert!(bash.ends_with("complete -o filenames -F _funs funs\n"));
+        let fish = script(Shell::Fish);
+        assert!(fish.contains(
+            "complete -c funs -n \"__fish_seen_subcommand_from lint\" -l allow -x -a \"non_snake_case hole\"\n"
+        ));
+    }
+}
@@ -0,0 +1,239 @@
+use crate::diagnostics::renderer::{Charset, Renderer};
+use crate::source::DEFAULT_MAX_FILE_SIZE;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// The name of the project configuration file
+pub const CONFIG_FILE: &str = "funs.toml";
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io {
+        file_path: PathBuf,
+        error: std::io::Error,
+    },
+    Parse {
+        file_path: PathBuf,
+        message: String,
+    },
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ConfigError::Io { file_path, error } => {
+                write!(
+                    f,
+                    "Error reading file \"{}\": {}",
+                    file_path.display(),
+                    error
+                )
+            }
+            ConfigError::Parse { file_path, message } => {
+                write!(f, "Error parsing \"{}\": {}", file_path.display(), message)
+            }
+        }
+    }
+}
+
+/// The project configuration, read from a `funs.toml` file.
+///
+/// ```toml
+/// [compiler]
+/// extensions = ["fs", "funs"]
+/// max_file_size = 1048576
+/// lossy_utf8 = true
+///
+/// [diagnostics]
+/// context_lines = 2
+/// charset = "ascii"
+///
+/// [lints]
+/// allow = ["hole"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub compiler: CompilerConfig,
+    pub diagnostics: DiagnosticsConfig,
+    pub lints: LintsConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CompilerConfig {
+    /// The extensions (without the leading dot) of the files that are funs sources
+    pub extensions: Vec<String>,
+    /// The size in bytes above which a source file is reported instead of read
+    pub max_file_size: u64,
+    /// Whether the bytes of a source file that are not UTF-8 are reported and replaced,
+    /// so that the rest of the file is checked, instead of stopping at the first one
+    pub lossy_utf8: bool,
+}
+
+impl Default for CompilerConfig {
+    fn default() -> CompilerConfig {
+        CompilerConfig {
+            extensions: vec!["fs".to_string()],
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            lossy_utf8: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiagnosticsConfig {
+    /// The number of source lines shown before and after the line of a diagnostic
+    pub context_lines: usize,
+    /// The characters used to draw the source snippets, `unicode` or `ascii`
+    pub charset: Charset,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LintsConfig {
+    /// The lint rules turned off in the whole project, e.g. `non_snake_case`
+    pub allow: Vec<String>,
+}
+
+impl DiagnosticsConfig {
+    pub fn renderer(&self) -> Renderer {
+        Renderer::new()
+            .with_context_lines(self.context_lines)
+            .with_charset(self.charset)
+    }
+}
+
+impl Config {
+    /// Finds the `funs.toml` that applies to `path`: the closest one in the directory of
+    /// `path` (or `path` itself if it is a directory) and its parents.
+    pub fn find(path: &Path) -> Option<PathBuf> {
+        let path = std::path::absolute(path).ok()?;
+        let dir = if path.is_dir() {
+            path.as_path()
+        } else {
+            path.parent()?
+        };
+        dir.ancestors()
+            .map(|dir| dir.join(CONFIG_FILE))
+            .find(|file_path| file_path.is_file())
+    }
+
+    /// Reads the configuration that applies to `path` (see `find`), falling back to the
+    /// default configuration if there is none.
+    pub fn discover(path: &Path) -> Result<Config, ConfigError> {
+        match Config::find(path) {
+            Some(file_path) => Config::from_file(&file_path),
+            None => Ok(Config::default()),
+        }
+    }
+
+    pub fn from_file(file_path: &Path) -> Result<Config, ConfigError> {
+        let content = std::fs::read_to_string(file_path).map_err(|error| ConfigError::Io {
+            file_path: file_path.to_path_buf(),
+            error,
+        })?;
+        let config = Config::parse(&content).map_err(|message| ConfigError::Parse {
+            file_path: file_path.to_path_buf(),
+            message,
+        })?;
+        info!("Loaded configuration from \"{}\"", file_path.display());
+        Ok(config)
+    }
+
+    pub fn parse(content: &str) -> Result<Config, String> {
+        toml::from_str(content).map_err(|e| e.message().to_string())
+    }
+
+    /// Renders the configuration as the content of a `funs.toml`
+    pub fn to_toml(&self) -> String {
+        // Every field is a string, a number or a list of strings, it always serializes
+        toml::to_string(self).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::file_handler::create_tmp_dir;
+
+    #[test]
+    fn test_config_default() {
+        let config = Config::parse("").unwrap();
+        assert_eq!(config, Config::default());
+        assert_eq!(config.compiler.extensions, vec!["fs".to_string()]);
+    }
+
+    #[test]
+    fn test_config_extensions() {
+        let config = Config::parse("[compiler]\nextensions = [\"fs\", \"funs\"]\n").unwrap();
+        assert_eq!(
+            config.compiler.extensions,
+            vec!["fs".to_string(), "funs".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_config_max_file_size() {
+        let config = Config::parse("[compiler]\nmax_file_size = 1024\n").unwrap();
+        assert_eq!(config.compiler.max_file_size, 1024);
+        assert_eq!(
+            Config::default().compiler.max_file_size,
+            DEFAULT_MAX_FILE_SIZE
+        );
+        assert!(Config::parse("[compiler]\nmax_file_size = -1\n").is_err());
+    }
+
+    #[test]
+    fn test_config_lossy_utf8() {
+        assert!(!Config::default().compiler.lossy_utf8);
+        let config = Config::parse("[compiler]\nlossy_utf8 = true\n").unwrap();
+        assert!(config.compiler.lossy_utf8);
+    }
+
+    #[test]
+    fn test_config_diagnostics() {
+        let config =
+            Config::parse("[diagnostics]\ncontext_lines = 2\ncharset = \"ascii\"\n").unwrap();
+        assert_eq!(config.diagnostics.context_lines, 2);
+        assert_eq!(config.diagnostics.charset, Charset::Ascii);
+        assert_eq!(Config::default().diagnostics.charset, Charset::Unicode);
+        assert!(Config::parse("[diagnostics]\ncharset = \"emoji\"\n").is_err());
+    }
+
+    #[test]
+    fn test_config_discover() {
+        let dir = create_tmp_dir("config_discover");
+        std::fs::create_dir_all(dir.join("src/nested")).unwrap();
+        std::fs::write(dir.join(CONFIG_FILE), "[lints]\nallow = [\"hole\"]\n").unwrap();
+        std::fs::write(dir.join("src/nested/main.fs"), "").unwrap();
+
+        let main = dir.join("src/nested/main.fs");
+        assert_eq!(Config::find(&main), Some(dir.join(CONFIG_FILE)));
+        assert_eq!(Config::find(&dir.join("src")), Some(dir.join(CONFIG_FILE)));
+        let config = Config::discover(&main).unwrap();
+        assert_eq!(config.lints.allow, vec!["hole".to_string()]);
+
+        // The closest file wins
+        std::fs::write(dir.join("src").join(CONFIG_FILE), "").unwrap();
+        assert_eq!(Config::find(&main), Some(dir.join("src").join(CONFIG_FILE)));
+        assert_eq!(Config::discover(&main).unwrap(), Config::default());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_config_to_toml() {
+        let config =
+            Config::parse("[lints]\nallow = [\"hole\"]\n[diagnostics]\ncharset = \"ascii\"\n")
+                .unwrap();
+        assert_eq!(Config::parse(&config.to_toml()).unwrap(), config);
+        assert!(config.to_toml().contains("charset = \"ascii\""));
+    }
+
+    #[test]
+    fn test_config_unknown_field() {
+        assert!(Config::parse("[compiler]\nextension = \"fs\"\n").is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use serde::Serialize;
+use std::path::{Component, Path, PathBuf};
+use tracing::info;
+
+#[derive(Debug)]
+pub enum DebugDirError {
+    CreateDirectory {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Write {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Serialize {
+        path: PathBuf,
+        error: serde_json::Error,
+    },
+}
+
+impl std::fmt::Display for DebugDirError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DebugDirError::CreateDirectory { path, error } => {
+                write!(
+                    f,
+                    "Error creating directory \"{}\": {}",
+                    path.display(),
+                    error
+                )
+            }
+            DebugDirError::Write { path, error } => {
+                write!(f, "Error writing file \"{}\": {}", path.display(), error)
+            }
+            DebugDirError::Serialize { path, error } => {
+                write!(f, "Error serializing \"{}\": {}", path.display(), error)
+            }
+        }
+    }
+}
+
+/// A directory receiving the intermediate results of the compilation of each file,
+/// as JSON, for debugging.
+///
+/// The artifacts of `src/main.fs` for the `tokens` stage are written to
+/// `<dir>/src/main.fs.tokens.json`, so files with the same name do not collide.
+#[derive(Debug, Clone)]
+pub struct DebugDir {
+    dir: PathBuf,
+}
+
+impl DebugDir {
+    pub fn new(dir: &Path) -> DebugDir {
+        DebugDir {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    /// The path of the artifact of `stage` for `file_path`
+    pub fn path(&self, file_path: &Path, stage: &str) -> PathBuf {
+        let mut path = self.dir.clone();
+        path.extend(
+            file_path
+                .components()
+                .filter_map(|component| match component {
+                    Component::Normal(name) => Some(name),
+                    _ => None,
+                }),
+        );
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(".{}.json", stage));
+        path.set_file_name(name);
+        path
+    }
+
+    /// Writes `value` as the artifact of `stage` for `file_path`
+    pub fn emit(
+        &self,
+        file_path: &Path,
+        stage: &str,
+        value: &impl Serialize,
+    ) -> Result<(), DebugDirError> {
+        let path = self.path(file_path, stage);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|error| DebugDirError::CreateDirectory {
+                path: parent.to_path_buf(),
+                error,
+            })?;
+        }
+        let mut json =
+            serde_json::to_string_pretty(value).map_err(|error| DebugDirError::Serialize {
+                path: path.clone(),
+                error,
+            })?;
+        json.push('\n');
+        std::fs::write(&path, json).map_err(|error| DebugDirError::Write {
+            path: path.clone(),
+            error,
+        })?;
+        info!("Wrote \"{}\"", path.display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::file_handler::create_tmp_dir;
+
+    #[test]
+    fn test_debug_dir_path() {
+        let debug_dir = DebugDir::new(Path::new("out"));
+        assert_eq!(
+            debug_dir.path(Path::new("src/main.fs"), "tokens"),
+            Path::new("out/src/main.fs.tokens.json")
+        );
+        assert_eq!(
+            debug_dir.path(Path::new("./../lib/a.fs"), "cst"),
+            Path::new("out/lib/a.fs.cst.json")
+        );
+    }
+
+    #[test]
+    fn test_debug_dir_emit() {
+        let dir = create_tmp_dir("debug_dir_emit");
+        let debug_dir = DebugDir::new(&dir);
+        debug_dir
+            .emit(Path::new("sub/a.fs"), "tokens", &vec!["x", "y"])
+            .unwrap();
+        let content = std::fs::read_to_string(dir.join("sub/a.fs.tokens.json")).unwrap();
+        assert_eq!(content, "[\n  \"x\",\n  \"y\"\n]\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
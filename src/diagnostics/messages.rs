@@ -0,0 +1,362 @@
+//! The text of every diagnostic, keyed by its code.
+//!
+//! A diagnostic is built as a `Message`, a code along with named arguments, and only
+//! turned into text by `Message::render` in the locale asked for. Templates refer to
+//! the arguments by name, e.g. `Expected a type, found {found}`, so that a translation
+//! can put them in any order.
+//!
+//! Adding a locale means adding a variant to `Locale` and a template for every code in
+//! `template`; the compiler points at each missing one.
+
+/// The language diagnostics are written in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+}
+
+impl Locale {
+    /// Every locale, in the order they are listed to users
+    pub const ALL: [Locale; 1] = [Locale::En];
+
+    /// The locale named `name`, e.g. `en`
+    pub fn from_name(name: &str) -> Option<Locale> {
+        Locale::ALL.into_iter().find(|locale| locale.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Locale::En => "en",
+        }
+    }
+}
+
+/// The code of a diagnostic, errors start with `E` and warnings with `W`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Code {
+    ExpectedToken,
+    ExpectedEndOfInput,
+    ExpectedStatement,
+    ExpectedAssign,
+    ExpectedType,
+    ExpectedExpression,
+    DuplicateDeclaration,
+    UnknownNumericSuffix,
+    IntegerOverflow,
+    InvalidNumber,
+    MalformedTree,
+    UnterminatedBlockComment,
+    UnknownName,
+    TypeMismatch,
+    InvalidOperand,
+    InvalidOperands,
+    InvalidCast,
+    DivisionByZero,
+    ArithmeticOverflow,
+    HoleReached,
+    CallDepth,
+    MisplacedAttribute,
+    UnknownAttribute,
+    AttributeWithParams,
+    ExpectedPattern,
+    NonExhaustiveMatch,
+    TailRecNonTailCall,
+    PatternMismatch,
+    DuplicateField,
+    UnknownField,
+    MissingField,
+    LowercaseConstructor,
+    InvalidUtf8,
+    ModuleNotFound,
+    InvalidModule,
+    ImportCycle,
+    UnterminatedString,
+    NestingTooDeep,
+    ConstructorArity,
+    NonSnakeCase,
+    Hole,
+    UnknownLintRule,
+    DeprecatedBool,
+    DeprecatedType,
+    NonTailCall,
+    HoleType,
+}
+
+impl Code {
+    /// Every code, in the order they are numbered
+    pub const ALL: [Code; 46] = [
+        Code::ExpectedToken,
+        Code::ExpectedEndOfInput,
+        Code::ExpectedStatement,
+        Code::ExpectedAssign,
+        Code::ExpectedType,
+        Code::ExpectedExpression,
+        Code::DuplicateDeclaration,
+        Code::UnknownNumericSuffix,
+        Code::IntegerOverflow,
+        Code::InvalidNumber,
+        Code::MalformedTree,
+        Code::UnterminatedBlockComment,
+        Code::UnknownName,
+        Code::TypeMismatch,
+        Code::InvalidOperand,
+        Code::InvalidOperands,
+        Code::InvalidCast,
+        Code::DivisionByZero,
+        Code::ArithmeticOverflow,
+        Code::HoleReached,
+        Code::CallDepth,
+        Code::MisplacedAttribute,
+        Code::UnknownAttribute,
+        Code::AttributeWithParams,
+        Code::ExpectedPattern,
+        Code::NonExhaustiveMatch,
+        Code::TailRecNonTailCall,
+        Code::PatternMismatch,
+        Code::DuplicateField,
+        Code::UnknownField,
+        Code::MissingField,
+        Code::LowercaseConstructor,
+        Code::InvalidUtf8,
+        Code::ModuleNotFound,
+        Code::InvalidModule,
+        Code::ImportCycle,
+        Code::UnterminatedString,
+        Code::NestingTooDeep,
+        Code::ConstructorArity,
+        Code::NonSnakeCase,
+        Code::Hole,
+        Code::UnknownLintRule,
+        Code::DeprecatedBool,
+        Code::DeprecatedType,
+        Code::NonTailCall,
+        Code::HoleType,
+    ];
+
+    /// The code as shown to users, e.g. `E0005`. A code is never reused for another
+    /// diagnostic.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Code::ExpectedToken => "E0001",
+            Code::ExpectedEndOfInput => "E0002",
+            Code::ExpectedStatement => "E0003",
+            Code::ExpectedAssign => "E0004",
+            Code::ExpectedType => "E0005",
+            Code::ExpectedExpression => "E0006",
+            Code::DuplicateDeclaration => "E0007",
+            Code::UnknownNumericSuffix => "E0008",
+            Code::IntegerOverflow => "E0010",
+            Code::InvalidNumber => "E0011",
+            Code::MalformedTree => "E0012",
+            Code::UnterminatedBlockComment => "E0013",
+            Code::UnknownName => "E0014",
+            Code::TypeMismatch => "E0015",
+            Code::InvalidOperand => "E0016",
+            Code::InvalidOperands => "E0017",
+            Code::InvalidCast => "E0018",
+            Code::DivisionByZero => "E0019",
+            Code::ArithmeticOverflow => "E0020",
+            Code::HoleReached => "E0021",
+            Code::CallDepth => "E0022",
+            Code::MisplacedAttribute => "E0023",
+            Code::UnknownAttribute => "E0024",
+            Code::AttributeWithParams => "E0025",
+            Code::ExpectedPattern => "E0026",
+            Code::NonExhaustiveMatch => "E0027",
+            Code::TailRecNonTailCall => "E0028",
+            Code::PatternMismatch => "E0029",
+            Code::DuplicateField => "E0030",
+            Code::UnknownField => "E0031",
+            Code::MissingField => "E0032",
+            Code::LowercaseConstructor => "E0033",
+            Code::InvalidUtf8 => "E0034",
+            Code::ModuleNotFound => "E0035",
+            Code::InvalidModule => "E0036",
+            Code::ImportCycle => "E0037",
+            Code::UnterminatedString => "E0038",
+            Code::NestingTooDeep => "E0039",
+            Code::ConstructorArity => "E0040",
+            Code::NonSnakeCase => "W0001",
+            Code::Hole => "W0002",
+            Code::UnknownLintRule => "W0003",
+            Code::DeprecatedBool => "W0004",
+            Code::DeprecatedType => "W0005",
+            Code::NonTailCall => "W0006",
+            Code::HoleType => "W0007",
+        }
+    }
+}
+
+impl std::fmt::Display for Code {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// The words used inside messages rather than as messages of their own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phrase {
+    EndOfFile,
+    EndOfLine,
+}
+
+impl Phrase {
+    pub fn render(&self, locale: Locale) -> &'static str {
+        match (locale, self) {
+            (Locale::En, Phrase::EndOfFile) => "end of file",
+            (Locale::En, Phrase::EndOfLine) => "end of line",
+        }
+    }
+}
+
+/// The template of `code` in `locale`, with `{name}` where the argument `name` goes
+fn template(code: Code, locale: Locale) -> &'static str {
+    match locale {
+        Locale::En => match code {
+            Code::ExpectedToken => "Expected {expected}",
+            Code::ExpectedEndOfInput => "Expected end of input, found {found}",
+            Code::ExpectedStatement => "Expected statement",
+            Code::ExpectedAssign => "Expected `=`, found {found}",
+            Code::ExpectedType => "Expected a type, found {found}",
+            Code::ExpectedExpression => "Expected expression",
+            Code::DuplicateDeclaration => {
+                "`{name}` is declared more than once, first at {first}, again at {second}"
+            }
+            Code::UnknownNumericSuffix => {
+                "Unknown numeric suffix in {found}, expected one of `i`, `_int`, `f` or `_float`"
+            }
+            Code::IntegerOverflow => "`{literal}` does not fit in an `int`",
+            Code::InvalidNumber => "`{literal}` is not a valid number",
+            Code::MalformedTree => "malformed {kind}, it is left out of the AST",
+            Code::UnterminatedBlockComment => "unterminated block comment",
+            Code::UnknownName => "`{name}` is not defined",
+            Code::TypeMismatch => "expected `{expected}`, found `{found}`",
+            Code::InvalidOperand => "`{op}` can not be applied to `{ty}`",
+            Code::InvalidOperands => "`{op}` can not be applied to `{lhs}` and `{rhs}`",
+            Code::InvalidCast => "`{from}` can not be cast to `{to}`",
+            Code::DivisionByZero => "division by zero",
+            Code::ArithmeticOverflow => "the result of `{op}` does not fit in an `int`",
+            Code::HoleReached => "reached `{hole}`, this code is not written yet",
+            Code::CallDepth => "too many nested calls, the limit is {limit}",
+            Code::MisplacedAttribute => "attributes only apply to top-level functions",
+            Code::UnknownAttribute => {
+                "unknown attribute `@{name}`, expected `@test`, `@bench` or `@tailrec`"
+            }
+            Code::AttributeWithParams => "a `@{name}` function can not have parameters",
+            Code::ExpectedPattern => "Expected a pattern, found {found}",
+            Code::NonExhaustiveMatch => "no arm of the `match` matches `{value}`",
+            Code::TailRecNonTailCall => {
+                "`{name}` is marked `@tailrec`, but this recursive call is not a tail call"
+            }
+            Code::PatternMismatch => "`{value}` does not match the pattern of the declaration",
+            Code::DuplicateField => "the field `{field}` appears more than once",
+            Code::UnknownField => "`{ty}` has no field `{field}`",
+            Code::MissingField => "`{ty}` needs a value for its field `{field}`",
+            Code::LowercaseConstructor => {
+                "the constructor `{name}` must start with an uppercase letter"
+            }
+            Code::InvalidUtf8 => "invalid UTF-8 at byte {offset}: {bytes}",
+            Code::ModuleNotFound => "the module `{name}` is not found, expected the file {path}",
+            Code::InvalidModule => "the module `{name}` can not be imported, {path} has errors",
+            Code::ImportCycle => "the imports form a cycle: {cycle}",
+            Code::UnterminatedString => "unterminated string literal",
+            Code::NestingTooDeep => "this is nested too deeply, the limit is {limit} levels",
+            Code::ConstructorArity => {
+                "the constructor `{name}` has {expected} field(s), the pattern has {found}"
+            }
+            Code::NonSnakeCase => "`{name}` should have a snake case name such as `{suggestion}`",
+            Code::Hole => "unfinished code, `{hole}` is a hole",
+            Code::UnknownLintRule => "unknown lint rule `{name}`",
+            Code::DeprecatedBool => "the boolean `{old}` is deprecated, use `{new}` instead",
+            Code::DeprecatedType => "the type `{old}` is deprecated, use `{new}` instead",
+            Code::NonTailCall => {
+                "this recursive call is not a tail call and may overflow for large inputs"
+            }
+            Code::HoleType => "`{hole}` stands for a value of type `{ty}`",
+        },
+    }
+}
+
+/// A diagnostic before it is rendered in a locale
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub code: Code,
+    args: Vec<(&'static str, String)>,
+}
+
+impl Message {
+    pub fn new(code: Code) -> Message {
+        Message {
+            code,
+            args: Vec::new(),
+        }
+    }
+
+    pub fn with_arg(mut self, name: &'static str, value: impl ToString) -> Message {
+        self.args.push((name, value.to_string()));
+        self
+    }
+
+    /// The message in `locale`, with the arguments in place of their names. A name
+    /// without an argument is left as is.
+    pub fn render(&self, locale: Locale) -> String {
+        let mut rendered = String::new();
+        let mut rest = template(self.code, locale);
+        while let Some(start) = rest.find('{') {
+            let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+                break;
+            };
+            let name = &rest[start + 1..end];
+            rendered.push_str(&rest[..start]);
+            match self.args.iter().find(|(arg, _)| *arg == name) {
+                Some((_, value)) => rendered.push_str(value),
+                None => rendered.push_str(&rest[start..=end]),
+            }
+            rest = &rest[end + 1..];
+        }
+        rendered.push_str(rest);
+        rendered
+    }
+}
+
+impl std::fmt::Display for Message {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.render(Locale::default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_messages_render() {
+        let message = Message::new(Code::ExpectedType).with_arg("found", "`=`");
+        assert_eq!(message.render(Locale::En), "Expected a type, found `=`");
+        assert_eq!(message.to_string(), "Expected a type, found `=`");
+        assert_eq!(
+            Message::new(Code::ExpectedType).render(Locale::En),
+            "Expected a type, found {found}"
+        );
+        assert_eq!(Locale::from_name("en"), Some(Locale::En));
+        assert_eq!(Locale::from_name("xx"), None);
+    }
+
+    #[test]
+    fn test_messages_catalog() {
+        let mut codes = Code::ALL.iter().map(Code::as_str).collect::<Vec<_>>();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), Code::ALL.len(), "a code is used twice");
+        for locale in Locale::ALL {
+            for code in Code::ALL {
+                assert!(
+                    !template(code, locale).is_empty(),
+                    "{} {}",
+                    locale.name(),
+                    code
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+pub mod messages;
+pub mod renderer;
+
+use crate::lexer::token::TokenLocation;
+use messages::{Locale, Message};
+
+/// How bad a diagnostic is: errors make the compilation fail, warnings do not
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A problem found in the source, reported by the phase that found it (the lexer, the
+/// parser, ...) and rendered with the source lines it points at by `renderer::Renderer`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: Message,
+    pub location: TokenLocation,
+    /// Extra explanations, shown after the source lines
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: Message, location: TokenLocation) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            message,
+            location,
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn warning(message: Message, location: TokenLocation) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(message, location)
+        }
+    }
+
+    pub fn with_note(mut self, note: impl ToString) -> Diagnostic {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// The message in `locale`, without the location nor the notes
+    pub fn render(&self, locale: Locale) -> String {
+        self.message.render(locale)
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: {}: {}", self.location, self.severity, self.message)
+    }
+}
@@ -0,0 +1,285 @@
+use super::messages::Locale;
+use super::{Diagnostic, Severity};
+use crate::lexer::token::TokenLocation;
+use crate::source::Source;
+use crate::utils::color;
+use serde::{Deserialize, Serialize};
+
+/// The characters used to draw the source snippets
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Charset {
+    #[default]
+    Unicode,
+    Ascii,
+}
+
+impl Charset {
+    fn arrow(&self) -> &'static str {
+        match self {
+            Charset::Unicode => "╭─▶",
+            Charset::Ascii => "-->",
+        }
+    }
+
+    fn gutter(&self) -> &'static str {
+        match self {
+            Charset::Unicode => "│",
+            Charset::Ascii => "|",
+        }
+    }
+
+    fn underline(&self) -> &'static str {
+        match self {
+            Charset::Unicode => "━",
+            Charset::Ascii => "^",
+        }
+    }
+}
+
+/// Renders a message along with the source lines it points at.
+///
+/// ```text
+/// error: Expected `=`, found `~`
+///   --> main.fs:1:8
+///   |
+/// 1 | x: int ~ = 0
+///   |        ^
+/// ```
+///
+/// Every phase reports through the same renderer, so that all diagnostics look alike.
+#[derive(Debug, Clone, Default)]
+pub struct Renderer {
+    /// The number of lines shown before and after the line of the location
+    context_lines: usize,
+    charset: Charset,
+    color: bool,
+}
+
+impl Renderer {
+    pub fn new() -> Renderer {
+        Renderer::default()
+    }
+
+    pub fn with_context_lines(mut self, context_lines: usize) -> Renderer {
+        self.context_lines = context_lines;
+        self
+    }
+
+    pub fn with_charset(mut self, charset: Charset) -> Renderer {
+        self.charset = charset;
+        self
+    }
+
+    pub fn with_color(mut self, color: bool) -> Renderer {
+        self.color = color;
+        self
+    }
+
+    /// Renders the error `message` pointing at `location` in `source`.
+    ///
+    /// Lines and columns are 0-based in `location` and displayed 1-based.
+    pub fn render(&self, message: &str, source: &Source, location: &TokenLocation) -> String {
+        self.render_parts(Severity::Error, message, &[], source, location)
+    }
+
+    /// Renders `diagnostic` in `locale`, followed by its notes.
+    ///
+    /// ```text
+    /// error: unterminated block comment
+    ///   --> main.fs:2:1
+    ///   |
+    /// 2 | #| a #| b |#
+    ///   | ^^^^^^^^^^^^
+    ///   = note: block comments nest, every `#|` needs its own `|#`
+    /// ```
+    pub fn render_diagnostic(
+        &self,
+        diagnostic: &Diagnostic,
+        source: &Source,
+        locale: Locale,
+    ) -> String {
+        self.render_parts(
+            diagnostic.severity,
+            &diagnostic.render(locale),
+            &diagnostic.notes,
+            source,
+            &diagnostic.location,
+        )
+    }
+
+    fn render_parts(
+        &self,
+        severity: Severity,
+        message: &str,
+        notes: &[String],
+        source: &Source,
+        location: &TokenLocation,
+    ) -> String {
+        let highlight = match severity {
+            Severity::Error => color::red,
+            Severity::Warning => color::yellow,
+        };
+        let lines = source.content().lines().collect::<Vec<_>>();
+        let first = location.line.saturating_sub(self.context_lines);
+        let last = (location.line + self.context_lines).min(lines.len().saturating_sub(1));
+        let width = (last + 1).to_string().len();
+        let gutter = self.paint(color::blue, self.charset.gutter());
+        let padding = " ".repeat(width);
+
+        let mut rendered = format!(
+            "{}: {}\n",
+            self.paint(highlight, severity.as_str()),
+            message
+        );
+        rendered.push_str(&format!(
+            "{} {} {}\n",
+            padding,
+            self.paint(color::blue, self.charset.arrow()),
+            location
+        ));
+        rendered.push_str(&format!("{} {}\n", padding, gutter));
+        for (index, line) in lines.iter().enumerate().take(last + 1).skip(first) {
+            let number = format!("{:>width$}", index + 1, width = width);
+            rendered.push_str(&format!(
+                "{} {} {}\n",
+                self.paint(color::blue, &number),
+                gutter,
+                line
+            ));
+            if index == location.line {
+                let underline = self.charset.underline().repeat(
+                    location
+                        .column_end
+                        .saturating_sub(location.column_start)
+                        .max(1),
+                );
+                rendered.push_str(&format!(
+                    "{} {} {}{}\n",
+                    padding,
+                    gutter,
+                    " ".repeat(location.column_start),
+                    self.paint(highlight, &underline)
+                ));
+            }
+        }
+        // The location is past the last line, e.g. at the end of the file
+        if location.line >= lines.len() {
+            rendered.push_str(&format!(
+                "{} {} {}\n",
+                padding,
+                gutter,
+                self.paint(highlight, self.charset.underline())
+            ));
+        }
+        for note in notes {
+            rendered.push_str(&format!(
+                "{} {} note: {}\n",
+                padding,
+                self.paint(color::blue, "="),
+                note
+            ));
+        }
+        rendered
+    }
+
+    fn paint(&self, paint: fn(&str) -> String, s: &str) -> String {
+        if self.color {
+            paint(s)
+        } else {
+            s.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::messages::{Code, Message};
+    use std::path::PathBuf;
+
+    fn location(line: usize, column_start: usize, column_end: usize) -> TokenLocation {
+        TokenLocation::new(PathBuf::from("main.fs"), line, column_start, column_end)
+    }
+
+    #[test]
+    fn test_renderer_ascii() {
+        let source = Source::from("x: int ~ = 0\n".to_string());
+        let rendered = Renderer::new().with_charset(Charset::Ascii).render(
+            "Expected `=`, found `~`",
+            &source,
+            &location(0, 7, 8),
+        );
+        assert_eq!(
+            rendered,
+            "error: Expected `=`, found `~`\n  \
+             --> main.fs:1:8\n  \
+             |\n\
+             1 | x: int ~ = 0\n  \
+             |        ^\n"
+        );
+    }
+
+    #[test]
+    fn test_renderer_unicode_context_lines() {
+        let source = Source::from("a: int = 1\nb: int = ~\nc: int = 3\n".to_string());
+        let rendered = Renderer::new().with_context_lines(1).render(
+            "Expected expression",
+            &source,
+            &location(1, 9, 10),
+        );
+        assert_eq!(
+            rendered,
+            "error: Expected expression\n  \
+             ╭─▶ main.fs:2:10\n  \
+             │\n\
+             1 │ a: int = 1\n\
+             2 │ b: int = ~\n  \
+             │          ━\n\
+             3 │ c: int = 3\n"
+        );
+    }
+
+    #[test]
+    fn test_renderer_end_of_file() {
+        let source = Source::from("x: int =".to_string());
+        let rendered = Renderer::new().with_charset(Charset::Ascii).render(
+            "Expected expression",
+            &source,
+            &location(1, 0, 0),
+        );
+        assert!(rendered.ends_with("  |\n  | ^\n"), "{}", rendered);
+    }
+
+    #[test]
+    fn test_renderer_diagnostic() {
+        let source = Source::from("x: int = 1\n#| a #| b |#\n".to_string());
+        let diagnostic = Diagnostic::warning(
+            Message::new(Code::UnterminatedBlockComment),
+            location(1, 0, 2),
+        )
+        .with_note("block comments nest");
+        let rendered = Renderer::new()
+            .with_charset(Charset::Ascii)
+            .render_diagnostic(&diagnostic, &source, Locale::En);
+        assert_eq!(
+            rendered,
+            "warning: unterminated block comment\n  \
+             --> main.fs:2:1\n  \
+             |\n\
+             2 | #| a #| b |#\n  \
+             | ^^\n  \
+             = note: block comments nest\n"
+        );
+
+        let colored =
+            Renderer::new()
+                .with_color(true)
+                .render_diagnostic(&diagnostic, &source, Locale::En);
+        assert!(
+            colored.starts_with(&color::yellow("warning")),
+            "{}",
+            colored
+        );
+    }
+}
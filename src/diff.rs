@@ -0,0 +1,192 @@
+use crate::lexer::token::{Token, TokenKind, TokenLocation};
+use crate::parser::{Tree, TreeKind};
+
+/// A top-level declaration, rendered independently of its formatting
+#[derive(Debug, Clone, PartialEq)]
+pub struct Declaration {
+    /// `StmtVarDecl` or `StmtFunDecl`
+    pub kind: TreeKind,
+    pub name: String,
+    /// The declared type
+    pub signature: String,
+    /// The initializer, for a function its parameters and body
+    pub value: String,
+    /// The location of the declared name
+    pub location: TokenLocation,
+}
+
+/// A semantic difference between two files
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added(Declaration),
+    Removed(Declaration),
+    SignatureChanged {
+        before: Declaration,
+        after: Declaration,
+    },
+    ValueChanged {
+        before: Declaration,
+        after: Declaration,
+    },
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Change::Added(declaration) => {
+                write!(f, "+ {}: {}", declaration.name, declaration.signature)
+            }
+            Change::Removed(declaration) => {
+                write!(f, "- {}: {}", declaration.name, declaration.signature)
+            }
+            Change::SignatureChanged { before, after } => write!(
+                f,
+                "~ {}: signature changed from `{}` to `{}`",
+                before.name, before.signature, after.signature
+            ),
+            Change::ValueChanged { before, after } => write!(
+                f,
+                "~ {}: value changed from `{}` to `{}`",
+                before.name, before.value, after.value
+            ),
+        }
+    }
+}
+
+/// Collects the top-level declarations of a file, ignoring comments.
+pub fn declarations(file: &Tree) -> Vec<Declaration> {
+    file.child_trees()
+        .filter(|tree| matches!(tree.kind(), TreeKind::StmtVarDecl | TreeKind::StmtFunDecl))
+        .filter_map(|tree| {
+            let tokens = tree.tokens();
+            let name = tree.declared_name()?;
+            let signature = tree
+                .child_trees()
+                .find(|child| *child.kind() == TreeKind::TypeExpr)
+                .map_or(String::new(), |child| render(&child.tokens()));
+            // Everything after the `=`, the `;` ending a function is not part of its value
+            let value = tokens
+                .iter()
+                .skip_while(|token| token.kind != TokenKind::TokenAssign)
+                .skip(1)
+                .filter(|token| token.kind != TokenKind::TokenSemicolon)
+                .copied()
+                .collect::<Vec<_>>();
+            Some(Declaration {
+                kind: tree.kind().clone(),
+                name: name.lexeme.clone(),
+                signature,
+                value: render(&value),
+                location: name.location.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Computes the declarations added, removed or changed going from `before` to `after`.
+pub fn diff(before: &Tree, after: &Tree) -> Vec<Change> {
+    let before = declarations(before);
+    let mut after = declarations(after);
+    let mut changes = Vec::new();
+
+    for old in before {
+        let Some(index) = after.iter().position(|new| new.name == old.name) else {
+            changes.push(Change::Removed(old));
+            continue;
+        };
+        let new = after.remove(index);
+        if old.signature != new.signature {
+            changes.push(Change::SignatureChanged {
+                before: old,
+                after: new,
+            });
+        } else if old.value != new.value {
+            changes.push(Change::ValueChanged {
+                before: old,
+                after: new,
+            });
+        }
+    }
+    changes.extend(after.into_iter().map(Change::Added));
+    changes
+}
+
+/// Renders tokens with a canonical spacing, so that formatting does not matter.
+fn render(tokens: &[&Token]) -> String {
+    let mut rendered = String::new();
+    for token in tokens {
+        if matches!(
+            token.kind,
+            TokenKind::TokenNewLine | TokenKind::TokenComment | TokenKind::TokenEOF
+        ) {
+            continue;
+        }
+        let glue = rendered.is_empty()
+            || rendered.ends_with('(')
+            || rendered.ends_with('[')
+            || rendered.ends_with('<')
+            || matches!(
+                token.kind,
+                TokenKind::TokenCloseParen
+                    | TokenKind::TokenCloseBracket
+                    | TokenKind::TokenComma
+                    | TokenKind::TokenLess
+                    | TokenKind::TokenGreater
+            );
+        if !glue {
+            rendered.push(' ');
+        }
+        rendered.push_str(&token.lexeme);
+    }
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer::Lexer, parser::Parser, source::Source};
+
+    fn parse(content: &str) -> Tree {
+        Parser::new(Lexer::new(&Source::from(content.to_string()))).parse()
+    }
+
+    #[test]
+    fn test_diff_ignores_formatting_and_comments() {
+        let before = parse("# the answer\nx: int = 42\ny: (int, str) = 1\n");
+        let after = parse("x:int=42\n# a comment\n   y :  ( int,str )  =  1\n");
+        assert_eq!(diff(&before, &after), vec![]);
+    }
+
+    #[test]
+    fn test_diff_functions() {
+        let before = parse("f: (int) -> int = (x) -> x ;\n");
+        let after = parse("f: (int) -> int = (y) ->\n  y\n;\n");
+        let changes = diff(&before, &after)
+            .iter()
+            .map(|change| change.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec!["~ f: value changed from `(x) -> x` to `(y) -> y`"]
+        );
+    }
+
+    #[test]
+    fn test_diff_changes() {
+        let before = parse("x: int = 1\ny: str = \"a\"\nz: bool = true\n");
+        let after = parse("x: int = 2\ny: float = 1.0\nw: int = 0\n");
+        let changes = diff(&before, &after)
+            .iter()
+            .map(|change| change.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                "~ x: value changed from `1` to `2`",
+                "~ y: signature changed from `str` to `float`",
+                "- z: bool",
+                "+ w: int",
+            ]
+        );
+    }
+}
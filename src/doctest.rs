@@ -0,0 +1,145 @@
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::source::Source;
+use std::path::PathBuf;
+
+const FENCE: &str = "```";
+
+/// A code example found in a comment block.
+///
+/// ```text
+/// # Adds one to its argument
+/// #
+/// # ```
+/// # y: int = 1
+/// # ```
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocTest {
+    /// The example as a virtual source named `<doctest>`
+    pub source: Source,
+    /// The file containing the comment
+    pub file_path: PathBuf,
+    /// The 0-based line of the first line of code of the example
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocTestFailure {
+    pub file_path: PathBuf,
+    pub line: usize,
+    pub errors: Vec<String>,
+}
+
+impl std::fmt::Display for DocTestFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}: doc example failed to compile: {}",
+            self.file_path.display(),
+            self.line + 1,
+            self.errors.join(", ")
+        )
+    }
+}
+
+/// Extracts the fenced code examples contained in the comments of `source`.
+///
+/// An example starts with a comment line made of "```" (optionally followed by `funs`)
+/// and ends at the next "```" comment line or at the first line that is not a comment.
+pub fn extract(source: &Source) -> Vec<DocTest> {
+    let mut doctests = Vec::new();
+    let mut example: Option<(usize, String)> = None;
+
+    for (line, text) in source.content().lines().enumerate() {
+        let comment = text.trim_start().strip_prefix('#').map(|comment| {
+            let comment = comment.strip_prefix(' ').unwrap_or(comment);
+            comment.trim_end()
+        });
+        match (comment, example.take()) {
+            (Some(comment), None) if is_opening_fence(comment) => {
+                example = Some((line + 1, String::new()));
+            }
+            (Some(comment), Some((start, code))) if comment == FENCE => {
+                doctests.push(DocTest {
+                    source: Source::from_snippet("doctest", code),
+                    file_path: source.file_path().clone(),
+                    line: start,
+                });
+            }
+            (Some(comment), Some((start, mut code))) => {
+                code.push_str(comment);
+                code.push('\n');
+                example = Some((start, code));
+            }
+            (_, _) => {}
+        }
+    }
+    doctests
+}
+
+fn is_opening_fence(comment: &str) -> bool {
+    matches!(comment.strip_prefix(FENCE), Some("") | Some("funs"))
+}
+
+/// Compiles a doc example, reporting the errors at the location of the comment.
+pub fn run(doctest: &DocTest) -> Result<(), DocTestFailure> {
+    let (_tree, errors) = Parser::new(Lexer::new(&doctest.source)).parse_with_errors();
+    if errors.is_empty() {
+        return Ok(());
+    }
+    Err(DocTestFailure {
+        file_path: doctest.file_path.clone(),
+        line: doctest.line,
+        errors,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_doctest_extract() {
+        let source = Source::from(
+            "# The answer\n\
+             #\n\
+             # ```\n\
+             # answer: int = 42\n\
+             # ```\n\
+             answer: int = 42\n\
+             # ```funs\n\
+             # x: float = 1.0\n\
+             # ```\n\
+             # ```text\n\
+             # not an example\n\
+             # ```\n"
+                .to_string(),
+        );
+        let doctests = extract(&source);
+        assert_eq!(doctests.len(), 2);
+        assert_eq!(doctests[0].line, 3);
+        assert_eq!(doctests[0].source.content(), "answer: int = 42\n");
+        assert!(doctests[0].source.is_virtual());
+        assert_eq!(doctests[1].line, 7);
+        assert_eq!(doctests[1].source.content(), "x: float = 1.0\n");
+    }
+
+    #[test]
+    fn test_doctest_unterminated_example_is_ignored() {
+        let source = Source::from("# ```\n# x: int = 1\nx: int = 1\n".to_string());
+        assert!(extract(&source).is_empty());
+    }
+
+    #[test]
+    fn test_doctest_run() {
+        let source =
+            Source::from("# ```\n# x: int = 1\n# ```\n# ```\n# x: int = \n# ```\n".to_string());
+        let doctests = extract(&source);
+        assert_eq!(doctests.len(), 2);
+        assert_eq!(run(&doctests[0]), Ok(()));
+        let failure = run(&doctests[1]).unwrap_err();
+        assert_eq!(failure.line, 4);
+        assert!(!failure.errors.is_empty());
+    }
+}
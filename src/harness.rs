@@ -0,0 +1,286 @@
+//! The runners of `funs test` and `funs bench`, for the top-level functions marked
+//! `@test` and `@bench`.
+//!
+//! The program runs first, after the modules it imports, so that the functions see its
+//! declarations, then each marked function is called without arguments in declaration
+//! order. `funs bench --program` times the whole program instead, see `run_program`.
+use crate::diagnostics::Diagnostic;
+use crate::interpreter::{Interpreter, Value};
+use crate::modules::{self, Module};
+use crate::parser::ast::{Attribute, File, FunDecl, Stmt};
+use std::time::{Duration, Instant};
+
+/// How many times `funs bench` runs each function by default
+pub const DEFAULT_ITERATIONS: usize = 100;
+
+/// How many untimed runs `funs bench` makes before the timed ones by default
+pub const DEFAULT_WARMUP: usize = 1;
+
+#[derive(Debug)]
+pub struct TestResult {
+    pub name: String,
+    /// What the test printed, shown when it fails
+    pub output: String,
+    pub failure: Option<TestFailure>,
+}
+
+#[derive(Debug)]
+pub enum TestFailure {
+    ReturnedFalse,
+    Error(Diagnostic),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchOptions {
+    /// The timed runs of each benchmark
+    pub iterations: usize,
+    /// The untimed runs before them
+    pub warmup: usize,
+}
+
+impl Default for BenchOptions {
+    fn default() -> BenchOptions {
+        BenchOptions {
+            iterations: DEFAULT_ITERATIONS,
+            warmup: DEFAULT_WARMUP,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct BenchResult {
+    pub name: String,
+    pub iterations: usize,
+    pub mean: Duration,
+    /// The standard deviation of the iterations
+    pub stddev: Duration,
+    /// The expressions evaluated by one run, the same for every run, see
+    /// `Interpreter::steps`
+    pub steps: u64,
+}
+
+impl std::fmt::Display for BenchResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: mean {:?}, stddev {:?}, {} steps over {} iterations",
+            self.name, self.mean, self.stddev, self.steps, self.iterations
+        )
+    }
+}
+
+/// The top-level functions of `file` marked with `attribute`
+pub fn marked(file: &File, attribute: Attribute) -> Vec<&FunDecl> {
+    file.stmts
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::FunDecl(decl) if decl.attributes.contains(&attribute) => Some(&**decl),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Runs the `@test` functions of `file`, a test passes unless it stops with an error or
+/// returns `false`. Fails if the program itself stops with an error.
+pub fn run_tests(file: &File, modules: &[Module]) -> Result<Vec<TestResult>, Diagnostic> {
+    let mut interpreter = Interpreter::new(Vec::new());
+    modules::run(&mut interpreter, modules)?;
+    interpreter.run(file)?;
+    interpreter.output().clear();
+    let results = marked(file, Attribute::Test)
+        .into_iter()
+        .map(|decl| {
+            let failure = match interpreter.call_global(&decl.name) {
+                Ok(Value::Bool(false)) => Some(TestFailure::ReturnedFalse),
+                Ok(_) => None,
+                Err(diagnostic) => Some(TestFailure::Error(diagnostic)),
+            };
+            let output = std::mem::take(interpreter.output());
+            TestResult {
+                name: decl.name.name.clone(),
+                output: String::from_utf8_lossy(&output).into_owned(),
+                failure,
+            }
+        })
+        .collect();
+    Ok(results)
+}
+
+/// Times the calls of each `@bench` function of `file`. What they print is discarded.
+/// Fails at the first error.
+pub fn run_benches(
+    file: &File,
+    modules: &[Module],
+    options: &BenchOptions,
+) -> Result<Vec<BenchResult>, Diagnostic> {
+    let mut interpreter = Interpreter::new(std::io::sink());
+    modules::run(&mut interpreter, modules)?;
+    interpreter.run(file)?;
+    marked(file, Attribute::Bench)
+        .into_iter()
+        .map(|decl| {
+            measure(&decl.name.name, options, || {
+                let start = interpreter.steps();
+                interpreter.call_global(&decl.name)?;
+                Ok(interpreter.steps() - start)
+            })
+        })
+        .collect()
+}
+
+/// Times the runs of the whole program `file`, each with a new interpreter, and names the
+/// result `name`. What it prints is discarded.
+pub fn run_program(
+    file: &File,
+    modules: &[Module],
+    name: &str,
+    options: &BenchOptions,
+) -> Result<BenchResult, Diagnostic> {
+    measure(name, options, || {
+        let mut interpreter = Interpreter::new(std::io::sink());
+        modules::run(&mut interpreter, modules)?;
+        interpreter.run(file)?;
+        Ok(interpreter.steps())
+    })
+}
+
+/// Makes the untimed runs of `options`, then times the others. `run` returns the steps it
+/// took.
+fn measure(
+    name: &str,
+    options: &BenchOptions,
+    mut run: impl FnMut() -> Result<u64, Diagnostic>,
+) -> Result<BenchResult, Diagnostic> {
+    for _ in 0..options.warmup {
+        run()?;
+    }
+    let mut samples = Vec::with_capacity(options.iterations);
+    let mut steps = 0;
+    for _ in 0..options.iterations {
+        let start = Instant::now();
+        steps = run()?;
+        samples.push(start.elapsed());
+    }
+    let (mean, stddev) = statistics(&samples);
+    Ok(BenchResult {
+        name: name.to_string(),
+        iterations: options.iterations,
+        mean,
+        stddev,
+        steps,
+    })
+}
+
+/// The mean and the standard deviation of `samples`, zero when there are none
+fn statistics(samples: &[Duration]) -> (Duration, Duration) {
+    if samples.is_empty() {
+        return (Duration::ZERO, Duration::ZERO);
+    }
+    let count = samples.len() as f64;
+    let seconds = samples.iter().map(Duration::as_secs_f64);
+    let mean = seconds.clone().sum::<f64>() / count;
+    let variance = seconds.map(|sample| (sample - mean).powi(2)).sum::<f64>() / count;
+    (
+        Duration::from_secs_f64(mean),
+        Duration::from_secs_f64(variance.sqrt()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::messages::Locale;
+    use crate::parser::lower::lower;
+    use crate::parser::snippet::parse_snippet;
+
+    fn file(content: &str) -> File {
+        let (file, errors) = lower(&parse_snippet(content).unwrap());
+        assert!(errors.is_empty(), "{:?}", errors);
+        file
+    }
+
+    #[test]
+    fn test_harness_tests() {
+        let file = file(
+            "limit: int = 10\n\
+             square: (int) -> int = (x) -> x * x ;\n\
+             @test\n\
+             squares: () -> bool = () -> square 3 == 9 ;\n\
+             @test\n\
+             wrong: () -> bool = () -> square limit == 10 ;\n\
+             @test\n\
+             fails: () -> int = () ->\n\
+             \x20 print \"dividing\"\n\
+             \x20 limit / 0\n\
+             ;\n\
+             @bench\n\
+             not_a_test: () -> bool = () -> false ;\n",
+        );
+        let results = run_tests(&file, &[]).unwrap();
+        let summary = results
+            .iter()
+            .map(|result| {
+                let failure = match &result.failure {
+                    None => "ok".to_string(),
+                    Some(TestFailure::ReturnedFalse) => "false".to_string(),
+                    Some(TestFailure::Error(diagnostic)) => diagnostic.render(Locale::En),
+                };
+                (result.name.as_str(), failure, result.output.as_str())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("squares", "ok".to_string(), ""),
+                ("wrong", "false".to_string(), ""),
+                ("fails", "division by zero".to_string(), "dividing\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_harness_benches() {
+        let benches = file(
+            "fib: (int) -> int = (n) -> if n < 2 then n else fib (n - 1) + fib (n - 2) ;\n\
+             @bench\n\
+             fib_10: () -> int = () -> fib 10 ;\n",
+        );
+        let options = BenchOptions {
+            iterations: 3,
+            warmup: 0,
+        };
+        let results = run_benches(&benches, &[], &options).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].name, "fib_10");
+        assert_eq!(results[0].iterations, 3);
+        assert!(results[0].mean > Duration::ZERO);
+        // Only the calls count, not the program declaring the function: `fib 10` is 3
+        // steps, then 89 calls of `fib` stop at `n < 2` in 5 steps and 88 recurse in 15
+        assert_eq!(results[0].steps, 3 + 89 * 5 + 88 * 15);
+
+        let broken = file("@bench\nbroken: () -> int = () -> 1 / 0 ;\n");
+        assert!(run_benches(&broken, &[], &options).is_err());
+
+        // The whole program runs again for each iteration
+        let program = file("x: int = 1 + 2\nprint x\n");
+        let result = run_program(&program, &[], "main.fs", &options).unwrap();
+        assert_eq!(result.name, "main.fs");
+        assert_eq!(result.steps, 6);
+        assert!(run_program(&broken, &[], "broken.fs", &options).is_ok());
+        assert!(run_program(&file("1 / 0\n"), &[], "broken.fs", &options).is_err());
+    }
+
+    #[test]
+    fn test_harness_statistics() {
+        let millis = |values: &[u64]| {
+            values
+                .iter()
+                .map(|value| Duration::from_millis(*value))
+                .collect::<Vec<_>>()
+        };
+        let (mean, stddev) = statistics(&millis(&[2, 4, 4, 4, 5, 5, 7, 9]));
+        assert_eq!(mean.as_millis(), 5);
+        assert_eq!(stddev.as_millis(), 2);
+        assert_eq!(statistics(&[]), (Duration::ZERO, Duration::ZERO));
+    }
+}
@@ -0,0 +1,204 @@
+//! Syntax highlighting with TextMate scopes, printed by `funs highlight --scopes`.
+//!
+//! The scopes follow the TextMate naming conventions (e.g. `constant.numeric`), so that
+//! editor themes color funs files without a grammar written for them.
+
+use crate::lexer::token::{Keyword, Literal, Token, TokenKind, TokenLocation};
+use crate::parser::{Child, Tree, TreeKind, NATIVE_TYPES};
+use serde::Serialize;
+
+/// The scope of a token, at the 0-based location of the token
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Highlight {
+    pub scope: &'static str,
+    pub location: TokenLocation,
+}
+
+/// Collects the scope of every token of `file` that is highlighted, in source order.
+///
+/// New lines and the end of file have no scope.
+pub fn highlights(file: &Tree) -> Vec<Highlight> {
+    let mut highlights = Vec::new();
+    collect(file, &mut highlights);
+    highlights
+}
+
+fn collect(tree: &Tree, highlights: &mut Vec<Highlight>) {
+    for child in tree.children() {
+        match child {
+            Child::Tree(child) => collect(child, highlights),
+            Child::Token(token) => {
+                if let Some(scope) = scope(token, tree.kind()) {
+                    highlights.push(Highlight {
+                        scope,
+                        location: token.location.clone(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// The scope of `token`, found in a tree of kind `parent`
+fn scope(token: &Token, parent: &TreeKind) -> Option<&'static str> {
+    let scope = match &token.kind {
+        TokenKind::TokenComment if token.lexeme.starts_with("#|") => "comment.block.funs",
+        TokenKind::TokenComment => "comment.line.number-sign.funs",
+        TokenKind::TokenLiteral(Literal::Int | Literal::Float) => "constant.numeric.funs",
+        TokenKind::TokenLiteral(Literal::Bool) => "constant.language.boolean.funs",
+        TokenKind::TokenLiteral(Literal::Str) => "string.quoted.double.funs",
+        TokenKind::TokenKeyword(Keyword::As) => "keyword.operator.cast.funs",
+        TokenKind::TokenKeyword(Keyword::Import) => "keyword.control.import.funs",
+        TokenKind::TokenKeyword(_) => "keyword.control.funs",
+        TokenKind::TokenHole => "variable.other.hole.funs",
+        TokenKind::TokenAt => "punctuation.definition.annotation.funs",
+        TokenKind::TokenIdentifier => match parent {
+            TreeKind::StmtImport => "entity.name.namespace.funs",
+            TreeKind::StmtVarDecl => "entity.name.variable.funs",
+            TreeKind::StmtFunDecl => "entity.name.function.funs",
+            TreeKind::StmtData | TreeKind::DeclVariant => "entity.name.type.funs",
+            TreeKind::VariantCase | TreeKind::PatConstructor => "variable.other.enummember.funs",
+            TreeKind::FieldDecl | TreeKind::FieldInit | TreeKind::ExprField => {
+                "variable.other.member.funs"
+            }
+            TreeKind::ParamList => "variable.parameter.funs",
+            TreeKind::Attribute => "storage.type.annotation.funs",
+            TreeKind::TypeExpr if NATIVE_TYPES.contains(&token.lexeme.as_str()) => {
+                "support.type.primitive.funs"
+            }
+            TreeKind::TypeExpr => "entity.name.type.funs",
+            // A boolean in a deprecated spelling, e.g. `True`
+            TreeKind::ExprLiteral | TreeKind::PatLiteral => "constant.language.boolean.funs",
+            _ => "variable.other.funs",
+        },
+        TokenKind::TokenOpenParen
+        | TokenKind::TokenCloseParen
+        | TokenKind::TokenOpenBrace
+        | TokenKind::TokenCloseBrace
+        | TokenKind::TokenOpenBracket
+        | TokenKind::TokenCloseBracket => "punctuation.section.brackets.funs",
+        TokenKind::TokenLess | TokenKind::TokenGreater if *parent == TreeKind::TypeExpr => {
+            "punctuation.definition.generic.funs"
+        }
+        TokenKind::TokenColon if *parent == TreeKind::ExprCons => "keyword.operator.funs",
+        TokenKind::TokenDot
+        | TokenKind::TokenColon
+        | TokenKind::TokenSemicolon
+        | TokenKind::TokenComma => "punctuation.separator.funs",
+        TokenKind::TokenAssign
+        | TokenKind::TokenEqualEqual
+        | TokenKind::TokenGreater
+        | TokenKind::TokenGreaterEqual
+        | TokenKind::TokenLess
+        | TokenKind::TokenLessEqual
+        | TokenKind::TokenRightArrow
+        | TokenKind::TokenRightDoubleArrow
+        | TokenKind::TokenPlusPlus
+        | TokenKind::TokenUnderscore
+        | TokenKind::TokenPipe
+        | TokenKind::TokenPipeForward
+        | TokenKind::TokenPlus
+        | TokenKind::TokenMinus
+        | TokenKind::TokenStar
+        | TokenKind::TokenSlash => "keyword.operator.funs",
+        TokenKind::TokenSingleQuote | TokenKind::TokenDoubleQuote => {
+            "punctuation.definition.string.funs"
+        }
+        TokenKind::TokenUnknown => "invalid.illegal.funs",
+        TokenKind::TokenSpace
+        | TokenKind::TokenTab
+        | TokenKind::TokenNewLine
+        | TokenKind::TokenEOF => return None,
+    };
+    Some(scope)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::snippet::parse_snippet;
+
+    #[test]
+    fn test_highlight_scopes() {
+        let tree =
+            parse_snippet("# doc\nx: option<point> = 1; y: str = ?todo\n#| a\nb |#\n").unwrap();
+        let scopes = highlights(&tree)
+            .into_iter()
+            .map(|highlight| {
+                (
+                    highlight.location.line,
+                    highlight.location.column_start,
+                    highlight.scope,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            scopes,
+            vec![
+                (0, 0, "comment.line.number-sign.funs"),
+                (1, 0, "entity.name.variable.funs"),
+                (1, 1, "punctuation.separator.funs"),
+                (1, 3, "entity.name.type.funs"),
+                (1, 9, "punctuation.definition.generic.funs"),
+                (1, 10, "entity.name.type.funs"),
+                (1, 15, "punctuation.definition.generic.funs"),
+                (1, 17, "keyword.operator.funs"),
+                (1, 19, "constant.numeric.funs"),
+                (1, 20, "punctuation.separator.funs"),
+                (1, 22, "entity.name.variable.funs"),
+                (1, 23, "punctuation.separator.funs"),
+                (1, 25, "support.type.primitive.funs"),
+                (1, 29, "keyword.operator.funs"),
+                (1, 31, "variable.other.hole.funs"),
+                (2, 0, "comment.block.funs"),
+            ]
+        );
+
+        let tree = parse_snippet("@test\nt: () -> bool = () -> true ;\n").unwrap();
+        let scopes = highlights(&tree)
+            .into_iter()
+            .take(3)
+            .map(|highlight| highlight.scope)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            scopes,
+            vec![
+                "punctuation.definition.annotation.funs",
+                "storage.type.annotation.funs",
+                "entity.name.function.funs",
+            ]
+        );
+
+        let tree = parse_snippet(
+            "data P = { x: int }
+p: P = { x = 1 }
+p.x
+",
+        )
+        .unwrap();
+        let members = highlights(&tree)
+            .into_iter()
+            .filter(|highlight| {
+                matches!(
+                    highlight.scope,
+                    "entity.name.type.funs" | "variable.other.member.funs"
+                )
+            })
+            .map(|highlight| (highlight.location.line, highlight.location.column_start))
+            .collect::<Vec<_>>();
+        // The name of the record, its field, the type of `p`, then the fields used
+        assert_eq!(members, vec![(0, 5), (0, 11), (1, 3), (1, 9), (2, 2)]);
+
+        let tree = parse_snippet(
+            "data C = Red | Rgb int int int\nc: C = match Red | Rgb _ _ _ => Red | c => c ;\n",
+        )
+        .unwrap();
+        let cases = highlights(&tree)
+            .into_iter()
+            .filter(|highlight| highlight.scope == "variable.other.enummember.funs")
+            .map(|highlight| (highlight.location.line, highlight.location.column_start))
+            .collect::<Vec<_>>();
+        // The constructors declared, then the one of the pattern; the others are values
+        assert_eq!(cases, vec![(0, 9), (0, 15), (1, 19)]);
+    }
+}
@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::panic::PanicHookInfo;
+use std::path::{Path, PathBuf};
+use tracing::error;
+
+const ISSUES_URL: &str = "https://github.com/funs-lang/funs/issues";
+
+/// The phases of the compiler driver, used to tell where an internal error happened and
+/// where the time goes, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    Reading,
+    Lexing,
+    Parsing,
+    Lowering,
+    Loading,
+    Resolving,
+    TypeChecking,
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Phase::Reading => write!(f, "reading"),
+            Phase::Lexing => write!(f, "lexing"),
+            Phase::Parsing => write!(f, "parsing"),
+            Phase::Lowering => write!(f, "lowering"),
+            Phase::Loading => write!(f, "loading modules"),
+            Phase::Resolving => write!(f, "name resolution"),
+            Phase::TypeChecking => write!(f, "type checking"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Context {
+    file_path: PathBuf,
+    phase: Phase,
+}
+
+thread_local! {
+    static CONTEXT: RefCell<Option<Context>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` recording that the driver is in `phase` for `file_path`.
+///
+/// If `f` panics, the panic hook installed by `install_panic_hook` reports the phase
+/// and the file in the internal compiler error message.
+pub fn with_phase<T>(phase: Phase, file_path: &Path, f: impl FnOnce() -> T) -> T {
+    let previous = CONTEXT.with(|context| {
+        context.replace(Some(Context {
+            file_path: file_path.to_path_buf(),
+            phase,
+        }))
+    });
+    let result = f();
+    CONTEXT.with(|context| context.replace(previous));
+    result
+}
+
+/// Replaces the default panic message with an "internal compiler error" report.
+///
+/// The report goes to stderr. The captured backtrace is written to the log file when the
+/// command set up the logger, and to stderr after the report otherwise.
+pub fn install_panic_hook() {
+    std::panic::set_hook(Box::new(|info| {
+        let context = CONTEXT.with(|context| context.borrow().clone());
+        let message = panic_message(info);
+        let logged = tracing::dispatcher::has_been_set();
+        eprintln!("{}", report(&message, context.as_ref(), logged));
+        let backtrace = Backtrace::force_capture();
+        match logged {
+            true => error!("internal compiler error: {}\n{}", message, backtrace),
+            false => eprintln!("{}", backtrace),
+        }
+    }));
+}
+
+fn panic_message(info: &PanicHookInfo) -> String {
+    let payload = if let Some(message) = info.payload().downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = info.payload().downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    };
+    match info.location() {
+        Some(location) => format!("{} at {}", payload, location),
+        None => payload,
+    }
+}
+
+/// The report of a panic with `message`, `logged` telling whether the backtrace goes to
+/// the debug log
+fn report(message: &str, context: Option<&Context>, logged: bool) -> String {
+    let mut report = format!("error: internal compiler error: {}\n", message);
+    if let Some(context) = context {
+        report.push_str(&format!(
+            "note: the compiler panicked while {} \"{}\"\n",
+            context.phase,
+            context.file_path.display()
+        ));
+    }
+    match logged {
+        true => report.push_str("note: the backtrace has been written to the debug log\n"),
+        false => report.push_str("note: the backtrace follows\n"),
+    }
+    report.push_str(&format!(
+        "note: this is a bug in funs, please file an issue at {}",
+        ISSUES_URL
+    ));
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ice_with_phase_restores_context() {
+        let path = PathBuf::from("main.fs");
+        with_phase(Phase::Lexing, &path, || {
+            let context = CONTEXT.with(|context| context.borrow().clone());
+            assert_eq!(context.map(|c| c.phase), Some(Phase::Lexing));
+            with_phase(Phase::Parsing, &path, || {
+                let context = CONTEXT.with(|context| context.borrow().clone());
+                assert_eq!(context.map(|c| c.phase), Some(Phase::Parsing));
+            });
+            let context = CONTEXT.with(|context| context.borrow().clone());
+            assert_eq!(context.map(|c| c.phase), Some(Phase::Lexing));
+        });
+        assert!(CONTEXT.with(|context| context.borrow().is_none()));
+    }
+
+    #[test]
+    fn test_ice_report() {
+        let context = Context {
+            file_path: PathBuf::from("main.fs"),
+            phase: Phase::Parsing,
+        };
+        let logged = report("boom", Some(&context), true);
+        assert!(logged.starts_with("error: internal compiler error: boom\n"));
+        assert!(logged.contains("while parsing \"main.fs\""));
+        assert!(logged.contains("written to the debug log"));
+        assert!(logged.contains(ISSUES_URL));
+    }
+
+    #[test]
+    fn test_ice_report_without_logger() {
+        let report = report("boom", None, false);
+        assert!(!report.contains("debug log"), "{}", report);
+        assert!(report.contains("note: the backtrace follows\n"));
+        assert!(!report.contains("panicked while"));
+    }
+}
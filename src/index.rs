@@ -0,0 +1,261 @@
+use crate::lexer::token::{TokenKind, TokenLocation};
+use crate::parser::node_id::{NodeId, NodeMap, Span};
+use crate::parser::{Child, Tree, TreeKind};
+use crate::symbols::{symbols, Symbol};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// The version of the JSON printed by `funs index`.
+///
+/// It changes whenever a tool reading the output would break, see `dev_doc/index_json.md`.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The output of `funs index`, the symbols of a set of files for code search tools and
+/// cross-repository indexers.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Index {
+    pub version: u32,
+    pub files: Vec<FileIndex>,
+}
+
+impl Index {
+    pub fn new(files: Vec<FileIndex>) -> Index {
+        Index {
+            version: FORMAT_VERSION,
+            files,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        // An index only holds strings and numbers, it always serializes
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct FileIndex {
+    pub file_path: PathBuf,
+    /// The top-level declarations, in source order
+    pub definitions: Vec<Definition>,
+    /// The uses of the definitions, in source order
+    pub references: Vec<Reference>,
+}
+
+/// A top-level declaration, with the span of the whole declaration
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Definition {
+    #[serde(flatten)]
+    pub symbol: Symbol,
+    pub span: Option<Span>,
+}
+
+/// A name used in an expression, along with the location of the name of its definition
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Reference {
+    pub name: String,
+    pub location: TokenLocation,
+    pub definition: TokenLocation,
+}
+
+/// Indexes the definitions of `file` and the references to them.
+///
+/// Names are resolved against the top-level declarations of the file only: a name bound by
+/// an enclosing function (a parameter, or a declaration of its body) is not a reference, and
+/// neither is a name declared nowhere in the file.
+pub fn index(file_path: PathBuf, file: &Tree) -> FileIndex {
+    let nodes = NodeMap::new(file);
+    let definitions = symbols(file)
+        .into_iter()
+        .map(|symbol| {
+            let span = file
+                .child_trees()
+                .filter(|tree| is_declaration(tree))
+                .find(|tree| {
+                    tree.declared_name()
+                        .is_some_and(|name| name.location == symbol.location)
+                })
+                .and_then(|tree| nodes.id(tree))
+                .and_then(|id| nodes.span(id))
+                .cloned();
+            Definition { symbol, span }
+        })
+        .collect::<Vec<_>>();
+
+    // The first declaration of a name is its definition, see `resolve::duplicates`
+    let mut defined = BTreeMap::new();
+    for definition in &definitions {
+        defined
+            .entry(definition.symbol.name.as_str())
+            .or_insert(&definition.symbol.location);
+    }
+
+    let references = nodes
+        .ids()
+        .filter(|id| *nodes.tree(*id).kind() == TreeKind::ExprName)
+        .filter_map(|id| {
+            let name = nodes.tree(id).tokens().first().copied()?;
+            let definition = defined.get(name.lexeme.as_str())?;
+            if is_bound_locally(&nodes, id, &name.lexeme) {
+                return None;
+            }
+            Some(Reference {
+                name: name.lexeme.clone(),
+                location: name.location.clone(),
+                definition: (*definition).clone(),
+            })
+        })
+        .collect();
+
+    FileIndex {
+        file_path,
+        definitions,
+        references,
+    }
+}
+
+fn is_declaration(tree: &Tree) -> bool {
+    matches!(tree.kind(), TreeKind::StmtVarDecl | TreeKind::StmtFunDecl)
+}
+
+/// Whether `name` is a parameter of a function enclosing `id`, is declared in its body, or
+/// is bound by the pattern of an enclosing `match` arm
+fn is_bound_locally(nodes: &NodeMap, id: NodeId, name: &str) -> bool {
+    let mut parent = nodes.parent(id);
+    while let Some(id) = parent {
+        let tree = nodes.tree(id);
+        let bound = match tree.kind() {
+            TreeKind::StmtFunDecl => binds(tree, name),
+            TreeKind::MatchArm => tree.child_trees().next().is_some_and(|pattern| {
+                pattern
+                    .pattern_names()
+                    .iter()
+                    .any(|token| token.lexeme == name)
+            }),
+            _ => false,
+        };
+        if bound {
+            return true;
+        }
+        parent = nodes.parent(id);
+    }
+    false
+}
+
+/// Whether the function `tree` has a parameter `name`, or declares `name` in its body
+fn binds(tree: &Tree, name: &str) -> bool {
+    tree.child_trees().any(|child| match child.kind() {
+        TreeKind::ParamList => child.children().iter().any(|child| {
+            matches!(child, Child::Token(token)
+                if token.kind == TokenKind::TokenIdentifier && token.lexeme == name)
+        }),
+        TreeKind::Block => child
+            .child_trees()
+            .filter(|tree| is_declaration(tree))
+            .flat_map(Tree::declared_names)
+            .any(|token| token.lexeme == name),
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::snippet::parse_snippet;
+
+    #[test]
+    fn test_index_references() {
+        let tree = parse_snippet(
+            "limit: int = 10
+\
+             double: (int) -> int = (x) -> x * 2 ;
+\
+             clamp: (int) -> int = (limit) ->
+  y: int = limit
+  y
+;
+\
+             twice: int = double + limit
+",
+        )
+        .unwrap();
+        let file = index(PathBuf::from("<snippet>"), &tree);
+
+        let definitions = file
+            .definitions
+            .iter()
+            .map(|definition| {
+                let span = definition.span.as_ref().unwrap();
+                (
+                    definition.symbol.name.as_str(),
+                    span.start.line,
+                    span.end.line,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            definitions,
+            vec![
+                ("limit", 0, 0),
+                ("double", 1, 1),
+                ("clamp", 2, 5),
+                ("twice", 6, 6)
+            ]
+        );
+
+        // `x`, `limit` and `y` in the functions are bound by the functions themselves
+        let references = file
+            .references
+            .iter()
+            .map(|reference| {
+                (
+                    reference.name.as_str(),
+                    reference.location.line,
+                    reference.definition.line,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(references, vec![("double", 6, 1), ("limit", 6, 0)]);
+    }
+
+    #[test]
+    fn test_index_match_bindings() {
+        let tree = parse_snippet("n: int = 1\nm: int = match n | n => n | _ => n ;\n").unwrap();
+        let file = index(PathBuf::from("<snippet>"), &tree);
+        // The scrutinee and the wildcard arm see the global `n`, the first arm binds its own
+        let columns = file
+            .references
+            .iter()
+            .map(|reference| reference.location.column_start)
+            .collect::<Vec<_>>();
+        assert_eq!(columns, vec![15, 33]);
+
+        // So does a name inside a tuple pattern
+        let tree = parse_snippet("n: int = 1\nm: int = match (n, 2) | (_, n) => n ;\n").unwrap();
+        let file = index(PathBuf::from("<snippet>"), &tree);
+        let columns = file
+            .references
+            .iter()
+            .map(|reference| reference.location.column_start)
+            .collect::<Vec<_>>();
+        assert_eq!(columns, vec![16]);
+    }
+
+    #[test]
+    fn test_index_json() {
+        let tree = parse_snippet("x: int = 1\ny: int = x\n").unwrap();
+        let index = Index::new(vec![index(PathBuf::from("main.fs"), &tree)]);
+        let json = serde_json::from_str::<serde_json::Value>(&index.to_json()).unwrap();
+        assert_eq!(json["version"], FORMAT_VERSION);
+        assert_eq!(json["files"][0]["file_path"], "main.fs");
+        let definition = &json["files"][0]["definitions"][1];
+        assert_eq!(definition["name"], "y");
+        assert_eq!(definition["kind"], "variable");
+        assert_eq!(definition["type"], "int");
+        assert_eq!(definition["span"]["start"]["line"], 1);
+        let reference = &json["files"][0]["references"][0];
+        assert_eq!(reference["name"], "x");
+        assert_eq!(reference["location"]["line"], 1);
+        assert_eq!(reference["definition"]["line"], 0);
+    }
+}
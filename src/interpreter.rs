@@ -0,0 +1,1247 @@
+//! Evaluates the AST of a program, run by `funs run`.
+//!
+//! A tree-walking interpreter: every expression is evaluated by walking its subtrees,
+//! in an environment of scopes mapping names to values. `typeck` checks the program
+//! before it runs, but the values are still checked as they are used, for the open
+//! types it leaves and for the programs run without it: adding a `str` to an `int` is
+//! an error at run time, reported as a `Diagnostic` located at the expression.
+//!
+//! Functions are curried: calling one with fewer arguments than parameters returns a
+//! function waiting for the rest, e.g. `twice (add 1) 3` gives `twice` a function
+//! adding 1.
+//!
+//! With `with_trace`, every expression evaluated is logged at the `debug` level once it
+//! has a value, indented by the depth of the call evaluating it, see `trace_line`.
+
+use crate::diagnostics::messages::{Code, Locale, Message};
+use crate::diagnostics::Diagnostic;
+use crate::parser::ast::{
+    BinaryOp, DataDecl, Expr, ExprKind, File, FunDecl, Ident, Pattern, PatternKind, Stmt, Type,
+    TypeKind, UnaryOp, VariantDecl,
+};
+use crate::parser::node_id::Span;
+use crate::parser::NATIVE_TYPES;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::rc::Rc;
+use tracing::debug;
+
+/// The number of calls that can be nested by default, past it the program is stopped
+/// instead of overflowing the stack of the interpreter
+pub const MAX_CALL_DEPTH: usize = 512;
+
+/// The size of the stack the programs run on, see `with_stack`.
+///
+/// A call takes about 40 KiB of it in a debug build when its body is a single `if`, and
+/// 10 KiB more for each expression nested in the body, so `MAX_CALL_DEPTH` calls fit
+/// with a wide margin; a release build needs ten times less.
+pub const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// The longest value shown by a trace line, longer ones are cut
+const TRACE_VALUE_LEN: usize = 40;
+
+/// Runs `f` on a thread with a stack of `STACK_SIZE` bytes, so that `MAX_CALL_DEPTH`
+/// nested calls are reported as an error before they overflow it.
+///
+/// A panic in `f` is resumed on the calling thread. The stack is only reserved, the
+/// pages a program does not reach are never used.
+pub fn with_stack<T: Send>(f: impl FnOnce() -> T + Send) -> std::io::Result<T> {
+    std::thread::scope(|scope| {
+        let thread = std::thread::Builder::new()
+            .name("interpreter".to_string())
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, f)?;
+        match thread.join() {
+            Ok(result) => Ok(result),
+            Err(payload) => std::panic::resume_unwind(payload),
+        }
+    })
+}
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Unit,
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+    List(Vec<Value>),
+    /// Two values or more, the empty tuple is `Unit`
+    Tuple(Vec<Value>),
+    /// The fields of a record and their values, in the order they were written
+    Record(Vec<(String, Value)>),
+    /// A value built by the constructor `name` of a variant, with its fields
+    Variant {
+        name: String,
+        fields: Vec<Value>,
+    },
+    Function(Rc<Function>),
+}
+
+impl Value {
+    /// The name of the type of the value, as written in a program
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Unit => "unit",
+            Value::Int(_) => "int",
+            Value::Float(_) => "float",
+            Value::Bool(_) => "bool",
+            Value::Str(_) => "str",
+            Value::List(_) => "list",
+            Value::Tuple(_) => "tuple",
+            Value::Record(_) => "record",
+            Value::Variant { .. } => "variant",
+            Value::Function(_) => "function",
+        }
+    }
+
+    /// The value of the field `name` of a record
+    fn field(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Record(fields) => fields
+                .iter()
+                .find_map(|(field, value)| (field == name).then_some(value)),
+            _ => None,
+        }
+    }
+}
+
+impl PartialEq for Value {
+    /// Functions are only equal to themselves, records are equal when they have the same
+    /// fields with equal values, whatever their order
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Unit, Value::Unit) => true,
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Str(a), Value::Str(b)) => a == b,
+            (Value::List(a), Value::List(b)) => a == b,
+            (Value::Tuple(a), Value::Tuple(b)) => a == b,
+            (Value::Record(a), Value::Record(b)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .all(|(name, value)| other.field(name) == Some(value))
+            }
+            (
+                Value::Variant { name, fields },
+                Value::Variant {
+                    name: other_name,
+                    fields: other_fields,
+                },
+            ) => name == other_name && fields == other_fields,
+            (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+/// The value as written in a program, e.g. `1.0` or `"funs"`
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Value::Unit => write!(f, "()"),
+            Value::Int(value) => write!(f, "{}", value),
+            // `{:?}` keeps the `.0` of a whole float
+            Value::Float(value) => write!(f, "{:?}", value),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Str(value) => write!(f, "\"{}\"", value),
+            Value::List(values) => write!(f, "[{}]", join(values)),
+            Value::Tuple(values) => write!(f, "({})", join(values)),
+            Value::Record(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|(name, value)| format!("{} = {}", name, value))
+                    .collect::<Vec<_>>();
+                write!(f, "{{{}}}", fields.join(", "))
+            }
+            Value::Variant { name, fields } => {
+                write!(f, "{}", name)?;
+                for field in fields {
+                    match field {
+                        // As an argument, see `ExprFunCall`
+                        Value::Variant { fields, .. } if !fields.is_empty() => {
+                            write!(f, " ({})", field)?
+                        }
+                        _ => write!(f, " {}", field)?,
+                    }
+                }
+                Ok(())
+            }
+            Value::Function(function) => write!(f, "<function {}>", function.name),
+        }
+    }
+}
+
+fn join(values: &[Value]) -> String {
+    values
+        .iter()
+        .map(Value::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// A function, along with the arguments it was given so far
+pub struct Function {
+    pub name: String,
+    body: Body,
+    /// A function is called once it has as many arguments as parameters
+    applied: Vec<Value>,
+}
+
+#[derive(Clone)]
+enum Body {
+    /// A function of the program, with the scope it was declared in
+    Declared {
+        decl: Rc<FunDecl>,
+        env: Env,
+    },
+    Builtin(Builtin),
+    /// The constructor of a case of a variant, with the number of its fields
+    Constructor(usize),
+}
+
+/// The functions every program can call without declaring them
+#[derive(Debug, Clone, Copy)]
+enum Builtin {
+    /// `print: (str) -> unit`, writes its argument and a new line to the output
+    Print,
+}
+
+impl Builtin {
+    const ALL: [Builtin; 1] = [Builtin::Print];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Builtin::Print => "print",
+        }
+    }
+
+    fn arity(&self) -> usize {
+        match self {
+            Builtin::Print => 1,
+        }
+    }
+}
+
+impl Function {
+    fn arity(&self) -> usize {
+        match &self.body {
+            Body::Declared { decl, .. } => decl.params.len(),
+            Body::Builtin(builtin) => builtin.arity(),
+            Body::Constructor(arity) => *arity,
+        }
+    }
+
+    /// Whether the function is declared without parameters, then it takes `()`. A hole
+    /// has no parameters either but runs with any arguments.
+    fn is_nullary(&self) -> bool {
+        matches!(&self.body, Body::Declared { decl, .. }
+            if matches!(&decl.ty.kind, TypeKind::Function { params, .. } if params.is_empty()))
+    }
+}
+
+impl std::fmt::Debug for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Function")
+            .field("name", &self.name)
+            .field("applied", &self.applied)
+            .finish()
+    }
+}
+
+/// A scope, along with the scopes enclosing it
+#[derive(Clone, Default)]
+struct Env(Rc<Scope>);
+
+#[derive(Default)]
+struct Scope {
+    values: RefCell<BTreeMap<String, Value>>,
+    parent: Option<Env>,
+}
+
+impl Env {
+    /// A new scope inside this one
+    fn child(&self) -> Env {
+        Env(Rc::new(Scope {
+            values: RefCell::default(),
+            parent: Some(self.clone()),
+        }))
+    }
+
+    /// Binds `name` in this scope, shadowing any previous binding
+    fn define(&self, name: &str, value: Value) {
+        self.0.values.borrow_mut().insert(name.to_string(), value);
+    }
+
+    /// The value of `name` in the innermost scope binding it
+    fn get(&self, name: &str) -> Option<Value> {
+        match self.0.values.borrow().get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.0.parent.as_ref()?.get(name),
+        }
+    }
+}
+
+/// Runs programs, writing what they print to `out`.
+///
+/// The top-level declarations of every program run are kept, so that a program can use
+/// the declarations of the ones run before it.
+pub struct Interpreter<W: Write> {
+    out: W,
+    globals: Env,
+    /// The records declared so far, by name
+    records: BTreeMap<String, Rc<DataDecl>>,
+    /// The variants declared so far, by name
+    variants: BTreeMap<String, Rc<VariantDecl>>,
+    depth: usize,
+    max_depth: usize,
+    /// The expressions evaluated so far, see `steps`
+    steps: u64,
+    trace: bool,
+}
+
+impl<W: Write> Interpreter<W> {
+    pub fn new(out: W) -> Interpreter<W> {
+        let globals = Env::default();
+        for builtin in Builtin::ALL {
+            let function = Function {
+                name: builtin.name().to_string(),
+                body: Body::Builtin(builtin),
+                applied: Vec::new(),
+            };
+            globals.define(builtin.name(), Value::Function(Rc::new(function)));
+        }
+        Interpreter {
+            out,
+            globals,
+            records: BTreeMap::new(),
+            variants: BTreeMap::new(),
+            depth: 0,
+            max_depth: MAX_CALL_DEPTH,
+            steps: 0,
+            trace: false,
+        }
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> Interpreter<W> {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Logs every expression evaluated, see `trace_line`
+    pub fn with_trace(mut self, trace: bool) -> Interpreter<W> {
+        self.trace = trace;
+        self
+    }
+
+    /// Where the programs print, e.g. for the REPL to print results along with them
+    pub fn output(&mut self) -> &mut W {
+        &mut self.out
+    }
+
+    pub fn into_output(self) -> W {
+        self.out
+    }
+
+    /// How many expressions were evaluated since the interpreter was created, a measure
+    /// of the work done by the programs that does not depend on the machine
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Runs `file` and returns the value of its last statement if it is an expression.
+    ///
+    /// The functions, the records and the variants are declared before any statement
+    /// runs, so a function can call the ones declared after it; variables are declared
+    /// in order.
+    pub fn run(&mut self, file: &File) -> Result<Option<Value>, Diagnostic> {
+        let globals = self.globals.clone();
+        let value = self.stmts(&file.stmts, &globals)?;
+        Ok(match file.stmts.last() {
+            Some(Stmt::Expr(_)) => Some(value),
+            _ => None,
+        })
+    }
+
+    /// Calls the top-level function `name` without arguments, after a program declaring
+    /// it has run, e.g. a `@test` function
+    pub fn call_global(&mut self, name: &Ident) -> Result<Value, Diagnostic> {
+        let function = self.globals.get(&name.name).ok_or_else(|| {
+            error(
+                Message::new(Code::UnknownName).with_arg("name", &name.name),
+                &name.span,
+            )
+        })?;
+        self.call(function, Vec::new(), &name.span)
+    }
+
+    /// Runs `stmts` in `env` and returns the value of the last one, `()` if it is a
+    /// declaration
+    fn stmts(&mut self, stmts: &[Stmt], env: &Env) -> Result<Value, Diagnostic> {
+        for stmt in stmts {
+            if let Stmt::Data(decl) = stmt {
+                let decl = Rc::new((**decl).clone());
+                self.records.insert(decl.name.name.clone(), decl);
+            }
+            if let Stmt::Variant(decl) = stmt {
+                // A case without fields is a value, the others are functions building one
+                for case in &decl.cases {
+                    let value = match case.fields.len() {
+                        0 => Value::Variant {
+                            name: case.name.name.clone(),
+                            fields: Vec::new(),
+                        },
+                        arity => Value::Function(Rc::new(Function {
+                            name: case.name.name.clone(),
+                            body: Body::Constructor(arity),
+                            applied: Vec::new(),
+                        })),
+                    };
+                    env.define(&case.name.name, value);
+                }
+                let decl = Rc::new((**decl).clone());
+                self.variants.insert(decl.name.name.clone(), decl);
+            }
+            if let Stmt::FunDecl(decl) = stmt {
+                let function = Function {
+                    name: decl.name.name.clone(),
+                    body: Body::Declared {
+                        decl: Rc::new((**decl).clone()),
+                        env: env.clone(),
+                    },
+                    applied: Vec::new(),
+                };
+                env.define(&decl.name.name, Value::Function(Rc::new(function)));
+            }
+        }
+        let mut value = Value::Unit;
+        for stmt in stmts {
+            value = match stmt {
+                Stmt::VarDecl(decl) => {
+                    let value = self.expr(&decl.value, env)?;
+                    self.check_type(&value, &decl.ty, &decl.value.span)?;
+                    env.define(&decl.name.name, value);
+                    Value::Unit
+                }
+                Stmt::PatternDecl(decl) => {
+                    let value = self.expr(&decl.value, env)?;
+                    self.check_type(&value, &decl.ty, &decl.value.span)?;
+                    let Some(bindings) = bindings(&decl.pattern, &value) else {
+                        let message = Message::new(Code::PatternMismatch).with_arg("value", &value);
+                        return Err(error(message, &decl.pattern.span));
+                    };
+                    for (name, value) in bindings {
+                        env.define(name, value);
+                    }
+                    Value::Unit
+                }
+                // The imported modules run before the file, see `modules::load`
+                Stmt::Import(_) | Stmt::FunDecl(_) | Stmt::Data(_) | Stmt::Variant(_) => {
+                    Value::Unit
+                }
+                Stmt::Expr(expr) => self.expr(expr, env)?,
+            };
+        }
+        Ok(value)
+    }
+
+    fn expr(&mut self, expr: &Expr, env: &Env) -> Result<Value, Diagnostic> {
+        self.steps += 1;
+        let result = self.eval(expr, env);
+        if self.trace {
+            debug!("{}", trace_line(self.depth, expr, &result));
+        }
+        result
+    }
+
+    fn eval(&mut self, expr: &Expr, env: &Env) -> Result<Value, Diagnostic> {
+        match &expr.kind {
+            ExprKind::Int(value) => Ok(Value::Int(*value)),
+            ExprKind::Float(value) => Ok(Value::Float(*value)),
+            ExprKind::Bool(value) => Ok(Value::Bool(*value)),
+            ExprKind::Str(value) => Ok(Value::Str(value.clone())),
+            ExprKind::List(elements) => {
+                let values = elements
+                    .iter()
+                    .map(|element| self.expr(element, env))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::List(values))
+            }
+            ExprKind::Tuple(elements) if elements.is_empty() => Ok(Value::Unit),
+            ExprKind::Tuple(elements) => {
+                let values = elements
+                    .iter()
+                    .map(|element| self.expr(element, env))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::Tuple(values))
+            }
+            ExprKind::Record(fields) => {
+                let values = fields
+                    .iter()
+                    .map(|field| Ok((field.name.name.clone(), self.expr(&field.value, env)?)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::Record(values))
+            }
+            ExprKind::Field {
+                expr: record,
+                field,
+            } => match self.expr(record, env)? {
+                value @ Value::Record(_) => value.field(&field.name).cloned().ok_or_else(|| {
+                    let message = Message::new(Code::UnknownField)
+                        .with_arg("ty", self.record_name(&value))
+                        .with_arg("field", &field.name);
+                    error(message, &expr.span)
+                }),
+                value => Err(mismatch("record", &value, &record.span)),
+            },
+            ExprKind::Name(name) => env.get(name).ok_or_else(|| {
+                error(
+                    Message::new(Code::UnknownName).with_arg("name", name),
+                    &expr.span,
+                )
+            }),
+            ExprKind::Hole(hole) => Err(error(
+                Message::new(Code::HoleReached).with_arg("hole", hole),
+                &expr.span,
+            )),
+            ExprKind::Unary { op, expr: operand } => {
+                let value = self.expr(operand, env)?;
+                unary(*op, value, &expr.span)
+            }
+            ExprKind::Binary { op, lhs, rhs } => {
+                let lhs = self.expr(lhs, env)?;
+                let rhs = self.expr(rhs, env)?;
+                binary(*op, lhs, rhs, &expr.span)
+            }
+            ExprKind::Cast { expr: operand, ty } => {
+                let value = self.expr(operand, env)?;
+                cast(value, ty, &expr.span)
+            }
+            ExprKind::Ascription { expr: operand, ty } => {
+                let value = self.expr(operand, env)?;
+                self.check_type(&value, ty, &operand.span)?;
+                Ok(value)
+            }
+            ExprKind::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => match self.expr(cond, env)? {
+                Value::Bool(true) => self.expr(then_branch, env),
+                Value::Bool(false) => self.expr(else_branch, env),
+                value => Err(mismatch("bool", &value, &cond.span)),
+            },
+            ExprKind::Call { callee, args } => {
+                let callee = self.expr(callee, env)?;
+                let args = args
+                    .iter()
+                    .map(|arg| self.expr(arg, env))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.call(callee, args, &expr.span)
+            }
+            ExprKind::Match { scrutinee, arms } => {
+                let value = self.expr(scrutinee, env)?;
+                let Some((arm, bindings)) = arms
+                    .iter()
+                    .find_map(|arm| Some((arm, bindings(&arm.pattern, &value)?)))
+                else {
+                    let message = Message::new(Code::NonExhaustiveMatch).with_arg("value", &value);
+                    return Err(error(message, &expr.span));
+                };
+                if bindings.is_empty() {
+                    return self.expr(&arm.body, env);
+                }
+                let env = env.child();
+                for (name, value) in bindings {
+                    env.define(name, value);
+                }
+                self.expr(&arm.body, &env)
+            }
+        }
+    }
+
+    /// Gives `args` to `callee`, which runs once it has all its arguments. The
+    /// arguments left over are given to the function it returns. A function without
+    /// parameters takes `()` as its argument, e.g. `f ()`.
+    fn call(&mut self, callee: Value, args: Vec<Value>, span: &Span) -> Result<Value, Diagnostic> {
+        let Value::Function(function) = callee else {
+            return Err(mismatch("function", &callee, span));
+        };
+        let mut args = args.into_iter();
+        if function.is_nullary() {
+            match args.next() {
+                None | Some(Value::Unit) => {}
+                Some(value) => return Err(mismatch("unit", &value, span)),
+            }
+        }
+        let mut applied = function.applied.clone();
+        applied.extend(args);
+        if applied.len() < function.arity() {
+            return Ok(Value::Function(Rc::new(Function {
+                name: function.name.clone(),
+                body: function.body.clone(),
+                applied,
+            })));
+        }
+        let rest = applied.split_off(function.arity());
+
+        if self.depth == self.max_depth {
+            let message = Message::new(Code::CallDepth).with_arg("limit", self.max_depth);
+            return Err(error(message, span));
+        }
+        self.depth += 1;
+        let result = self.apply(&function, applied);
+        self.depth -= 1;
+
+        if rest.is_empty() {
+            result
+        } else {
+            self.call(result?, rest, span)
+        }
+    }
+
+    /// Runs `function` with exactly as many arguments as it has parameters
+    fn apply(&mut self, function: &Function, args: Vec<Value>) -> Result<Value, Diagnostic> {
+        match &function.body {
+            Body::Declared { decl, env } => {
+                let env = env.child();
+                for (param, arg) in decl.params.iter().zip(args) {
+                    env.define(&param.name, arg);
+                }
+                self.stmts(&decl.body, &env)
+            }
+            Body::Builtin(Builtin::Print) => {
+                let text = match &args[0] {
+                    Value::Str(text) => text.clone(),
+                    value => value.to_string(),
+                };
+                // A program whose output is closed keeps running, like with a pipe
+                let _ = writeln!(self.out, "{}", text);
+                Ok(Value::Unit)
+            }
+            Body::Constructor(_) => Ok(Value::Variant {
+                name: function.name.clone(),
+                fields: args,
+            }),
+        }
+    }
+
+    /// Checks that `value` has the type `ty` when it is a native type, a list, a tuple, a
+    /// record or a variant, the types of functions and of generic values are not known at
+    /// run time
+    fn check_type(&self, value: &Value, ty: &Type, span: &Span) -> Result<(), Diagnostic> {
+        match &ty.kind {
+            TypeKind::Named { name, args }
+                if args.is_empty()
+                    && NATIVE_TYPES.contains(&name.as_str())
+                    && value.type_name() != name =>
+            {
+                Err(mismatch(name, value, span))
+            }
+            TypeKind::Named { name, args } if args.is_empty() => {
+                match (self.records.get(name), self.variants.get(name)) {
+                    (Some(decl), _) => self.check_record(value, decl, span),
+                    (None, Some(decl)) => self.check_variant(value, decl, span),
+                    (None, None) => Ok(()),
+                }
+            }
+            TypeKind::List(element) => match value {
+                Value::List(values) => values
+                    .iter()
+                    .try_for_each(|value| self.check_type(value, element, span)),
+                _ => Err(mismatch(&ty.to_string(), value, span)),
+            },
+            TypeKind::Tuple(types) => match value {
+                Value::Unit if types.is_empty() => Ok(()),
+                Value::Tuple(values) if values.len() == types.len() => values
+                    .iter()
+                    .zip(types)
+                    .try_for_each(|(value, ty)| self.check_type(value, ty, span)),
+                _ => Err(mismatch(&ty.to_string(), value, span)),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that `value` is a record with the fields of `decl`, no more, each of its type
+    fn check_record(&self, value: &Value, decl: &DataDecl, span: &Span) -> Result<(), Diagnostic> {
+        let Value::Record(fields) = value else {
+            return Err(mismatch(&decl.name.name, value, span));
+        };
+        let field_error = |code: Code, field: &str| {
+            let message = Message::new(code)
+                .with_arg("ty", &decl.name.name)
+                .with_arg("field", field);
+            error(message, span)
+        };
+        for (name, _) in fields {
+            if !decl.fields.iter().any(|field| field.name.name == *name) {
+                return Err(field_error(Code::UnknownField, name));
+            }
+        }
+        for field in &decl.fields {
+            match value.field(&field.name.name) {
+                Some(value) => self.check_type(value, &field.ty, span)?,
+                None => return Err(field_error(Code::MissingField, &field.name.name)),
+            }
+        }
+        Ok(())
+    }
+
+    /// The name of the record declared with the fields of `value`, `record` if there is
+    /// none
+    fn record_name(&self, value: &Value) -> &str {
+        let Value::Record(fields) = value else {
+            return value.type_name();
+        };
+        self.records
+            .values()
+            .find(|decl| {
+                decl.fields.len() == fields.len()
+                    && decl
+                        .fields
+                        .iter()
+                        .all(|field| value.field(&field.name.name).is_some())
+            })
+            .map_or(value.type_name(), |decl| &decl.name.name)
+    }
+
+    /// Checks that `value` was built by a constructor of `decl`, with fields of its types
+    fn check_variant(
+        &self,
+        value: &Value,
+        decl: &VariantDecl,
+        span: &Span,
+    ) -> Result<(), Diagnostic> {
+        let case = match value {
+            Value::Variant { name, .. } => decl.cases.iter().find(|case| case.name.name == *name),
+            _ => None,
+        };
+        match (case, value) {
+            (Some(case), Value::Variant { fields, .. }) => fields
+                .iter()
+                .zip(&case.fields)
+                .try_for_each(|(value, ty)| self.check_type(value, ty, span)),
+            _ => Err(mismatch(&decl.name.name, value, span)),
+        }
+    }
+}
+
+fn unary(op: UnaryOp, value: Value, span: &Span) -> Result<Value, Diagnostic> {
+    match (op, value) {
+        (UnaryOp::Neg, Value::Int(value)) => value
+            .checked_neg()
+            .map(Value::Int)
+            .ok_or_else(|| overflow(op, span)),
+        (UnaryOp::Neg, Value::Float(value)) => Ok(Value::Float(-value)),
+        (op, value) => {
+            let message = Message::new(Code::InvalidOperand)
+                .with_arg("op", op)
+                .with_arg("ty", value.type_name());
+            Err(error(message, span))
+        }
+    }
+}
+
+fn binary(op: BinaryOp, lhs: Value, rhs: Value, span: &Span) -> Result<Value, Diagnostic> {
+    use BinaryOp::*;
+    let value = match (op, &lhs, &rhs) {
+        (Add | Sub | Mul | Div, Value::Int(a), Value::Int(b)) => {
+            let value = match op {
+                Add => a.checked_add(*b),
+                Sub => a.checked_sub(*b),
+                Mul => a.checked_mul(*b),
+                _ if *b == 0 => return Err(error(Message::new(Code::DivisionByZero), span)),
+                _ => a.checked_div(*b),
+            };
+            Value::Int(value.ok_or_else(|| overflow(op, span))?)
+        }
+        (Add | Sub | Mul | Div, Value::Float(a), Value::Float(b)) => Value::Float(match op {
+            Add => a + b,
+            Sub => a - b,
+            Mul => a * b,
+            _ => a / b,
+        }),
+        (Add, Value::Str(a), Value::Str(b)) => Value::Str(format!("{}{}", a, b)),
+        (Cons, head, Value::List(tail)) => {
+            let mut values = Vec::with_capacity(tail.len() + 1);
+            values.push(head.clone());
+            values.extend(tail.iter().cloned());
+            Value::List(values)
+        }
+        (Concat, Value::List(a), Value::List(b)) => Value::List([a.as_slice(), b].concat()),
+        (Eq, a, b) if a.type_name() == b.type_name() && !matches!(a, Value::Function(_)) => {
+            Value::Bool(a == b)
+        }
+        (Lt | Le | Gt | Ge, a, b) => {
+            let ordering = match (a, b) {
+                (Value::Int(a), Value::Int(b)) => a.partial_cmp(b),
+                (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+                (Value::Str(a), Value::Str(b)) => a.partial_cmp(b),
+                _ => return Err(invalid_operands(op, &lhs, &rhs, span)),
+            };
+            // A comparison with NaN is false
+            Value::Bool(ordering.is_some_and(|ordering| match op {
+                Lt => ordering.is_lt(),
+                Le => ordering.is_le(),
+                Gt => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            }))
+        }
+        _ => return Err(invalid_operands(op, &lhs, &rhs, span)),
+    };
+    Ok(value)
+}
+
+/// A step of the trace: the kind of `expr`, its 1-based location and its value, or its
+/// error, e.g. `  binary at 3:7 => 42`
+fn trace_line(depth: usize, expr: &Expr, result: &Result<Value, Diagnostic>) -> String {
+    let outcome = match result {
+        Ok(value) => {
+            let value = value.to_string();
+            match value.char_indices().nth(TRACE_VALUE_LEN) {
+                Some((end, _)) => format!("{}...", &value[..end]),
+                None => value,
+            }
+        }
+        Err(diagnostic) => format!("error: {}", diagnostic.render(Locale::En)),
+    };
+    format!(
+        "{}{} at {}:{} => {}",
+        "  ".repeat(depth),
+        expr.kind.name(),
+        expr.span.start.line + 1,
+        expr.span.start.column_start + 1,
+        outcome
+    )
+}
+
+/// The values bound by the names of `pattern` when `value` matches it, a literal of
+/// another type does not match
+fn bindings<'a>(pattern: &'a Pattern, value: &Value) -> Option<Vec<(&'a str, Value)>> {
+    let matches = match (&pattern.kind, value) {
+        (PatternKind::Name(name), value) => return Some(vec![(name, value.clone())]),
+        (PatternKind::Tuple(patterns), Value::Unit) => patterns.is_empty(),
+        (PatternKind::Tuple(patterns), Value::Tuple(values)) if patterns.len() == values.len() => {
+            let mut bound = Vec::new();
+            for (pattern, value) in patterns.iter().zip(values) {
+                bound.extend(bindings(pattern, value)?);
+            }
+            return Some(bound);
+        }
+        (
+            PatternKind::Constructor { name, fields },
+            Value::Variant {
+                name: built_by,
+                fields: values,
+            },
+        ) if name == built_by && fields.len() == values.len() => {
+            let mut bound = Vec::new();
+            for (pattern, value) in fields.iter().zip(values) {
+                bound.extend(bindings(pattern, value)?);
+            }
+            return Some(bound);
+        }
+        (PatternKind::Wildcard, _) => true,
+        (PatternKind::Int(a), Value::Int(b)) => a == b,
+        (PatternKind::Float(a), Value::Float(b)) => a == b,
+        (PatternKind::Bool(a), Value::Bool(b)) => a == b,
+        (PatternKind::Str(a), Value::Str(b)) => a == b,
+        _ => false,
+    };
+    matches.then(Vec::new)
+}
+
+/// `value as ty`, which only converts between `int` and `float`
+fn cast(value: Value, ty: &Type, span: &Span) -> Result<Value, Diagnostic> {
+    let name = match &ty.kind {
+        TypeKind::Named { name, args } if args.is_empty() => name.as_str(),
+        _ => "",
+    };
+    match (value, name) {
+        (Value::Int(value), "float") => Ok(Value::Float(value as f64)),
+        (Value::Float(value), "int") => {
+            // `i64::MAX as f64` rounds up to 2^63, which does not fit
+            if value.is_finite() && value >= i64::MIN as f64 && value < i64::MAX as f64 {
+                Ok(Value::Int(value.trunc() as i64))
+            } else {
+                Err(overflow("as", span))
+            }
+        }
+        (value, name) if value.type_name() == name => Ok(value),
+        (value, _) => {
+            let message = Message::new(Code::InvalidCast)
+                .with_arg("from", value.type_name())
+                .with_arg("to", ty);
+            Err(error(message, span))
+        }
+    }
+}
+
+/// An error located at `span`, or at its start when it spans several lines
+fn error(message: Message, span: &Span) -> Diagnostic {
+    Diagnostic::error(message, span.location())
+}
+
+fn mismatch(expected: &str, found: &Value, span: &Span) -> Diagnostic {
+    let message = Message::new(Code::TypeMismatch)
+        .with_arg("expected", expected)
+        .with_arg("found", found.type_name());
+    error(message, span)
+}
+
+fn invalid_operands(op: BinaryOp, lhs: &Value, rhs: &Value, span: &Span) -> Diagnostic {
+    let message = Message::new(Code::InvalidOperands)
+        .with_arg("op", op)
+        .with_arg("lhs", lhs.type_name())
+        .with_arg("rhs", rhs.type_name());
+    error(message, span)
+}
+
+fn overflow(op: impl ToString, span: &Span) -> Diagnostic {
+    error(
+        Message::new(Code::ArithmeticOverflow).with_arg("op", op),
+        span,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::messages::Locale;
+    use crate::parser::lower::lower;
+    use crate::parser::snippet::parse_snippet;
+
+    /// Runs `content`, returning its result and what it printed
+    fn run(content: &str) -> (Result<Option<Value>, Diagnostic>, String) {
+        run_with(Interpreter::new(Vec::new()), content)
+    }
+
+    fn run_with(
+        mut interpreter: Interpreter<Vec<u8>>,
+        content: &str,
+    ) -> (Result<Option<Value>, Diagnostic>, String) {
+        let tree = parse_snippet(content).unwrap();
+        let (file, errors) = lower(&tree);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let result = interpreter.run(&file);
+        (result, String::from_utf8(interpreter.out).unwrap())
+    }
+
+    fn value(content: &str) -> String {
+        match run(content).0 {
+            Ok(Some(value)) => value.to_string(),
+            result => panic!("{}: {:?}", content, result),
+        }
+    }
+
+    fn error(content: &str) -> String {
+        match run(content).0 {
+            Err(diagnostic) => diagnostic.render(Locale::En),
+            result => panic!("{}: {:?}", content, result),
+        }
+    }
+
+    #[test]
+    fn test_interpreter_expressions() {
+        assert_eq!(value("1 + 2 * 3\n"), "7");
+        assert_eq!(value("(1 + 2) * 3 - -4\n"), "13");
+        assert_eq!(value("7 / 2\n"), "3");
+        assert_eq!(value("7.0 / 2.0\n"), "3.5");
+        assert_eq!(value("\"fu\" + \"ns\"\n"), "\"funs\"");
+        assert_eq!(value("1 <= 2\n"), "true");
+        assert_eq!(value("\"a\" > \"b\"\n"), "false");
+        assert_eq!(value("true == false\n"), "false");
+        assert_eq!(value("2 as float\n"), "2.0");
+        assert_eq!(value("-2.7 as int\n"), "-2");
+        assert_eq!(value("(1 : int)\n"), "1");
+        assert_eq!(value("if 1 < 2 then \"yes\" else \"no\"\n"), "\"yes\"");
+        assert_eq!(value("x: int = 2\ny: int = x * x\ny + x\n"), "6");
+        assert_eq!(
+            value("match 1 + 1 | 1 => \"one\" | 2 => \"two\" | _ => \"many\" ;\n"),
+            "\"two\""
+        );
+        assert_eq!(value("match -1.5 | -1.5 => 1 | _ => 2 ;\n"), "1");
+        assert_eq!(
+            value("xs: [int] = [2, 3]\n0 : 1 : xs ++ [4]\n"),
+            "[0, 1, 2, 3, 4]"
+        );
+        assert_eq!(value("[[1], []] == [[1], []]\n"), "true");
+        assert_eq!(value("(1, (\"a\", [2.5]))\n"), "(1, (\"a\", [2.5]))");
+        assert_eq!(value("(1, 2) == (1, 2)\n"), "true");
+        assert_eq!(value("()\n"), "()");
+        assert_eq!(
+            value("(a, (b, _)): (int, (str, bool)) = (1, (\"b\", true))\nb + \"c\"\n"),
+            "\"bc\""
+        );
+        assert_eq!(
+            value("match (1, (2, 3)) | (0, _) => 0 | (a, (_, c)) => a + c ;\n"),
+            "4"
+        );
+        let point = "data Point = { x: int, y: int }\np: Point = { y = 2, x = 1 }\n";
+        assert_eq!(value(&format!("{}p\n", point)), "{y = 2, x = 1}");
+        assert_eq!(value(&format!("{}p.x - p.y\n", point)), "-1");
+        assert_eq!(
+            value(&format!("{}p == {{ x = 1, y = 2 }}\n", point)),
+            "true"
+        );
+        assert_eq!(value("{ a = { b = [1] } }.a.b\n"), "[1]");
+        let color = "data Color = Red | Green | Rgb int int int\n";
+        assert_eq!(value(&format!("{}Red\n", color)), "Red");
+        assert_eq!(value(&format!("{}Rgb 1 2 3\n", color)), "Rgb 1 2 3");
+        assert_eq!(value(&format!("{}Rgb 1 2\n", color)), "<function Rgb>");
+        assert_eq!(
+            value(&format!("{}c: [Color] = [Red, Rgb 0 0 255]\nc\n", color)),
+            "[Red, Rgb 0 0 255]"
+        );
+        assert_eq!(value(&format!("{}Rgb 1 2 3 == Rgb 1 2 3\n", color)), "true");
+        assert_eq!(value(&format!("{}Red == Green\n", color)), "false");
+        assert_eq!(
+            value(&format!(
+                "{}blue: (Color) -> int = (c) ->\n\
+                 \x20 match c | Rgb _ _ b => b | _ => 0 ;\n\
+                 ;\n\
+                 blue (Rgb 1 2 3) + blue Red\n",
+                color
+            )),
+            "3"
+        );
+        let nested = "data Tree = None | Some Tree | Leaf int\n";
+        assert_eq!(
+            value(&format!("{}Some (Some (Leaf 1))\n", nested)),
+            "Some (Some (Leaf 1))"
+        );
+        assert_eq!(
+            value(&format!(
+                "{}match Some (Leaf 2) | Some (Leaf n) => n | Some None => 0 | _ => -1 ;\n",
+                nested
+            )),
+            "2"
+        );
+        // A name binds the value in its arm only, a literal of another type does not match
+        assert_eq!(
+            value("x: str = \"b\"\n(match \"a\" | 1 => \"c\" | x => x ;) + x\n"),
+            "\"ab\""
+        );
+        // A program ending with a declaration has no result
+        assert_eq!(run("x: int = 1\n").0, Ok(None));
+    }
+
+    #[test]
+    fn test_interpreter_functions() {
+        let program = "fact: (int) -> int = (n) -> if n == 0 then 1 else n * fact (n - 1) ;\n\
+                       fact 10\n";
+        assert_eq!(value(program), "3628800");
+
+        // Functions can be called before they are declared, and are curried
+        let program = "twice (add 1) 3\n\
+                       add: (int, int) -> int = (a, b) -> a + b ;\n\
+                       twice: ((int) -> int, int) -> int = (f, x) -> f (f x) ;\n\
+                       twice (add 1) 3\n";
+        assert_eq!(value(program), "5");
+        assert_eq!(
+            value("f: (int, int) -> int = (a, b) -> a ;\nf 1\n"),
+            "<function f>"
+        );
+
+        // A function without parameters runs when given `()`
+        let program = "answer: () -> int = () -> 42 ;\nanswer () + 1\n";
+        assert_eq!(value(program), "43");
+        assert_eq!(
+            value("answer: () -> int = () -> 42 ;\nanswer\n"),
+            "<function answer>"
+        );
+
+        // A body sees the scope the function is declared in
+        let program = "outer: (int) -> int = (x) ->\n\
+                       \x20 inner: (int) -> int = (y) -> x + y ;\n\
+                       \x20 inner 10\n\
+                       ;\n\
+                       outer 5\n";
+        assert_eq!(value(program), "15");
+
+        // Each expression evaluated is a step: `f 2` is 3 steps, its body `x * x` 3 more
+        let mut interpreter = Interpreter::new(Vec::new());
+        let tree = parse_snippet("f: (int) -> int = (x) -> x * x ;\nf 2\n").unwrap();
+        interpreter.run(&lower(&tree).0).unwrap();
+        assert_eq!(interpreter.steps(), 6);
+
+        let (result, output) = run("print \"hello\"\nprint 42\n");
+        assert_eq!(result, Ok(Some(Value::Unit)));
+        assert_eq!(output, "hello\n42\n");
+    }
+
+    #[test]
+    fn test_interpreter_trace() {
+        let tree = parse_snippet("f: (int) -> str = (x) -> ?todo ;\nf 21\n").unwrap();
+        let (file, _) = lower(&tree);
+        let Stmt::Expr(call) = &file.stmts[1] else {
+            panic!("{:?}", file.stmts[1]);
+        };
+        assert_eq!(
+            trace_line(0, call, &Ok(Value::Int(42))),
+            "call at 2:1 => 42"
+        );
+        let long = Value::Str("a".repeat(50));
+        assert_eq!(
+            trace_line(2, call, &Ok(long)),
+            format!("    call at 2:1 => \"{}...", "a".repeat(39))
+        );
+        let diagnostic = run("f: (int) -> str = (x) -> ?todo ;\nf 21\n")
+            .0
+            .unwrap_err();
+        assert_eq!(
+            trace_line(1, call, &Err(diagnostic)),
+            "  call at 2:1 => error: reached `?todo`, this code is not written yet"
+        );
+    }
+
+    #[test]
+    fn test_interpreter_errors() {
+        assert_eq!(error("x + 1\n"), "`x` is not defined");
+        assert_eq!(
+            error("1 + \"a\"\n"),
+            "`+` can not be applied to `int` and `str`"
+        );
+        assert_eq!(error("-true\n"), "`-` can not be applied to `bool`");
+        assert_eq!(error("1 / 0\n"), "division by zero");
+        assert_eq!(
+            error("match 3 | 1 => 1 | 2 => 2 ;\n"),
+            "no arm of the `match` matches `3`"
+        );
+        assert_eq!(
+            error("9223372036854775807 + 1\n"),
+            "the result of `+` does not fit in an `int`"
+        );
+        assert_eq!(
+            error("10000000000000000000.0 as int\n"),
+            "the result of `as` does not fit in an `int`"
+        );
+        assert_eq!(error("\"1\" as int\n"), "`str` can not be cast to `int`");
+        assert_eq!(
+            error("if 1 then 2 else 3\n"),
+            "expected `bool`, found `int`"
+        );
+        assert_eq!(error("x: int = \"a\"\n"), "expected `int`, found `str`");
+        assert_eq!(error("x: [int] = 1\n"), "expected `[int]`, found `int`");
+        assert_eq!(
+            error("x: [int] = [1, 2.0]\n"),
+            "expected `int`, found `float`"
+        );
+        assert_eq!(
+            error("t: (int, str) = (1, 2)\n"),
+            "expected `str`, found `int`"
+        );
+        assert_eq!(
+            error("t: (int, int) = 1\n"),
+            "expected `(int, int)`, found `int`"
+        );
+        assert_eq!(
+            error("(0, b): (int, int) = (1, 2)\n"),
+            "`(1, 2)` does not match the pattern of the declaration"
+        );
+        assert_eq!(
+            error("match (1, 2) | (a, b, c) => a ;\n"),
+            "no arm of the `match` matches `(1, 2)`"
+        );
+        let point = "data Point = { x: int, y: int }\n";
+        assert_eq!(
+            error(&format!("{}p: Point = {{ x = 1 }}\n", point)),
+            "`Point` needs a value for its field `y`"
+        );
+        assert_eq!(
+            error(&format!("{}p: Point = {{ x = 1, y = 2, z = 3 }}\n", point)),
+            "`Point` has no field `z`"
+        );
+        assert_eq!(
+            error(&format!("{}p: [Point] = [{{ x = 1, y = \"2\" }}]\n", point)),
+            "expected `int`, found `str`"
+        );
+        assert_eq!(
+            error(&format!("{}p: Point = (1, 2)\n", point)),
+            "expected `Point`, found `tuple`"
+        );
+        let color = "data Color = Red | Rgb int int int\ndata Other = Blue\n";
+        assert_eq!(
+            error(&format!("{}c: Color = Rgb 1 2 \"3\"\n", color)),
+            "expected `int`, found `str`"
+        );
+        assert_eq!(
+            error(&format!("{}c: Color = Blue\n", color)),
+            "expected `Color`, found `variant`"
+        );
+        assert_eq!(
+            error(&format!("{}c: [Color] = [1]\n", color)),
+            "expected `Color`, found `int`"
+        );
+        assert_eq!(
+            error(&format!("{}match Red | Rgb r _ _ => r ;\n", color)),
+            "no arm of the `match` matches `Red`"
+        );
+        assert_eq!(error("{ a = 1 }.b\n"), "`record` has no field `b`");
+        assert_eq!(
+            error("data P = { a: int }\np: P = { a = 1 }\np.b\n"),
+            "`P` has no field `b`"
+        );
+        assert_eq!(error("x: int = 1\nx.a\n"), "expected `record`, found `int`");
+        assert_eq!(
+            error("[1] ++ 2\n"),
+            "`++` can not be applied to `list` and `int`"
+        );
+        assert_eq!(
+            error("x: int = 1\nx 2\n"),
+            "expected `function`, found `int`"
+        );
+        assert_eq!(
+            error("f: (int) -> int = ?todo\nf 1\n"),
+            "reached `?todo`, this code is not written yet"
+        );
+
+        let (result, _) = run_with(
+            Interpreter::new(Vec::new()).with_max_depth(16),
+            "f: (int) -> int = (n) -> f n ;\nf 1\n",
+        );
+        let diagnostic = result.unwrap_err();
+        assert_eq!(
+            diagnostic.render(Locale::En),
+            "too many nested calls, the limit is 16"
+        );
+        // Located at the call, on its line only
+        assert_eq!(
+            (
+                diagnostic.location.line,
+                diagnostic.location.column_start,
+                diagnostic.location.column_end
+            ),
+            (0, 25, 28)
+        );
+    }
+
+    /// The default limit is reached before the stack of `with_stack` overflows, with
+    /// bodies whose calls nest expressions too
+    #[test]
+    fn test_interpreter_default_call_depth() {
+        let recurse = |body: &str, n: usize| {
+            let content = format!(
+                "f: (int) -> int = (n) -> if n == 0 then 0 else {} ;\nf {}\n",
+                body, n
+            );
+            with_stack(move || match run(&content).0 {
+                Ok(value) => format!("{:?}", value),
+                Err(diagnostic) => diagnostic.render(Locale::En),
+            })
+            .unwrap()
+        };
+        let limit = format!("too many nested calls, the limit is {}", MAX_CALL_DEPTH);
+        assert_eq!(
+            recurse("1 + f (n - 1)", MAX_CALL_DEPTH - 1),
+            "Some(Int(511))"
+        );
+        assert_eq!(recurse("1 + f (n - 1)", MAX_CALL_DEPTH), limit);
+        assert_eq!(recurse("1 + f (n - 1)", 100_000), limit);
+        assert_eq!(recurse("1 * (2 + (3 - (1 + f (n - 1))))", 100_000), limit);
+        assert_eq!(recurse("f (n - 1)", 100_000), limit);
+    }
+}
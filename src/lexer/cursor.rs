@@ -5,7 +5,9 @@ use super::token::TokenLocation;
 pub struct Cursor {
     source: Source,
     location: TokenLocation,
+    /// Byte index of the first character of the current lexeme
     index: usize,
+    /// Byte index of the character under the cursor
     offset: usize,
 }
 
@@ -14,7 +16,27 @@ impl Cursor {
         if self.is_eof() {
             return None;
         }
-        self.source.content().chars().nth(self.offset)
+        self.source.content().get(self.offset..)?.chars().next()
+    }
+
+    /// Returns the character after the one under the cursor
+    pub fn peek_next(&self) -> Option<char> {
+        if self.is_eof() {
+            return None;
+        }
+        let mut chars = self.source.content().get(self.offset..)?.chars();
+        chars.next();
+        chars.next()
+    }
+
+    /// Returns the character before the start of the current lexeme
+    pub fn peek_before_index(&self) -> Option<char> {
+        self.source.content().get(..self.index)?.chars().next_back()
+    }
+
+    /// The width in bytes of the character under the cursor
+    fn width(&self) -> usize {
+        self.peek().map_or(1, char::len_utf8)
     }
 
     pub fn source(&self) -> &Source {
@@ -60,10 +82,11 @@ impl Cursor {
         if self.is_eof() {
             return;
         }
+        let width = self.width();
         self.location.advance_column_start();
         self.location.advance_column_end();
-        self.index += 1;
-        self.offset += 1;
+        self.index += width;
+        self.offset += width;
     }
 
     /// Advances the cursor without consuming the current character
@@ -90,8 +113,9 @@ impl Cursor {
             return;
         }
 
+        let width = self.width();
         self.location.advance_column_end();
-        self.offset += 1;
+        self.offset += width;
     }
 
     /// Aligns the column start with the column end
@@ -114,34 +138,41 @@ impl Cursor {
         self.index = self.offset;
     }
 
-    /// Advances only the cursor indexes
-    pub fn remove_carriage_return(&mut self) {
-        self.source.content_mut().remove(self.offset);
+    /// The width in bytes of the line break under the cursor, `\n` or `\r\n`
+    pub fn line_break(&self) -> Option<usize> {
+        let rest = self.source.content().get(self.offset..)?;
+        if rest.starts_with('\n') {
+            Some(1)
+        } else if rest.starts_with("\r\n") {
+            Some(2)
+        } else {
+            None
+        }
     }
 
-    /// Advances the cursor to the next line
+    /// Moves the cursor past the line break under the cursor, to the start of the next line
     /// ```text
     /// Before new line:
-    /// test\ntest2
+    /// test\r\ntest2
     ///     ^_____ index = 4
     ///     ^_____ offset = 4
     ///     ^_____ column_start = 4
     ///     ^_____ column_end = 4
     ///
     /// After new line:
-    /// test\ntest2
-    ///       ^_____ index = 5
-    ///       ^_____ offset = 5
-    ///       ^_____ column_start = 0
-    ///       ^_____ column_end = 0
+    /// test\r\ntest2
+    ///         ^_____ index = 6
+    ///         ^_____ offset = 6
+    ///         ^_____ column_start = 0
+    ///         ^_____ column_end = 0
+    /// ```
     pub fn new_line(&mut self) {
-        if self.is_eof() {
+        let Some(width) = self.line_break() else {
             return;
-        }
-
+        };
         self.location.advance_line();
+        self.offset += width;
         self.index = self.offset;
-        self.offset += 1;
     }
 }
 
@@ -167,6 +198,24 @@ mod tests {
         assert_eq!(cursor.peek(), Some('t'));
     }
 
+    #[test]
+    fn test_lexer_cursor_peek_next() {
+        let source = Source::from("ab".to_string());
+        let mut cursor = Cursor::from(&source);
+        assert_eq!(cursor.peek_next(), Some('b'));
+        cursor.advance_offset();
+        assert_eq!(cursor.peek_next(), None);
+    }
+
+    #[test]
+    fn test_lexer_cursor_peek_before_index() {
+        let source = Source::from("'a".to_string());
+        let mut cursor = Cursor::from(&source);
+        assert_eq!(cursor.peek_before_index(), None);
+        cursor.consume();
+        assert_eq!(cursor.peek_before_index(), Some('\''));
+    }
+
     #[test]
     fn test_lexer_cursor_consume() {
         let source = Source::from("test_id".to_string());
@@ -185,6 +234,16 @@ mod tests {
         assert_eq!(cursor.location().column_end, 1);
     }
 
+    #[test]
+    fn test_lexer_cursor_advance_multibyte() {
+        let source = Source::from("👾x".to_string());
+        let mut cursor = Cursor::from(&source);
+        cursor.advance_offset();
+        assert_eq!(cursor.offset(), '👾'.len_utf8());
+        assert_eq!(cursor.location().column_end, 1);
+        assert_eq!(cursor.peek(), Some('x'));
+    }
+
     #[test]
     fn test_lexer_cursor_align() {
         let source = Source::from("test_id".to_string());
@@ -194,4 +253,34 @@ mod tests {
         assert_eq!(cursor.location().column_start, 1);
         assert_eq!(cursor.location().column_end, 1);
     }
+
+    #[test]
+    fn test_lexer_cursor_new_line() {
+        let source = Source::from("a\nb\r\nc\rd".to_string());
+        let mut cursor = Cursor::from(&source);
+        assert_eq!(cursor.line_break(), None);
+        cursor.consume();
+        assert_eq!(cursor.line_break(), Some(1));
+        cursor.new_line();
+        assert_eq!(
+            (cursor.location().line, cursor.location().column_start),
+            (1, 0)
+        );
+        assert_eq!(cursor.peek(), Some('b'));
+
+        cursor.consume();
+        assert_eq!(cursor.line_break(), Some(2));
+        cursor.new_line();
+        assert_eq!(
+            (cursor.location().line, cursor.location().column_start),
+            (2, 0)
+        );
+        assert_eq!(cursor.peek(), Some('c'));
+
+        // A lone carriage return is not a line break
+        cursor.consume();
+        assert_eq!(cursor.line_break(), None);
+        cursor.new_line();
+        assert_eq!(cursor.location().line, 2);
+    }
 }
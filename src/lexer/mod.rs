@@ -2,22 +2,26 @@ pub mod cursor;
 pub mod states;
 pub mod token;
 
+use crate::diagnostics::Diagnostic;
 use crate::lexer::token::Token;
 use crate::source::Source;
 use cursor::Cursor;
-use states::{State, StateStart, Transition, TransitionKind};
+use states::{State, StateLineStart, Transition, TransitionKind};
 use tracing::{error, info};
 
 pub struct Lexer {
     cursor: Cursor,
     state: Box<dyn State>,
+    /// The problems found in the tokens emitted so far
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl Lexer {
     pub fn new(source: &Source) -> Lexer {
         let lexer = Lexer {
             cursor: Cursor::from(source),
-            state: Box::new(StateStart),
+            state: Box::new(StateLineStart),
+            diagnostics: Vec::new(),
         };
         info!("Created Lexer");
         lexer
@@ -27,6 +31,12 @@ impl Lexer {
         &self.cursor
     }
 
+    /// The problems found in the tokens emitted so far, e.g. an unterminated block
+    /// comment. They do not stop the lexer, the tokens still cover the whole source.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
     fn proceed(state: Box<dyn State>, transition_kind: TransitionKind) -> Transition {
         Transition::new(state, transition_kind)
     }
@@ -44,7 +54,11 @@ impl Iterator for Lexer {
                     return None;
                 }
             };
-            let (state, transition_kind) = transition.into_parts();
+            let (state, transition_kind, diagnostic) = transition.into_parts();
+            if let Some(diagnostic) = diagnostic {
+                error!("{}", diagnostic);
+                self.diagnostics.push(diagnostic);
+            }
 
             self.state = state;
             transition_kind.apply(&mut self.cursor);
@@ -73,17 +87,233 @@ impl std::fmt::Display for LexerError {
 #[cfg(test)]
 pub mod tests {
     use crate::{
-        lexer::{token::Token, Lexer},
+        diagnostics::messages::Locale,
+        lexer::{
+            token::{Literal, Token, TokenKind},
+            Lexer,
+        },
         source::Source,
         utils::file_handler::collect_fs_files,
     };
     use pretty_assertions::assert_eq;
     use tracing::info;
 
+    fn kinds(content: &str) -> Vec<TokenKind> {
+        Lexer::new(&Source::from(content.to_string()))
+            .map(|token| token.kind)
+            .collect()
+    }
+
+    #[test]
+    fn test_lexer_maximal_munch() {
+        use TokenKind::*;
+        let cases = [
+            ("->", vec![TokenRightArrow]),
+            ("=>", vec![TokenRightDoubleArrow]),
+            ("++", vec![TokenPlusPlus]),
+            ("|>", vec![TokenPipeForward]),
+            ("==", vec![TokenEqualEqual]),
+            ("<=", vec![TokenLessEqual]),
+            (">=", vec![TokenGreaterEqual]),
+            ("- >", vec![TokenMinus, TokenGreater]),
+            ("->>", vec![TokenRightArrow, TokenGreater]),
+            ("-->", vec![TokenMinus, TokenRightArrow]),
+            ("===", vec![TokenEqualEqual, TokenAssign]),
+            ("+++", vec![TokenPlusPlus, TokenPlus]),
+            ("||>", vec![TokenPipe, TokenPipeForward]),
+            ("|>>", vec![TokenPipeForward, TokenGreater]),
+            ("=>=", vec![TokenRightDoubleArrow, TokenAssign]),
+            ("<-", vec![TokenLess, TokenMinus]),
+            ("-(", vec![TokenMinus, TokenOpenParen]),
+            ("(-", vec![TokenOpenParen, TokenMinus]),
+        ];
+        for (input, mut expected) in cases {
+            expected.push(TokenEOF);
+            assert_eq!(kinds(input), expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_lexer_symbol_before_new_line() {
+        use TokenKind::*;
+        for symbol in ["-", "=", "+", "<", ">", "|", "->", "=="] {
+            let input = format!("{}\n", symbol);
+            assert_eq!(
+                kinds(&input),
+                vec![TokenKind::from(&symbol.to_string()), TokenNewLine, TokenEOF],
+                "{:?}",
+                input
+            );
+        }
+    }
+
+    /// The kind, line, column start and column end of each token
+    fn positions(content: &str) -> Vec<(TokenKind, usize, usize, usize)> {
+        Lexer::new(&Source::from(content.to_string()))
+            .map(|token| {
+                let location = token.location;
+                (
+                    token.kind,
+                    location.line,
+                    location.column_start,
+                    location.column_end,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_lexer_new_line_positions() {
+        use TokenKind::*;
+        let expected = vec![
+            (TokenIdentifier, 0, 0, 1),
+            (TokenNewLine, 0, 1, 1),
+            (TokenIdentifier, 1, 2, 3),
+            (TokenNewLine, 1, 3, 3),
+            (TokenEOF, 2, 0, 0),
+        ];
+        assert_eq!(positions("a\n  b\n"), expected);
+        assert_eq!(positions("a\r\n  b\r\n"), expected);
+    }
+
+    #[test]
+    fn test_lexer_blank_lines() {
+        use TokenKind::*;
+        assert_eq!(
+            positions("\n  \na\n\n \t\r\n\nb\n\n"),
+            vec![
+                (TokenIdentifier, 2, 0, 1),
+                (TokenNewLine, 2, 1, 1),
+                (TokenIdentifier, 6, 0, 1),
+                (TokenNewLine, 6, 1, 1),
+                (TokenEOF, 8, 0, 0),
+            ]
+        );
+        assert_eq!(kinds("\n\n"), vec![TokenEOF]);
+        // A carriage return that does not end a line is whitespace
+        assert_eq!(
+            positions("a\rb"),
+            vec![
+                (TokenIdentifier, 0, 0, 1),
+                (TokenIdentifier, 0, 2, 3),
+                (TokenEOF, 0, 3, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lexer_block_comments() {
+        use TokenKind::*;
+        assert_eq!(
+            positions("#| a |# \nx"),
+            vec![
+                (TokenComment, 0, 0, 7),
+                (TokenNewLine, 0, 8, 8),
+                (TokenIdentifier, 1, 0, 1),
+                (TokenEOF, 1, 1, 1),
+            ]
+        );
+        // Nested, and over several lines: located at the first line, the tokens after
+        // it keep their positions
+        let content = "#| a\n #| b |#\r\n|# x\ny";
+        let tokens = Lexer::new(&Source::from(content.to_string())).collect::<Vec<_>>();
+        assert_eq!(tokens[0].lexeme, "#| a\n #| b |#\r\n|#");
+        assert_eq!(
+            positions(content),
+            vec![
+                (TokenComment, 0, 0, 4),
+                (TokenIdentifier, 2, 3, 4),
+                (TokenNewLine, 2, 4, 4),
+                (TokenIdentifier, 3, 0, 1),
+                (TokenEOF, 3, 1, 1),
+            ]
+        );
+        assert_eq!(
+            kinds("#|#||#|#\n"),
+            vec![TokenComment, TokenNewLine, TokenEOF]
+        );
+        // A `#` that is not followed by `|` is a line comment
+        assert_eq!(
+            kinds("# |# x\n"),
+            vec![TokenComment, TokenNewLine, TokenEOF]
+        );
+        // An unterminated comment runs to the end of the file
+        assert_eq!(kinds("#| a #| b |#\nx"), vec![TokenComment, TokenEOF]);
+        let source = Source::from("x\n#| a #| b |#\n".to_string());
+        let mut lexer = Lexer::new(&source);
+        assert!(lexer.by_ref().all(|token| token.kind != TokenUnknown));
+        let diagnostics = lexer.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].render(Locale::En),
+            "unterminated block comment"
+        );
+        assert_eq!(
+            (
+                diagnostics[0].location.line,
+                diagnostics[0].location.column_start
+            ),
+            (1, 0)
+        );
+    }
+
+    #[test]
+    fn test_lexer_strings() {
+        use TokenKind::*;
+        assert_eq!(
+            positions("x = \"a b\"\n"),
+            vec![
+                (TokenIdentifier, 0, 0, 1),
+                (TokenAssign, 0, 2, 3),
+                (TokenLiteral(Literal::Str), 0, 4, 9),
+                (TokenNewLine, 0, 9, 9),
+                (TokenEOF, 1, 0, 0),
+            ]
+        );
+        // A string ends with its line: the line break and the next line are lexed as
+        // usual, `\r\n` included
+        for content in ["\"a b\nx", "\"a b\r\nx"] {
+            assert_eq!(
+                positions(content),
+                vec![
+                    (TokenLiteral(Literal::Str), 0, 0, 4),
+                    (TokenNewLine, 0, 4, 4),
+                    (TokenIdentifier, 1, 0, 1),
+                    (TokenEOF, 1, 1, 1),
+                ]
+            );
+        }
+        // Or with the file
+        assert_eq!(
+            positions("x \"a"),
+            vec![
+                (TokenIdentifier, 0, 0, 1),
+                (TokenLiteral(Literal::Str), 0, 2, 4),
+                (TokenEOF, 0, 4, 4),
+            ]
+        );
+        for (content, span) in [("x \"a", (0, 2, 4)), ("\"\"\n\"a b\n", (1, 0, 4))] {
+            let source = Source::from(content.to_string());
+            let mut lexer = Lexer::new(&source);
+            lexer.by_ref().for_each(drop);
+            let diagnostics = lexer.diagnostics();
+            assert_eq!(diagnostics.len(), 1, "{:?}", content);
+            assert_eq!(
+                diagnostics[0].render(Locale::En),
+                "unterminated string literal"
+            );
+            let location = &diagnostics[0].location;
+            assert_eq!(
+                (location.line, location.column_start, location.column_end),
+                span
+            );
+        }
+    }
+
     #[test]
     fn test_lexer_native_types() {
         let fs_files = collect_fs_files("./testdata/native_types", true);
-        assert_eq!(fs_files.len(), 15);
+        assert_eq!(fs_files.len(), 17);
 
         for path in fs_files {
             info!("file -> {:?}", path);
@@ -106,7 +336,7 @@ pub mod tests {
     #[test]
     fn test_lexer_functions() {
         let fs_files = collect_fs_files("./testdata/functions", true);
-        assert_eq!(fs_files.len(), 9);
+        assert_eq!(fs_files.len(), 13);
 
         for path in fs_files {
             info!("file -> {:?}", path);
@@ -213,10 +443,135 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn test_lexer_empty_files() {
+        let fs_files = collect_fs_files("./testdata/empty", true);
+        assert_eq!(fs_files.len(), 3);
+
+        for path in fs_files {
+            info!("file -> {:?}", path);
+            eprintln!("file -> {:?}", path);
+            let content = std::fs::read_to_string(&path).unwrap();
+            let output_tokens = Lexer::new(&Source::from(content)).collect::<Vec<Token>>();
+
+            let tokens_file = path.to_str().unwrap().replace(".fs", ".tokens.json");
+            let tokens = std::fs::File::open(tokens_file).unwrap();
+            let expected_tokens: Vec<Token> = serde_json::from_reader(tokens).unwrap();
+            assert_eq!(output_tokens, expected_tokens);
+        }
+    }
+
+    #[test]
+    fn test_lexer_identifiers() {
+        let fs_files = collect_fs_files("./testdata/identifiers", true);
+        assert_eq!(fs_files.len(), 2);
+
+        for path in fs_files {
+            info!("file -> {:?}", path);
+            eprintln!("file -> {:?}", path);
+            let input = std::fs::File::open(path.clone()).unwrap();
+            let content = std::io::read_to_string(input).unwrap();
+            let source = Source::from(content);
+            let lexer = Lexer::new(&source);
+            let output_tokens = lexer.collect::<Vec<Token>>();
+
+            let fs_file = path.to_str().unwrap();
+            let tokens_file = fs_file.to_string().replace(".fs", ".tokens.json");
+            let tokens = std::fs::File::open(tokens_file).unwrap();
+            let expected_tokens: Vec<Token> = serde_json::from_reader(tokens).unwrap();
+            assert_eq!(output_tokens, expected_tokens);
+        }
+    }
+
+    #[test]
+    fn test_lexer_operators() {
+        let fs_files = collect_fs_files("./testdata/operators", true);
+        assert_eq!(fs_files.len(), 2);
+
+        for path in fs_files {
+            info!("file -> {:?}", path);
+            eprintln!("file -> {:?}", path);
+            let input = std::fs::File::open(path.clone()).unwrap();
+            let content = std::io::read_to_string(input).unwrap();
+            let source = Source::from(content);
+            let lexer = Lexer::new(&source);
+            let output_tokens = lexer.collect::<Vec<Token>>();
+
+            let fs_file = path.to_str().unwrap();
+            let tokens_file = fs_file.to_string().replace(".fs", ".tokens.json");
+            let tokens = std::fs::File::open(tokens_file).unwrap();
+            let expected_tokens: Vec<Token> = serde_json::from_reader(tokens).unwrap();
+            assert_eq!(output_tokens, expected_tokens);
+        }
+    }
+
+    #[test]
+    fn test_lexer_holes() {
+        let fs_files = collect_fs_files("./testdata/holes", true);
+        assert_eq!(fs_files.len(), 1);
+
+        for path in fs_files {
+            info!("file -> {:?}", path);
+            eprintln!("file -> {:?}", path);
+            let input = std::fs::File::open(path.clone()).unwrap();
+            let content = std::io::read_to_string(input).unwrap();
+            let source = Source::from(content);
+            let lexer = Lexer::new(&source);
+            let output_tokens = lexer.collect::<Vec<Token>>();
+
+            let fs_file = path.to_str().unwrap();
+            let tokens_file = fs_file.to_string().replace(".fs", ".tokens.json");
+            let tokens = std::fs::File::open(tokens_file).unwrap();
+            let expected_tokens: Vec<Token> = serde_json::from_reader(tokens).unwrap();
+            assert_eq!(output_tokens, expected_tokens);
+        }
+    }
+
+    #[test]
+    fn test_lexer_locations() {
+        let fs_files = collect_fs_files("./testdata/locations", true);
+        assert_eq!(fs_files.len(), 3);
+
+        for path in fs_files {
+            info!("file -> {:?}", path);
+            eprintln!("file -> {:?}", path);
+            let input = std::fs::File::open(path.clone()).unwrap();
+            let content = std::io::read_to_string(input).unwrap();
+            let source = Source::from(content);
+            let lexer = Lexer::new(&source);
+            let output_tokens = lexer.collect::<Vec<Token>>();
+
+            let fs_file = path.to_str().unwrap();
+            let tokens_file = fs_file.to_string().replace(".fs", ".tokens.json");
+            let tokens = std::fs::File::open(tokens_file).unwrap();
+            let expected_tokens: Vec<Token> = serde_json::from_reader(tokens).unwrap();
+            assert_eq!(output_tokens, expected_tokens);
+        }
+    }
+
+    #[test]
+    fn test_lexer_no_trailing_newline() {
+        let fs_files = collect_fs_files("./testdata/no_trailing_newline", true);
+        assert_eq!(fs_files.len(), 3);
+
+        for path in fs_files {
+            info!("file -> {:?}", path);
+            eprintln!("file -> {:?}", path);
+            let content = std::fs::read_to_string(&path).unwrap();
+            assert!(!content.ends_with('\n'), "{:?}", path);
+            let output_tokens = Lexer::new(&Source::from(content)).collect::<Vec<Token>>();
+
+            let tokens_file = path.to_str().unwrap().replace(".fs", ".tokens.json");
+            let tokens = std::fs::File::open(tokens_file).unwrap();
+            let expected_tokens: Vec<Token> = serde_json::from_reader(tokens).unwrap();
+            assert_eq!(output_tokens, expected_tokens);
+        }
+    }
+
     #[test]
     fn test_lexer_errors() {
         let fs_files = collect_fs_files("./testdata/errors", true);
-        assert_eq!(fs_files.len(), 2);
+        assert_eq!(fs_files.len(), 3);
 
         for path in fs_files {
             info!("file -> {:?}", path);
@@ -2,8 +2,11 @@ use super::cursor::Cursor;
 use super::token::Literal;
 use super::Lexer;
 use super::LexerError;
+use crate::diagnostics::messages::{Code, Message};
+use crate::diagnostics::Diagnostic;
 use crate::lexer::token::Token;
 use crate::lexer::token::TokenKind;
+use crate::lexer::token::TokenLocation;
 use std::fmt::Debug;
 
 pub trait State: Debug {
@@ -39,6 +42,8 @@ impl TransitionKind {
 pub struct Transition {
     state: Box<dyn State>,
     transition_kind: TransitionKind,
+    /// A problem found while visiting the state, reported by the lexer
+    diagnostic: Option<Diagnostic>,
 }
 
 impl Transition {
@@ -46,11 +51,17 @@ impl Transition {
         Transition {
             state,
             transition_kind: consume_kind,
+            diagnostic: None,
         }
     }
 
-    pub fn into_parts(self) -> (Box<dyn State>, TransitionKind) {
-        (self.state, self.transition_kind)
+    pub fn with_diagnostic(mut self, diagnostic: Diagnostic) -> Transition {
+        self.diagnostic = Some(diagnostic);
+        self
+    }
+
+    pub fn into_parts(self) -> (Box<dyn State>, TransitionKind, Option<Diagnostic>) {
+        (self.state, self.transition_kind, self.diagnostic)
     }
 }
 
@@ -69,10 +80,23 @@ impl State for StateStart {
                 // cursor.advance_offset();
                 // Ok(Lexer::proceed(Box::new(StateStart),TransitionKind::EmitToken(Token::new(TokenKind::from(&c.to_string()),c.to_string(),cursor.location().clone(),)),))
             }
-            Some(c) if c.eq(&'\r') => {
-                cursor.remove_carriage_return();
-                Ok(Lexer::proceed(Box::new(StateStart), TransitionKind::Empty))
+            Some('\n' | '\r') if cursor.line_break().is_some() => {
+                let location = cursor.location().clone();
+                cursor.new_line();
+                Ok(Lexer::proceed(
+                    Box::new(StateLineStart),
+                    TransitionKind::EmitToken(Token::new(
+                        TokenKind::TokenNewLine,
+                        "\\n".to_string(),
+                        location,
+                    )),
+                ))
             }
+            // A carriage return that does not end a line is whitespace
+            Some('\r') => Ok(Lexer::proceed(
+                Box::new(StateStart),
+                TransitionKind::Consume,
+            )),
             Some(c) if c.eq(&'"') => Ok(Lexer::proceed(
                 Box::new(StateString),
                 TransitionKind::AdvanceOffset,
@@ -81,9 +105,29 @@ impl State for StateStart {
                 Box::new(StateWord),
                 TransitionKind::AdvanceOffset,
             )),
+            // A hole is a `?` immediately followed by a name, e.g. `?todo`
+            Some('?')
+                if cursor
+                    .peek_next()
+                    .is_some_and(|c| c.is_alphabetic() || c.eq(&'_')) =>
+            {
+                Ok(Lexer::proceed(
+                    Box::new(StateWord),
+                    TransitionKind::AdvanceOffset,
+                ))
+            }
             Some(c) if TokenKind::is_symbol(c.to_string().as_str()) => {
                 Ok(Lexer::proceed(Box::new(StateSymbol), TransitionKind::Empty))
             }
+            Some('#') if cursor.peek_next() == Some('|') => Ok(Lexer::proceed(
+                Box::new(StateBlockComment {
+                    start: cursor.index(),
+                    location: cursor.location().clone(),
+                    first_line_end: None,
+                    depth: 0,
+                }),
+                TransitionKind::Empty,
+            )),
             Some('#') => Ok(Lexer::proceed(
                 Box::new(StateComment),
                 TransitionKind::AdvanceOffset,
@@ -108,40 +152,76 @@ impl State for StateStart {
     }
 }
 
+/// The start of a line, before its first token.
+///
+/// Blank lines (made only of whitespace) are skipped here, so that a run of line breaks
+/// is lexed as a single new line token and a file never starts with one.
 #[derive(Debug)]
-pub struct StateString;
+pub struct StateLineStart;
 
-impl State for StateString {
+impl State for StateLineStart {
     fn visit(&self, cursor: &mut Cursor) -> Result<Transition, LexerError> {
         match cursor.peek() {
-            Some(c) if c.ne(&'"') => Ok(Lexer::proceed(
-                Box::new(StateString),
-                TransitionKind::AdvanceOffset,
+            Some(' ' | '\t') => Ok(Lexer::proceed(
+                Box::new(StateLineStart),
+                TransitionKind::Consume,
             )),
-            Some(c) if c.eq(&'"') => {
-                cursor.advance_offset();
+            Some('\r' | '\n') if cursor.line_break().is_some() => {
+                cursor.new_line();
                 Ok(Lexer::proceed(
-                    Box::new(StateStart),
-                    TransitionKind::EmitToken(Token::new(
-                        TokenKind::TokenLiteral(Literal::Str),
-                        cursor.source().content()[cursor.index()..cursor.offset()].to_string(),
-                        cursor.location().clone(),
-                    )),
+                    Box::new(StateLineStart),
+                    TransitionKind::Empty,
                 ))
             }
-            Some(c) => Ok(Lexer::proceed(
-                Box::new(StateStart),
-                TransitionKind::EmitToken(Token::new(
-                    TokenKind::TokenUnknown,
-                    c.to_string(),
-                    cursor.location().clone(),
-                )),
+            _ => Ok(Lexer::proceed(Box::new(StateStart), TransitionKind::Empty)),
+        }
+    }
+}
+
+/// A string literal, from its `"` to the next one on the same line.
+///
+/// A string cut by the end of its line or of the file is still emitted as a string
+/// literal, along with an `UnterminatedString` diagnostic spanning it; the line break is
+/// left for the next token.
+#[derive(Debug)]
+pub struct StateString;
+
+impl State for StateString {
+    fn visit(&self, cursor: &mut Cursor) -> Result<Transition, LexerError> {
+        if cursor.line_break().is_some() || cursor.peek().is_none() {
+            let transition = emit_string(cursor);
+            let diagnostic = Diagnostic::error(
+                Message::new(Code::UnterminatedString),
+                cursor.location().clone(),
+            )
+            .with_note("a string ends with `\"` on the line it starts");
+            return Ok(transition.with_diagnostic(diagnostic));
+        }
+        match cursor.peek() {
+            Some('"') => {
+                cursor.advance_offset();
+                Ok(emit_string(cursor))
+            }
+            _ => Ok(Lexer::proceed(
+                Box::new(StateString),
+                TransitionKind::AdvanceOffset,
             )),
-            None => Ok(Lexer::proceed(Box::new(StateEOF), TransitionKind::Consume)),
         }
     }
 }
 
+/// Emits the string literal from the index of `cursor` to its offset
+fn emit_string(cursor: &Cursor) -> Transition {
+    Lexer::proceed(
+        Box::new(StateStart),
+        TransitionKind::EmitToken(Token::new(
+            TokenKind::TokenLiteral(Literal::Str),
+            cursor.source().content()[cursor.index()..cursor.offset()].to_string(),
+            cursor.location().clone(),
+        )),
+    )
+}
+
 #[derive(Debug)]
 pub struct StateComment;
 
@@ -164,6 +244,97 @@ impl State for StateComment {
     }
 }
 
+/// A block comment, from `#|` to the matching `|#`, e.g. `#| a #| nested |# comment |#`.
+///
+/// It is emitted as a `TokenComment` located at its first line: the column end is the
+/// end of the comment if it fits on one line, and the end of its first line otherwise.
+/// The tokens after it keep their exact line and column.
+///
+/// An unterminated comment runs to the end of the file: it is still emitted as a
+/// `TokenComment`, along with an `UnterminatedBlockComment` diagnostic.
+#[derive(Debug)]
+pub struct StateBlockComment {
+    /// Byte index of the `#` starting the comment
+    start: usize,
+    /// Where the comment starts
+    location: TokenLocation,
+    /// The column end of the first line, once the comment spans several lines
+    first_line_end: Option<usize>,
+    /// The number of comments open at the cursor
+    depth: usize,
+}
+
+impl StateBlockComment {
+    /// The same comment, at `depth` with the first line ending at `first_line_end`
+    fn with(&self, depth: usize, first_line_end: Option<usize>) -> Box<StateBlockComment> {
+        Box::new(StateBlockComment {
+            start: self.start,
+            location: self.location.clone(),
+            first_line_end,
+            depth,
+        })
+    }
+
+    fn emit(&self, cursor: &Cursor) -> Transition {
+        let column_end = self.first_line_end.unwrap_or(cursor.location().column_end);
+        Lexer::proceed(
+            Box::new(StateStart),
+            TransitionKind::EmitToken(Token::new(
+                TokenKind::TokenComment,
+                cursor.source().content()[self.start..cursor.offset()].to_string(),
+                self.location.with_column_end(column_end),
+            )),
+        )
+    }
+}
+
+impl State for StateBlockComment {
+    fn visit(&self, cursor: &mut Cursor) -> Result<Transition, LexerError> {
+        let rest = &cursor.source().content()[cursor.offset()..];
+        if rest.starts_with("#|") {
+            cursor.advance_offset();
+            return Ok(Lexer::proceed(
+                self.with(self.depth + 1, self.first_line_end),
+                TransitionKind::AdvanceOffset,
+            ));
+        }
+        if rest.starts_with("|#") {
+            cursor.advance_offset();
+            cursor.advance_offset();
+            if self.depth == 1 {
+                return Ok(self.emit(cursor));
+            }
+            return Ok(Lexer::proceed(
+                self.with(self.depth - 1, self.first_line_end),
+                TransitionKind::Empty,
+            ));
+        }
+        if cursor.line_break().is_some() {
+            let first_line_end = self.first_line_end.unwrap_or(cursor.location().column_end);
+            cursor.new_line();
+            return Ok(Lexer::proceed(
+                self.with(self.depth, Some(first_line_end)),
+                TransitionKind::Empty,
+            ));
+        }
+        match cursor.peek() {
+            Some(_) => Ok(Lexer::proceed(
+                self.with(self.depth, self.first_line_end),
+                TransitionKind::AdvanceOffset,
+            )),
+            None => {
+                let location = self
+                    .location
+                    .with_column_end(self.location.column_start + 2);
+                let diagnostic =
+                    Diagnostic::error(Message::new(Code::UnterminatedBlockComment), location)
+                        .with_note("block comments nest, every `#|` needs its own `|#`");
+                Ok(self.emit(cursor).with_diagnostic(diagnostic))
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct StateNumber;
 
@@ -174,6 +345,10 @@ impl State for StateNumber {
                 Box::new(StateNumber),
                 TransitionKind::AdvanceOffset,
             )),
+            Some(c) if c.is_alphabetic() || c.eq(&'_') => Ok(Lexer::proceed(
+                Box::new(StateNumberSuffix),
+                TransitionKind::AdvanceOffset,
+            )),
             _ => {
                 let lexeme = cursor.source().content()[cursor.index()..cursor.offset()].to_string();
                 let location = cursor.location().clone();
@@ -187,82 +362,87 @@ impl State for StateNumber {
     }
 }
 
+/// The type suffix of a numeric literal, e.g. `i` in `3i` or `_float` in `3_float`
 #[derive(Debug)]
-pub struct StateWord;
+pub struct StateNumberSuffix;
 
-impl State for StateWord {
+impl State for StateNumberSuffix {
     fn visit(&self, cursor: &mut Cursor) -> Result<Transition, LexerError> {
         match cursor.peek() {
             Some(c) if c.is_alphanumeric() || c.eq(&'_') => Ok(Lexer::proceed(
-                Box::new(StateWord),
+                Box::new(StateNumberSuffix),
                 TransitionKind::AdvanceOffset,
             )),
             _ => {
-                // Emit token when we encounter a non-alphabetic character
                 let lexeme = cursor.source().content()[cursor.index()..cursor.offset()].to_string();
-                let token_kind = TokenKind::from(&lexeme);
                 let location = cursor.location().clone();
-                Ok(Transition {
-                    state: Box::new(StateStart),
-                    transition_kind: TransitionKind::EmitToken(Token::new(
-                        token_kind, lexeme, location,
-                    )),
-                })
+                let token_kind = TokenKind::from(&lexeme);
+                Ok(Lexer::proceed(
+                    Box::new(StateStart),
+                    TransitionKind::EmitToken(Token::new(token_kind, lexeme, location)),
+                ))
             }
         }
     }
 }
 
 #[derive(Debug)]
-pub struct StateSymbol;
+pub struct StateWord;
 
-impl State for StateSymbol {
+impl State for StateWord {
     fn visit(&self, cursor: &mut Cursor) -> Result<Transition, LexerError> {
         match cursor.peek() {
-            Some('\n') => {
-                let lexeme = cursor.source().content()[cursor.index()..cursor.offset()].to_string();
-                let token_kind = TokenKind::from(&lexeme);
-
-                let valid_token_at_end_of_line = [TokenKind::TokenAssign];
-
-                if valid_token_at_end_of_line.contains(&token_kind) {
-                    return Ok(Lexer::proceed(
-                        Box::new(StateStart),
-                        TransitionKind::EmitToken(Token::new(
-                            token_kind,
-                            lexeme,
-                            cursor.location().clone(),
-                        )),
-                    ));
-                }
-
-                let transition = Lexer::proceed(
-                    Box::new(StateStart),
-                    TransitionKind::EmitToken(Token::new(
-                        TokenKind::TokenNewLine,
-                        "\\n".to_string(),
-                        cursor.location().clone(),
-                    )),
-                );
-                cursor.new_line();
-                Ok(transition)
+            Some(c) if c.is_alphanumeric() || c.eq(&'_') => Ok(Lexer::proceed(
+                Box::new(StateWord),
+                TransitionKind::AdvanceOffset,
+            )),
+            // A single quote ends an identifier as a prime (e.g. `x'`, `go''`), unless:
+            // - it is followed by a character that could start a new word (e.g. `x'a`)
+            // - the word itself is quoted (e.g. `'a'`)
+            // in which case it is left for `StateSymbol` to emit as `TokenSingleQuote`
+            Some('\'')
+                if cursor.peek_before_index() != Some('\'')
+                    && !cursor
+                        .peek_next()
+                        .is_some_and(|c| c.is_alphanumeric() || c.eq(&'_')) =>
+            {
+                Ok(Lexer::proceed(
+                    Box::new(StateWord),
+                    TransitionKind::AdvanceOffset,
+                ))
             }
-            Some(c) if TokenKind::can_be_followed_by_another_symbol(c.to_string().as_str()) => Ok(
-                Lexer::proceed(Box::new(StateSymbol), TransitionKind::AdvanceOffset),
-            ),
-            Some(_) if TokenKind::is_symbol(cursor.peek().unwrap().to_string().as_str()) => {
-                let lexeme =
-                    cursor.source().content()[cursor.index()..cursor.offset() + 1].to_string();
+            _ => {
+                // Emit token when we encounter a non-alphabetic character
+                let lexeme = cursor.source().content()[cursor.index()..cursor.offset()].to_string();
                 let token_kind = TokenKind::from(&lexeme);
-                cursor.advance_offset();
                 let location = cursor.location().clone();
                 Ok(Lexer::proceed(
                     Box::new(StateStart),
                     TransitionKind::EmitToken(Token::new(token_kind, lexeme, location)),
                 ))
             }
-            Some(_) if !TokenKind::is_symbol(cursor.peek().unwrap().to_string().as_str()) => {
-                let lexeme = cursor.source().content()[cursor.index()..cursor.offset()].to_string();
+        }
+    }
+}
+
+/// Lexes the longest symbol of `SYMBOLS` matching the input (maximal munch), e.g. `->`
+/// rather than `-` followed by `>`.
+///
+/// Every prefix of a symbol is itself a symbol, so the symbol is extended one character
+/// at a time for as long as it is the prefix of a registered symbol.
+#[derive(Debug)]
+pub struct StateSymbol;
+
+impl State for StateSymbol {
+    fn visit(&self, cursor: &mut Cursor) -> Result<Transition, LexerError> {
+        let pending = &cursor.source().content()[cursor.index()..cursor.offset()];
+        match cursor.peek() {
+            Some(c) if TokenKind::is_symbol(&format!("{}{}", pending, c)) => Ok(Lexer::proceed(
+                Box::new(StateSymbol),
+                TransitionKind::AdvanceOffset,
+            )),
+            _ if !pending.is_empty() => {
+                let lexeme = pending.to_string();
                 let token_kind = TokenKind::from(&lexeme);
                 let location = cursor.location().clone();
                 Ok(Lexer::proceed(
@@ -270,15 +450,7 @@ impl State for StateSymbol {
                     TransitionKind::EmitToken(Token::new(token_kind, lexeme, location)),
                 ))
             }
-            Some(c) => Ok(Lexer::proceed(
-                Box::new(StateStart),
-                TransitionKind::EmitToken(Token::new(
-                    TokenKind::TokenUnknown,
-                    c.to_string(),
-                    cursor.location().clone(),
-                )),
-            )),
-            None => Ok(Lexer::proceed(Box::new(StateEOF), TransitionKind::Consume)),
+            _ => Ok(Lexer::proceed(Box::new(StateEOF), TransitionKind::Consume)),
         }
     }
 }
@@ -289,14 +461,14 @@ pub struct StateEOF;
 impl State for StateEOF {
     fn visit(&self, cursor: &mut Cursor) -> Result<Transition, LexerError> {
         cursor.align();
-        Ok(Transition {
-            state: Box::new(StateEnd),
-            transition_kind: TransitionKind::EmitToken(Token::new(
+        Ok(Lexer::proceed(
+            Box::new(StateEnd),
+            TransitionKind::EmitToken(Token::new(
                 TokenKind::TokenEOF,
                 "".to_string(),
                 cursor.location().clone(),
             )),
-        })
+        ))
     }
 }
 
@@ -305,9 +477,6 @@ pub struct StateEnd;
 
 impl State for StateEnd {
     fn visit(&self, _cursor: &mut Cursor) -> Result<Transition, LexerError> {
-        Ok(Transition {
-            state: Box::new(StateEnd),
-            transition_kind: TransitionKind::End,
-        })
+        Ok(Lexer::proceed(Box::new(StateEnd), TransitionKind::End))
     }
 }
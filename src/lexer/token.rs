@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
+const INT_SUFFIXES: [&str; 2] = ["i", "_int"];
+const FLOAT_SUFFIXES: [&str; 2] = ["f", "_float"];
+
 const KEYWORD_BOOL_TRUE: &str = "true";
 const KEYWORD_BOOL_FALSE: &str = "false";
 const KEYWORD_MATCH: &str = "match";
@@ -8,31 +11,45 @@ const KEYWORD_IF: &str = "if";
 const KEYWORD_THEN: &str = "then";
 const KEYWORD_ELSE: &str = "else";
 const KEYWORD_DATA: &str = "data";
-
-const DOT: &str = ".";
-const COLON: &str = ":";
-const SEMICOLON: &str = ";";
-const ASSIGN: &str = "=";
-const NEW_LINE: &str = "\n";
-const SINGLE_QUOTE: &str = "'";
-const DOUBLE_QUOTE: &str = "\"";
-const OPEN_PAREN: &str = "(";
-const CLOSE_PAREN: &str = ")";
-const OPEN_BRACKET: &str = "{";
-const CLOSE_BRACKET: &str = "}";
-const OPEN_BRACE: &str = "[";
-const CLOSE_BRACE: &str = "]";
-const COMMA: &str = ",";
-const MINUS: &str = "-";
-const PLUS: &str = "+";
-const STAR: &str = "*";
-const SLASH: &str = "/";
-const GREATER: &str = ">";
-const RIGHT_ARROW: &str = "->";
-const RIGHT_DOUBLE_ARROW: &str = "=>";
-const PLUS_PLUS: &str = "++"; // concat for list
-const UNDERSCORE: &str = "_";
-const PIPE: &str = "|";
+const KEYWORD_AS: &str = "as";
+const KEYWORD_IMPORT: &str = "import";
+
+/// Every separator and operator of the language.
+///
+/// This is the only place listing them: the lexer takes the longest symbol of the
+/// table that matches the input, so a symbol that is the prefix of a longer one
+/// (e.g. `-` and `->`) does not need any special handling.
+pub const SYMBOLS: [(&str, TokenKind); 29] = [
+    (".", TokenKind::TokenDot),
+    (":", TokenKind::TokenColon),
+    (";", TokenKind::TokenSemicolon),
+    ("=", TokenKind::TokenAssign),
+    ("==", TokenKind::TokenEqualEqual),
+    ("'", TokenKind::TokenSingleQuote),
+    ("\"", TokenKind::TokenDoubleQuote),
+    ("(", TokenKind::TokenOpenParen),
+    (")", TokenKind::TokenCloseParen),
+    ("{", TokenKind::TokenOpenBrace),
+    ("}", TokenKind::TokenCloseBrace),
+    ("[", TokenKind::TokenOpenBracket),
+    ("]", TokenKind::TokenCloseBracket),
+    (",", TokenKind::TokenComma),
+    ("_", TokenKind::TokenUnderscore),
+    ("|", TokenKind::TokenPipe),
+    ("|>", TokenKind::TokenPipeForward),
+    ("+", TokenKind::TokenPlus),
+    ("-", TokenKind::TokenMinus),
+    ("*", TokenKind::TokenStar),
+    ("/", TokenKind::TokenSlash),
+    (">", TokenKind::TokenGreater),
+    (">=", TokenKind::TokenGreaterEqual),
+    ("<", TokenKind::TokenLess),
+    ("<=", TokenKind::TokenLessEqual),
+    ("->", TokenKind::TokenRightArrow),
+    ("=>", TokenKind::TokenRightDoubleArrow),
+    ("++", TokenKind::TokenPlusPlus), // concat for list
+    ("@", TokenKind::TokenAt),
+];
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub enum Literal {
@@ -49,6 +66,8 @@ pub enum Keyword {
     Then,
     Else,
     Data,
+    As,
+    Import,
 }
 
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
@@ -64,6 +83,7 @@ pub enum TokenKind {
     TokenColon,            // :
     TokenSemicolon,        // ;
     TokenAssign,           // =
+    TokenEqualEqual,       // ==
     TokenSingleQuote,      // '
     TokenDoubleQuote,      // "
     TokenOpenParen,        // (
@@ -74,11 +94,17 @@ pub enum TokenKind {
     TokenCloseBracket,     // ]
     TokenComma,            // ,
     TokenGreater,          // >
+    TokenGreaterEqual,     // >=
+    TokenLess,             // <
+    TokenLessEqual,        // <=
     TokenRightArrow,       // ->
     TokenRightDoubleArrow, // =>
     TokenPlusPlus,         // ++
     TokenUnderscore,       // _
     TokenPipe,             // |
+    TokenPipeForward,      // |>
+    TokenHole,             // ?name
+    TokenAt,               // @
     TokenEOF,              // End of file
     // Operators
     TokenPlus,  // +
@@ -89,35 +115,14 @@ pub enum TokenKind {
 }
 
 impl TokenKind {
-    pub fn can_be_followed_by_another_symbol(c: &str) -> bool {
-        matches!(c, MINUS | ASSIGN | PLUS)
+    /// Whether `lexeme` is a separator or an operator, e.g. `(` or `->`
+    pub fn is_separator(lexeme: &str) -> bool {
+        TokenKind::match_separator(lexeme).is_some()
     }
 
-    pub fn is_symbol(c: &str) -> bool {
-        matches!(
-            c,
-            COLON
-                | DOT
-                | SEMICOLON
-                | ASSIGN
-                | SINGLE_QUOTE
-                | DOUBLE_QUOTE
-                | OPEN_PAREN
-                | CLOSE_PAREN
-                | OPEN_BRACE
-                | CLOSE_BRACE
-                | OPEN_BRACKET
-                | CLOSE_BRACKET
-                | UNDERSCORE
-                | PIPE
-                | COMMA
-                | MINUS
-                | PLUS
-                | STAR
-                | SLASH
-                | GREATER
-                | NEW_LINE
-        )
+    /// Whether `lexeme` is the start of a symbol, e.g. `-` or `->`
+    pub fn is_symbol(lexeme: &str) -> bool {
+        SYMBOLS.iter().any(|(symbol, _)| symbol.starts_with(lexeme))
     }
 
     fn match_keyword(lexeme: &str) -> Option<TokenKind> {
@@ -129,48 +134,44 @@ impl TokenKind {
             KEYWORD_THEN => Some(TokenKind::TokenKeyword(Keyword::Then)),
             KEYWORD_ELSE => Some(TokenKind::TokenKeyword(Keyword::Else)),
             KEYWORD_DATA => Some(TokenKind::TokenKeyword(Keyword::Data)),
+            KEYWORD_AS => Some(TokenKind::TokenKeyword(Keyword::As)),
+            KEYWORD_IMPORT => Some(TokenKind::TokenKeyword(Keyword::Import)),
             _ => None,
         }
     }
 
     fn match_number(lexeme: &str) -> Option<TokenKind> {
-        if lexeme.chars().all(char::is_numeric) {
-            return Some(TokenKind::TokenLiteral(Literal::Int));
+        if !lexeme.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
         }
 
-        if lexeme.contains('.') {
-            return Some(TokenKind::TokenLiteral(Literal::Float));
+        let digits = strip_numeric_suffix(lexeme);
+        let suffix = &lexeme[digits.len()..];
+        let literal = if digits.chars().all(char::is_numeric) {
+            Literal::Int
+        } else if digits.contains('.') {
+            Literal::Float
+        } else {
+            return None;
+        };
+
+        match (literal, suffix) {
+            (literal, "") => Some(TokenKind::TokenLiteral(literal)),
+            (Literal::Int, suffix) if INT_SUFFIXES.contains(&suffix) => {
+                Some(TokenKind::TokenLiteral(Literal::Int))
+            }
+            (_, suffix) if FLOAT_SUFFIXES.contains(&suffix) => {
+                Some(TokenKind::TokenLiteral(Literal::Float))
+            }
+            _ => Some(TokenKind::TokenUnknown),
         }
-
-        None
     }
 
     fn match_separator(lexeme: &str) -> Option<TokenKind> {
-        match lexeme {
-            DOT => Some(TokenKind::TokenDot),
-            COLON => Some(TokenKind::TokenColon),
-            SEMICOLON => Some(TokenKind::TokenSemicolon),
-            ASSIGN => Some(TokenKind::TokenAssign),
-            SINGLE_QUOTE => Some(TokenKind::TokenSingleQuote),
-            DOUBLE_QUOTE => Some(TokenKind::TokenDoubleQuote),
-            OPEN_PAREN => Some(TokenKind::TokenOpenParen),
-            CLOSE_PAREN => Some(TokenKind::TokenCloseParen),
-            OPEN_BRACE => Some(TokenKind::TokenOpenBrace),
-            CLOSE_BRACE => Some(TokenKind::TokenCloseBrace),
-            OPEN_BRACKET => Some(TokenKind::TokenOpenBracket),
-            CLOSE_BRACKET => Some(TokenKind::TokenCloseBracket),
-            UNDERSCORE => Some(TokenKind::TokenUnderscore),
-            COMMA => Some(TokenKind::TokenComma),
-            PLUS => Some(TokenKind::TokenPlus),
-            MINUS => Some(TokenKind::TokenMinus),
-            STAR => Some(TokenKind::TokenStar),
-            SLASH => Some(TokenKind::TokenSlash),
-            RIGHT_ARROW => Some(TokenKind::TokenRightArrow),
-            RIGHT_DOUBLE_ARROW => Some(TokenKind::TokenRightDoubleArrow),
-            PLUS_PLUS => Some(TokenKind::TokenPlusPlus),
-            PIPE => Some(TokenKind::TokenPipe),
-            _ => None,
-        }
+        SYMBOLS
+            .iter()
+            .find(|(symbol, _)| *symbol == lexeme)
+            .map(|(_, kind)| kind.clone())
     }
 }
 
@@ -186,6 +187,10 @@ impl From<&String> for TokenKind {
             return TokenKind::TokenSpace;
         }
 
+        if lexeme.starts_with('?') {
+            return TokenKind::TokenHole;
+        }
+
         if let Some(keyword) = TokenKind::match_keyword(lexeme) {
             return keyword;
         }
@@ -199,7 +204,25 @@ impl From<&String> for TokenKind {
         TokenKind::TokenIdentifier
     }
 }
-/// The location of a token in the source code in a uman-readable format
+
+/// Returns the digits of a numeric literal without its type suffix, e.g. `3` for `3_int`
+pub fn strip_numeric_suffix(lexeme: &str) -> &str {
+    match lexeme.find(|c: char| c.is_alphabetic() || c == '_') {
+        Some(index) => &lexeme[..index],
+        None => lexeme,
+    }
+}
+
+/// Whether `lexeme` is a numeric literal with a suffix that is not a known type suffix
+pub fn has_unknown_numeric_suffix(lexeme: &str) -> bool {
+    TokenKind::match_number(lexeme) == Some(TokenKind::TokenUnknown)
+}
+
+/// The location of a token in the source code.
+///
+/// Lines and columns are 0-based and columns count characters. They are shown 1-based
+/// to users (like editors do) only when displayed, see `display_line` and
+/// `display_column`.
 #[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
 pub struct TokenLocation {
     pub file_path: PathBuf,
@@ -223,6 +246,16 @@ impl TokenLocation {
         }
     }
 
+    /// The 1-based line shown to users
+    pub fn display_line(&self) -> usize {
+        self.line + 1
+    }
+
+    /// The 1-based column of the first character, shown to users
+    pub fn display_column(&self) -> usize {
+        self.column_start + 1
+    }
+
     pub fn advance_line(&mut self) {
         self.line += 1;
         self.column_start = 0;
@@ -332,6 +365,8 @@ impl std::fmt::Display for Keyword {
             Keyword::Then => write!(f, "Then"),
             Keyword::Else => write!(f, "Else"),
             Keyword::Data => write!(f, "Data"),
+            Keyword::As => write!(f, "As"),
+            Keyword::Import => write!(f, "Import"),
         }
     }
 }
@@ -350,6 +385,7 @@ impl std::fmt::Display for TokenKind {
             TokenKind::TokenSemicolon => write!(f, "TokenSemicolon"),
             TokenKind::TokenColon => write!(f, "TokenColon"),
             TokenKind::TokenAssign => write!(f, "TokenAssign"),
+            TokenKind::TokenEqualEqual => write!(f, "TokenEqualEqual"),
             TokenKind::TokenSingleQuote => write!(f, "TokenTick"),
             TokenKind::TokenDoubleQuote => write!(f, "TokenDoubleTick"),
             TokenKind::TokenOpenParen => write!(f, "TokenOpenParen"),
@@ -359,12 +395,18 @@ impl std::fmt::Display for TokenKind {
             TokenKind::TokenOpenBracket => write!(f, "TokenOpenBracket"),
             TokenKind::TokenCloseBracket => write!(f, "TokenCloseBracket"),
             TokenKind::TokenGreater => write!(f, "TokenGreater"),
+            TokenKind::TokenGreaterEqual => write!(f, "TokenGreaterEqual"),
+            TokenKind::TokenLess => write!(f, "TokenLess"),
+            TokenKind::TokenLessEqual => write!(f, "TokenLessEqual"),
             TokenKind::TokenComma => write!(f, "TokenComma"),
             TokenKind::TokenRightArrow => write!(f, "TokenRightArrow"),
             TokenKind::TokenRightDoubleArrow => write!(f, "TokenRightDoubleArrow"),
             TokenKind::TokenPlusPlus => write!(f, "TokenPlusPlus"),
             TokenKind::TokenUnderscore => write!(f, "TokenUnderscore"),
             TokenKind::TokenPipe => write!(f, "TokenPipe"),
+            TokenKind::TokenPipeForward => write!(f, "TokenPipeForward"),
+            TokenKind::TokenHole => write!(f, "TokenHole"),
+            TokenKind::TokenAt => write!(f, "TokenAt"),
             TokenKind::TokenEOF => write!(f, "TokenEOF"),
             TokenKind::TokenPlus => write!(f, "TokenPlus"),
             TokenKind::TokenMinus => write!(f, "TokenMinus"),
@@ -379,11 +421,10 @@ impl std::fmt::Display for TokenLocation {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(
             f,
-            "TokenLocation {{ \"{}\", {}, {}, {} }}",
+            "{}:{}:{}",
             self.file_path.display(),
-            self.line,
-            self.column_start,
-            self.column_end
+            self.display_line(),
+            self.display_column()
         )
     }
 }
@@ -401,3 +442,101 @@ impl std::fmt::Display for Token {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether a kind is lexed from a symbol; every variant is listed so that a new kind
+    /// can not be forgotten in `SYMBOLS`
+    fn is_symbol_kind(kind: &TokenKind) -> bool {
+        match kind {
+            TokenKind::TokenDot
+            | TokenKind::TokenColon
+            | TokenKind::TokenSemicolon
+            | TokenKind::TokenAssign
+            | TokenKind::TokenEqualEqual
+            | TokenKind::TokenSingleQuote
+            | TokenKind::TokenDoubleQuote
+            | TokenKind::TokenOpenParen
+            | TokenKind::TokenCloseParen
+            | TokenKind::TokenOpenBrace
+            | TokenKind::TokenCloseBrace
+            | TokenKind::TokenOpenBracket
+            | TokenKind::TokenCloseBracket
+            | TokenKind::TokenComma
+            | TokenKind::TokenGreater
+            | TokenKind::TokenGreaterEqual
+            | TokenKind::TokenLess
+            | TokenKind::TokenLessEqual
+            | TokenKind::TokenRightArrow
+            | TokenKind::TokenRightDoubleArrow
+            | TokenKind::TokenPlusPlus
+            | TokenKind::TokenUnderscore
+            | TokenKind::TokenPipe
+            | TokenKind::TokenPipeForward
+            | TokenKind::TokenPlus
+            | TokenKind::TokenMinus
+            | TokenKind::TokenStar
+            | TokenKind::TokenSlash
+            | TokenKind::TokenAt => true,
+            TokenKind::TokenLiteral(_)
+            | TokenKind::TokenKeyword(_)
+            | TokenKind::TokenIdentifier
+            | TokenKind::TokenComment
+            | TokenKind::TokenSpace
+            | TokenKind::TokenTab
+            | TokenKind::TokenNewLine
+            | TokenKind::TokenHole
+            | TokenKind::TokenEOF
+            | TokenKind::TokenUnknown => false,
+        }
+    }
+
+    #[test]
+    fn test_token_symbols_registry() {
+        for (index, (symbol, kind)) in SYMBOLS.iter().enumerate() {
+            assert!(is_symbol_kind(kind), "{} is not a symbol kind", kind);
+            assert!(
+                SYMBOLS[index + 1..]
+                    .iter()
+                    .all(|(other, other_kind)| other != symbol && other_kind != kind),
+                "{:?} is registered twice",
+                symbol
+            );
+            assert_eq!(TokenKind::from(&symbol.to_string()), *kind);
+            // The lexer reaches a symbol one character at a time
+            for (end, _) in symbol.char_indices().skip(1) {
+                assert!(
+                    TokenKind::is_separator(&symbol[..end]),
+                    "the prefix of {:?} is not a symbol",
+                    symbol
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_token_brackets_and_braces() {
+        assert_eq!(
+            TokenKind::from(&"[".to_string()),
+            TokenKind::TokenOpenBracket
+        );
+        assert_eq!(TokenKind::from(&"{".to_string()), TokenKind::TokenOpenBrace);
+    }
+
+    #[test]
+    fn test_token_location_display() {
+        // `y` in `x: int = 1\n  y`, 0-based internally and 1-based when displayed
+        let location = TokenLocation::new(PathBuf::from("main.fs"), 1, 2, 3);
+        assert_eq!(location.display_line(), 2);
+        assert_eq!(location.display_column(), 3);
+        assert_eq!(location.to_string(), "main.fs:2:3");
+
+        let token = Token::new(TokenKind::TokenIdentifier, "y".to_string(), location);
+        assert_eq!(
+            token.to_string(),
+            "Token { TokenIdentifier, \"y\", main.fs:2:3 }"
+        );
+    }
+}
@@ -0,0 +1,26 @@
+use super::{Context, Lint, Rule};
+use crate::diagnostics::messages::{Code, Message};
+use crate::lexer::token::TokenKind;
+
+/// Holes (e.g. `?todo`) mark code that is not written yet
+pub struct Hole;
+
+impl Rule for Hole {
+    fn name(&self) -> &'static str {
+        "hole"
+    }
+
+    fn check(&self, context: &Context) -> Vec<Lint> {
+        context
+            .file
+            .tokens()
+            .into_iter()
+            .filter(|token| token.kind == TokenKind::TokenHole)
+            .map(|token| Lint {
+                rule: self.name(),
+                message: Message::new(Code::Hole).with_arg("hole", &token.lexeme),
+                location: token.location.clone(),
+            })
+            .collect()
+    }
+}
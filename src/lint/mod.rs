@@ -0,0 +1,174 @@
+//! Style and correctness warnings, run by `funs lint`.
+//!
+//! Each rule lives in its own module and implements `Rule`. A file can turn rules off
+//! with a comment line naming them:
+//!
+//! ```text
+//! # @allow(non_snake_case, hole)
+//! ```
+//!
+//! and a project with the `[lints]` section of its `funs.toml`, or `funs lint --allow`.
+
+pub mod hole;
+pub mod non_snake_case;
+
+use crate::diagnostics::messages::{Code, Locale, Message};
+use crate::diff::{declarations, Declaration};
+use crate::lexer::token::{TokenKind, TokenLocation};
+use crate::parser::{Tree, TreeKind};
+
+/// A warning reported by a rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lint {
+    /// The name of the rule, e.g. `non_snake_case`
+    pub rule: &'static str,
+    pub message: Message,
+    pub location: TokenLocation,
+}
+
+impl Lint {
+    pub fn render(&self, locale: Locale) -> String {
+        format!(
+            "{}: warning: {} [{}]",
+            self.location,
+            self.message.render(locale),
+            self.rule
+        )
+    }
+}
+
+impl std::fmt::Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.render(Locale::default()))
+    }
+}
+
+/// What the rules know about the file they check
+pub struct Context<'a> {
+    pub file: &'a Tree,
+    /// The top-level declarations, in source order
+    pub declarations: Vec<Declaration>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(file: &'a Tree) -> Context<'a> {
+        Context {
+            file,
+            declarations: declarations(file),
+        }
+    }
+}
+
+pub trait Rule {
+    /// The name used to report and to allow the rule
+    fn name(&self) -> &'static str;
+
+    fn check(&self, context: &Context) -> Vec<Lint>;
+}
+
+/// Every rule, run in this order
+pub fn rules() -> Vec<Box<dyn Rule>> {
+    vec![Box::new(non_snake_case::NonSnakeCase), Box::new(hole::Hole)]
+}
+
+/// Whether `name` is the name of a rule
+pub fn is_rule(name: &str) -> bool {
+    rules().iter().any(|rule| rule.name() == name)
+}
+
+/// Runs the rules that neither `allow` nor `file` allow, returning their lints in
+/// source order.
+pub fn lint(file: &Tree, allow: &[String]) -> Vec<Lint> {
+    let rules = rules();
+    let (allowed, mut lints) = allowed_rules(file, &rules);
+    let context = Context::new(file);
+    let is_allowed =
+        |name: &str| allowed.contains(&name) || allow.iter().any(|allow| allow == name);
+    for rule in rules.iter().filter(|rule| !is_allowed(rule.name())) {
+        lints.extend(rule.check(&context));
+    }
+    lints.sort_by_key(|lint| (lint.location.line, lint.location.column_start));
+    lints
+}
+
+/// Collects the rules named by the `# @allow(...)` comments of `file`, along with a lint
+/// for each name that is not a rule.
+fn allowed_rules(file: &Tree, rules: &[Box<dyn Rule>]) -> (Vec<&'static str>, Vec<Lint>) {
+    let mut allowed = Vec::new();
+    let mut unknown = Vec::new();
+    let comments = file
+        .child_trees()
+        .filter(|tree| *tree.kind() == TreeKind::Comment)
+        .flat_map(|tree| tree.tokens())
+        .filter(|token| token.kind == TokenKind::TokenComment);
+    for comment in comments {
+        let Some(names) = comment
+            .lexeme
+            .trim_start_matches('#')
+            .trim()
+            .strip_prefix("@allow(")
+            .and_then(|rest| rest.strip_suffix(')'))
+        else {
+            continue;
+        };
+        for name in names.split(',').map(str::trim) {
+            match rules.iter().find(|rule| rule.name() == name) {
+                Some(rule) => allowed.push(rule.name()),
+                None => unknown.push(Lint {
+                    rule: "unknown_rule",
+                    message: Message::new(Code::UnknownLintRule).with_arg("name", name),
+                    location: comment.location.clone(),
+                }),
+            }
+        }
+    }
+    (allowed, unknown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::snippet::parse_snippet;
+
+    fn messages(content: &str) -> Vec<String> {
+        lint(&parse_snippet(content).unwrap(), &[])
+            .iter()
+            .map(|lint| lint.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_lint_rules() {
+        assert_eq!(
+            messages("maxSize: int = ?todo\nmin_size: int = 0\n"),
+            vec![
+                "<snippet>:1:1: warning: `maxSize` should have a snake case name such as `max_size` [non_snake_case]",
+                "<snippet>:1:16: warning: unfinished code, `?todo` is a hole [hole]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_allow_from_config() {
+        let tree = parse_snippet("maxSize: int = ?todo\n").unwrap();
+        let lints = lint(&tree, &["non_snake_case".to_string()]);
+        assert_eq!(
+            lints.iter().map(|lint| lint.rule).collect::<Vec<_>>(),
+            vec!["hole"]
+        );
+        assert!(is_rule("hole"));
+        assert!(!is_rule("nope"));
+    }
+
+    #[test]
+    fn test_lint_allow() {
+        assert!(messages("# @allow(non_snake_case, hole)\nmaxSize: int = ?todo\n").is_empty());
+        assert_eq!(
+            messages("#@allow(hole , nope)\nmaxSize: int = ?todo\n"),
+            vec![
+                "<snippet>:1:1: warning: unknown lint rule `nope` [unknown_rule]",
+                "<snippet>:2:1: warning: `maxSize` should have a snake case name such as `max_size` [non_snake_case]",
+            ]
+        );
+    }
+}
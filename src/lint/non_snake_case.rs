@@ -0,0 +1,67 @@
+use super::{Context, Lint, Rule};
+use crate::diagnostics::messages::{Code, Message};
+
+/// Top-level names are written in snake case, e.g. `max_size` rather than `maxSize`
+pub struct NonSnakeCase;
+
+impl Rule for NonSnakeCase {
+    fn name(&self) -> &'static str {
+        "non_snake_case"
+    }
+
+    fn check(&self, context: &Context) -> Vec<Lint> {
+        context
+            .declarations
+            .iter()
+            .filter(|declaration| !is_snake_case(&declaration.name))
+            .map(|declaration| Lint {
+                rule: self.name(),
+                message: Message::new(Code::NonSnakeCase)
+                    .with_arg("name", &declaration.name)
+                    .with_arg("suggestion", to_snake_case(&declaration.name)),
+                location: declaration.location.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Whether `name` has no uppercase letter, leading underscores (e.g. `_unused`) are fine
+fn is_snake_case(name: &str) -> bool {
+    !name.chars().any(char::is_uppercase)
+}
+
+/// Lowercases `name`, starting a new word at each uppercase letter that follows a
+/// lowercase one or that ends an acronym (`HTTPPort` is `http_port`)
+fn to_snake_case(name: &str) -> String {
+    let chars = name.chars().collect::<Vec<_>>();
+    let mut snake = String::new();
+    for (index, c) in chars.iter().enumerate() {
+        if c.is_uppercase() && index > 0 {
+            let previous = chars[index - 1];
+            let next = chars.get(index + 1);
+            let starts_word = previous.is_lowercase()
+                || previous.is_ascii_digit()
+                || (previous.is_uppercase() && next.is_some_and(|next| next.is_lowercase()));
+            if starts_word {
+                snake.push('_');
+            }
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint_snake_case() {
+        assert!(is_snake_case("max_size"));
+        assert!(is_snake_case("_x_int"));
+        assert!(!is_snake_case("maxSize"));
+        assert_eq!(to_snake_case("maxSize"), "max_size");
+        assert_eq!(to_snake_case("HTTPPort"), "http_port");
+        assert_eq!(to_snake_case("Max_Size"), "max_size");
+    }
+}
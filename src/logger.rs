@@ -5,57 +5,102 @@ use std::sync::Arc;
 use tracing_subscriber::EnvFilter;
 use tracing_subscriber::{filter, prelude::*};
 
+#[derive(Debug)]
+pub enum LoggerError {
+    CreateDirectory {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    CreateFile {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    InvalidPath {
+        path: PathBuf,
+    },
+    Subscriber(String),
+}
+
+impl std::fmt::Display for LoggerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LoggerError::CreateDirectory { path, error } => {
+                write!(
+                    f,
+                    "Error creating directory: \"{}\": {}",
+                    path.display(),
+                    error
+                )
+            }
+            LoggerError::CreateFile { path, error } => {
+                write!(f, "Error creating file: \"{}\": {}", path.display(), error)
+            }
+            LoggerError::InvalidPath { path } => write!(
+                f,
+                "Error getting parent directory of file: \"{}\"",
+                path.display()
+            ),
+            LoggerError::Subscriber(error) => {
+                write!(f, "Error setting up the tracing subscriber: {}", error)
+            }
+        }
+    }
+}
+
 pub struct Logger {
     file_path: PathBuf,
+    /// The filter used when no `RUST_LOG` environment variable is set
+    default_filter: String,
 }
 
 // https://stackoverflow.com/questions/70013172/how-to-use-the-tracing-library
 impl Logger {
-    pub fn new(file_path: impl AsRef<Path>) -> Logger {
-        let file_path = file_path.as_ref().to_path_buf();
-        let logger = Logger { file_path };
-        logger.set_rust_log_variable();
-        logger.create_log_directory();
-        logger.set_tracing_subscribers();
-        logger
+    pub fn new(file_path: impl AsRef<Path>) -> Result<Logger, LoggerError> {
+        Logger::with_default_filter(file_path, "info")
     }
 
-    fn set_rust_log_variable(&self) {
-        std::env::set_var(
-            "RUST_LOG",
-            std::env::var("RUST_LOG").unwrap_or("info".to_string()),
-        );
+    /// Like `new`, but records what `default_filter` lets through when `RUST_LOG` is not
+    /// set, e.g. `info,funs::interpreter=debug`.
+    pub fn with_default_filter(
+        file_path: impl AsRef<Path>,
+        default_filter: &str,
+    ) -> Result<Logger, LoggerError> {
+        let file_path = file_path.as_ref().to_path_buf();
+        let default_filter = default_filter.to_string();
+        let logger = Logger {
+            file_path,
+            default_filter,
+        };
+        logger.create_log_directory()?;
+        logger.set_tracing_subscribers()?;
+        Ok(logger)
     }
 
-    fn create_log_directory(&self) {
-        let log_directory = self.file_path.parent().unwrap_or_else(|| {
-            panic!(
-                "Error getting parent directory of file: \"{}\"",
-                self.file_path.display()
-            )
-        });
+    fn create_log_directory(&self) -> Result<(), LoggerError> {
+        let log_directory = match self.file_path.parent() {
+            Some(log_directory) => log_directory,
+            None => {
+                return Err(LoggerError::InvalidPath {
+                    path: self.file_path.clone(),
+                })
+            }
+        };
         if !log_directory.exists() {
-            match fs::create_dir_all(log_directory) {
-                Ok(_) => {}
-                Err(error) => panic!(
-                    "Error creating directory: \"{}\": {}",
-                    log_directory.display(),
-                    error
-                ),
+            if let Err(error) = fs::create_dir_all(log_directory) {
+                return Err(LoggerError::CreateDirectory {
+                    path: log_directory.to_path_buf(),
+                    error,
+                });
             }
         }
+        Ok(())
     }
 
-    fn create_log_file(&self) -> fs::File {
-        let file = fs::File::create(&self.file_path);
-        match file {
-            Ok(file) => file,
-            Err(error) => panic!(
-                "Error creating file: \"{}\": {}",
-                self.file_path.display(),
-                error
-            ),
-        }
+    fn create_log_file(&self) -> Result<fs::File, LoggerError> {
+        fs::File::create(&self.file_path).map_err(|error| LoggerError::CreateFile {
+            path: self.file_path.clone(),
+            error,
+        })
     }
 
     ///  Set up the tracing subscribers.
@@ -65,19 +110,20 @@ impl Logger {
     /// While the `debug` event will only be seen by the debug log file layer.
     ///
     /// If a `RUST_LOG` environment variable is set, the `env_filter` layer will
-    /// take it into account.
+    /// take it into account instead of the default filter.
     /// But the `stdout_log` layer will only log events with a level greater than or equal to
     /// `INFO`.
-    fn set_tracing_subscribers(&self) {
+    fn set_tracing_subscribers(&self) -> Result<(), LoggerError> {
         // A layer that logs events to stdout.
         let stdout_log = tracing_subscriber::fmt::layer().compact().without_time(); // .pretty();
 
         // A layer that logs events to a file.
-        let file = self.create_log_file();
+        let file = self.create_log_file()?;
         let debug_log = tracing_subscriber::fmt::layer().with_writer(Arc::new(file)); // .with_ansi(false);
 
         // A filter that takes the `RUST_LOG` environment variable into account.
-        let env_filter = EnvFilter::from_default_env();
+        let env_filter = EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| EnvFilter::new(&self.default_filter));
 
         tracing_subscriber::registry()
             .with(env_filter)
@@ -89,6 +135,7 @@ impl Logger {
                     // `debug_log` layer, producing a new `Layered` layer.
                     .and_then(debug_log),
             )
-            .init();
+            .try_init()
+            .map_err(|error| LoggerError::Subscriber(error.to_string()))
     }
 }
@@ -1,44 +1,1586 @@
+pub mod ast_json;
+pub mod bench_json;
+pub mod cli;
+pub mod completions;
+pub mod config;
+pub mod debug_dir;
+pub mod diagnostics;
+pub mod diff;
+pub mod doctest;
+pub mod harness;
+pub mod highlight;
+pub mod ice;
+pub mod index;
+pub mod interpreter;
 pub mod lexer;
+pub mod lint;
 pub mod logger;
+pub mod migrate;
+pub mod minimize;
+pub mod modules;
 pub mod parser;
+pub mod playground;
+pub mod repl;
+pub mod resolve;
 pub mod source;
+pub mod spec;
+pub mod stats_json;
+pub mod symbols;
+pub mod tailcall;
+pub mod typeck;
 pub mod utils;
 
 // use crate::parser::old_parser::Parser;
+use crate::parser::ast;
+use crate::parser::lower::lower;
 use crate::parser::Parser;
+use cli::{Command, ExitStatus};
+use config::{Config, CONFIG_FILE};
+use debug_dir::DebugDir;
+use diagnostics::messages::Locale;
+use diagnostics::renderer::Renderer;
+use diagnostics::Diagnostic;
+use ice::Phase;
+use interpreter::{Interpreter, Value};
+use lexer::token::Token;
 use lexer::Lexer;
 use logger::Logger;
-use source::Source;
-use std::{env, path::PathBuf};
+use migrate::Deprecation;
+use modules::Module;
+use source::{read_source, Source};
+use stats_json::{StatsJson, Timings};
+use std::collections::BTreeMap;
+use std::io::{IsTerminal, Write};
+use std::process::ExitCode;
+use std::time::{Instant, SystemTime};
+use std::{env, path::Path, path::PathBuf};
+use utils::file_handler::FsFileFinder;
 
-fn set_up_logger() {
-    let pwd: PathBuf = env::current_dir().unwrap_or_else(|e| {
-        panic!("Error getting current directory: {}", e);
-    });
+fn set_up_logger() -> Result<Logger, String> {
+    set_up_logger_with("info")
+}
+
+/// Sets up the logger with `default_filter` as the levels to record when `RUST_LOG` is not
+/// set.
+fn set_up_logger_with(default_filter: &str) -> Result<Logger, String> {
+    let pwd: PathBuf = match env::current_dir() {
+        Ok(pwd) => pwd,
+        Err(e) => return Err(format!("Error getting current directory: {}", e)),
+    };
     let logger_file_path = pwd.join(".log").join("debug.log");
-    let _logger = Logger::new(logger_file_path);
-}
-
-fn main() {
-    set_up_logger();
-
-    let usage_message: &str = "Usage: \n\
-                               funs <file.fs>";
-    let args: &[String] = &env::args().collect::<Vec<String>>()[1..];
-    if args.len() != 1 {
-        println!("{}", usage_message);
-        return;
-    }
-
-    let file_path: &str = &args[0];
-    let source = Source::new(file_path);
-    let lexer = Lexer::new(&source);
-    // let tokens = (&mut lexer).collect::<Vec<Token>>();
-    // if lexer.errors().is_empty() {
-    //     println!("No errors found");
-    // } else {
-    //     lexer.emit_errors();
-    // }
-    let parser = Parser::new(lexer); // It can accepts lexer or tokens
-    let _tree = parser.parse();
+    Logger::with_default_filter(logger_file_path, default_filter).map_err(|e| e.to_string())
+}
+
+/// What compiling a single file found
+#[derive(Default)]
+struct Report {
+    /// The source that was compiled, `None` if it could not be read
+    source: Option<Source>,
+    /// The errors without a location in the source, e.g. failing to write the debug output
+    errors: Vec<String>,
+    /// The errors and warnings found in the source, in the order of the phases
+    diagnostics: Vec<Diagnostic>,
+    deprecations: Vec<Deprecation>,
+    /// The AST of the source, `None` if it has parse errors
+    ast: Option<ast::File>,
+    /// The modules the source imports, directly or not, in the order they run
+    modules: Vec<Module>,
+    timings: Timings,
+}
+
+impl Report {
+    fn error_count(&self) -> usize {
+        self.errors.len() + self.diagnostics.iter().filter(|d| d.is_error()).count()
+    }
+
+    /// The message of every error in `locale`, without its location
+    fn error_messages(&self, locale: Locale) -> Vec<String> {
+        self.errors
+            .iter()
+            .cloned()
+            .chain(
+                self.diagnostics
+                    .iter()
+                    .filter(|d| d.is_error())
+                    .map(|d| d.render(locale)),
+            )
+            .collect()
+    }
+
+    /// The message of every warning in `locale`, the deprecations first, as `funs check`
+    /// prints them
+    fn warning_messages(&self, locale: Locale) -> Vec<String> {
+        self.deprecations
+            .iter()
+            .map(|deprecation| deprecation.message.render(locale))
+            .chain(
+                self.diagnostics
+                    .iter()
+                    .filter(|d| !d.is_error())
+                    .map(|d| d.render(locale)),
+            )
+            .collect()
+    }
+}
+
+/// Compiles a single file, returning the errors and the deprecation warnings found in it.
+///
+/// The tokens and the CST are written to `debug_dir` if given, the words the parser puts
+/// in its messages (e.g. "end of file") are written in `locale`.
+fn compile(path: &Path, debug_dir: Option<&DebugDir>, locale: Locale) -> Report {
+    let mut timings = Timings::default();
+    let mut report = match timings.time(Phase::Reading, path, || read_source(path)) {
+        Ok(source) => compile_source(source, debug_dir, locale),
+        Err(e) => Report {
+            errors: vec![e.to_string()],
+            ..Report::default()
+        },
+    };
+    report.timings.add(&timings);
+    report
+}
+
+/// Runs the phases of `funs check` on `source`, timing each of them, see `compile`.
+fn compile_source(source: Source, debug_dir: Option<&DebugDir>, locale: Locale) -> Report {
+    // The source is moved into the report at the end
+    let file_path = source.file_path().clone();
+    let path = file_path.as_path();
+    let mut timings = Timings::default();
+    let mut lexer = Lexer::new(&source);
+    let tokens = timings.time(Phase::Lexing, path, || {
+        lexer.by_ref().collect::<Vec<Token>>()
+    });
+    // The bytes replaced when reading are in no token, they come first
+    let mut report = Report {
+        diagnostics: source
+            .invalid_utf8()
+            .iter()
+            .map(|invalid| Diagnostic::error(invalid.message(), invalid.location.clone()))
+            .chain(lexer.diagnostics().iter().cloned())
+            .collect(),
+        ..Report::default()
+    };
+    if let Some(debug_dir) = debug_dir {
+        if let Err(e) = debug_dir.emit(path, "tokens", &tokens) {
+            report.errors.push(e.to_string());
+        }
+    }
+    let parser = Parser::new(tokens).with_locale(locale);
+    let (tree, parse_diagnostics) =
+        timings.time(Phase::Parsing, path, || parser.parse_with_diagnostics());
+    if let Some(debug_dir) = debug_dir {
+        if let Err(e) = debug_dir.emit(path, "cst", &tree) {
+            report.errors.push(e.to_string());
+        }
+    }
+    // A tree with parse errors has malformed nodes, they are already reported
+    if parse_diagnostics.is_empty() {
+        let (ast, lower_diagnostics) = timings.time(Phase::Lowering, path, || lower(&tree));
+        report.diagnostics.extend(lower_diagnostics);
+        let (modules, module_diagnostics) =
+            timings.time(Phase::Loading, path, || modules::load(path, &ast));
+        report.diagnostics.extend(module_diagnostics);
+        // The modules are checked with the entry file, their errors point into them
+        for module in &modules {
+            let file_path = module.source.file_path();
+            let imports = imported_asts(&module.ast, &modules);
+            let (_, resolve_diagnostics) = timings.time(Phase::Resolving, file_path, || {
+                resolve::resolve_with_imports(&module.ast, &imports)
+            });
+            report.diagnostics.extend(resolve_diagnostics);
+            let (_, type_diagnostics) = timings.time(Phase::TypeChecking, file_path, || {
+                typeck::check_with_imports(&module.ast, &imports)
+            });
+            report.diagnostics.extend(type_diagnostics);
+        }
+        let imports = imported_asts(&ast, &modules);
+        let (_, resolve_diagnostics) = timings.time(Phase::Resolving, path, || {
+            resolve::resolve_with_imports(&ast, &imports)
+        });
+        report.diagnostics.extend(resolve_diagnostics);
+        report.diagnostics.extend(tailcall::non_tail_calls(&ast));
+        let (_, type_diagnostics) = timings.time(Phase::TypeChecking, path, || {
+            typeck::check_with_imports(&ast, &imports)
+        });
+        report.diagnostics.extend(type_diagnostics);
+        report.ast = Some(ast);
+        report.modules = modules;
+    }
+    report.diagnostics.extend(parse_diagnostics);
+    report.diagnostics.extend(
+        resolve::duplicates(&tree)
+            .iter()
+            .map(|duplicate| Diagnostic::error(duplicate.message(), duplicate.second.clone())),
+    );
+    report.deprecations = migrate::deprecations(&tree);
+    report.source = Some(source);
+    report.timings = timings;
+    report
+}
+
+/// The ASTs of the modules of `modules` that `file` imports itself
+fn imported_asts<'a>(file: &ast::File, modules: &'a [Module]) -> Vec<&'a ast::File> {
+    modules::imported(file, modules)
+        .into_iter()
+        .map(|module| &module.ast)
+        .collect()
+}
+
+/// Collects the files in `paths`, walking directories recursively and skipping the
+/// files matching `excludes`.
+///
+/// A directory is walked with the source extensions of the `funs.toml` that applies to it.
+fn collect_files(paths: &[String], excludes: &[String]) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    for path in paths.iter().map(Path::new) {
+        if !path.is_dir() {
+            files.push(path.to_path_buf());
+            continue;
+        }
+        let config = Config::discover(path).map_err(|e| e.to_string())?;
+        let finder = excludes.iter().fold(
+            FsFileFinder::new().with_extensions(&config.compiler.extensions),
+            |finder, pattern| finder.with_exclude(pattern),
+        );
+        files.extend(finder.find(path).map_err(|e| e.to_string())?);
+    }
+    Ok(files)
+}
+
+/// The renderer of the diagnostics of `file`, set up by the `funs.toml` that applies to it.
+///
+/// Colors are only used when stderr is a terminal. A configuration that can not be read
+/// falls back to the default renderer, the diagnostics matter more than their looks.
+fn snippet_renderer(file: &Path) -> Renderer {
+    let renderer = match Config::discover(file) {
+        Ok(config) => config.diagnostics.renderer(),
+        Err(_) => Renderer::new(),
+    };
+    renderer.with_color(std::io::stderr().is_terminal())
+}
+
+/// Prints the errors and warnings of `report`, the diagnostics along with the source
+/// lines they point at.
+fn print_errors(file: &Path, report: &Report, locale: Locale) {
+    for error in &report.errors {
+        eprintln!("{}: {}", file.display(), error);
+    }
+    if let Some(source) = &report.source {
+        let renderer = snippet_renderer(file);
+        for diagnostic in &report.diagnostics {
+            let source = modules::source_of(diagnostic, source, &report.modules);
+            eprint!("{}", renderer.render_diagnostic(diagnostic, source, locale));
+        }
+    }
+}
+
+/// Checks every file in `paths`, walking directories recursively, and reports the
+/// errors grouped by file. With `stats_file` the run is added to the runs recorded in
+/// that file, see `stats_json`.
+fn check(
+    paths: &[String],
+    excludes: &[String],
+    emit_debug_dir: Option<&str>,
+    stats_file: Option<&str>,
+    locale: Locale,
+) -> ExitStatus {
+    let started_at = SystemTime::now();
+    let start = Instant::now();
+    if let Err(e) = set_up_logger() {
+        eprintln!("{}", e);
+        return ExitStatus::CompileError;
+    }
+    let files = match collect_files(paths, excludes) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitStatus::CompileError;
+        }
+    };
+
+    let debug_dir = emit_debug_dir.map(|dir| DebugDir::new(Path::new(dir)));
+    let mut files_with_errors = 0;
+    let mut total_errors = 0;
+    let mut total_warnings = 0;
+    let mut timings = Timings::default();
+    let mut diagnostics = BTreeMap::new();
+    for file in &files {
+        let report = compile(file, debug_dir.as_ref(), locale);
+        for deprecation in &report.deprecations {
+            eprintln!("{}", deprecation.render(locale));
+        }
+        print_errors(file, &report, locale);
+        timings.add(&report.timings);
+        total_warnings += report.warning_messages(locale).len();
+        let codes = report
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.code)
+            .chain(report.deprecations.iter().map(|d| d.message.code));
+        for code in codes {
+            *diagnostics.entry(code.as_str().to_string()).or_insert(0) += 1;
+        }
+        if report.error_count() == 0 {
+            continue;
+        }
+        files_with_errors += 1;
+        total_errors += report.error_count();
+    }
+
+    if let Some(stats_file) = stats_file {
+        let run = stats_json::Run {
+            funs: env!("CARGO_PKG_VERSION").to_string(),
+            started_at: started_at
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs()),
+            total_ns: stats_json::nanos(start.elapsed()),
+            files: files.len(),
+            files_with_errors,
+            errors: total_errors,
+            warnings: total_warnings,
+            diagnostics,
+            phases_ns: timings.to_nanos(),
+        };
+        if let Err(e) = record_run(stats_file, run) {
+            eprintln!("{}", e);
+            return ExitStatus::CompileError;
+        }
+    }
+    if total_errors == 0 {
+        return ExitStatus::Success;
+    }
+    eprintln!(
+        "Found {} error(s) in {} of {} file(s)",
+        total_errors,
+        files_with_errors,
+        files.len()
+    );
+    ExitStatus::CompileError
+}
+
+/// Adds `run` to the runs recorded in the stats file at `path`, creating it if needed
+fn record_run(path: &str, run: stats_json::Run) -> Result<(), String> {
+    let mut stats = match std::fs::read_to_string(path) {
+        Ok(json) => StatsJson::from_json(&json).map_err(|e| format!("{}: {}", path, e))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => StatsJson::new(Vec::new()),
+        Err(e) => return Err(format!("{}: {}", path, e)),
+    };
+    stats.runs.push(run);
+    std::fs::write(path, stats.to_json()).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Compiles the code examples found in the comments of every file in `paths`.
+fn test_doc(paths: &[String], excludes: &[String]) -> ExitStatus {
+    if let Err(e) = set_up_logger() {
+        eprintln!("{}", e);
+        return ExitStatus::CompileError;
+    }
+    let files = match collect_files(paths, excludes) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitStatus::CompileError;
+        }
+    };
+
+    let mut total = 0;
+    let mut failed = 0;
+    for file in &files {
+        let source = match read_source(file) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("{}", e);
+                failed += 1;
+                continue;
+            }
+        };
+        for doctest in doctest::extract(&source) {
+            total += 1;
+            let result = ice::with_phase(Phase::Parsing, &doctest.file_path, || {
+                doctest::run(&doctest)
+            });
+            if let Err(failure) = result {
+                failed += 1;
+                eprintln!("{}", failure);
+            }
+        }
+    }
+
+    println!("{} doc example(s), {} failed", total, failed);
+    if failed == 0 {
+        ExitStatus::Success
+    } else {
+        ExitStatus::CompileError
+    }
+}
+
+/// Runs every file in `paths` as a spec, see `spec`.
+fn test_spec(paths: &[String], excludes: &[String]) -> ExitStatus {
+    if let Err(e) = set_up_logger() {
+        eprintln!("{}", e);
+        return ExitStatus::CompileError;
+    }
+    let files = match collect_files(paths, excludes) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitStatus::CompileError;
+        }
+    };
+
+    let mut failed = 0;
+    for file in &files {
+        let result = read_source(file)
+            .map_err(|e| e.to_string())
+            .and_then(|source| spec::Spec::parse(&source).map_err(|e| e.to_string()))
+            .and_then(|spec| spec::run(&spec).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            failed += 1;
+            eprintln!("{}", e);
+        }
+    }
+
+    println!("{} spec(s), {} failed", files.len(), failed);
+    if failed == 0 {
+        ExitStatus::Success
+    } else {
+        ExitStatus::CompileError
+    }
+}
+
+/// Compiles `file` for `funs test` and `funs bench`, reporting its errors in `locale`.
+fn compile_for_harness(file: &Path, locale: Locale) -> Option<(Source, ast::File, Vec<Module>)> {
+    let report = compile(file, None, locale);
+    print_errors(file, &report, locale);
+    if report.error_count() > 0 {
+        return None;
+    }
+    let (source, ast) = report.source.zip(report.ast)?;
+    Some((source, ast, report.modules))
+}
+
+/// Runs the `@test` functions of every file in `paths`.
+///
+/// A failing test is reported with what it printed. A file that does not compile, or
+/// whose program stops with an error, counts as one failed test.
+fn test_functions(paths: &[String], excludes: &[String], locale: Locale) -> ExitStatus {
+    if let Err(e) = set_up_logger() {
+        eprintln!("{}", e);
+        return ExitStatus::CompileError;
+    }
+    let files = match collect_files(paths, excludes) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitStatus::CompileError;
+        }
+    };
+
+    let mut status = ExitStatus::Success;
+    let (mut count, mut failed) = (0, 0);
+    for file in &files {
+        let Some((source, ast, modules)) = compile_for_harness(file, locale) else {
+            status = ExitStatus::CompileError;
+            continue;
+        };
+        let renderer = snippet_renderer(file);
+        let render = |diagnostic: &Diagnostic| {
+            let source = modules::source_of(diagnostic, &source, &modules);
+            renderer.render_diagnostic(diagnostic, source, locale)
+        };
+        let results = match harness::run_tests(&ast, &modules) {
+            Ok(results) => results,
+            Err(diagnostic) => {
+                eprint!("{}", render(&diagnostic));
+                (count, failed) = (count + 1, failed + 1);
+                continue;
+            }
+        };
+        for result in results {
+            count += 1;
+            let Some(failure) = &result.failure else {
+                println!("test {}::{} ... ok", file.display(), result.name);
+                continue;
+            };
+            failed += 1;
+            println!("test {}::{} ... FAILED", file.display(), result.name);
+            eprint!("{}", result.output);
+            match failure {
+                harness::TestFailure::ReturnedFalse => {
+                    eprintln!("`{}` returned false", result.name)
+                }
+                harness::TestFailure::Error(diagnostic) => eprint!("{}", render(diagnostic)),
+            }
+        }
+    }
+
+    println!("{} test(s), {} failed", count, failed);
+    match (status, failed) {
+        (ExitStatus::Success, 0) => ExitStatus::Success,
+        (ExitStatus::Success, _) => ExitStatus::RuntimeError,
+        (status, _) => status,
+    }
+}
+
+/// The baselines of `funs bench`: the one to compare with and the one to save to.
+struct Baselines<'a> {
+    baseline: Option<&'a str>,
+    save_baseline: Option<&'a str>,
+    /// The slowdown, in percent, past which a benchmark is a regression
+    threshold: u32,
+}
+
+/// Times the `@bench` functions of every file in `paths`, or with `program` the files
+/// themselves, then saves the results to `save_baseline` and reports the regressions
+/// against `baseline`.
+fn bench_files(
+    paths: &[String],
+    excludes: &[String],
+    options: &harness::BenchOptions,
+    program: bool,
+    baselines: Baselines,
+    locale: Locale,
+) -> ExitStatus {
+    let Baselines {
+        baseline,
+        save_baseline,
+        threshold,
+    } = baselines;
+    // The baseline is read first, so that a wrong path does not wait for the benchmarks
+    let baseline = match baseline.map(read_baseline).transpose() {
+        Ok(baseline) => baseline,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitStatus::CompileError;
+        }
+    };
+    let files = match collect_files(paths, excludes) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitStatus::CompileError;
+        }
+    };
+
+    let mut status = ExitStatus::Success;
+    let mut failed = false;
+    let mut entries = Vec::new();
+    for file in &files {
+        let Some((source, ast, modules)) = compile_for_harness(file, locale) else {
+            status = ExitStatus::CompileError;
+            failed = true;
+            continue;
+        };
+        // A whole program is named after its file, a function `<file>::<function>`
+        let name = file.display().to_string();
+        let (prefix, results) = match program {
+            true => (
+                String::new(),
+                harness::run_program(&ast, &modules, &name, options).map(|result| vec![result]),
+            ),
+            false => (
+                format!("{}::", name),
+                harness::run_benches(&ast, &modules, options),
+            ),
+        };
+        match results {
+            Ok(results) => {
+                for result in results {
+                    println!("{}{}", prefix, result);
+                    let entry_name = format!("{}{}", prefix, result.name);
+                    entries.push(bench_json::BenchEntry::new(entry_name, &result));
+                }
+            }
+            Err(diagnostic) => {
+                let renderer = snippet_renderer(file);
+                let source = modules::source_of(&diagnostic, &source, &modules);
+                eprint!(
+                    "{}",
+                    renderer.render_diagnostic(&diagnostic, source, locale)
+                );
+                failed = true;
+                if status == ExitStatus::Success {
+                    status = ExitStatus::RuntimeError;
+                }
+            }
+        }
+    }
+
+    if let Some(baseline) = baseline {
+        let regressions = baseline.compare(&entries, threshold);
+        if !regressions.is_empty() {
+            println!("{} regression(s) against the baseline:", regressions.len());
+            for regression in regressions {
+                println!("  {}", regression);
+            }
+            if status == ExitStatus::Success {
+                status = ExitStatus::RuntimeError;
+            }
+        }
+    }
+    if let Some(path) = save_baseline {
+        // The benchmarks missing from a baseline are not compared, so one that failed to
+        // run would go unnoticed from then on
+        if failed {
+            eprintln!("Baseline \"{}\" not saved, a benchmark failed", path);
+        } else {
+            let json = bench_json::BenchJson::new(entries).to_json();
+            if let Err(e) = std::fs::write(path, format!("{}\n", json)) {
+                eprintln!("Error writing file \"{}\": {}", path, e);
+                status = ExitStatus::CompileError;
+            }
+        }
+    }
+    status
+}
+
+fn read_baseline(path: &str) -> Result<bench_json::BenchJson, String> {
+    let json = std::fs::read_to_string(path)
+        .map_err(|e| format!("Error reading file \"{}\": {}", path, e))?;
+    bench_json::BenchJson::from_json(&json).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Parses a file for `diff`, reporting its errors.
+fn parse_for_diff(file_path: &str) -> Option<parser::Tree> {
+    let path = Path::new(file_path);
+    let source = match read_source(path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("{}", e);
+            return None;
+        }
+    };
+    let (tree, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+    if errors.is_empty() {
+        return Some(tree);
+    }
+    for error in errors {
+        eprintln!("{}: {}", path.display(), error);
+    }
+    None
+}
+
+/// Prints the semantic differences between two files.
+fn diff_files(before: &str, after: &str) -> ExitStatus {
+    if let Err(e) = set_up_logger() {
+        eprintln!("{}", e);
+        return ExitStatus::CompileError;
+    }
+    let (Some(before), Some(after)) = (parse_for_diff(before), parse_for_diff(after)) else {
+        return ExitStatus::CompileError;
+    };
+
+    let changes = diff::diff(&before, &after);
+    if changes.is_empty() {
+        println!("No semantic differences");
+    }
+    for change in changes {
+        println!("{}", change);
+    }
+    ExitStatus::Success
+}
+
+/// Rewrites the deprecated syntax used in every file in `paths`.
+///
+/// Files that do not parse are left untouched, see `migrate_file`.
+fn migrate_files(paths: &[String], excludes: &[String]) -> ExitStatus {
+    if let Err(e) = set_up_logger() {
+        eprintln!("{}", e);
+        return ExitStatus::CompileError;
+    }
+    let files = match collect_files(paths, excludes) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitStatus::CompileError;
+        }
+    };
+
+    let mut migrated = 0;
+    let mut status = ExitStatus::Success;
+    for file in &files {
+        match migrate_file(file) {
+            Ok(deprecations) if deprecations.is_empty() => {}
+            Ok(deprecations) => {
+                migrated += 1;
+                for deprecation in deprecations {
+                    println!("{}", deprecation);
+                }
+            }
+            Err(errors) => {
+                for error in errors {
+                    eprintln!("{}", error);
+                }
+                status = ExitStatus::CompileError;
+            }
+        }
+    }
+
+    println!("Migrated {} of {} file(s)", migrated, files.len());
+    status
+}
+
+/// Rewrites the deprecated syntax used in `file`, returning what was rewritten.
+///
+/// A file that does not parse is left untouched, and so is a file with bytes that are
+/// not UTF-8 even if `lossy_utf8` let it be read: writing it back would replace them
+/// with `U+FFFD`.
+fn migrate_file(file: &Path) -> Result<Vec<Deprecation>, Vec<String>> {
+    let source = read_source(file).map_err(|e| vec![e.to_string()])?;
+    if !source.invalid_utf8().is_empty() {
+        let mut errors = source
+            .invalid_utf8()
+            .iter()
+            .map(|invalid| {
+                Diagnostic::error(invalid.message(), invalid.location.clone()).to_string()
+            })
+            .collect::<Vec<_>>();
+        errors.push(format!(
+            "{}: not migrated, rewriting it would replace the bytes that are not UTF-8",
+            file.display()
+        ));
+        return Err(errors);
+    }
+    let (tree, errors) = ice::with_phase(Phase::Parsing, file, || {
+        Parser::new(Lexer::new(&source)).parse_with_errors()
+    });
+    if !errors.is_empty() {
+        return Err(errors
+            .iter()
+            .map(|error| format!("{}: {}", file.display(), error))
+            .collect());
+    }
+    let deprecations = migrate::deprecations(&tree);
+    if deprecations.is_empty() {
+        return Ok(deprecations);
+    }
+    std::fs::write(file, migrate::apply(source.content(), &deprecations))
+        .map_err(|e| vec![format!("Error writing file \"{}\": {}", file.display(), e)])?;
+    Ok(deprecations)
+}
+
+/// Prints the syntax tree of a file as JSON, see `dev_doc/ast_json.md`.
+///
+/// The tree is printed even if the file has parse errors, they are part of the output.
+fn print_ast(path: &str) -> ExitStatus {
+    // No logger: stdout must only hold the JSON
+    let path = Path::new(path);
+    let source = match read_source(path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitStatus::CompileError;
+        }
+    };
+    let (tree, errors) = ice::with_phase(Phase::Parsing, path, || {
+        Parser::new(Lexer::new(&source)).parse_with_errors()
+    });
+    let status = if errors.is_empty() {
+        ExitStatus::Success
+    } else {
+        ExitStatus::CompileError
+    };
+    let json = ast_json::AstJson::new(path.to_path_buf(), errors, tree).to_json();
+    match print_for_tool(&json) {
+        Ok(()) => status,
+        Err(e) => {
+            eprintln!("Error writing the AST: {}", e);
+            ExitStatus::InternalError
+        }
+    }
+}
+
+/// Prints the TextMate scopes of the tokens of a file as JSON.
+///
+/// Files with parse errors are highlighted too, editors show code while it is written.
+fn print_highlights(path: &str) -> ExitStatus {
+    // No logger: stdout must only hold the JSON
+    let path = Path::new(path);
+    let source = match read_source(path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitStatus::CompileError;
+        }
+    };
+    let tree = ice::with_phase(Phase::Parsing, path, || {
+        Parser::new(Lexer::new(&source)).parse()
+    });
+    let highlights = highlight::highlights(&tree);
+    // Scopes and locations only hold strings and numbers, they always serialize
+    let json = serde_json::to_string_pretty(&highlights).unwrap_or_default();
+    match print_for_tool(&json) {
+        Ok(()) => ExitStatus::Success,
+        Err(e) => {
+            eprintln!("Error writing the highlights: {}", e);
+            ExitStatus::InternalError
+        }
+    }
+}
+
+/// Prints the configuration that applies to `path`, as a `funs.toml`.
+fn show_config(path: &str) -> ExitStatus {
+    let path = Path::new(path);
+    let file_path = Config::find(path);
+    let config = match &file_path {
+        Some(file_path) => Config::from_file(file_path),
+        None => Ok(Config::default()),
+    };
+    let config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitStatus::CompileError;
+        }
+    };
+    match file_path {
+        Some(file_path) => println!("# From {}", file_path.display()),
+        None => println!("# No {} found, using the defaults", CONFIG_FILE),
+    }
+    print!("{}", config.to_toml());
+    ExitStatus::Success
+}
+
+/// Prints `output` for another program, which may stop reading it early (e.g.
+/// `funs ast --json main.fs | head`).
+fn print_for_tool(output: &str) -> std::io::Result<()> {
+    match writeln!(std::io::stdout(), "{}", output) {
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
+
+/// Reports the lints of every file in `paths`.
+///
+/// Files that do not parse are reported and skipped.
+/// The rules in the `allow` of the `funs.toml` of each file are off, and so are the
+/// rules in `allow`.
+fn lint_files(
+    paths: &[String],
+    excludes: &[String],
+    allow: &[String],
+    locale: Locale,
+) -> ExitStatus {
+    if let Some(name) = allow.iter().find(|name| !lint::is_rule(name)) {
+        eprintln!("Unknown lint rule \"{}\"", name);
+        return ExitStatus::UsageError;
+    }
+    if let Err(e) = set_up_logger() {
+        eprintln!("{}", e);
+        return ExitStatus::CompileError;
+    }
+    let files = match collect_files(paths, excludes) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitStatus::CompileError;
+        }
+    };
+
+    let mut total_lints = 0;
+    let mut status = ExitStatus::Success;
+    for file in &files {
+        let source = match read_source(file) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("{}", e);
+                status = ExitStatus::CompileError;
+                continue;
+            }
+        };
+        let (tree, errors) = ice::with_phase(Phase::Parsing, file, || {
+            Parser::new(Lexer::new(&source))
+                .with_locale(locale)
+                .parse_with_errors()
+        });
+        if !errors.is_empty() {
+            for error in errors {
+                eprintln!("{}: {}", file.display(), error);
+            }
+            status = ExitStatus::CompileError;
+            continue;
+        }
+        let config = match Config::discover(file) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("{}", e);
+                status = ExitStatus::CompileError;
+                continue;
+            }
+        };
+        let unknown = config
+            .lints
+            .allow
+            .iter()
+            .filter(|name| !lint::is_rule(name));
+        for name in unknown {
+            eprintln!(
+                "{}: unknown lint rule \"{}\" in {}",
+                file.display(),
+                name,
+                CONFIG_FILE
+            );
+            status = ExitStatus::CompileError;
+        }
+        let allow = [config.lints.allow, allow.to_vec()].concat();
+        let lints = lint::lint(&tree, &allow);
+        total_lints += lints.len();
+        for lint in lints {
+            eprintln!("{}", lint.render(locale));
+        }
+    }
+
+    if total_lints > 0 {
+        eprintln!(
+            "Found {} warning(s) in {} file(s)",
+            total_lints,
+            files.len()
+        );
+        status = ExitStatus::CompileError;
+    }
+    status
+}
+
+/// Prints the top-level declarations of every file in `paths`, as a table or as JSON.
+///
+/// Files that do not parse are reported and skipped.
+fn list_symbols(paths: &[String], excludes: &[String], json: bool) -> ExitStatus {
+    // The logger writes to stdout, which must only hold the JSON for other tools to read it
+    if !json {
+        if let Err(e) = set_up_logger() {
+            eprintln!("{}", e);
+            return ExitStatus::CompileError;
+        }
+    }
+    let files = match collect_files(paths, excludes) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitStatus::CompileError;
+        }
+    };
+
+    let mut symbols = Vec::new();
+    let mut status = ExitStatus::Success;
+    for file in &files {
+        let source = match read_source(file) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("{}", e);
+                status = ExitStatus::CompileError;
+                continue;
+            }
+        };
+        let (tree, errors) = ice::with_phase(Phase::Parsing, file, || {
+            Parser::new(Lexer::new(&source)).parse_with_errors()
+        });
+        if !errors.is_empty() {
+            for error in errors {
+                eprintln!("{}: {}", file.display(), error);
+            }
+            status = ExitStatus::CompileError;
+            continue;
+        }
+        symbols.extend(symbols::symbols(&tree));
+    }
+
+    if json {
+        match serde_json::to_string_pretty(&symbols) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                eprintln!("{}", e);
+                return ExitStatus::InternalError;
+            }
+        }
+    } else {
+        print!("{}", symbols::table(&symbols));
+    }
+    status
+}
+
+/// Prints the definitions of every file in `paths` and the references to them as JSON.
+///
+/// Files that do not parse are reported and left out of the index.
+fn index_files(paths: &[String], excludes: &[String]) -> ExitStatus {
+    // No logger: stdout must only hold the JSON
+    let files = match collect_files(paths, excludes) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitStatus::CompileError;
+        }
+    };
+
+    let mut indexed = Vec::new();
+    let mut status = ExitStatus::Success;
+    for file in &files {
+        let source = match read_source(file) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("{}", e);
+                status = ExitStatus::CompileError;
+                continue;
+            }
+        };
+        let (tree, errors) = ice::with_phase(Phase::Parsing, file, || {
+            Parser::new(Lexer::new(&source)).parse_with_errors()
+        });
+        if !errors.is_empty() {
+            for error in errors {
+                eprintln!("{}: {}", file.display(), error);
+            }
+            status = ExitStatus::CompileError;
+            continue;
+        }
+        indexed.push(index::index(file.clone(), &tree));
+    }
+
+    match print_for_tool(&index::Index::new(indexed).to_json()) {
+        Ok(()) => status,
+        Err(e) => {
+            eprintln!("Error writing the index: {}", e);
+            ExitStatus::InternalError
+        }
+    }
+}
+
+/// Runs the program at `path` and prints the value of its last expression, if it ends
+/// with one that is not `()`.
+///
+/// The program is checked first, a program with errors does not run. What it prints
+/// goes to stdout, the errors to stderr, and with `trace_eval` its evaluation steps to
+/// the debug log.
+fn run_file(path: &str, locale: Locale, trace_eval: bool) -> ExitStatus {
+    let path = Path::new(path);
+    let report = compile(path, None, locale);
+    for deprecation in &report.deprecations {
+        eprintln!("{}", deprecation.render(locale));
+    }
+    print_errors(path, &report, locale);
+    let (Some(source), Some(ast), 0) = (&report.source, &report.ast, report.error_count()) else {
+        return ExitStatus::CompileError;
+    };
+
+    // The logger is set up once the program compiled, so that only the trace is logged
+    if trace_eval {
+        // The trace is at the `debug` level, which only goes to the debug log
+        if let Err(e) = set_up_logger_with("info,funs::interpreter=debug") {
+            eprintln!("{}", e);
+            return ExitStatus::CompileError;
+        }
+    }
+    let mut interpreter = Interpreter::new(std::io::stdout()).with_trace(trace_eval);
+    let result =
+        modules::run(&mut interpreter, &report.modules).and_then(|()| interpreter.run(ast));
+    match result {
+        Ok(None | Some(Value::Unit)) => ExitStatus::Success,
+        Ok(Some(value)) => match print_for_tool(&value.to_string()) {
+            Ok(()) => ExitStatus::Success,
+            Err(e) => {
+                eprintln!("Error writing the result: {}", e);
+                ExitStatus::InternalError
+            }
+        },
+        Err(diagnostic) => {
+            let renderer = snippet_renderer(path);
+            let source = modules::source_of(&diagnostic, source, &report.modules);
+            eprint!(
+                "{}",
+                renderer.render_diagnostic(&diagnostic, source, locale)
+            );
+            ExitStatus::RuntimeError
+        }
+    }
+}
+
+/// Runs the statements read from stdin until its end.
+///
+/// The prompts are only shown when stdin is a terminal, so that piped inputs print
+/// their results alone.
+fn repl(locale: Locale) -> ExitStatus {
+    let interactive = std::io::stdin().is_terminal();
+    let current_dir = env::current_dir().unwrap_or_default();
+    let mut repl = repl::Repl::new(std::io::stdout(), std::io::stderr())
+        .with_locale(locale)
+        .with_renderer(snippet_renderer(&current_dir));
+    let mut prompt = repl::Prompt::Input;
+    let mut line = String::new();
+    loop {
+        if interactive {
+            print!("{}", prompt.as_str());
+            // A prompt that can not be shown does not stop the session
+            let _ = std::io::stdout().flush();
+        }
+        line.clear();
+        let result = match std::io::stdin().read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => repl.feed(&line),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(next) => prompt = next,
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => return ExitStatus::Success,
+            Err(e) => {
+                eprintln!("Error in the REPL: {}", e);
+                return ExitStatus::InternalError;
+            }
+        }
+    }
+    if interactive {
+        println!();
+    }
+    match repl.finish() {
+        Ok(_) => ExitStatus::Success,
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => ExitStatus::Success,
+        Err(e) => {
+            eprintln!("Error in the REPL: {}", e);
+            ExitStatus::InternalError
+        }
+    }
+}
+
+/// Prints the smallest program found that fails like the file at `path`: with the
+/// error containing `error`, or with an internal compiler error.
+fn minimize_file(path: &str, error: Option<String>) -> ExitStatus {
+    let source = match read_source(Path::new(path)) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitStatus::CompileError;
+        }
+    };
+    let failure = match error {
+        Some(text) => minimize::Failure::Error(text),
+        None => minimize::Failure::Crash,
+    };
+    // Every candidate that crashes would print a report
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+    let minimal = minimize::minimize(source.content(), |content| failure.reproduces(content));
+    std::panic::set_hook(hook);
+
+    match minimal {
+        Some(minimal) => {
+            eprintln!(
+                "Reduced {} from {} to {} line(s)",
+                path,
+                source.content().lines().count(),
+                minimal.lines().count()
+            );
+            match print_for_tool(&minimal) {
+                Ok(()) => ExitStatus::Success,
+                Err(e) => {
+                    eprintln!("Error writing the program: {}", e);
+                    ExitStatus::InternalError
+                }
+            }
+        }
+        None => {
+            match failure {
+                minimize::Failure::Crash => eprintln!("{} does not crash the compiler", path),
+                minimize::Failure::Error(text) => {
+                    eprintln!("{} has no error containing \"{}\"", path, text)
+                }
+            }
+            ExitStatus::CompileError
+        }
+    }
+}
+
+/// Runs a command evaluating programs on the stack of the interpreter, see
+/// `interpreter::with_stack`
+fn with_interpreter_stack(command: impl FnOnce() -> ExitStatus + Send) -> ExitStatus {
+    match interpreter::with_stack(command) {
+        Ok(status) => status,
+        Err(e) => {
+            eprintln!("Error starting the interpreter: {}", e);
+            ExitStatus::InternalError
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let status = match Command::parse(&args) {
+        Ok(Command::Help) => {
+            println!("{}", cli::USAGE);
+            ExitStatus::Success
+        }
+        Ok(Command::Completions { shell }) => {
+            print!("{}", completions::script(shell));
+            ExitStatus::Success
+        }
+        Ok(Command::Version { all, json }) => {
+            match (all, json) {
+                (false, _) => println!("{}", cli::version()),
+                (true, false) => println!("{}", cli::Versions::current()),
+                (true, true) => println!("{}", cli::Versions::current().to_json()),
+            }
+            ExitStatus::Success
+        }
+        Ok(Command::Check {
+            paths,
+            excludes,
+            emit_debug_dir,
+            stats_file,
+            locale,
+        }) => {
+            ice::install_panic_hook();
+            std::panic::catch_unwind(|| {
+                check(
+                    &paths,
+                    &excludes,
+                    emit_debug_dir.as_deref(),
+                    stats_file.as_deref(),
+                    locale,
+                )
+            })
+            .unwrap_or(ExitStatus::InternalError)
+        }
+        Ok(Command::Test {
+            doc: true,
+            spec: false,
+            paths,
+            excludes,
+            ..
+        }) => {
+            ice::install_panic_hook();
+            std::panic::catch_unwind(|| test_doc(&paths, &excludes))
+                .unwrap_or(ExitStatus::InternalError)
+        }
+        Ok(Command::Diff { before, after }) => {
+            ice::install_panic_hook();
+            std::panic::catch_unwind(|| diff_files(&before, &after))
+                .unwrap_or(ExitStatus::InternalError)
+        }
+        Ok(Command::Migrate { paths, excludes }) => {
+            ice::install_panic_hook();
+            std::panic::catch_unwind(|| migrate_files(&paths, &excludes))
+                .unwrap_or(ExitStatus::InternalError)
+        }
+        Ok(Command::Lint {
+            paths,
+            excludes,
+            allow,
+            locale,
+        }) => {
+            ice::install_panic_hook();
+            std::panic::catch_unwind(|| lint_files(&paths, &excludes, &allow, locale))
+                .unwrap_or(ExitStatus::InternalError)
+        }
+        Ok(Command::Symbols {
+            json,
+            paths,
+            excludes,
+        }) => {
+            ice::install_panic_hook();
+            std::panic::catch_unwind(|| list_symbols(&paths, &excludes, json))
+                .unwrap_or(ExitStatus::InternalError)
+        }
+        Ok(Command::Index { paths, excludes }) => {
+            ice::install_panic_hook();
+            std::panic::catch_unwind(|| index_files(&paths, &excludes))
+                .unwrap_or(ExitStatus::InternalError)
+        }
+        Ok(Command::Minimize { error, path }) => minimize_file(&path, error),
+        Ok(Command::Run {
+            path,
+            locale,
+            trace_eval,
+        }) => {
+            ice::install_panic_hook();
+            std::panic::catch_unwind(|| {
+                with_interpreter_stack(|| run_file(&path, locale, trace_eval))
+            })
+            .unwrap_or(ExitStatus::InternalError)
+        }
+        Ok(Command::Repl { locale }) => {
+            ice::install_panic_hook();
+            std::panic::catch_unwind(|| with_interpreter_stack(|| repl(locale)))
+                .unwrap_or(ExitStatus::InternalError)
+        }
+        Ok(Command::Ast { json: true, path }) => {
+            ice::install_panic_hook();
+            std::panic::catch_unwind(|| print_ast(&path)).unwrap_or(ExitStatus::InternalError)
+        }
+        Ok(Command::Ast { json: false, .. }) => {
+            eprintln!("Only JSON output is supported for now, use `funs ast --json`");
+            ExitStatus::UsageError
+        }
+        Ok(Command::Highlight { scopes: true, path }) => {
+            ice::install_panic_hook();
+            std::panic::catch_unwind(|| print_highlights(&path))
+                .unwrap_or(ExitStatus::InternalError)
+        }
+        Ok(Command::Highlight { scopes: false, .. }) => {
+            eprintln!("Only TextMate scopes are supported for now, use `funs highlight --scopes`");
+            ExitStatus::UsageError
+        }
+        Ok(Command::Config { show: true, path }) => show_config(&path),
+        Ok(Command::Config { show: false, .. }) => {
+            eprintln!(
+                "Only showing the configuration is supported for now, use `funs config --show`"
+            );
+            ExitStatus::UsageError
+        }
+        Ok(Command::Test {
+            doc: false,
+            spec: true,
+            paths,
+            excludes,
+            ..
+        }) => {
+            ice::install_panic_hook();
+            std::panic::catch_unwind(|| with_interpreter_stack(|| test_spec(&paths, &excludes)))
+                .unwrap_or(ExitStatus::InternalError)
+        }
+        Ok(Command::Test {
+            doc: false,
+            spec: false,
+            paths,
+            excludes,
+            locale,
+        }) => {
+            ice::install_panic_hook();
+            std::panic::catch_unwind(|| {
+                with_interpreter_stack(|| test_functions(&paths, &excludes, locale))
+            })
+            .unwrap_or(ExitStatus::InternalError)
+        }
+        Ok(Command::Test { .. }) => {
+            eprintln!("`funs test --doc` and `--spec` can not be combined, run them one at a time");
+            ExitStatus::UsageError
+        }
+        Ok(Command::Bench {
+            options,
+            program,
+            baseline,
+            save_baseline,
+            threshold,
+            paths,
+            excludes,
+            locale,
+        }) => {
+            ice::install_panic_hook();
+            std::panic::catch_unwind(|| {
+                with_interpreter_stack(|| {
+                    bench_files(
+                        &paths,
+                        &excludes,
+                        &options,
+                        program,
+                        Baselines {
+                            baseline: baseline.as_deref(),
+                            save_baseline: save_baseline.as_deref(),
+                            threshold,
+                        },
+                        locale,
+                    )
+                })
+            })
+            .unwrap_or(ExitStatus::InternalError)
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("{}", cli::USAGE);
+            ExitStatus::UsageError
+        }
+    };
+    status.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use utils::file_handler::create_tmp_dir;
+
+    /// What `funs check --debug-dir <debug_dir>` and `funs index` produce for the files
+    /// in `paths`: the diagnostics with their locations, the index and the artifacts, by
+    /// path relative to `debug_dir`
+    fn outputs(paths: &[String], debug_dir: &Path) -> (Vec<String>, BTreeMap<PathBuf, Vec<u8>>) {
+        let mut printed = Vec::new();
+        let mut indexed = Vec::new();
+        for file in collect_files(paths, &[]).unwrap() {
+            let report = compile(&file, Some(&DebugDir::new(debug_dir)), Locale::En);
+            printed.extend(report.errors.iter().cloned());
+            printed.extend(report.diagnostics.iter().map(Diagnostic::to_string));
+            printed.extend(
+                report.deprecations.iter().map(|deprecation| {
+                    format!("{}: {}", deprecation.location, deprecation.message)
+                }),
+            );
+            // A file that can not be read is in the errors already
+            let Ok(source) = Source::new(&file) else {
+                continue;
+            };
+            let (tree, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+            if errors.is_empty() {
+                indexed.push(index::index(file, &tree));
+            }
+        }
+        printed.push(index::Index::new(indexed).to_json());
+
+        let artifacts = FsFileFinder::new()
+            .with_extensions(&["json".to_string()])
+            .find(debug_dir)
+            .unwrap()
+            .into_iter()
+            .map(|path| {
+                let content = std::fs::read(&path).unwrap();
+                (path.strip_prefix(debug_dir).unwrap().to_path_buf(), content)
+            })
+            .collect();
+        (printed, artifacts)
+    }
+
+    /// Compiling the same files twice gives byte-identical outputs, whatever the order
+    /// the maps of the compiler are built in
+    #[test]
+    fn test_compile_repeatable() {
+        let paths = ["testdata".to_string(), "spec".to_string()];
+        let dir = create_tmp_dir("main_compile_repeatable");
+        let dirs = ["first", "second"].map(|run| dir.join(run));
+
+        let (first_printed, first_artifacts) = outputs(&paths, &dirs[0]);
+        let (second_printed, second_artifacts) = outputs(&paths, &dirs[1]);
+        assert!(first_printed.len() > 1);
+        assert!(!first_artifacts.is_empty());
+        assert_eq!(first_printed, second_printed);
+        assert_eq!(
+            first_artifacts.keys().collect::<Vec<_>>(),
+            second_artifacts.keys().collect::<Vec<_>>()
+        );
+        for (path, content) in &first_artifacts {
+            assert!(
+                second_artifacts[path] == *content,
+                "{} differs between runs",
+                path.display()
+            );
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// `funs check <paths>...` checks the files given and the source files found in the
+    /// directories given, their subdirectories included
+    #[test]
+    fn test_check_files_and_directories() {
+        let dir = create_tmp_dir("main_check_paths");
+        let write = |relative: &str, content: &str| {
+            let path = dir.join(relative);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        write("given.txt", "x: int = 1\n");
+        write("src/a.fs", "x: int = 1\n");
+        write("src/notes.txt", "not funs\n");
+        write("src/sub/deeper/b.fs", "x: int = \n");
+
+        let paths =
+            [dir.join("given.txt"), dir.join("src")].map(|path| path.to_string_lossy().to_string());
+        let files = collect_files(&paths, &[]).unwrap();
+        let expected = ["given.txt", "src/a.fs", "src/sub/deeper/b.fs"].map(|file| dir.join(file));
+        assert_eq!(files, expected);
+        let errors = files
+            .iter()
+            .map(|file| compile(file, None, Locale::En).error_count())
+            .collect::<Vec<_>>();
+        assert_eq!(errors[..2], [0, 0]);
+        assert!(errors[2] > 0);
+
+        let missing = [dir.join("missing").to_string_lossy().to_string()];
+        assert_eq!(collect_files(&missing, &[]).unwrap(), [dir.join("missing")]);
+        assert!(compile(&dir.join("missing"), None, Locale::En).error_count() > 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// The uses of the declarations of an imported module are checked against their
+    /// types, and the module itself is checked, its errors pointing into it
+    #[test]
+    fn test_check_imports() {
+        let dir = create_tmp_dir("main_check_imports");
+        let lib = "data P = { x: int }\ninc: (int) -> int = (n) -> n + 1 ;\nk: int = 1\n";
+        std::fs::write(dir.join("lib.fs"), lib).unwrap();
+        let main = "import lib\n\
+                    x: str = inc 1\n\
+                    y: str = k\n\
+                    print (inc \"a\")\n\
+                    p: P = { x = 1, y = 2 }\n";
+        std::fs::write(dir.join("main.fs"), main).unwrap();
+
+        let report = compile(&dir.join("main.fs"), None, Locale::En);
+        assert_eq!(
+            report.error_messages(Locale::En),
+            vec![
+                "expected `str`, found `int`",
+                "expected `str`, found `int`",
+                "expected `int`, found `str`",
+                "`P` has no field `y`",
+            ]
+        );
+
+        std::fs::write(dir.join("lib.fs"), "k: int = \"a\"\n").unwrap();
+        std::fs::write(dir.join("main.fs"), "import lib\ny: int = k\n").unwrap();
+        let report = compile(&dir.join("main.fs"), None, Locale::En);
+        let printed = report
+            .diagnostics
+            .iter()
+            .map(Diagnostic::to_string)
+            .collect::<Vec<_>>();
+        let lib = std::path::absolute(dir.join("lib.fs")).unwrap();
+        assert_eq!(
+            printed,
+            vec![format!(
+                "{}:1:10: error: expected `int`, found `str`",
+                lib.display()
+            )]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// A program recursing past `MAX_CALL_DEPTH` stops with a runtime error instead of
+    /// overflowing the stack
+    #[test]
+    fn test_run_deep_recursion() {
+        let dir = create_tmp_dir("main_deep_recursion");
+        let path = dir.join("deep.fs");
+        std::fs::write(&path, "f: (int) -> int = (n) -> f n ;\nf 1\n").unwrap();
+        let path = path.to_string_lossy().to_string();
+        let status = with_interpreter_stack(|| run_file(&path, Locale::En, false));
+        assert_eq!(status, ExitStatus::RuntimeError);
+        assert_eq!(status.code(), 3);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// `funs migrate` does not write back a file read with `lossy_utf8`, which would
+    /// lose its bytes that are not UTF-8
+    #[test]
+    fn test_migrate_invalid_utf8() {
+        let dir = create_tmp_dir("main_migrate_invalid_utf8");
+        let file = dir.join("main.fs");
+        let content = b"yes: bool = True\nname: str = \"caf\xe9\"\n";
+        std::fs::write(&file, content).unwrap();
+
+        let errors = migrate_file(&file).unwrap_err();
+        assert!(errors[0].contains("not valid UTF-8"), "{:?}", errors);
+        assert_eq!(std::fs::read(&file).unwrap(), content);
+
+        std::fs::write(dir.join(CONFIG_FILE), "[compiler]\nlossy_utf8 = true\n").unwrap();
+        let errors = migrate_file(&file).unwrap_err();
+        assert!(
+            errors[0].contains("invalid UTF-8 at byte 33"),
+            "{:?}",
+            errors
+        );
+        assert!(errors[1].contains("not migrated"), "{:?}", errors);
+        assert_eq!(std::fs::read(&file).unwrap(), content);
+
+        std::fs::write(&file, "yes: bool = True\n").unwrap();
+        assert_eq!(migrate_file(&file).unwrap().len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "yes: bool = true\n"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
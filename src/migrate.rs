@@ -0,0 +1,148 @@
+use crate::diagnostics::messages::{Code, Locale, Message};
+use crate::lexer::token::{Token, TokenKind, TokenLocation};
+use crate::parser::{Child, Tree, TreeKind};
+
+/// Booleans spelled as in older versions of the language, with their replacement
+const DEPRECATED_BOOLS: [(&str, &str); 2] = [("True", "true"), ("False", "false")];
+/// Type names from older versions of the language, with their replacement
+const DEPRECATED_TYPES: [(&str, &str); 1] = [("Opt", "option")];
+
+/// A syntax that is still accepted but will be removed, along with the rewrite that
+/// replaces it by the current syntax.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deprecation {
+    pub message: Message,
+    /// The span to replace
+    pub location: TokenLocation,
+    /// The text replacing the span
+    pub replacement: String,
+}
+
+impl Deprecation {
+    pub fn render(&self, locale: Locale) -> String {
+        format!(
+            "{}: warning: {}",
+            self.location,
+            self.message.render(locale)
+        )
+    }
+}
+
+impl std::fmt::Display for Deprecation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.render(Locale::default()))
+    }
+}
+
+/// Whether `lexeme` is a boolean in a deprecated spelling, e.g. `True`
+pub fn is_deprecated_bool(lexeme: &str) -> bool {
+    DEPRECATED_BOOLS.iter().any(|(old, _)| *old == lexeme)
+}
+
+/// Collects the deprecated syntax used in a file, in source order.
+pub fn deprecations(file: &Tree) -> Vec<Deprecation> {
+    let mut deprecations = Vec::new();
+    collect(file, &mut deprecations);
+    deprecations
+}
+
+fn collect(tree: &Tree, deprecations: &mut Vec<Deprecation>) {
+    for child in tree.children() {
+        match child {
+            Child::Tree(child) => collect(child, deprecations),
+            Child::Token(token) if token.kind == TokenKind::TokenIdentifier => {
+                let deprecated = match tree.kind() {
+                    TreeKind::ExprLiteral | TreeKind::PatLiteral => {
+                        find(&DEPRECATED_BOOLS, token, Code::DeprecatedBool)
+                    }
+                    TreeKind::TypeExpr => find(&DEPRECATED_TYPES, token, Code::DeprecatedType),
+                    _ => None,
+                };
+                deprecations.extend(deprecated);
+            }
+            Child::Token(_) => {}
+        }
+    }
+}
+
+fn find(table: &[(&str, &str)], token: &Token, code: Code) -> Option<Deprecation> {
+    let (old, new) = table.iter().find(|(old, _)| *old == token.lexeme)?;
+    Some(Deprecation {
+        message: Message::new(code).with_arg("old", old).with_arg("new", new),
+        location: token.location.clone(),
+        replacement: new.to_string(),
+    })
+}
+
+/// Applies the rewrites of `deprecations` to `content`.
+///
+/// Each deprecation replaces a span within a single line.
+pub fn apply(content: &str, deprecations: &[Deprecation]) -> String {
+    let mut migrated = String::new();
+    for (index, line) in content.split_inclusive('\n').enumerate() {
+        let mut rewrites = deprecations
+            .iter()
+            .filter(|deprecation| deprecation.location.line == index)
+            .collect::<Vec<_>>();
+        rewrites.sort_by_key(|deprecation| deprecation.location.column_start);
+
+        let mut chars = line.chars().enumerate().peekable();
+        for rewrite in rewrites {
+            while let Some((_, c)) = chars.next_if(|(i, _)| *i < rewrite.location.column_start) {
+                migrated.push(c);
+            }
+            while chars
+                .next_if(|(i, _)| *i < rewrite.location.column_end)
+                .is_some()
+            {}
+            migrated.push_str(&rewrite.replacement);
+        }
+        migrated.extend(chars.map(|(_, c)| c));
+    }
+    migrated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer::Lexer, parser::Parser, source::Source};
+
+    fn parse(content: &str) -> (Tree, Vec<String>) {
+        Parser::new(Lexer::new(&Source::from(content.to_string()))).parse_with_errors()
+    }
+
+    #[test]
+    fn test_migrate_deprecations() {
+        let content = "a: bool = True\nb: Opt<bool> = False\nc: bool = true\n";
+        let (tree, errors) = parse(content);
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let deprecations = deprecations(&tree);
+        let messages = deprecations
+            .iter()
+            .map(|deprecation| deprecation.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                ":1:11: warning: the boolean `True` is deprecated, use `true` instead",
+                ":2:4: warning: the type `Opt` is deprecated, use `option` instead",
+                ":2:16: warning: the boolean `False` is deprecated, use `false` instead",
+            ]
+        );
+
+        let migrated = apply(content, &deprecations);
+        assert_eq!(
+            migrated,
+            "a: bool = true\nb: option<bool> = false\nc: bool = true\n"
+        );
+        let (tree, _) = parse(&migrated);
+        assert!(super::deprecations(&tree).is_empty());
+    }
+
+    #[test]
+    fn test_migrate_identifiers_are_not_rewritten_elsewhere() {
+        let (tree, _) = parse("True: Option = 1\n");
+        assert!(deprecations(&tree).is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+//! Shrinks a program that makes the compiler fail, run by `funs minimize`.
+//!
+//! The reduction is a delta debugging (ddmin) over the lines of the program, then over
+//! the characters of what is left: a part is removed whenever the program without it
+//! still fails the same way, until no single part can be removed anymore. Every
+//! candidate is compiled from memory with the phases of `funs check`.
+
+use crate::diagnostics::messages::Locale;
+use crate::source::Source;
+
+/// How the program to minimize fails, every candidate must fail the same way
+#[derive(Debug, Clone, PartialEq)]
+pub enum Failure {
+    /// The compiler panics (an internal compiler error)
+    Crash,
+    /// The compiler reports an error containing this text
+    Error(String),
+}
+
+impl Failure {
+    /// Whether compiling `content` fails this way
+    pub fn reproduces(&self, content: &str) -> bool {
+        let source = Source::from_snippet("minimize", content.to_string());
+        let result = std::panic::catch_unwind(|| crate::compile_source(source, None, Locale::En));
+        match (self, result) {
+            (Failure::Crash, result) => result.is_err(),
+            (Failure::Error(text), Ok(report)) => report
+                .error_messages(Locale::En)
+                .iter()
+                .any(|error| error.contains(text.as_str())),
+            (Failure::Error(_), Err(_)) => false,
+        }
+    }
+}
+
+/// Shrinks `content` while `reproduces` holds, `None` if it does not hold for `content`
+/// in the first place.
+pub fn minimize(content: &str, mut reproduces: impl FnMut(&str) -> bool) -> Option<String> {
+    if !reproduces(content) {
+        return None;
+    }
+    let lines = content.split_inclusive('\n').collect::<Vec<_>>();
+    let lines = ddmin(lines, |lines| reproduces(&lines.concat()));
+    let chars = lines.concat().chars().collect::<Vec<_>>();
+    let chars = ddmin(chars, |chars| reproduces(&chars.iter().collect::<String>()));
+    Some(chars.into_iter().collect())
+}
+
+/// Removes parts of `items` while `fails` holds, halving the parts down to single items.
+///
+/// The result is 1-minimal: removing any single item of it makes `fails` false.
+fn ddmin<T: Clone>(mut items: Vec<T>, mut fails: impl FnMut(&[T]) -> bool) -> Vec<T> {
+    let mut parts = 2;
+    while !items.is_empty() {
+        let size = items.len().div_ceil(parts);
+        let reduced = (0..items.len()).step_by(size).find_map(|start| {
+            let end = (start + size).min(items.len());
+            let candidate = [&items[..start], &items[end..]].concat();
+            fails(&candidate).then_some(candidate)
+        });
+        match reduced {
+            Some(candidate) => {
+                items = candidate;
+                parts = (parts - 1).max(2);
+            }
+            None if size == 1 => break,
+            None => parts = (parts * 2).min(items.len()),
+        }
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minimize_ddmin() {
+        let items = (0..20).collect::<Vec<_>>();
+        let minimal = ddmin(items, |items| items.contains(&3) && items.contains(&17));
+        assert_eq!(minimal, vec![3, 17]);
+
+        let minimal = ddmin(vec![1, 2, 3], |_| true);
+        assert!(minimal.is_empty());
+    }
+
+    #[test]
+    fn test_minimize_error() {
+        let content = "# constants\n\
+                       small: int = 1\n\
+                       big: int = 99999999999999999999\n\
+                       name: str = \"funs\"\n";
+        let failure = Failure::Error("does not fit in an `int`".to_string());
+        let minimal = minimize(content, |content| failure.reproduces(content)).unwrap();
+        assert!(failure.reproduces(&minimal));
+        assert_eq!(minimal, "g:t=9999999999999999999");
+
+        assert_eq!(
+            minimize("x: int = 1\n", |content| failure.reproduces(content)),
+            None
+        );
+        assert!(!Failure::Crash.reproduces(content));
+    }
+}
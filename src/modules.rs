@@ -0,0 +1,315 @@
+//! Loads the modules imported by a file.
+//!
+//! `import a.b` names the file `a/b.fs` in the root of the program: the directory of the
+//! `funs.toml` that applies to the entry file (the file given to `funs check` or `funs
+//! run`), or the directory of the entry file without one. Every import of a program is
+//! found from there, whichever module it is written in, so a module can be checked on
+//! its own. A module imported several times is loaded once.
+use crate::config::Config;
+use crate::diagnostics::messages::{Code, Message};
+use crate::diagnostics::Diagnostic;
+use crate::interpreter::Interpreter;
+use crate::lexer::Lexer;
+use crate::parser::ast::{File, Import, Stmt};
+use crate::parser::lower::lower;
+use crate::parser::node_id::Span;
+use crate::parser::Parser;
+use crate::source::{read_source, Source, SourceError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The extension of the file of a module
+const MODULE_EXTENSION: &str = "fs";
+
+/// A module loaded for an import
+#[derive(Debug, Clone)]
+pub struct Module {
+    /// The path as written in the import, e.g. `math.trig`
+    pub name: String,
+    pub source: Source,
+    pub ast: File,
+}
+
+/// Loads the modules imported by `file`, the AST of the entry file `file_path`, and the
+/// ones they import, each after the modules it imports so that they can run in order.
+///
+/// A module that is missing, has errors or imports itself is reported on the import of
+/// `file` leading to it, the renderer only has the source of `file`.
+pub fn load(file_path: &Path, file: &File) -> (Vec<Module>, Vec<Diagnostic>) {
+    // The paths are compared to find the cycles
+    let file_path = std::path::absolute(file_path).unwrap_or(file_path.to_path_buf());
+    let root = Config::find(&file_path)
+        .as_deref()
+        .unwrap_or(&file_path)
+        .parent()
+        .unwrap_or(Path::new(""))
+        .to_path_buf();
+    // The entry file is named like a module, e.g. `cycle.a`, the root holds it
+    let entry = file_path
+        .strip_prefix(&root)
+        .unwrap_or(&file_path)
+        .with_extension("")
+        .iter()
+        .map(|part| part.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(".");
+    let mut loader = Loader {
+        root,
+        modules: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    for import in imports(file) {
+        let mut stack = vec![(entry.clone(), file_path.clone())];
+        loader.import(import, &import.span, &mut stack);
+    }
+    (loader.modules, loader.diagnostics)
+}
+
+/// The modules of `modules` that `file` imports itself, the ones whose declarations it
+/// can use
+pub fn imported<'a>(file: &File, modules: &'a [Module]) -> Vec<&'a Module> {
+    let names = imports(file).map(Import::name).collect::<Vec<_>>();
+    modules
+        .iter()
+        .filter(|module| names.contains(&module.name))
+        .collect()
+}
+
+/// Runs `modules` in order, so that the file importing them can use their declarations.
+/// An error points into the module it stopped, see `source_of`.
+pub fn run<W: Write>(
+    interpreter: &mut Interpreter<W>,
+    modules: &[Module],
+) -> Result<(), Diagnostic> {
+    for module in modules {
+        interpreter.run(&module.ast)?;
+    }
+    Ok(())
+}
+
+/// The source `diagnostic` points into: the one of a module of `modules`, or `source`,
+/// the file importing them
+pub fn source_of<'a>(
+    diagnostic: &Diagnostic,
+    source: &'a Source,
+    modules: &'a [Module],
+) -> &'a Source {
+    modules
+        .iter()
+        .map(|module| &module.source)
+        .find(|module| *module.file_path() == diagnostic.location.file_path)
+        .unwrap_or(source)
+}
+
+fn imports(file: &File) -> impl Iterator<Item = &Import> {
+    file.stmts.iter().filter_map(|stmt| match stmt {
+        Stmt::Import(import) => Some(&**import),
+        _ => None,
+    })
+}
+
+struct Loader {
+    /// The directory the imports are found from, an absolute path
+    root: PathBuf,
+    modules: Vec<Module>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Loader {
+    /// Loads the module of `import` and the ones it imports. `stack` holds the name and
+    /// the path of the modules being loaded, the entry file first, and `span` is the
+    /// import of the entry file being followed.
+    fn import(&mut self, import: &Import, span: &Span, stack: &mut Vec<(String, PathBuf)>) {
+        let name = import.name();
+        let mut file_path = self.root.clone();
+        file_path.extend(import.path.iter().map(|ident| &ident.name));
+        file_path.set_extension(MODULE_EXTENSION);
+
+        if let Some(start) = stack.iter().position(|(_, path)| *path == file_path) {
+            let cycle = stack[start..]
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .chain([name.as_str()])
+                .collect::<Vec<_>>()
+                .join(" -> ");
+            self.report(
+                Message::new(Code::ImportCycle).with_arg("cycle", cycle),
+                span,
+            );
+            return;
+        }
+        if self
+            .modules
+            .iter()
+            .any(|module| *module.source.file_path() == file_path)
+        {
+            return;
+        }
+        let (source, ast) = match parse(&file_path) {
+            Ok(module) => module,
+            Err(code) => {
+                let message = Message::new(code)
+                    .with_arg("name", &name)
+                    .with_arg("path", file_path.display());
+                self.report(message, span);
+                return;
+            }
+        };
+
+        stack.push((name.clone(), file_path));
+        for inner in imports(&ast) {
+            self.import(inner, span, stack);
+        }
+        stack.pop();
+        self.modules.push(Module { name, source, ast });
+    }
+
+    fn report(&mut self, message: Message, span: &Span) {
+        self.diagnostics
+            .push(Diagnostic::error(message, span.location()));
+    }
+}
+
+/// Reads and lowers the module at `file_path`, the code of the error to report if it is
+/// missing or has errors; the errors themselves are reported when it is checked.
+///
+/// The module is read like the entry file, with the options of its `funs.toml`: a
+/// module larger than `max_file_size` is invalid, and so is one with bytes that are
+/// not UTF-8 even if `lossy_utf8` let it be read.
+fn parse(file_path: &Path) -> Result<(Source, File), Code> {
+    let source = match read_source(file_path) {
+        Ok(source) if !source.invalid_utf8().is_empty() => return Err(Code::InvalidModule),
+        Ok(source) => source,
+        Err(SourceError::Io { error, .. }) if error.kind() == std::io::ErrorKind::NotFound => {
+            return Err(Code::ModuleNotFound)
+        }
+        Err(_) => return Err(Code::InvalidModule),
+    };
+    let mut lexer = Lexer::new(&source);
+    let tokens = lexer.by_ref().collect::<Vec<_>>();
+    let (tree, diagnostics) = Parser::new(tokens).parse_with_diagnostics();
+    if !lexer.diagnostics().is_empty() || !diagnostics.is_empty() {
+        return Err(Code::InvalidModule);
+    }
+    match lower(&tree) {
+        (ast, errors) if errors.is_empty() => Ok((source, ast)),
+        _ => Err(Code::InvalidModule),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::file_handler::create_tmp_dir;
+
+    fn load_file(path: &str) -> (Vec<Module>, Vec<String>) {
+        let (_, ast) = parse(Path::new(path)).unwrap();
+        let (modules, diagnostics) = load(Path::new(path), &ast);
+        let messages = diagnostics
+            .iter()
+            .map(|diagnostic| format!("{}: {}", diagnostic.location, diagnostic.message))
+            .collect();
+        (modules, messages)
+    }
+
+    #[test]
+    fn test_modules_load() {
+        let (modules, messages) = load_file("testdata/modules/main.fs");
+        assert!(messages.is_empty(), "{:?}", messages);
+        // `util` runs before `geometry.shapes`, which imports it, and is loaded once
+        let names = modules
+            .iter()
+            .map(|module| module.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["util", "geometry.shapes"]);
+        let root = std::path::absolute("testdata/modules").unwrap();
+        assert_eq!(
+            *modules[1].source.file_path(),
+            root.join("geometry").join("shapes.fs")
+        );
+
+        // A module finds its imports from the same root as the entry file
+        let (modules, messages) = load_file("testdata/modules/geometry/shapes.fs");
+        assert!(messages.is_empty(), "{:?}", messages);
+        assert_eq!(modules.len(), 1);
+    }
+
+    #[test]
+    fn test_modules_errors() {
+        let (modules, messages) = load_file("testdata/modules/broken.fs");
+        let missing = std::path::absolute("testdata/modules/missing.fs").unwrap();
+        assert_eq!(
+            messages,
+            vec![
+                format!(
+                    "testdata/modules/broken.fs:1:1: the module `missing` is not found, expected the file {}",
+                    missing.display()
+                ),
+                "testdata/modules/broken.fs:2:1: the imports form a cycle: cycle.a -> cycle.b -> cycle.a"
+                    .to_string(),
+            ]
+        );
+        // The modules of a cycle are loaded, the import closing it is left out
+        let names = modules
+            .iter()
+            .map(|module| module.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["cycle.b", "cycle.a"]);
+
+        let (_, messages) = load_file("testdata/modules/cycle/a.fs");
+        assert_eq!(
+            messages,
+            vec!["testdata/modules/cycle/a.fs:1:1: the imports form a cycle: cycle.a -> cycle.b -> cycle.a"]
+        );
+    }
+
+    #[test]
+    fn test_modules_run() {
+        let path = "testdata/modules/main.fs";
+        let (_, ast) = parse(Path::new(path)).unwrap();
+        let (modules, _) = load(Path::new(path), &ast);
+        let imported = imported(&ast, &modules)
+            .iter()
+            .map(|module| module.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(imported, vec!["util", "geometry.shapes"]);
+
+        let mut interpreter = Interpreter::new(Vec::new());
+        run(&mut interpreter, &modules).unwrap();
+        interpreter.run(&ast).unwrap();
+        assert_eq!(String::from_utf8(interpreter.into_output()).unwrap(), "4\n");
+    }
+
+    /// Modules are read with the `max_file_size` and `lossy_utf8` of the `funs.toml`,
+    /// like the entry file
+    #[test]
+    fn test_modules_read_like_the_entry_file() {
+        let dir = create_tmp_dir("modules_read");
+        let main = dir.join("main.fs");
+        std::fs::write(&main, "import big\nimport latin\n").unwrap();
+        std::fs::write(
+            dir.join("big.fs"),
+            format!("x: str = \"{}\"\n", "a".repeat(64)),
+        )
+        .unwrap();
+        std::fs::write(dir.join("latin.fs"), b"s: str = \"caf\xe9\"\n").unwrap();
+        let codes = || {
+            let (_, ast) = parse(&main).unwrap();
+            let (modules, diagnostics) = load(&main, &ast);
+            let names = modules.into_iter().map(|module| module.name);
+            let codes = diagnostics
+                .iter()
+                .map(|d| d.message.code.as_str().to_string());
+            names.chain(codes).collect::<Vec<_>>()
+        };
+
+        assert_eq!(codes(), ["big", "E0036"]);
+        std::fs::write(
+            dir.join(crate::config::CONFIG_FILE),
+            "[compiler]\nmax_file_size = 32\nlossy_utf8 = true\n",
+        )
+        .unwrap();
+        assert_eq!(codes(), ["E0036", "E0036"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
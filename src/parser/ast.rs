@@ -0,0 +1,394 @@
+//! The typed syntax tree, lowered from the CST by `lower`.
+//!
+//! Unlike the CST it has no trivia (new lines, comments, terminators, parentheses) and
+//! its literals hold values. Every node keeps the `NodeId` of the tree it was lowered
+//! from, so that facts found on the AST can be reported on the CST, and its span.
+
+use super::node_id::{NodeId, Span};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct File {
+    pub stmts: Vec<Stmt>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    Import(Box<Import>),
+    VarDecl(Box<VarDecl>),
+    PatternDecl(Box<PatternDecl>),
+    FunDecl(Box<FunDecl>),
+    Data(Box<DataDecl>),
+    Variant(Box<VariantDecl>),
+    Expr(Box<Expr>),
+}
+
+/// `import a.b`, makes the declarations of the module `a/b.fs` available
+#[derive(Debug, Clone, PartialEq)]
+pub struct Import {
+    pub id: NodeId,
+    /// The names of the path, at least one
+    pub path: Vec<Ident>,
+    pub span: Span,
+}
+
+impl Import {
+    /// The path as written, e.g. `math.trig`
+    pub fn name(&self) -> String {
+        self.path
+            .iter()
+            .map(|ident| ident.name.as_str())
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+/// `name: ty = value`
+#[derive(Debug, Clone, PartialEq)]
+pub struct VarDecl {
+    pub id: NodeId,
+    pub name: Ident,
+    pub ty: Type,
+    pub value: Expr,
+    pub span: Span,
+}
+
+/// `(a, b): ty = value`, declares the names of the pattern
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternDecl {
+    pub id: NodeId,
+    pub pattern: Pattern,
+    pub ty: Type,
+    pub value: Expr,
+    pub span: Span,
+}
+
+/// `name: ty = (params) -> body`
+///
+/// A function whose parameters and body are a hole, e.g. `f: () -> int = ?todo`, has no
+/// parameters and a body made of the hole.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunDecl {
+    pub id: NodeId,
+    /// The attributes written before the function, in order
+    pub attributes: Vec<Attribute>,
+    pub name: Ident,
+    pub ty: Type,
+    pub params: Vec<Ident>,
+    /// The statements of the body, the last one is the result
+    pub body: Vec<Stmt>,
+    pub span: Span,
+}
+
+/// `data Name = { field: ty, ... }`, declares a record type
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataDecl {
+    pub id: NodeId,
+    pub name: Ident,
+    /// The fields in the order they are declared
+    pub fields: Vec<FieldDecl>,
+    pub span: Span,
+}
+
+/// `field: ty`, in a record declaration
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDecl {
+    pub name: Ident,
+    pub ty: Type,
+}
+
+/// `data Name = Case ty... | ...`, declares a variant type and the constructors of its
+/// cases
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantDecl {
+    pub id: NodeId,
+    pub name: Ident,
+    /// The cases in the order they are declared
+    pub cases: Vec<VariantCase>,
+    pub span: Span,
+}
+
+/// `Case ty...`, a case of a variant and the types of its fields
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantCase {
+    pub name: Ident,
+    pub fields: Vec<Type>,
+}
+
+/// `@test`, `@bench` or `@tailrec`, on a top-level function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attribute {
+    /// Run by `funs test`, the function passes unless it fails or returns `false`
+    Test,
+    /// Timed by `funs bench`
+    Bench,
+    /// Its recursive calls must be tail calls, see `tailcall`
+    TailRec,
+}
+
+impl Attribute {
+    pub fn from_name(name: &str) -> Option<Attribute> {
+        match name {
+            "test" => Some(Attribute::Test),
+            "bench" => Some(Attribute::Bench),
+            "tailrec" => Some(Attribute::TailRec),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Attribute::Test => "test",
+            Attribute::Bench => "bench",
+            Attribute::TailRec => "tailrec",
+        }
+    }
+
+    /// Whether a runner calls the function, without arguments
+    pub fn is_runner(&self) -> bool {
+        matches!(self, Attribute::Test | Attribute::Bench)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ident {
+    pub name: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Type {
+    pub id: NodeId,
+    pub kind: TypeKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeKind {
+    /// `int`, `option<int>`
+    Named { name: String, args: Vec<Type> },
+    /// `[int]`
+    List(Box<Type>),
+    /// `(int, str)`, `()` is the empty tuple. A single type in parentheses is that type.
+    Tuple(Vec<Type>),
+    /// `(int, str) -> bool`
+    Function { params: Vec<Type>, ret: Box<Type> },
+}
+
+/// The type as written, e.g. `(int, [str]) -> option<bool>`
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let join = |types: &[Type]| {
+            types
+                .iter()
+                .map(Type::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match &self.kind {
+            TypeKind::Named { name, args } if args.is_empty() => write!(f, "{}", name),
+            TypeKind::Named { name, args } => write!(f, "{}<{}>", name, join(args)),
+            TypeKind::List(ty) => write!(f, "[{}]", ty),
+            TypeKind::Tuple(types) => write!(f, "({})", join(types)),
+            TypeKind::Function { params, ret } => write!(f, "({}) -> {}", join(params), ret),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub id: NodeId,
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    /// The content of the string, without its quotes
+    Str(String),
+    Name(String),
+    /// The hole as written, e.g. `?todo`
+    Hole(String),
+    Unary {
+        op: UnaryOp,
+        expr: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    /// `expr as ty`
+    Cast {
+        expr: Box<Expr>,
+        ty: Type,
+    },
+    /// `(expr : ty)`
+    Ascription {
+        expr: Box<Expr>,
+        ty: Type,
+    },
+    /// `if cond then then_branch else else_branch`
+    If {
+        cond: Box<Expr>,
+        then_branch: Box<Expr>,
+        else_branch: Box<Expr>,
+    },
+    /// `[a, b, ...]`, the elements in order
+    List(Vec<Expr>),
+    /// `(a, b, ...)`, `()` is the empty tuple, the unit value
+    Tuple(Vec<Expr>),
+    /// `{ a = x, b = y, ... }`, the fields in the order they are written
+    Record(Vec<FieldInit>),
+    /// `expr.field`
+    Field {
+        expr: Box<Expr>,
+        field: Ident,
+    },
+    /// `callee arg...`, there is at least one argument, `callee ()` for a function without
+    /// parameters
+    Call {
+        callee: Box<Expr>,
+        args: Vec<Expr>,
+    },
+    /// `match scrutinee | pattern => body ... ;`, there is at least one arm
+    Match {
+        scrutinee: Box<Expr>,
+        arms: Vec<MatchArm>,
+    },
+}
+
+impl ExprKind {
+    /// The kind of expression, e.g. `binary` or `call`
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExprKind::Int(_) => "int",
+            ExprKind::Float(_) => "float",
+            ExprKind::Bool(_) => "bool",
+            ExprKind::Str(_) => "str",
+            ExprKind::Name(_) => "name",
+            ExprKind::Hole(_) => "hole",
+            ExprKind::Unary { .. } => "unary",
+            ExprKind::Binary { .. } => "binary",
+            ExprKind::Cast { .. } => "cast",
+            ExprKind::Ascription { .. } => "ascription",
+            ExprKind::If { .. } => "if",
+            ExprKind::List(_) => "list",
+            ExprKind::Tuple(_) => "tuple",
+            ExprKind::Record(_) => "record",
+            ExprKind::Field { .. } => "field",
+            ExprKind::Call { .. } => "call",
+            ExprKind::Match { .. } => "match",
+        }
+    }
+}
+
+/// `field = value`, in a record
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldInit {
+    pub name: Ident,
+    pub value: Expr,
+}
+
+/// `| pattern => body`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchArm {
+    pub id: NodeId,
+    pub pattern: Pattern,
+    pub body: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub id: NodeId,
+    pub kind: PatternKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatternKind {
+    /// A literal matches an equal value, e.g. `1`, `-2.5` or `"a"`
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+    /// A name matches any value and binds it in the body of its arm
+    Name(String),
+    /// `_` matches any value
+    Wildcard,
+    /// `(a, b, ...)` matches a tuple of as many values, each matching its pattern
+    Tuple(Vec<Pattern>),
+    /// `Case a b ...` matches a value built by the constructor `name`, whose fields match
+    /// the patterns
+    Constructor {
+        name: String,
+        fields: Vec<Pattern>,
+    },
+}
+
+impl Pattern {
+    /// The names bound by the pattern, in source order
+    pub fn names(&self) -> Vec<&str> {
+        match &self.kind {
+            PatternKind::Name(name) => vec![name],
+            PatternKind::Tuple(patterns)
+            | PatternKind::Constructor {
+                fields: patterns, ..
+            } => patterns.iter().flat_map(Pattern::names).collect(),
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    /// `-`
+    Neg,
+}
+
+impl std::fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            UnaryOp::Neg => write!(f, "-"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// `:`, puts an element in front of a list
+    Cons,
+    /// `++`, joins two lists
+    Concat,
+}
+
+impl std::fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let op = match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Eq => "==",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Cons => ":",
+            BinaryOp::Concat => "++",
+        };
+        write!(f, "{}", op)
+    }
+}
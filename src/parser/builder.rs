@@ -0,0 +1,308 @@
+//! Builds trees in Rust instead of parsing them from source text.
+//!
+//! ```text
+//! file()
+//!     .comment("# the answer")
+//!     .var_decl("x", ty_int(), int(42))
+//!     .build()
+//! ```
+//!
+//! The tokens are laid out canonically (one declaration per line, a space after `:`
+//! and around `=`...), so a built tree is equal to the tree parsed from the same
+//! declarations written that way, locations included.
+
+use super::{Child, Tree, TreeKind};
+use crate::lexer::token::{Literal, Token, TokenKind, TokenLocation};
+use std::path::PathBuf;
+
+/// A tree whose tokens have no location yet
+#[derive(Debug, Clone)]
+pub struct Node {
+    kind: TreeKind,
+    children: Vec<NodeChild>,
+}
+
+#[derive(Debug, Clone)]
+enum NodeChild {
+    Node(Node),
+    Token(TokenKind, String),
+}
+
+impl Node {
+    fn new(kind: TreeKind) -> Node {
+        Node {
+            kind,
+            children: Vec::new(),
+        }
+    }
+
+    fn token(mut self, kind: TokenKind, lexeme: &str) -> Node {
+        self.children
+            .push(NodeChild::Token(kind, lexeme.to_string()));
+        self
+    }
+
+    fn node(mut self, node: Node) -> Node {
+        self.children.push(NodeChild::Node(node));
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FileBuilder {
+    file_path: PathBuf,
+    stmts: Vec<Node>,
+}
+
+/// Starts building a file
+pub fn file() -> FileBuilder {
+    FileBuilder {
+        file_path: PathBuf::new(),
+        stmts: Vec::new(),
+    }
+}
+
+impl FileBuilder {
+    /// Sets the path of the file in the locations of the tokens
+    pub fn with_file_path(mut self, file_path: &str) -> FileBuilder {
+        self.file_path = PathBuf::from(file_path);
+        self
+    }
+
+    /// Adds a comment, `text` includes the leading `#`
+    pub fn comment(mut self, text: &str) -> FileBuilder {
+        self.stmts.push(
+            Node::new(TreeKind::Comment)
+                .token(TokenKind::TokenComment, text)
+                .token(TokenKind::TokenNewLine, "\\n"),
+        );
+        self
+    }
+
+    /// Adds `name: ty = expr`
+    pub fn var_decl(mut self, name: &str, ty: Node, expr: Node) -> FileBuilder {
+        self.stmts.push(
+            Node::new(TreeKind::StmtVarDecl)
+                .token(TokenKind::TokenIdentifier, name)
+                .token(TokenKind::TokenColon, ":")
+                .node(ty)
+                .token(TokenKind::TokenAssign, "=")
+                .node(
+                    Node::new(TreeKind::StmtExpr)
+                        .node(expr)
+                        .token(TokenKind::TokenNewLine, "\\n"),
+                ),
+        );
+        self
+    }
+
+    pub fn build(self) -> Tree {
+        let mut layout = Layout {
+            file_path: self.file_path,
+            line: 0,
+            column: 0,
+            previous: None,
+        };
+        let mut children = self
+            .stmts
+            .into_iter()
+            .map(|stmt| Child::Tree(layout.tree(stmt)))
+            .collect::<Vec<_>>();
+        children.push(Child::Token(
+            layout.token(TokenKind::TokenEOF, String::new()),
+        ));
+        Tree {
+            kind: TreeKind::File,
+            children,
+        }
+    }
+}
+
+/// A named type, e.g. `int` or `my_type`
+pub fn ty(name: &str) -> Node {
+    Node::new(TreeKind::TypeExpr).token(TokenKind::TokenIdentifier, name)
+}
+
+pub fn ty_int() -> Node {
+    ty("int")
+}
+
+pub fn ty_float() -> Node {
+    ty("float")
+}
+
+pub fn ty_bool() -> Node {
+    ty("bool")
+}
+
+pub fn ty_str() -> Node {
+    ty("str")
+}
+
+/// A generic type, e.g. `option<int>`
+pub fn ty_generic(name: &str, args: Vec<Node>) -> Node {
+    let mut node = ty(name).token(TokenKind::TokenLess, "<");
+    for (index, arg) in args.into_iter().enumerate() {
+        if index > 0 {
+            node = node.token(TokenKind::TokenComma, ",");
+        }
+        node = node.node(arg);
+    }
+    node.token(TokenKind::TokenGreater, ">")
+}
+
+/// A tuple type, e.g. `(int, str)`
+pub fn ty_tuple(elements: Vec<Node>) -> Node {
+    let mut node = Node::new(TreeKind::TypeExpr).token(TokenKind::TokenOpenParen, "(");
+    for (index, element) in elements.into_iter().enumerate() {
+        if index > 0 {
+            node = node.token(TokenKind::TokenComma, ",");
+        }
+        node = node.node(element);
+    }
+    node.token(TokenKind::TokenCloseParen, ")")
+}
+
+fn literal(literal: Literal, lexeme: &str) -> Node {
+    Node::new(TreeKind::ExprLiteral).token(TokenKind::TokenLiteral(literal), lexeme)
+}
+
+pub fn int(value: u64) -> Node {
+    literal(Literal::Int, &value.to_string())
+}
+
+/// A float literal, always written with a `.` (e.g. `1.0`)
+pub fn float(value: f64) -> Node {
+    literal(Literal::Float, &format!("{:?}", value))
+}
+
+pub fn bool(value: bool) -> Node {
+    literal(Literal::Bool, &value.to_string())
+}
+
+/// A string literal, `value` is written between double quotes as is
+pub fn str(value: &str) -> Node {
+    literal(Literal::Str, &format!("\"{}\"", value))
+}
+
+/// A hole, e.g. `?todo` for `hole("todo")`
+pub fn hole(name: &str) -> Node {
+    Node::new(TreeKind::ExprHole).token(TokenKind::TokenHole, &format!("?{}", name))
+}
+
+/// Assigns to each token the location it has in the canonical source
+struct Layout {
+    file_path: PathBuf,
+    line: usize,
+    column: usize,
+    previous: Option<TokenKind>,
+}
+
+impl Layout {
+    fn tree(&mut self, node: Node) -> Tree {
+        let children = node
+            .children
+            .into_iter()
+            .map(|child| match child {
+                NodeChild::Node(node) => Child::Tree(self.tree(node)),
+                NodeChild::Token(kind, lexeme) => Child::Token(self.token(kind, lexeme)),
+            })
+            .collect();
+        Tree {
+            kind: node.kind,
+            children,
+        }
+    }
+
+    fn token(&mut self, kind: TokenKind, lexeme: String) -> Token {
+        if self.is_spaced(&kind) {
+            self.column += 1;
+        }
+        // The new line token is escaped but takes no room
+        let width = match kind {
+            TokenKind::TokenNewLine => 0,
+            _ => lexeme.chars().count(),
+        };
+        let location = TokenLocation::new(
+            self.file_path.clone(),
+            self.line,
+            self.column,
+            self.column + width,
+        );
+        self.column += width;
+        if kind == TokenKind::TokenNewLine {
+            self.line += 1;
+            self.column = 0;
+            self.previous = None;
+        } else {
+            self.previous = Some(kind.clone());
+        }
+        Token::new(kind, lexeme, location)
+    }
+
+    /// Whether a space separates the previous token from a token of this kind
+    fn is_spaced(&self, kind: &TokenKind) -> bool {
+        match &self.previous {
+            None => false,
+            Some(TokenKind::TokenOpenParen | TokenKind::TokenLess) => false,
+            Some(_) => !matches!(
+                kind,
+                TokenKind::TokenColon
+                    | TokenKind::TokenComma
+                    | TokenKind::TokenCloseParen
+                    | TokenKind::TokenLess
+                    | TokenKind::TokenGreater
+                    | TokenKind::TokenNewLine
+                    | TokenKind::TokenEOF
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer::Lexer, parser::Parser, source::Source};
+    use std::path::Path;
+
+    fn parse(content: &str) -> Tree {
+        Parser::new(Lexer::new(&Source::from(content.to_string()))).parse()
+    }
+
+    #[test]
+    fn test_builder_matches_parser() {
+        let built = file()
+            .comment("# the answer")
+            .var_decl("x", ty_int(), int(42))
+            .var_decl("y", ty_float(), float(1.0))
+            .var_decl("z", ty_generic("option", vec![ty_bool()]), bool(true))
+            .var_decl(
+                "t",
+                ty_generic("option", vec![ty_tuple(vec![ty_str(), ty_int()])]),
+                str("👾"),
+            )
+            .var_decl("u", ty("unit"), hole("todo"))
+            .build();
+        let parsed = parse(
+            "# the answer\n\
+             x: int = 42\n\
+             y: float = 1.0\n\
+             z: option<bool> = true\n\
+             t: option<(str, int)> = \"👾\"\n\
+             u: unit = ?todo\n",
+        );
+        assert_eq!(built, parsed);
+    }
+
+    #[test]
+    fn test_builder_file_path() {
+        let tree = file()
+            .with_file_path("main.fs")
+            .var_decl("x", ty_int(), int(1))
+            .build();
+        assert!(tree
+            .tokens()
+            .iter()
+            .all(|token| token.location.file_path == Path::new("main.fs")));
+    }
+}
@@ -0,0 +1,1043 @@
+//! Lowers the CST built by the parser into the typed AST of `ast`.
+//!
+//! The lowering never fails: a tree it can not make sense of (an `ErrorTree`, or a
+//! declaration missing a part) is reported and left out, so a file with parse errors
+//! still gets an AST of what could be parsed.
+
+use super::ast::{
+    Attribute, BinaryOp, DataDecl, Expr, ExprKind, FieldDecl, FieldInit, File, FunDecl, Ident,
+    Import, MatchArm, Pattern, PatternDecl, PatternKind, Stmt, Type, TypeKind, UnaryOp, VarDecl,
+    VariantCase, VariantDecl,
+};
+use super::node_id::{NodeId, NodeMap, Span};
+use super::{is_constructor, Child, Tree, TreeKind};
+use crate::diagnostics::messages::{Code, Message};
+use crate::diagnostics::Diagnostic;
+use crate::lexer::token::{strip_numeric_suffix, Literal, Token, TokenKind, TokenLocation};
+use std::path::PathBuf;
+
+/// Lowers `file`, returning its AST along with the errors found, in source order: a tree
+/// that could not be lowered, or a literal whose value is invalid.
+pub fn lower(file: &Tree) -> (File, Vec<Diagnostic>) {
+    let mut lowerer = Lowerer {
+        nodes: NodeMap::new(file),
+        errors: Vec::new(),
+    };
+    let stmts = lowerer.stmts(file);
+    (File { stmts }, lowerer.errors)
+}
+
+struct Lowerer<'a> {
+    nodes: NodeMap<'a>,
+    errors: Vec<Diagnostic>,
+}
+
+impl<'a> Lowerer<'a> {
+    /// The statements among the children of `tree`, comments are left out
+    fn stmts(&mut self, tree: &'a Tree) -> Vec<Stmt> {
+        tree.child_trees()
+            .filter(|child| *child.kind() != TreeKind::Comment)
+            .filter_map(|child| self.stmt(child))
+            .collect()
+    }
+
+    fn stmt(&mut self, tree: &'a Tree) -> Option<Stmt> {
+        match tree.kind() {
+            TreeKind::StmtImport => self
+                .import(tree)
+                .map(|import| Stmt::Import(Box::new(import))),
+            TreeKind::StmtVarDecl if child(tree, TreeKind::PatTuple).is_some() => self
+                .pattern_decl(tree)
+                .map(|decl| Stmt::PatternDecl(Box::new(decl))),
+            TreeKind::StmtVarDecl => self
+                .var_decl(tree)
+                .map(|decl| Stmt::VarDecl(Box::new(decl))),
+            TreeKind::StmtFunDecl => self
+                .fun_decl(tree)
+                .map(|decl| Stmt::FunDecl(Box::new(decl))),
+            TreeKind::StmtData => self.data(tree).map(|decl| Stmt::Data(Box::new(decl))),
+            TreeKind::DeclVariant => self.variant(tree).map(|decl| Stmt::Variant(Box::new(decl))),
+            TreeKind::StmtExpr => self.stmt_expr(tree).map(|expr| Stmt::Expr(Box::new(expr))),
+            _ => self.malformed(tree),
+        }
+    }
+
+    // StmtImport = "import" Ident ("." Ident)*
+    fn import(&mut self, tree: &'a Tree) -> Option<Import> {
+        let (id, span) = self.locate(tree)?;
+        let path = tree
+            .tokens()
+            .into_iter()
+            .filter(|token| token.kind == TokenKind::TokenIdentifier)
+            .map(Ident::from)
+            .collect::<Vec<_>>();
+        if path.is_empty() {
+            return self.malformed(tree);
+        }
+        Some(Import { id, path, span })
+    }
+
+    // StmtData = "data" Ident "=" "{" FieldDecl* "}"
+    fn data(&mut self, tree: &'a Tree) -> Option<DataDecl> {
+        let (id, span) = self.locate(tree)?;
+        let Some(name) = ident(tree) else {
+            return self.malformed(tree);
+        };
+        let mut fields = Vec::new();
+        for field in tree.child_trees() {
+            let (Some(name), Some(ty)) = (ident(field), child(field, TreeKind::TypeExpr)) else {
+                return self.malformed(field);
+            };
+            fields.push(FieldDecl {
+                name,
+                ty: self.ty(ty)?,
+            });
+        }
+        self.check_fields(fields.iter().map(|field| &field.name));
+        Some(DataDecl {
+            id,
+            name,
+            fields,
+            span,
+        })
+    }
+
+    // StmtVarDecl = Ident ":" TypeExpr "=" StmtExpr
+    fn var_decl(&mut self, tree: &'a Tree) -> Option<VarDecl> {
+        let (id, span) = self.locate(tree)?;
+        let (Some(name), Some(ty), Some(value)) = (
+            ident(tree),
+            child(tree, TreeKind::TypeExpr),
+            child(tree, TreeKind::StmtExpr),
+        ) else {
+            return self.malformed(tree);
+        };
+        Some(VarDecl {
+            id,
+            name,
+            ty: self.ty(ty)?,
+            value: self.stmt_expr(value)?,
+            span,
+        })
+    }
+
+    // StmtVarDecl = PatTuple ":" TypeExpr "=" StmtExpr
+    fn pattern_decl(&mut self, tree: &'a Tree) -> Option<PatternDecl> {
+        let (id, span) = self.locate(tree)?;
+        let (Some(pattern), Some(ty), Some(value)) = (
+            child(tree, TreeKind::PatTuple),
+            child(tree, TreeKind::TypeExpr),
+            child(tree, TreeKind::StmtExpr),
+        ) else {
+            return self.malformed(tree);
+        };
+        Some(PatternDecl {
+            id,
+            pattern: self.pattern(pattern)?,
+            ty: self.ty(ty)?,
+            value: self.stmt_expr(value)?,
+            span,
+        })
+    }
+
+    // StmtFunDecl = Attribute* Ident ":" TypeExpr "=" (ParamList "->" (StmtExpr | Block) | StmtExpr)
+    fn fun_decl(&mut self, tree: &'a Tree) -> Option<FunDecl> {
+        let (id, span) = self.locate(tree)?;
+        let (Some(name), Some(ty)) = (ident(tree), child(tree, TreeKind::TypeExpr)) else {
+            return self.malformed(tree);
+        };
+        let attributes = tree
+            .child_trees()
+            .filter(|child| *child.kind() == TreeKind::Attribute)
+            .filter_map(|attribute| self.attribute(attribute))
+            .collect::<Vec<_>>();
+        let ty = self.ty(ty)?;
+        let params = child(tree, TreeKind::ParamList).map(|params| {
+            params
+                .children()
+                .iter()
+                .filter_map(|child| match child {
+                    Child::Token(token) if token.kind == TokenKind::TokenIdentifier => {
+                        Some(Ident::from(token))
+                    }
+                    _ => None,
+                })
+                .collect()
+        });
+        let body = match (
+            child(tree, TreeKind::Block),
+            child(tree, TreeKind::StmtExpr),
+        ) {
+            (Some(block), _) => self.stmts(block),
+            (None, Some(expr)) => vec![Stmt::Expr(Box::new(self.stmt_expr(expr)?))],
+            (None, None) => return self.malformed(tree),
+        };
+        let params: Vec<Ident> = params.unwrap_or_default();
+        // The runners of `funs test` and `funs bench` call them without arguments
+        let runner = attributes.iter().find(|attribute| attribute.is_runner());
+        if let (Some(attribute), false) = (runner, params.is_empty()) {
+            let message =
+                Message::new(Code::AttributeWithParams).with_arg("name", attribute.name());
+            self.errors
+                .push(Diagnostic::error(message, name.span.start.clone()));
+        }
+        Some(FunDecl {
+            id,
+            attributes,
+            name,
+            ty,
+            params,
+            body,
+            span,
+        })
+    }
+
+    // Attribute = "@" Ident
+    fn attribute(&mut self, tree: &'a Tree) -> Option<Attribute> {
+        let Some(name) = tree.declared_name() else {
+            return self.malformed(tree);
+        };
+        match Attribute::from_name(&name.lexeme) {
+            Some(attribute) => Some(attribute),
+            None => self.error(
+                name,
+                Message::new(Code::UnknownAttribute).with_arg("name", &name.lexeme),
+            ),
+        }
+    }
+
+    // StmtExpr = Expr Terminator
+    fn stmt_expr(&mut self, tree: &'a Tree) -> Option<Expr> {
+        match tree.child_trees().next() {
+            Some(expr) => self.expr(expr),
+            None => self.malformed(tree),
+        }
+    }
+
+    fn expr(&mut self, tree: &'a Tree) -> Option<Expr> {
+        let (id, span) = self.locate(tree)?;
+        let mut operands = tree.child_trees();
+        let kind = match tree.kind() {
+            TreeKind::ExprLiteral => self.literal(tree)?,
+            TreeKind::ExprName => ExprKind::Name(first_token(tree)?.lexeme.clone()),
+            TreeKind::ExprHole => ExprKind::Hole(first_token(tree)?.lexeme.clone()),
+            // Parentheses only group, the AST keeps the expression inside
+            TreeKind::ExprParen => return self.expr(operands.next()?),
+            TreeKind::ExprUnary => {
+                let expr = self.operand(tree, operands.next())?;
+                ExprKind::Unary {
+                    op: UnaryOp::Neg,
+                    expr: Box::new(expr),
+                }
+            }
+            // A cons and a concatenation are binary operations of the AST
+            TreeKind::ExprBinary | TreeKind::ExprCons | TreeKind::ExprConcat => {
+                let Some(op) = operator(tree).and_then(|token| binary_op(&token.kind)) else {
+                    return self.malformed(tree);
+                };
+                let lhs = self.operand(tree, operands.next())?;
+                let rhs = self.operand(tree, operands.next())?;
+                ExprKind::Binary {
+                    op,
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                }
+            }
+            TreeKind::ExprCast | TreeKind::ExprAscription => {
+                let expr = self.operand(tree, operands.next())?;
+                let Some(ty) = operands.next() else {
+                    return self.malformed(tree);
+                };
+                let ty = self.ty(ty)?;
+                let expr = Box::new(expr);
+                match tree.kind() {
+                    TreeKind::ExprCast => ExprKind::Cast { expr, ty },
+                    _ => ExprKind::Ascription { expr, ty },
+                }
+            }
+            TreeKind::ExprIf => {
+                let cond = self.operand(tree, operands.next())?;
+                let then_branch = self.operand(tree, operands.next())?;
+                let else_branch = self.operand(tree, operands.next())?;
+                ExprKind::If {
+                    cond: Box::new(cond),
+                    then_branch: Box::new(then_branch),
+                    else_branch: Box::new(else_branch),
+                }
+            }
+            TreeKind::ExprList => ExprKind::List(
+                operands
+                    .map(|element| self.expr(element))
+                    .collect::<Option<Vec<_>>>()?,
+            ),
+            TreeKind::ExprTuple => ExprKind::Tuple(
+                operands
+                    .map(|element| self.expr(element))
+                    .collect::<Option<Vec<_>>>()?,
+            ),
+            TreeKind::ExprRecord => {
+                let mut fields = Vec::new();
+                for field in operands {
+                    let (Some(name), Some(value)) = (ident(field), field.child_trees().next())
+                    else {
+                        return self.malformed(field);
+                    };
+                    fields.push(FieldInit {
+                        name,
+                        value: self.expr(value)?,
+                    });
+                }
+                self.check_fields(fields.iter().map(|field| &field.name));
+                ExprKind::Record(fields)
+            }
+            TreeKind::ExprField => {
+                let expr = self.operand(tree, operands.next())?;
+                let Some(field) = tree.children().iter().find_map(|child| match child {
+                    Child::Token(token) if token.kind == TokenKind::TokenIdentifier => {
+                        Some(Ident::from(token))
+                    }
+                    _ => None,
+                }) else {
+                    return self.malformed(tree);
+                };
+                ExprKind::Field {
+                    expr: Box::new(expr),
+                    field,
+                }
+            }
+            TreeKind::ExprCall => {
+                let callee = self.operand(tree, operands.next())?;
+                let args = operands
+                    .map(|arg| self.expr(arg))
+                    .collect::<Option<Vec<_>>>()?;
+                if args.is_empty() {
+                    return self.malformed(tree);
+                }
+                ExprKind::Call {
+                    callee: Box::new(callee),
+                    args,
+                }
+            }
+            TreeKind::ExprMatch => {
+                let scrutinee = self.operand(tree, operands.next())?;
+                let arms = operands
+                    .map(|arm| self.match_arm(arm))
+                    .collect::<Option<Vec<_>>>()?;
+                if arms.is_empty() {
+                    return self.malformed(tree);
+                }
+                ExprKind::Match {
+                    scrutinee: Box::new(scrutinee),
+                    arms,
+                }
+            }
+            _ => return self.malformed(tree),
+        };
+        Some(Expr { id, kind, span })
+    }
+
+    // MatchArm = "|" Pattern "=>" Expr
+    fn match_arm(&mut self, tree: &'a Tree) -> Option<MatchArm> {
+        let (id, span) = self.locate(tree)?;
+        let mut children = tree.child_trees();
+        let (Some(pattern), Some(body)) = (children.next(), children.next()) else {
+            return self.malformed(tree);
+        };
+        Some(MatchArm {
+            id,
+            pattern: self.pattern(pattern)?,
+            body: self.expr(body)?,
+            span,
+        })
+    }
+
+    // Pattern = PatLiteral | PatName | PatWildcard | PatTuple | PatConstructor
+    fn pattern(&mut self, tree: &'a Tree) -> Option<Pattern> {
+        let (id, span) = self.locate(tree)?;
+        let kind = match tree.kind() {
+            TreeKind::PatLiteral => {
+                let tokens = tree.tokens();
+                let (negative, token) = match tokens.as_slice() {
+                    [token] => (false, *token),
+                    [minus, token] if minus.kind == TokenKind::TokenMinus => (true, *token),
+                    _ => return self.malformed(tree),
+                };
+                // The parser only accepts a `-` before a number
+                match self.literal_token(tree, token)? {
+                    ExprKind::Int(value) => PatternKind::Int(if negative { -value } else { value }),
+                    ExprKind::Float(value) => {
+                        PatternKind::Float(if negative { -value } else { value })
+                    }
+                    ExprKind::Bool(value) => PatternKind::Bool(value),
+                    ExprKind::Str(value) => PatternKind::Str(value),
+                    _ => return self.malformed(tree),
+                }
+            }
+            TreeKind::PatName => PatternKind::Name(first_token(tree)?.lexeme.clone()),
+            TreeKind::PatConstructor => PatternKind::Constructor {
+                name: first_token(tree)?.lexeme.clone(),
+                fields: tree
+                    .child_trees()
+                    .map(|pattern| self.pattern(pattern))
+                    .collect::<Option<Vec<_>>>()?,
+            },
+            TreeKind::PatWildcard => PatternKind::Wildcard,
+            TreeKind::PatTuple => {
+                let mut patterns = tree
+                    .child_trees()
+                    .map(|pattern| self.pattern(pattern))
+                    .collect::<Option<Vec<_>>>()?;
+                // Without a `,` the parentheses only group
+                let comma = tree.children().iter().any(|child| {
+                    matches!(child, Child::Token(token) if token.kind == TokenKind::TokenComma)
+                });
+                match (patterns.len(), comma) {
+                    (1, false) => return patterns.pop(),
+                    _ => PatternKind::Tuple(patterns),
+                }
+            }
+            _ => return self.malformed(tree),
+        };
+        Some(Pattern { id, kind, span })
+    }
+
+    // DeclVariant = "data" Ident "=" VariantCase+
+    // VariantCase = Ident TypeExpr*
+    fn variant(&mut self, tree: &'a Tree) -> Option<VariantDecl> {
+        let (id, span) = self.locate(tree)?;
+        let Some(name) = ident(tree) else {
+            return self.malformed(tree);
+        };
+        let mut cases = Vec::new();
+        for case in tree.child_trees() {
+            let Some(name) = ident(case) else {
+                return self.malformed(case);
+            };
+            // Patterns tell constructors from names by their first letter
+            if !is_constructor(&name.name) {
+                let message = Message::new(Code::LowercaseConstructor).with_arg("name", &name.name);
+                self.errors
+                    .push(Diagnostic::error(message, name.span.start.clone()));
+            }
+            let fields = case
+                .child_trees()
+                .map(|ty| self.ty(ty))
+                .collect::<Option<Vec<_>>>()?;
+            cases.push(VariantCase { name, fields });
+        }
+        Some(VariantDecl {
+            id,
+            name,
+            cases,
+            span,
+        })
+    }
+
+    /// Reports the fields of a record named more than once, the record is still lowered
+    fn check_fields<'b>(&mut self, names: impl Iterator<Item = &'b Ident>) {
+        let mut seen = Vec::new();
+        for name in names {
+            if seen.contains(&&name.name) {
+                let message = Message::new(Code::DuplicateField).with_arg("field", &name.name);
+                self.errors
+                    .push(Diagnostic::error(message, name.span.start.clone()));
+            } else {
+                seen.push(&name.name);
+            }
+        }
+    }
+
+    /// Lowers the operand of `tree`, which is malformed without one
+    fn operand(&mut self, tree: &'a Tree, operand: Option<&'a Tree>) -> Option<Expr> {
+        match operand {
+            Some(operand) => self.expr(operand),
+            None => self.malformed(tree),
+        }
+    }
+
+    fn literal(&mut self, tree: &'a Tree) -> Option<ExprKind> {
+        let token = first_token(tree)?;
+        self.literal_token(tree, token)
+    }
+
+    /// The value of the literal `token` of `tree`
+    fn literal_token(&mut self, tree: &'a Tree, token: &'a Token) -> Option<ExprKind> {
+        let lexeme = token.lexeme.as_str();
+        let kind = match &token.kind {
+            TokenKind::TokenLiteral(Literal::Int) => match strip_numeric_suffix(lexeme).parse() {
+                Ok(value) => ExprKind::Int(value),
+                Err(e) => {
+                    let code = match e.kind() {
+                        std::num::IntErrorKind::PosOverflow => Code::IntegerOverflow,
+                        _ => Code::InvalidNumber,
+                    };
+                    return self.error(token, Message::new(code).with_arg("literal", lexeme));
+                }
+            },
+            TokenKind::TokenLiteral(Literal::Float) => match strip_numeric_suffix(lexeme).parse() {
+                Ok(value) => ExprKind::Float(value),
+                Err(_) => {
+                    let message = Message::new(Code::InvalidNumber).with_arg("literal", lexeme);
+                    return self.error(token, message);
+                }
+            },
+            TokenKind::TokenLiteral(Literal::Str) => {
+                ExprKind::Str(lexeme.trim_matches('"').to_string())
+            }
+            // `True` and `False` are deprecated spellings, see `migrate`
+            TokenKind::TokenLiteral(Literal::Bool) | TokenKind::TokenIdentifier => {
+                ExprKind::Bool(lexeme.eq_ignore_ascii_case("true"))
+            }
+            _ => return self.malformed(tree),
+        };
+        Some(kind)
+    }
+
+    // TypeExpr =
+    //   Ident ("<" TypeExpr ("," TypeExpr)* ">")?
+    // | "[" TypeExpr "]"
+    // | "(" (TypeExpr ("," TypeExpr)*)? ")" ("->" TypeExpr)?
+    fn ty(&mut self, tree: &'a Tree) -> Option<Type> {
+        let (id, span) = self.locate(tree)?;
+        let mut types = Vec::new();
+        for child in tree.child_trees() {
+            types.push(self.ty(child)?);
+        }
+        let has_token = |kind: TokenKind| {
+            tree.children()
+                .iter()
+                .any(|child| matches!(child, Child::Token(token) if token.kind == kind))
+        };
+        let kind = match first_token(tree).map(|token| &token.kind) {
+            Some(TokenKind::TokenIdentifier) => TypeKind::Named {
+                name: first_token(tree)?.lexeme.clone(),
+                args: types,
+            },
+            Some(TokenKind::TokenOpenBracket) if types.len() == 1 => {
+                TypeKind::List(Box::new(types.remove(0)))
+            }
+            Some(TokenKind::TokenOpenParen) if has_token(TokenKind::TokenRightArrow) => {
+                let Some(ret) = types.pop() else {
+                    return self.malformed(tree);
+                };
+                TypeKind::Function {
+                    params: types,
+                    ret: Box::new(ret),
+                }
+            }
+            Some(TokenKind::TokenOpenParen)
+                if types.len() == 1 && !has_token(TokenKind::TokenComma) =>
+            {
+                return types.pop();
+            }
+            Some(TokenKind::TokenOpenParen) => TypeKind::Tuple(types),
+            _ => return self.malformed(tree),
+        };
+        Some(Type { id, kind, span })
+    }
+
+    /// The id and the span of `tree`, a tree without tokens is malformed
+    fn locate(&mut self, tree: &'a Tree) -> Option<(NodeId, Span)> {
+        let id = self.nodes.id(tree)?;
+        match self.nodes.span(id) {
+            Some(span) => Some((id, span.clone())),
+            None => self.malformed(tree),
+        }
+    }
+
+    fn malformed<T>(&mut self, tree: &Tree) -> Option<T> {
+        let message =
+            Message::new(Code::MalformedTree).with_arg("kind", format!("{:?}", tree.kind()));
+        let location = self.location(tree);
+        self.errors.push(Diagnostic::error(message, location));
+        None
+    }
+
+    fn error<T>(&mut self, token: &Token, message: Message) -> Option<T> {
+        self.errors
+            .push(Diagnostic::error(message, token.location.clone()));
+        None
+    }
+
+    /// Where `tree` starts, or its closest ancestor with tokens for a tree without any
+    fn location(&self, tree: &Tree) -> TokenLocation {
+        let mut id = self.nodes.id(tree);
+        while let Some(current) = id {
+            if let Some(span) = self.nodes.span(current) {
+                return span.start.clone();
+            }
+            id = self.nodes.parent(current);
+        }
+        // A file always has its `TokenEOF`, unless it was built by hand
+        TokenLocation::new(PathBuf::new(), 0, 0, 0)
+    }
+}
+
+impl From<&Token> for Ident {
+    fn from(token: &Token) -> Ident {
+        Ident {
+            name: token.lexeme.clone(),
+            span: Span {
+                start: token.location.clone(),
+                end: token.location.clone(),
+            },
+        }
+    }
+}
+
+/// The first child tree of `tree` of kind `kind`
+fn child(tree: &Tree, kind: TreeKind) -> Option<&Tree> {
+    tree.child_trees().find(|child| *child.kind() == kind)
+}
+
+fn first_token(tree: &Tree) -> Option<&Token> {
+    tree.tokens().first().copied()
+}
+
+/// The name of a declaration
+fn ident(tree: &Tree) -> Option<Ident> {
+    tree.declared_name().map(Ident::from)
+}
+
+/// The operator of an expression, its only token that is not in an operand
+fn operator(tree: &Tree) -> Option<&Token> {
+    tree.children().iter().find_map(|child| match child {
+        Child::Token(token) => Some(token),
+        Child::Tree(_) => None,
+    })
+}
+
+fn binary_op(kind: &TokenKind) -> Option<BinaryOp> {
+    let op = match kind {
+        TokenKind::TokenPlus => BinaryOp::Add,
+        TokenKind::TokenMinus => BinaryOp::Sub,
+        TokenKind::TokenStar => BinaryOp::Mul,
+        TokenKind::TokenSlash => BinaryOp::Div,
+        TokenKind::TokenEqualEqual => BinaryOp::Eq,
+        TokenKind::TokenLess => BinaryOp::Lt,
+        TokenKind::TokenLessEqual => BinaryOp::Le,
+        TokenKind::TokenGreater => BinaryOp::Gt,
+        TokenKind::TokenGreaterEqual => BinaryOp::Ge,
+        TokenKind::TokenColon => BinaryOp::Cons,
+        TokenKind::TokenPlusPlus => BinaryOp::Concat,
+        _ => return None,
+    };
+    Some(op)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::snippet::parse_snippet;
+    use crate::parser::Parser;
+    use crate::source::Source;
+
+    fn lower_snippet(content: &str) -> File {
+        let (file, errors) = lower(&parse_snippet(content).unwrap());
+        assert!(errors.is_empty(), "{:?}", errors);
+        file
+    }
+
+    fn value(stmt: &Stmt) -> &ExprKind {
+        match stmt {
+            Stmt::VarDecl(decl) => &decl.value.kind,
+            stmt => panic!("not a variable: {:?}", stmt),
+        }
+    }
+
+    #[test]
+    fn test_lower_literals() {
+        let file = lower_snippet(
+            "a: int = 42_int\nb: float = 2.5f\nc: float = 3f\nd: bool = True\ne: str = \"hi there\"\nf: int = ?todo\n",
+        );
+        let values = file.stmts.iter().map(value).cloned().collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            vec![
+                ExprKind::Int(42),
+                ExprKind::Float(2.5),
+                ExprKind::Float(3.0),
+                ExprKind::Bool(true),
+                ExprKind::Str("hi there".to_string()),
+                ExprKind::Hole("?todo".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lower_expressions() {
+        let file = lower_snippet("x: float = -(1 + 2) * 3 as float\n");
+        let Stmt::VarDecl(decl) = &file.stmts[0] else {
+            panic!("{:?}", file.stmts[0]);
+        };
+        assert_eq!(decl.name.name, "x");
+        assert_eq!((decl.span.start.column_start, decl.span.end.line), (0, 0));
+        let ExprKind::Binary { op, lhs, rhs } = &decl.value.kind else {
+            panic!("{:?}", decl.value);
+        };
+        assert_eq!(*op, BinaryOp::Mul);
+        let ExprKind::Unary { expr, .. } = &lhs.kind else {
+            panic!("{:?}", lhs);
+        };
+        assert!(matches!(
+            expr.kind,
+            ExprKind::Binary {
+                op: BinaryOp::Add,
+                ..
+            }
+        ));
+        let ExprKind::Cast { ty, .. } = &rhs.kind else {
+            panic!("{:?}", rhs);
+        };
+        assert_eq!(
+            ty.kind,
+            TypeKind::Named {
+                name: "float".to_string(),
+                args: vec![]
+            }
+        );
+    }
+
+    #[test]
+    fn test_lower_lists() {
+        let file = lower_snippet("xs: [int] = 0 : [1, 2] ++ []\n");
+        let ExprKind::Binary { op, lhs, rhs } = value(&file.stmts[0]) else {
+            panic!("{:?}", file.stmts[0]);
+        };
+        assert_eq!(*op, BinaryOp::Cons);
+        assert_eq!(lhs.kind, ExprKind::Int(0));
+        let ExprKind::Binary { op, lhs, rhs } = &rhs.kind else {
+            panic!("{:?}", rhs);
+        };
+        assert_eq!(*op, BinaryOp::Concat);
+        let ExprKind::List(elements) = &lhs.kind else {
+            panic!("{:?}", lhs);
+        };
+        let elements = elements.iter().map(|e| e.kind.clone()).collect::<Vec<_>>();
+        assert_eq!(elements, vec![ExprKind::Int(1), ExprKind::Int(2)]);
+        assert_eq!(rhs.kind, ExprKind::List(vec![]));
+    }
+
+    #[test]
+    fn test_lower_tuples() {
+        let file = lower_snippet(
+            "t: (int, str) = (1, \"a\")\nu: () = ()\n(a, (b, _)): (int, (int, int)) = (1, ((2), 3))\n",
+        );
+        let ExprKind::Tuple(elements) = value(&file.stmts[0]) else {
+            panic!("{:?}", file.stmts[0]);
+        };
+        let elements = elements.iter().map(|e| e.kind.clone()).collect::<Vec<_>>();
+        assert_eq!(
+            elements,
+            vec![ExprKind::Int(1), ExprKind::Str("a".to_string())]
+        );
+        assert_eq!(*value(&file.stmts[1]), ExprKind::Tuple(vec![]));
+
+        let Stmt::PatternDecl(decl) = &file.stmts[2] else {
+            panic!("{:?}", file.stmts[2]);
+        };
+        assert_eq!(decl.pattern.names(), vec!["a", "b"]);
+        let PatternKind::Tuple(patterns) = &decl.pattern.kind else {
+            panic!("{:?}", decl.pattern);
+        };
+        assert_eq!(patterns[0].kind, PatternKind::Name("a".to_string()));
+        assert!(matches!(&patterns[1].kind, PatternKind::Tuple(inner) if inner.len() == 2));
+        assert!(matches!(decl.ty.kind, TypeKind::Tuple(_)));
+
+        // Parentheses without a `,` only group, in patterns too
+        let file = lower_snippet("x: int = match 1 | (n) => n | () => 0 ;\n");
+        let ExprKind::Match { arms, .. } = value(&file.stmts[0]) else {
+            panic!("{:?}", file.stmts[0]);
+        };
+        assert_eq!(arms[0].pattern.kind, PatternKind::Name("n".to_string()));
+        assert_eq!(arms[1].pattern.kind, PatternKind::Tuple(vec![]));
+    }
+
+    #[test]
+    fn test_lower_records() {
+        let file = lower_snippet(
+            "data Point = { x: int, y: [int] }\np: Point = { y = [], x = 1 }\nz: int = p.x\n",
+        );
+        let Stmt::Data(decl) = &file.stmts[0] else {
+            panic!("{:?}", file.stmts[0]);
+        };
+        assert_eq!(decl.name.name, "Point");
+        let fields = decl
+            .fields
+            .iter()
+            .map(|field| (field.name.name.as_str(), field.ty.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            vec![("x", "int".to_string()), ("y", "[int]".to_string())]
+        );
+
+        let ExprKind::Record(fields) = value(&file.stmts[1]) else {
+            panic!("{:?}", file.stmts[1]);
+        };
+        let fields = fields
+            .iter()
+            .map(|field| (field.name.name.as_str(), field.value.kind.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            vec![("y", ExprKind::List(vec![])), ("x", ExprKind::Int(1))]
+        );
+
+        let ExprKind::Field { expr, field } = value(&file.stmts[2]) else {
+            panic!("{:?}", file.stmts[2]);
+        };
+        assert_eq!(expr.kind, ExprKind::Name("p".to_string()));
+        assert_eq!(field.name, "x");
+
+        // A field named twice is reported, the record is still lowered
+        let tree =
+            parse_snippet("data R = { a: int, a: str }\nr: R = { a = 1, b = 2, a = 3 }\n").unwrap();
+        let (file, errors) = lower(&tree);
+        assert_eq!(file.stmts.len(), 2);
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec![
+                "<snippet>:1:20: error: the field `a` appears more than once",
+                "<snippet>:2:24: error: the field `a` appears more than once",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lower_match() {
+        let file = lower_snippet(
+            "x: str = match n\n| -2 => \"a\"\n| 1.5 => \"b\"\n| false => \"c\"\n| \"s\" => \"d\"\n| m => \"e\"\n| _ => \"f\"\n;\n",
+        );
+        let ExprKind::Match { scrutinee, arms } = value(&file.stmts[0]) else {
+            panic!("{:?}", file.stmts[0]);
+        };
+        assert_eq!(scrutinee.kind, ExprKind::Name("n".to_string()));
+        let patterns = arms
+            .iter()
+            .map(|arm| arm.pattern.kind.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            patterns,
+            vec![
+                PatternKind::Int(-2),
+                PatternKind::Float(1.5),
+                PatternKind::Bool(false),
+                PatternKind::Str("s".to_string()),
+                PatternKind::Name("m".to_string()),
+                PatternKind::Wildcard,
+            ]
+        );
+        assert_eq!(arms[5].body.kind, ExprKind::Str("f".to_string()));
+        assert_eq!((arms[1].span.start.line, arms[1].span.end.line), (2, 2));
+    }
+
+    #[test]
+    fn test_lower_variants() {
+        let file = lower_snippet(
+            "data Shape = Dot | Circle float | Rect (float, float) [int]\nx: int = match s | Circle r => 1 | Rect (w, _) _ => 2 | Dot => 3 ;\n",
+        );
+        let Stmt::Variant(decl) = &file.stmts[0] else {
+            panic!("{:?}", file.stmts[0]);
+        };
+        assert_eq!(decl.name.name, "Shape");
+        let cases = decl
+            .cases
+            .iter()
+            .map(|case| {
+                let fields = case.fields.iter().map(|ty| ty.to_string());
+                (case.name.name.as_str(), fields.collect::<Vec<_>>())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            cases,
+            vec![
+                ("Dot", vec![]),
+                ("Circle", vec!["float".to_string()]),
+                (
+                    "Rect",
+                    vec!["(float, float)".to_string(), "[int]".to_string()]
+                ),
+            ]
+        );
+
+        let ExprKind::Match { arms, .. } = value(&file.stmts[1]) else {
+            panic!("{:?}", file.stmts[1]);
+        };
+        let PatternKind::Constructor {
+            name: circle,
+            fields,
+        } = &arms[0].pattern.kind
+        else {
+            panic!("{:?}", arms[0].pattern);
+        };
+        assert_eq!((circle.as_str(), fields.len()), ("Circle", 1));
+        assert_eq!(fields[0].kind, PatternKind::Name("r".to_string()));
+        // The parentheses around a single pattern only group
+        let PatternKind::Constructor { fields, .. } = &arms[1].pattern.kind else {
+            panic!("{:?}", arms[1].pattern);
+        };
+        assert!(matches!(&fields[0].kind, PatternKind::Tuple(inner) if inner.len() == 2));
+        assert_eq!(arms[1].pattern.names(), vec!["w"]);
+        assert_eq!(
+            arms[2].pattern.kind,
+            PatternKind::Constructor {
+                name: "Dot".to_string(),
+                fields: vec![]
+            }
+        );
+
+        let tree = parse_snippet("data V = A | b int | C\n").unwrap();
+        let (file, errors) = lower(&tree);
+        assert_eq!(file.stmts.len(), 1);
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec!["<snippet>:1:14: error: the constructor `b` must start with an uppercase letter"]
+        );
+    }
+
+    #[test]
+    fn test_lower_imports() {
+        let file = lower_snippet("import math\nimport math.trig\n");
+        let imports = file
+            .stmts
+            .iter()
+            .map(|stmt| match stmt {
+                Stmt::Import(import) => import.name(),
+                stmt => panic!("not an import: {:?}", stmt),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(imports, vec!["math", "math.trig"]);
+        let Stmt::Import(import) = &file.stmts[1] else {
+            panic!("{:?}", file.stmts[1]);
+        };
+        assert_eq!(import.path[1].name, "trig");
+        assert_eq!(import.span.location().to_string(), "<snippet>:2:1");
+    }
+
+    #[test]
+    fn test_lower_declarations() {
+        let file = lower_snippet(
+            "# doc\nf: (int, str) -> [int] = (a, b) ->\n  c: int = a\n  c\n;\ng: () -> int = ?todo\n",
+        );
+        assert_eq!(file.stmts.len(), 2);
+        let Stmt::FunDecl(f) = &file.stmts[0] else {
+            panic!("{:?}", file.stmts[0]);
+        };
+        let params = f.params.iter().map(|param| param.name.as_str());
+        assert_eq!(params.collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(f.body.len(), 2);
+        assert!(matches!(f.body[0], Stmt::VarDecl(_)));
+        assert!(
+            matches!(&f.body[1], Stmt::Expr(expr) if expr.kind == ExprKind::Name("c".to_string()))
+        );
+        let TypeKind::Function { params, ret } = &f.ty.kind else {
+            panic!("{:?}", f.ty);
+        };
+        assert_eq!(params.len(), 2);
+        assert!(matches!(ret.kind, TypeKind::List(_)));
+        assert_eq!((f.span.start.line, f.span.end.line), (1, 4));
+
+        let Stmt::FunDecl(g) = &file.stmts[1] else {
+            panic!("{:?}", file.stmts[1]);
+        };
+        assert!(g.params.is_empty());
+        assert!(matches!(&g.body[0], Stmt::Expr(expr) if matches!(expr.kind, ExprKind::Hole(_))));
+        assert!(g.attributes.is_empty());
+
+        let file = lower_snippet("@test\n@bench\nt: () -> bool = () -> true ;\n");
+        let Stmt::FunDecl(t) = &file.stmts[0] else {
+            panic!("{:?}", file.stmts[0]);
+        };
+        assert_eq!(t.attributes, vec![Attribute::Test, Attribute::Bench]);
+        assert_eq!(t.name.name, "t");
+
+        // Unlike the runners, `@tailrec` takes functions with parameters
+        let file = lower_snippet("@tailrec\nf: (int) -> int = (n) -> n ;\n");
+        let Stmt::FunDecl(f) = &file.stmts[0] else {
+            panic!("{:?}", file.stmts[0]);
+        };
+        assert_eq!(f.attributes, vec![Attribute::TailRec]);
+    }
+
+    #[test]
+    fn test_lower_empty_files() {
+        for content in ["", "  \n\t\n", "# a\n#| b |#\n"] {
+            let tree = Parser::new(Lexer::new(&Source::from(content.to_string()))).parse();
+            let (file, errors) = lower(&tree);
+            assert!(file.stmts.is_empty(), "{:?}: {:?}", content, file.stmts);
+            assert!(errors.is_empty(), "{:?}: {:?}", content, errors);
+        }
+    }
+
+    #[test]
+    fn test_lower_types() {
+        let file = lower_snippet(
+            "a: (int) = 1\nb: (int, str) = ?t\nc: () = ?u\nd: map<str, [int]> = ?v\n",
+        );
+        let kinds = file
+            .stmts
+            .iter()
+            .map(|stmt| match stmt {
+                Stmt::VarDecl(decl) => decl.ty.kind.clone(),
+                stmt => panic!("{:?}", stmt),
+            })
+            .collect::<Vec<_>>();
+        let named = |name: &str| TypeKind::Named {
+            name: name.to_string(),
+            args: vec![],
+        };
+        assert_eq!(kinds[0], named("int"));
+        assert!(matches!(&kinds[1], TypeKind::Tuple(types) if types.len() == 2));
+        assert_eq!(kinds[2], TypeKind::Tuple(vec![]));
+        let TypeKind::Named { name, args } = &kinds[3] else {
+            panic!("{:?}", kinds[3]);
+        };
+        assert_eq!(name, "map");
+        assert_eq!(args[0].kind, named("str"));
+        assert!(matches!(args[1].kind, TypeKind::List(_)));
+    }
+
+    #[test]
+    fn test_lower_errors() {
+        let tree = parse_snippet("big: int = 99999999999999999999\nok: int = 1\n").unwrap();
+        let (file, errors) = lower(&tree);
+        assert_eq!(file.stmts.len(), 1);
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec!["<snippet>:1:12: error: `99999999999999999999` does not fit in an `int`"]
+        );
+
+        // What could be parsed is still lowered
+        let source = Source::from("x: int = 1\n~\ny: int =\n".to_string());
+        let (tree, parse_errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+        assert!(!parse_errors.is_empty());
+        let (file, errors) = lower(&tree);
+        assert_eq!(file.stmts.len(), 1);
+        // `~`, then the missing value of `y`; the new line after `~` is skipped
+        let malformed = errors
+            .iter()
+            .map(|e| (e.message.code, e.location.line))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            malformed,
+            vec![(Code::MalformedTree, 1), (Code::MalformedTree, 2),]
+        );
+
+        // Attributes are checked, the function is still lowered
+        let tree = parse_snippet(
+            "@tset\nt: () -> bool = () -> true ;\n@test\nf: (int) -> bool = (x) -> true ;\n",
+        )
+        .unwrap();
+        let (file, errors) = lower(&tree);
+        assert_eq!(file.stmts.len(), 2);
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec![
+                "<snippet>:1:2: error: unknown attribute `@tset`, expected `@test`, `@bench` or `@tailrec`",
+                "<snippet>:4:1: error: a `@test` function can not have parameters",
+            ]
+        );
+    }
+}
@@ -1,34 +1,182 @@
+pub mod ast;
+pub mod builder;
+pub mod lower;
+pub mod node_id;
+pub mod snippet;
+
+use crate::diagnostics::messages::{Code, Locale, Message, Phrase};
+use crate::diagnostics::Diagnostic;
+use crate::lexer::token::has_unknown_numeric_suffix;
+use crate::lexer::token::Keyword;
 use crate::lexer::token::Literal;
 use crate::lexer::token::Token;
 use crate::lexer::token::TokenKind;
+use crate::lexer::token::TokenLocation;
+use crate::migrate::is_deprecated_bool;
 use serde::Deserialize;
 use serde::Serialize;
 use std::cell::Cell;
+use std::path::PathBuf;
 use tracing::error;
 
+/// The version of the grammar accepted by the parser, reported by `funs version`.
+///
+/// It changes whenever a program written for it could be rejected or read differently.
+pub const LANGUAGE_VERSION: u32 = 1;
+
 #[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub struct Tree {
     kind: TreeKind,
     children: Vec<Child>,
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
-enum TreeKind {
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub enum TreeKind {
     ErrorTree,
     File,
+    StmtImport,
     StmtVarDecl,
+    StmtFunDecl,
+    StmtData,
+    FieldDecl,
+    DeclVariant,
+    VariantCase,
+    Attribute,
+    ParamList,
+    Block,
     Comment,
     TypeExpr,
     StmtExpr,
     ExprLiteral,
+    ExprHole,
+    ExprName,
+    ExprParen,
+    ExprTuple,
+    ExprRecord,
+    FieldInit,
+    ExprField,
+    ExprAscription,
+    ExprCast,
+    ExprBinary,
+    ExprUnary,
+    ExprIf,
+    ExprCall,
+    ExprList,
+    ExprCons,
+    ExprConcat,
+    ExprMatch,
+    MatchArm,
+    PatLiteral,
+    PatName,
+    PatWildcard,
+    PatTuple,
+    PatConstructor,
 }
 
 #[derive(Debug, Deserialize, Serialize, PartialEq)]
-enum Child {
+pub enum Child {
     Tree(Tree),
     Token(Token),
 }
 
+impl Tree {
+    pub fn kind(&self) -> &TreeKind {
+        &self.kind
+    }
+
+    pub fn children(&self) -> &[Child] {
+        &self.children
+    }
+
+    /// The subtrees that are direct children of this tree
+    pub fn child_trees(&self) -> impl Iterator<Item = &Tree> {
+        self.children.iter().filter_map(|child| match child {
+            Child::Tree(tree) => Some(tree),
+            Child::Token(_) => None,
+        })
+    }
+
+    /// The declarations of a `File`, as a `File` without its expressions, imports and
+    /// comments: what a program declares, without running anything else
+    pub fn into_declarations(self) -> Tree {
+        let children = self
+            .children
+            .into_iter()
+            .filter(|child| match child {
+                Child::Tree(tree) => matches!(
+                    tree.kind,
+                    TreeKind::StmtVarDecl
+                        | TreeKind::StmtFunDecl
+                        | TreeKind::StmtData
+                        | TreeKind::DeclVariant
+                ),
+                Child::Token(_) => false,
+            })
+            .collect();
+        Tree {
+            kind: self.kind,
+            children,
+        }
+    }
+
+    /// Whether both trees have the same structure and the same tokens, regardless of
+    /// where the tokens are in the source
+    pub fn eq_ignoring_locations(&self, other: &Tree) -> bool {
+        self.kind == other.kind
+            && self.children.len() == other.children.len()
+            && self
+                .children
+                .iter()
+                .zip(&other.children)
+                .all(|pair| match pair {
+                    (Child::Tree(a), Child::Tree(b)) => a.eq_ignoring_locations(b),
+                    (Child::Token(a), Child::Token(b)) => a.kind == b.kind && a.lexeme == b.lexeme,
+                    _ => false,
+                })
+    }
+
+    /// The name introduced by a declaration: its first own identifier, the ones of its
+    /// attributes are in subtrees. A declaration with a pattern has none.
+    pub fn declared_name(&self) -> Option<&Token> {
+        self.children.iter().find_map(|child| match child {
+            Child::Token(token) if token.kind == TokenKind::TokenIdentifier => Some(token),
+            _ => None,
+        })
+    }
+
+    /// The names introduced by a declaration, its name or the ones of its pattern
+    pub fn declared_names(&self) -> Vec<&Token> {
+        match self.declared_name() {
+            Some(name) => vec![name],
+            None => self
+                .child_trees()
+                .filter(|child| *child.kind() == TreeKind::PatTuple)
+                .flat_map(Tree::pattern_names)
+                .collect(),
+        }
+    }
+
+    /// The names bound by the pattern `self`, in source order
+    pub fn pattern_names(&self) -> Vec<&Token> {
+        match self.kind {
+            TreeKind::PatName => self.tokens(),
+            _ => self.child_trees().flat_map(Tree::pattern_names).collect(),
+        }
+    }
+
+    /// All the tokens of this tree, in source order
+    pub fn tokens(&self) -> Vec<&Token> {
+        let mut tokens = Vec::new();
+        for child in &self.children {
+            match child {
+                Child::Tree(tree) => tokens.extend(tree.tokens()),
+                Child::Token(token) => tokens.push(token),
+            }
+        }
+        tokens
+    }
+}
+
 #[derive(Debug)]
 enum Event {
     Open { kind: TreeKind },
@@ -40,6 +188,16 @@ struct MarkOpened {
     index: usize,
 }
 
+struct MarkClosed {
+    index: usize,
+}
+
+/// Where the parser was when it entered a nested construct, see `Parser::enter`
+struct Level {
+    depth: usize,
+    pos: usize,
+}
+
 // Grammar:
 //
 // File = (Stmt | Comment)*
@@ -47,24 +205,100 @@ struct MarkOpened {
 // Stmt =
 //   StmtVarDecl
 // | StmtFunDecl
+// | StmtData
+// | DeclVariant
 // | StmtExpr
 //
-// StmtExpr = Expr "\n"
-// StmtDeclVar = Ident: Type "=" Expr
-// Comment = "#" [^\n]* "\n"
+// StmtExpr = Expr Terminator
+// StmtDeclVar = (Ident | PatTuple) ":" Type "=" Expr
+// StmtFunDecl = Attribute* Ident ":" Type "=" (ParamList "->" FunBody | ExprHole Terminator)
+// Attribute = "@" Ident "\n"?
+// ParamList = "(" (Ident ("," Ident)*)? ")"
+// FunBody = Expr ";" "\n"? | Block
+// Block = "\n" (StmtVarDecl | StmtFunDecl | StmtExpr | Comment)* ";" "\n"?
+// StmtData = "data" Ident "=" "{" (FieldDecl Separator)* FieldDecl? "}" Terminator
+// FieldDecl = Ident ":" Type
+// Separator = "," "\n"* | "\n"+
+// DeclVariant =
+//   "data" Ident "=" "|"? VariantCase ("|" VariantCase)* Terminator
+// | "data" Ident "=" "\n" ("|" VariantCase "\n")+ ";" "\n"?
+// VariantCase = Ident Type*
+//
+// A declaration is a function when its type is a function type, e.g. `(int) -> int`.
+// A statement starting with parentheses holding a `,` and followed by `:` declares the
+// names of a tuple pattern, e.g. `(a, b): (int, str) = (1, "a")`.
+// Only top-level functions have attributes, e.g. `@test`, and records and variants are
+// only declared at the top level. The fields of a record are separated by `,` or new
+// lines, so they can share a line or have one each. The cases of a variant share the
+// line of the declaration, or have one each after it and end with a `;`. A case lists
+// the types of its fields, `Rgb int int int`; `Some(int)` has a single field.
+// Inside a block statements end at the end of their line, so that the `;` closing the
+// block can follow the last expression.
+// Terminator = ";" "\n"? | "\n" | EOF
+// Comment = "#" [^\n]* ("\n" | EOF)
 //
 // Expr =
 //   Ident
 // | ExprLiteral
+// | ExprHole
 // | ExprBinary
 // | ExprUnary
 // | ExprParen
+// | ExprTuple
+// | ExprRecord
+// | ExprField
+// | ExprAscription
+// | ExprIf
+// | ExprMatch
+// | ExprList
+// | ExprCons
+// | ExprConcat
 // | ExprFunCall
 //
 // ExprLiteral = Int | Float | Bool | Str
+// ExprHole = "?" Ident
 // ExprBinary = Expr ("+" | "-" | "*" | "/") Expr
 // ExprUnary = ("+" | "-") Expr
 // ExprParen = "(" Expr ")"
+// ExprTuple = "(" (Expr ("," Expr)+)? ")"
+// ExprAscription = "(" Expr ":" Type ")"
+// ExprRecord = "{" (FieldInit Separator)* FieldInit? "}"
+// FieldInit = Ident "=" Expr
+// ExprField = ExprPrimary ("." Ident)+
+// ExprCast = Expr "as" Type
+// ExprIf = "if" Expr "then" Expr "else" Expr
+// ExprList = "[" (Expr ("," Expr)*)? "]"
+// ExprCons = Expr ":" Expr
+// ExprConcat = Expr "++" Expr
+// ExprMatch = "match" Expr "\n"? MatchArm+ ";"
+// MatchArm = "|" Pattern "=>" Expr "\n"?
+// Pattern = PatLiteral | PatName | PatWildcard | PatTuple | PatConstructor
+// PatLiteral = Int | Float | Bool | Str | "-" (Int | Float)
+// PatName = Ident
+// PatWildcard = "_"
+// PatTuple = "(" (Pattern ("," Pattern)*)? ")"
+// PatConstructor = Ident Pattern*
+//
+// A name starting with an uppercase letter is a constructor, in patterns as in
+// expressions: `Rgb r g b` matches a value built by `Rgb` and binds its fields. The
+// patterns of its fields are constructors only by themselves, `Some (Some x)` needs the
+// parentheses.
+//
+// The arms of a `match` are tried in order, a name matches anything and binds it. The
+// arms usually go on their own lines, the `;` after the last one ends the `match`.
+//
+// Parentheses holding a `,` make a tuple, `()` is the empty one; otherwise they only
+// group: `(1)` is `1`, in expressions as in patterns and types.
+//
+// An ascription is always between parentheses, so its `:` cannot be mistaken for the
+// one of a declaration.
+//
+// `:` puts an element in front of a list and associates to the right, `0 : 1 : xs` is
+// `0 : (1 : xs)`; `++` joins two lists and associates to the left. They bind looser
+// than arithmetic and tighter than comparisons, and `++` tighter than `:`. A `:` followed
+// by a type name (a native type, a capitalized name or a generic name), possibly in a
+// list or function type, is the one of an ascription: `(x : [int])` is an ascription,
+// `(x : xs)` a cons. At the start of a statement `x : xs` is a declaration of `x`.
 //
 // Ident = [a-zA-Z_][a-zA-Z0-9_]*
 // Int = [0-9]+
@@ -72,17 +306,47 @@ struct MarkOpened {
 // Bool = "true" | "false"
 // Str = "\"" [^\n]* "\""
 // Type =
-//   Ident
+//   Ident ("<" Type ("," Type)* ">")?
 // | "[" Type "]"
-// | "(" Type ("," Type)* ")"
+// | "(" (Type ("," Type)*)? ")" ("->" Type)?
 //
-// ExprFunCall = Ident Expr*
+// Generic arguments are only written in types, so `<` and `>` are comparison operators
+// everywhere else. A `>=` closing the arguments (`x: option<int>= 1`) is split in `>` and `=`.
 //
-// --- TODO ---
-// DeclFun = Ident ":" ParamList "->" Type = (Ident) "->" (Expr | Block) ";"
-// TypeParamList = "(" ((Type | "unit") ("," Type)*)? ")"
+// ExprFunCall = Ident Argument+
+// Argument = ExprPostfix | "-" ExprPostfix
+// ExprPostfix = ExprPrimary | ExprField
+//
+// The arguments of a call are primary expressions (literals, names, holes, lists,
+// records and parenthesized expressions) and their fields, so a call binds tighter than
+// any operator but looser than a field access: `f r.x + 1` is `(f (r.x)) + 1`. A new
+// line ends the call, so two statements on consecutive lines are never one call.
+//
+// A `-` after a name is a negative argument when it is separated from what precedes it
+// and touches what follows it, and a subtraction otherwise:
+// `f -1` is `f (-1)`, while `f - 1` and `f-1` are `f - 1`. The rule is about spacing
+// only, so `x -1` is a call of `x` even if `x` is not a function; write `x - 1`.
+
+/// The result of `Parser::parse_partial`
+#[derive(Debug, PartialEq)]
+pub enum Partial {
+    /// The input is a whole file, along with the errors found in it
+    Complete(Tree, Vec<Diagnostic>),
+    /// The input ends in the middle of a statement
+    Incomplete,
+}
+
+/// The types built into the language
+pub const NATIVE_TYPES: [&str; 5] = ["int", "float", "str", "bool", "unit"];
 
 const INITIAL_FUEL: u32 = 256;
+
+/// How deeply expressions, types, patterns and blocks can nest, e.g. in parentheses or
+/// in the operands of `-` and `:`. A construct nested deeper is reported and skipped,
+/// so that neither the parser nor the phases walking the tree after it overflow their
+/// stack on hostile input.
+pub const MAX_NESTING: usize = 256;
+
 pub struct Parser {
     /// The tokens that the parser is consuming.
     tokens: Vec<Token>,
@@ -93,6 +357,14 @@ pub struct Parser {
     pos: usize,
     /// The events that the parser has generated in the first pass.
     events: Vec<Event>,
+    /// The errors that the parser has encountered so far.
+    diagnostics: Vec<Diagnostic>,
+    /// How many of the errors were found at the end of the input, see `parse_partial`.
+    errors_at_end: usize,
+    /// The locale the errors are written in.
+    locale: Locale,
+    /// How many constructs the current one is nested in, see `MAX_NESTING`.
+    depth: usize,
 }
 
 impl Parser {
@@ -102,9 +374,18 @@ impl Parser {
             fuel: Cell::new(INITIAL_FUEL),
             pos: 0,
             events: Vec::new(),
+            diagnostics: Vec::new(),
+            errors_at_end: 0,
+            locale: Locale::default(),
+            depth: 0,
         }
     }
 
+    pub fn with_locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
     // This function is used to open a new tree in the event list.
     //
     // It will mark the current position as an `TokenKind::ErrorTree` and return a `MarkOpened`
@@ -124,9 +405,25 @@ impl Parser {
     /// The `mark` argument indicates the position of the `open` call in the event list.
     /// The `kind` argument indicates the kind of the tree that is being closed, replacing
     /// the `TokenKind::ErrorTree` that was used when the tree was opened.
-    fn close(&mut self, mark: MarkOpened, kind: TreeKind) {
+    fn close(&mut self, mark: MarkOpened, kind: TreeKind) -> MarkClosed {
         self.events[mark.index] = Event::Open { kind };
         self.events.push(Event::Close);
+        MarkClosed { index: mark.index }
+    }
+
+    /// Opens a new tree before the tree closed at `mark`, which becomes its first child.
+    ///
+    /// It is used for postfix constructs, e.g. the `as` of a cast is only seen once the
+    /// expression before it is parsed.
+    fn open_before(&mut self, mark: MarkClosed) -> MarkOpened {
+        let mark = MarkOpened { index: mark.index };
+        self.events.insert(
+            mark.index,
+            Event::Open {
+                kind: TreeKind::ErrorTree,
+            },
+        );
+        mark
     }
 
     /// This function is used to advance the parser to the next token.
@@ -173,17 +470,168 @@ impl Parser {
             return;
         }
 
-        // TODO: Error reporting
-        eprintln!("Expected {kind:?}");
+        self.error(Message::new(Code::ExpectedToken).with_arg("expected", kind));
+    }
+
+    /// Reports `message` at the current token, or at the last one past the end.
+    fn error(&mut self, message: Message) {
+        error!("{}", message.render(self.locale));
+        let at_end = self
+            .tokens
+            .get(self.pos)
+            .is_none_or(|token| token.kind == TokenKind::TokenEOF);
+        if at_end {
+            self.errors_at_end += 1;
+        }
+        let location = match self.tokens.get(self.pos).or(self.tokens.last()) {
+            Some(token) => token.location.clone(),
+            // Tokens from a lexer always end with `TokenEOF`, only a hand-built stream
+            // can be empty
+            None => TokenLocation::new(PathBuf::new(), 0, 0, 0),
+        };
+        self.diagnostics.push(Diagnostic::error(message, location));
+    }
+
+    /// The messages of the diagnostics reported so far, in the locale of the parser
+    fn take_errors(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.diagnostics)
+            .iter()
+            .map(|diagnostic| diagnostic.render(self.locale))
+            .collect()
+    }
+
+    /// Describes the current token for error messages, e.g. "`~`" or "end of line".
+    fn found(&self) -> String {
+        match self.tokens.get(self.pos) {
+            None => Phrase::EndOfFile.render(self.locale).to_string(),
+            Some(token) => match token.kind {
+                TokenKind::TokenEOF => Phrase::EndOfFile.render(self.locale).to_string(),
+                TokenKind::TokenNewLine => Phrase::EndOfLine.render(self.locale).to_string(),
+                _ => format!("`{}`", token.lexeme),
+            },
+        }
+    }
+
+    /// Skips tokens until one of `recovery` (or the end of the statement) is reached,
+    /// wrapping them in an `ErrorTree`.
+    fn recover_until(&mut self, recovery: &[TokenKind]) {
+        let at_recovery = |parser: &Parser| {
+            parser.eof()
+                || parser.at(TokenKind::TokenNewLine)
+                || parser.at(TokenKind::TokenSemicolon)
+                || parser.at(TokenKind::TokenEOF)
+                || recovery.contains(&parser.nth(0))
+        };
+        if at_recovery(self) {
+            return;
+        }
+        let m = self.open();
+        while !at_recovery(self) {
+            self.advance();
+        }
+        self.close(m, TreeKind::ErrorTree);
+    }
+
+    /// Splits a `>=` token in a `>` followed by a `=`.
+    ///
+    /// It is used when closing generic arguments, where `>=` is lexed as a single token.
+    fn split_greater_equal(&mut self) {
+        if !self.at(TokenKind::TokenGreaterEqual) {
+            return;
+        }
+        let token = &mut self.tokens[self.pos];
+        let column_start = token.location.column_start;
+        token.kind = TokenKind::TokenGreater;
+        token.lexeme = ">".to_string();
+        token.location = token.location.with_column_end(column_start + 1);
+        let assign = Token::new(
+            TokenKind::TokenAssign,
+            "=".to_string(),
+            token
+                .location
+                .with_column_start(column_start + 1)
+                .with_column_end(column_start + 2),
+        );
+        self.tokens.insert(self.pos + 1, assign);
+    }
+
+    /// Enters a construct nested in the one being parsed. Past `MAX_NESTING` levels the
+    /// construct is reported and skipped instead, see `skip_too_deep`, and the mark of
+    /// the skipped tokens is returned as the error.
+    fn enter(&mut self) -> Result<Level, MarkClosed> {
+        self.enter_nested(false)
+    }
+
+    /// Enters a type nested in the one being parsed, see `enter`.
+    fn enter_type(&mut self) -> Result<Level, MarkClosed> {
+        self.enter_nested(true)
+    }
+
+    fn enter_nested(&mut self, in_type: bool) -> Result<Level, MarkClosed> {
+        if self.depth >= MAX_NESTING {
+            return Err(self.skip_too_deep(in_type));
+        }
+        let level = Level {
+            depth: self.depth,
+            pos: self.pos,
+        };
+        self.depth += 1;
+        Ok(level)
+    }
+
+    /// Leaves the construct entered at `level`.
+    ///
+    /// The levels left one after the other do not advance, so the fuel is refilled
+    /// when the construct did, as `advance` would have.
+    fn leave(&mut self, level: Level) {
+        self.depth = level.depth;
+        if self.pos > level.pos {
+            self.fuel.set(INITIAL_FUEL);
+        }
     }
 
-    fn advance_with_error(&mut self, error: &str) {
+    /// Reports a construct nested deeper than `MAX_NESTING` and skips it: the tokens up
+    /// to the end of the statement, or up to the `,` or the bracket that ends it in the
+    /// construct around it. In a type, `<` and `>` are brackets too.
+    fn skip_too_deep(&mut self, in_type: bool) -> MarkClosed {
         let m = self.open();
+        self.error(Message::new(Code::NestingTooDeep).with_arg("limit", MAX_NESTING));
+        let mut brackets = 0;
+        while !self.eof() {
+            if in_type {
+                self.split_greater_equal();
+            }
+            let kind = self.nth(0);
+            let opens = matches!(
+                kind,
+                TokenKind::TokenOpenParen | TokenKind::TokenOpenBracket | TokenKind::TokenOpenBrace
+            ) || (in_type && kind == TokenKind::TokenLess);
+            let closes = matches!(
+                kind,
+                TokenKind::TokenCloseParen
+                    | TokenKind::TokenCloseBracket
+                    | TokenKind::TokenCloseBrace
+            ) || (in_type && kind == TokenKind::TokenGreater);
+            match kind {
+                TokenKind::TokenNewLine | TokenKind::TokenEOF => break,
+                TokenKind::TokenSemicolon | TokenKind::TokenComma if brackets == 0 => break,
+                _ if closes && brackets == 0 => break,
+                _ if closes => brackets -= 1,
+                _ if opens => brackets += 1,
+                _ => {}
+            }
+            self.advance();
+        }
+        self.close(m, TreeKind::ErrorTree)
+    }
 
-        // TODO: Error reporting
-        eprintln!("{error}");
-        error!("{error}");
-        self.advance();
+    fn advance_with_error(&mut self, message: Message) {
+        let m = self.open();
+
+        self.error(message);
+        if !self.eof() {
+            self.advance();
+        }
         self.close(m, TreeKind::ErrorTree);
     }
 
@@ -230,63 +678,379 @@ impl Parser {
         stack.pop().unwrap()
     }
 
-    pub fn parse(mut self) -> Tree {
+    pub fn parse(self) -> Tree {
+        self.parse_with_errors().0
+    }
+
+    /// Parses the whole file and returns the tree together with the errors
+    /// encountered while building it.
+    pub fn parse_with_errors(mut self) -> (Tree, Vec<String>) {
+        self.parse_file();
+        let errors = self.take_errors();
+        (self.build_tree(), errors)
+    }
+
+    /// Parses the whole file and returns the tree together with the diagnostics,
+    /// located at the token where each error was found.
+    pub fn parse_with_diagnostics(mut self) -> (Tree, Vec<Diagnostic>) {
         self.parse_file();
-        self.build_tree()
+        let diagnostics = std::mem::take(&mut self.diagnostics);
+        (self.build_tree(), diagnostics)
+    }
+
+    /// Parses an input that may be cut short, e.g. the lines typed in the REPL so far.
+    ///
+    /// The input is incomplete when it has errors, all found at its end: more input could
+    /// complete it, e.g. a function body waiting for its `;`. An input with an error
+    /// before its end can not be completed, it is parsed as a whole file.
+    pub fn parse_partial(mut self) -> Partial {
+        self.parse_file();
+        if self.errors_at_end > 0 && self.errors_at_end == self.diagnostics.len() {
+            return Partial::Incomplete;
+        }
+        let diagnostics = std::mem::take(&mut self.diagnostics);
+        Partial::Complete(self.build_tree(), diagnostics)
+    }
+
+    /// Parses a single expression, e.g. an input of the REPL.
+    ///
+    /// The tree is rooted at the expression instead of a `File`.
+    pub fn parse_expr_only(mut self) -> (Tree, Vec<String>) {
+        self.parse_expr();
+        self.finish_fragment()
+    }
+
+    /// Parses a single type, e.g. `option<int>`.
+    ///
+    /// The tree is rooted at the `TypeExpr` instead of a `File`.
+    pub fn parse_type_only(mut self) -> (Tree, Vec<String>) {
+        self.parse_type();
+        self.finish_fragment()
+    }
+
+    /// Builds the tree of a fragment, which must be followed only by the end of the
+    /// input. The trailing new line and end of file are left out of the tree.
+    fn finish_fragment(mut self) -> (Tree, Vec<String>) {
+        let trailing = self.tokens[self.pos..]
+            .iter()
+            .find(|token| !matches!(token.kind, TokenKind::TokenNewLine | TokenKind::TokenEOF));
+        if let Some(token) = trailing {
+            let found = format!("`{}`", token.lexeme);
+            self.error(Message::new(Code::ExpectedEndOfInput).with_arg("found", found));
+        }
+        self.tokens.truncate(self.pos);
+        let errors = self.take_errors();
+        (self.build_tree(), errors)
     }
 
-    // File = (Stmt | Comment)*
+    // File = (Stmt | Comment)* EOF
+    //
+    // An empty file, or one with only blank lines and comments, has no statement.
     //
     // Stmt =
-    //   StmtVarDecl
+    //   StmtImport
+    // | StmtVarDecl
     // | StmtFunDecl
     // | Expr
     fn parse_file(&mut self) {
         let m = self.open();
         while !self.eof() {
             match self.nth(0) {
-                TokenKind::TokenEOF => self.advance(),
+                // The lexer collapses blank lines, one left between statements is not an
+                // empty statement
+                TokenKind::TokenEOF | TokenKind::TokenNewLine => self.advance(),
                 TokenKind::TokenComment => self.parse_comment(),
-                TokenKind::TokenIdentifier => {
-                    if self.nth(1) == TokenKind::TokenColon {
-                        if self.nth(2) == TokenKind::TokenOpenParen {
-                            self.parse_fun_decl();
-                        } else {
-                            self.parse_var_decl();
-                        }
-                    } else {
-                        self.parse_expr();
-                    }
+                TokenKind::TokenIdentifier if self.nth(1) == TokenKind::TokenColon => {
+                    self.parse_decl()
                 }
-                _ => self.advance_with_error("Expected statement"),
+                TokenKind::TokenOpenParen if self.at_tuple_decl() => self.parse_var_decl(),
+                TokenKind::TokenKeyword(Keyword::Import) => self.parse_import(),
+                TokenKind::TokenKeyword(Keyword::Data) => self.parse_data(),
+                TokenKind::TokenAt if self.at_fun_decl() => self.parse_fun_decl(),
+                TokenKind::TokenAt => self.parse_misplaced_attributes(),
+                _ if self.at_expr_start() => self.parse_stmt_expr(),
+                _ => self.advance_with_error(Message::new(Code::ExpectedStatement)),
             }
         }
         self.close(m, TreeKind::File);
     }
 
-    // StmtDeclVar = Ident: Type "=" StmtExpr
+    fn parse_decl(&mut self) {
+        if self.at_fun_decl() {
+            self.parse_fun_decl();
+        } else {
+            self.parse_var_decl();
+        }
+    }
+
+    /// Whether the declaration at the cursor, after its attributes, has a function type,
+    /// i.e. the parentheses after its `:` are followed by `->`.
+    fn at_fun_decl(&self) -> bool {
+        let start = self.pos + self.attributes_len();
+        let kind = |index: usize| self.tokens.get(index).map(|token| &token.kind);
+        if kind(start) != Some(&TokenKind::TokenIdentifier)
+            || kind(start + 1) != Some(&TokenKind::TokenColon)
+            || kind(start + 2) != Some(&TokenKind::TokenOpenParen)
+        {
+            return false;
+        }
+        let mut depth = 0;
+        for (index, token) in self.tokens.iter().enumerate().skip(start + 2) {
+            match token.kind {
+                TokenKind::TokenOpenParen => depth += 1,
+                TokenKind::TokenCloseParen if depth == 1 => {
+                    return self
+                        .tokens
+                        .get(index + 1)
+                        .is_some_and(|token| token.kind == TokenKind::TokenRightArrow);
+                }
+                TokenKind::TokenCloseParen => depth -= 1,
+                TokenKind::TokenNewLine | TokenKind::TokenEOF => return false,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// Whether the parentheses at the cursor hold a `,` and are followed by `:`, i.e. they
+    /// are the tuple pattern of a declaration
+    fn at_tuple_decl(&self) -> bool {
+        let mut depth = 0;
+        let mut tuple = false;
+        for (index, token) in self.tokens.iter().enumerate().skip(self.pos) {
+            match token.kind {
+                TokenKind::TokenOpenParen => depth += 1,
+                TokenKind::TokenCloseParen if depth == 1 => {
+                    return tuple
+                        && self
+                            .tokens
+                            .get(index + 1)
+                            .is_some_and(|token| token.kind == TokenKind::TokenColon);
+                }
+                TokenKind::TokenCloseParen => depth -= 1,
+                TokenKind::TokenComma if depth == 1 => tuple = true,
+                TokenKind::TokenNewLine | TokenKind::TokenEOF => return false,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// The number of tokens of the attributes at the cursor
+    fn attributes_len(&self) -> usize {
+        let mut len = 0;
+        let kind = |index: usize| self.tokens.get(self.pos + index).map(|token| &token.kind);
+        while kind(len) == Some(&TokenKind::TokenAt)
+            && kind(len + 1) == Some(&TokenKind::TokenIdentifier)
+        {
+            len += 2;
+            if kind(len) == Some(&TokenKind::TokenNewLine) {
+                len += 1;
+            }
+        }
+        len
+    }
+
+    // Attribute = "@" Ident "\n"?
+    fn parse_attribute(&mut self) {
+        let m = self.open();
+        self.expext(TokenKind::TokenAt);
+        self.expext(TokenKind::TokenIdentifier);
+        self.eat(TokenKind::TokenNewLine);
+        self.close(m, TreeKind::Attribute);
+    }
+
+    /// Reports attributes that are not before a top-level function, the statement after
+    /// them is parsed on its own.
+    ///
+    /// The error points at that statement, so that attributes at the end of the input
+    /// wait for their function in `parse_partial`.
+    fn parse_misplaced_attributes(&mut self) {
+        let m = self.open();
+        while self.at(TokenKind::TokenAt) {
+            self.parse_attribute();
+        }
+        self.error(Message::new(Code::MisplacedAttribute));
+        self.close(m, TreeKind::ErrorTree);
+    }
+
+    // StmtDeclVar = (Ident | PatTuple) ":" Type "=" StmtExpr
     fn parse_var_decl(&mut self) {
-        assert!(self.at(TokenKind::TokenIdentifier));
         let m = self.open();
 
-        self.expext(TokenKind::TokenIdentifier);
+        if self.at(TokenKind::TokenOpenParen) {
+            self.parse_pattern();
+        } else {
+            self.expext(TokenKind::TokenIdentifier);
+        }
         self.expext(TokenKind::TokenColon);
+        let errors = self.diagnostics.len();
         self.parse_type();
+        if !self.at(TokenKind::TokenAssign) {
+            // Errors inside the type have already been reported
+            if self.diagnostics.len() == errors {
+                self.error(Message::new(Code::ExpectedAssign).with_arg("found", self.found()));
+            }
+            self.recover_until(&[TokenKind::TokenAssign]);
+            if !self.at(TokenKind::TokenAssign) {
+                // The statement ended, so there is no initializer to parse
+                self.eat_terminator();
+                self.close(m, TreeKind::StmtVarDecl);
+                return;
+            }
+        }
         self.expext(TokenKind::TokenAssign);
         self.parse_stmt_expr();
 
         self.close(m, TreeKind::StmtVarDecl);
     }
 
+    // StmtImport = "import" Ident ("." Ident)* Terminator
+    fn parse_import(&mut self) {
+        let m = self.open();
+        self.expext(TokenKind::TokenKeyword(Keyword::Import));
+        self.expext(TokenKind::TokenIdentifier);
+        while self.eat(TokenKind::TokenDot) {
+            self.expext(TokenKind::TokenIdentifier);
+        }
+        self.expect_terminator();
+        self.close(m, TreeKind::StmtImport);
+    }
+
+    // StmtData = "data" Ident "=" "{" (FieldDecl Separator)* FieldDecl? "}" Terminator
+    // DeclVariant =
+    //   "data" Ident "=" "|"? VariantCase ("|" VariantCase)* Terminator
+    // | "data" Ident "=" "\n" ("|" VariantCase "\n")+ ";" "\n"?
+    fn parse_data(&mut self) {
+        let m = self.open();
+        self.expext(TokenKind::TokenKeyword(Keyword::Data));
+        self.expext(TokenKind::TokenIdentifier);
+        self.expext(TokenKind::TokenAssign);
+        if self.at(TokenKind::TokenOpenBrace) {
+            self.parse_fields(Parser::parse_field_decl);
+            self.expect_terminator();
+            self.close(m, TreeKind::StmtData);
+            return;
+        }
+        if self.eat(TokenKind::TokenNewLine) {
+            // One case per line, until the `;`
+            loop {
+                while self.eat(TokenKind::TokenNewLine) || self.eat(TokenKind::TokenComment) {}
+                if !self.eat(TokenKind::TokenPipe) {
+                    break;
+                }
+                self.parse_variant_case();
+            }
+            self.expext(TokenKind::TokenSemicolon);
+            self.eat(TokenKind::TokenNewLine);
+        } else {
+            self.eat(TokenKind::TokenPipe);
+            self.parse_variant_case();
+            while self.eat(TokenKind::TokenPipe) {
+                self.parse_variant_case();
+            }
+            self.expect_terminator();
+        }
+        self.close(m, TreeKind::DeclVariant);
+    }
+
+    // VariantCase = Ident Type*
+    fn parse_variant_case(&mut self) {
+        let m = self.open();
+        if self.eat(TokenKind::TokenIdentifier) {
+            while matches!(
+                self.nth(0),
+                TokenKind::TokenIdentifier
+                    | TokenKind::TokenOpenBracket
+                    | TokenKind::TokenOpenParen
+            ) {
+                self.parse_type();
+            }
+        } else {
+            self.expext(TokenKind::TokenIdentifier);
+            self.recover_until(&[TokenKind::TokenPipe]);
+        }
+        self.close(m, TreeKind::VariantCase);
+    }
+
+    // FieldDecl = Ident ":" Type
+    fn parse_field_decl(&mut self) {
+        let m = self.open();
+        self.expext(TokenKind::TokenIdentifier);
+        self.expext(TokenKind::TokenColon);
+        self.parse_type();
+        self.close(m, TreeKind::FieldDecl);
+    }
+
+    // FieldInit = Ident "=" Expr
+    fn parse_field_init(&mut self) {
+        let m = self.open();
+        self.expext(TokenKind::TokenIdentifier);
+        if self.eat(TokenKind::TokenAssign) {
+            self.parse_expr();
+        } else {
+            self.expext(TokenKind::TokenAssign);
+            self.recover_until(&[TokenKind::TokenComma, TokenKind::TokenCloseBrace]);
+        }
+        self.close(m, TreeKind::FieldInit);
+    }
+
+    /// Parses the fields of a record between braces with `parse_field`, separated by `,`
+    /// or new lines, e.g. `{ a = 1, b = 2 }`.
+    ///
+    /// Without its `{` the rest of the statement is skipped.
+    fn parse_fields(&mut self, parse_field: fn(&mut Parser)) {
+        if !self.eat(TokenKind::TokenOpenBrace) {
+            self.expext(TokenKind::TokenOpenBrace);
+            self.recover_until(&[]);
+            return;
+        }
+        loop {
+            // A field can be preceded by comments on their own lines
+            while self.eat(TokenKind::TokenNewLine) || self.eat(TokenKind::TokenComment) {}
+            if self.eof() || self.at(TokenKind::TokenCloseBrace) || self.at(TokenKind::TokenEOF) {
+                break;
+            }
+            if self.at(TokenKind::TokenIdentifier) {
+                parse_field(self);
+            } else {
+                self.expext(TokenKind::TokenIdentifier);
+                self.recover_until(&[TokenKind::TokenComma, TokenKind::TokenCloseBrace]);
+            }
+            if !self.eat(TokenKind::TokenComma) && !self.at(TokenKind::TokenNewLine) {
+                break;
+            }
+        }
+        if !self.at(TokenKind::TokenCloseBrace) {
+            // The rest of the line is skipped, up to the `}` if it is on it
+            self.expext(TokenKind::TokenCloseBrace);
+            self.recover_until(&[TokenKind::TokenCloseBrace]);
+        }
+        self.eat(TokenKind::TokenCloseBrace);
+    }
+
     // Type =
-    //   Ident
+    //   Ident ("<" Type ("," Type)* ">")?
     // | "[" Type "]"
     // | "(" Type ("," Type)* ")"
     fn parse_type(&mut self) {
+        let Ok(level) = self.enter_type() else {
+            return;
+        };
         let m = self.open();
 
         match self.nth(0) {
-            TokenKind::TokenIdentifier => self.expext(TokenKind::TokenIdentifier),
+            TokenKind::TokenIdentifier => {
+                self.expext(TokenKind::TokenIdentifier);
+                if self.eat(TokenKind::TokenLess) {
+                    self.parse_type();
+                    while self.eat(TokenKind::TokenComma) {
+                        self.parse_type();
+                    }
+                    self.split_greater_equal();
+                    self.expext(TokenKind::TokenGreater);
+                }
+            }
             TokenKind::TokenOpenBracket => {
                 self.expext(TokenKind::TokenOpenBracket);
                 self.parse_type();
@@ -294,71 +1058,1844 @@ impl Parser {
             }
             TokenKind::TokenOpenParen => {
                 self.expext(TokenKind::TokenOpenParen);
-                self.parse_type();
-                while self.eat(TokenKind::TokenComma) {
+                if !self.at(TokenKind::TokenCloseParen) {
                     self.parse_type();
+                    while self.eat(TokenKind::TokenComma) {
+                        self.parse_type();
+                    }
                 }
                 self.expext(TokenKind::TokenCloseParen);
+                if self.eat(TokenKind::TokenRightArrow) {
+                    self.parse_type();
+                }
+            }
+            _ => {
+                self.error(Message::new(Code::ExpectedType).with_arg("found", self.found()));
+                self.recover_until(&[
+                    TokenKind::TokenAssign,
+                    TokenKind::TokenComma,
+                    TokenKind::TokenCloseParen,
+                    TokenKind::TokenGreater,
+                ]);
             }
-            _ => self.advance_with_error("Expected type"),
         }
 
         self.close(m, TreeKind::TypeExpr);
+        self.leave(level);
     }
 
-    // StmtExpr = Expr "\n"
+    // StmtExpr = Expr Terminator
     fn parse_stmt_expr(&mut self) {
         let m = self.open();
         self.parse_expr();
-        self.expext(TokenKind::TokenNewLine);
+        self.expect_terminator();
         self.close(m, TreeKind::StmtExpr);
     }
 
+    // Terminator = ";" "\n"? | Comment? ("\n" | EOF)
+    //
+    // A `;` lets several statements share a line, e.g. `x: int = 1; y: int = 2`.
+    // The end of file also ends the last statement, so a file does not need a final
+    // new line; it is left for `parse_file`. A comment can end the line of a statement,
+    // e.g. `x: int = 1 # one`, it is kept as a token of the statement.
+    fn eat_terminator(&mut self) -> bool {
+        if self.eat(TokenKind::TokenSemicolon) {
+            self.eat(TokenKind::TokenNewLine);
+            return true;
+        }
+        if self.at(TokenKind::TokenComment)
+            && matches!(self.nth(1), TokenKind::TokenNewLine | TokenKind::TokenEOF)
+        {
+            self.advance();
+        }
+        self.eat(TokenKind::TokenNewLine) || self.eof() || self.at(TokenKind::TokenEOF)
+    }
+
+    fn expect_terminator(&mut self) {
+        if !self.eat_terminator() {
+            self.expext(TokenKind::TokenNewLine);
+        }
+    }
+
     // Expr =
     //   Ident
     // | ExprLiteral
     // | ExprBinary
     // | ExprUnary
     // | ExprParen
+    // | ExprAscription
+    // | ExprCast
+    // | ExprIf
     // | ExprFunCall
     fn parse_expr(&mut self) {
-        let m = self.open();
+        self.parse_expr_binary(0);
+    }
 
-        match self.nth(0) {
-            TokenKind::TokenLiteral(Literal::Int)
-            | TokenKind::TokenLiteral(Literal::Float)
-            | TokenKind::TokenLiteral(Literal::Bool)
-            | TokenKind::TokenLiteral(Literal::Str) => {
-                self.advance();
-                self.close(m, TreeKind::ExprLiteral);
+    // ExprBinary = Expr BinaryOp Expr
+    // ExprCons = Expr ":" Expr
+    // ExprConcat = Expr "++" Expr
+    //
+    // Operators of the same precedence associate to the left, `a - b - c` is
+    // `(a - b) - c`, except `:`. Only operators binding tighter than `min_precedence`
+    // are parsed.
+    fn parse_expr_binary(&mut self, min_precedence: u8) -> MarkClosed {
+        let level = match self.enter() {
+            Ok(level) => level,
+            Err(skipped) => return skipped,
+        };
+        let mut lhs = self.parse_expr_cast();
+        while let Some(precedence) = binary_precedence(&self.nth(0)) {
+            if precedence <= min_precedence {
+                break;
+            }
+            let operator = self.nth(0);
+            if operator == TokenKind::TokenColon && self.at_ascription() {
+                break;
             }
-            _ => unimplemented!(),
+            // The operands so far nest in the operation, a long chain is deep too
+            if self.depth >= MAX_NESTING {
+                self.skip_too_deep(false);
+                break;
+            }
+            self.depth += 1;
+            let m = self.open_before(lhs);
+            self.advance();
+            let kind = match operator {
+                TokenKind::TokenColon => {
+                    // The right operand can be another cons
+                    self.parse_expr_binary(precedence - 1);
+                    TreeKind::ExprCons
+                }
+                TokenKind::TokenPlusPlus => {
+                    self.parse_expr_binary(precedence);
+                    TreeKind::ExprConcat
+                }
+                _ => {
+                    self.parse_expr_binary(precedence);
+                    TreeKind::ExprBinary
+                }
+            };
+            lhs = self.close(m, kind);
         }
+        self.leave(level);
+        lhs
     }
 
-    // Comment = "#" [^\n]*
-    fn parse_comment(&mut self) {
-        assert!(self.at(TokenKind::TokenComment));
-        let m = self.open();
-        self.expext(TokenKind::TokenComment);
-        self.expext(TokenKind::TokenNewLine);
-        self.close(m, TreeKind::Comment);
+    /// Whether the `:` at the cursor is the one of an ascription rather than a cons, see
+    /// the grammar of `ExprCons`. Without an expression after it, the type is missing.
+    fn at_ascription(&self) -> bool {
+        !is_expr_start(&self.nth(1)) || self.at_type_name(1)
     }
 
-    fn parse_fun_decl(&mut self) {}
-}
+    /// Whether a type naming a type (not a variable) starts `lookahead` tokens ahead
+    fn at_type_name(&self, lookahead: usize) -> bool {
+        match self.nth(lookahead) {
+            TokenKind::TokenIdentifier => {
+                let name = &self.tokens[self.pos + lookahead].lexeme;
+                NATIVE_TYPES.contains(&name.as_str())
+                    || name.starts_with(|c: char| c.is_ascii_uppercase())
+                    || self.nth(lookahead + 1) == TokenKind::TokenLess
+            }
+            TokenKind::TokenOpenBracket => self.at_type_name(lookahead + 1),
+            // A function type, `() -> int` has no parameters
+            TokenKind::TokenOpenParen => {
+                self.nth(lookahead + 1) == TokenKind::TokenCloseParen
+                    || self.at_type_name(lookahead + 1)
+            }
+            _ => false,
+        }
+    }
+
+    // ExprCast = Expr "as" Type
+    fn parse_expr_cast(&mut self) -> MarkClosed {
+        let mut expr = self.parse_expr_unary();
+        while self.at(TokenKind::TokenKeyword(Keyword::As)) {
+            let m = self.open_before(expr);
+            self.advance();
+            self.parse_type();
+            expr = self.close(m, TreeKind::ExprCast);
+        }
+        expr
+    }
+
+    // ExprUnary = "-" Expr
+    //
+    // It binds tighter than any other operator, `-a as float` is `(-a) as float`.
+    fn parse_expr_unary(&mut self) -> MarkClosed {
+        if !self.at(TokenKind::TokenMinus) {
+            return self.parse_expr_call();
+        }
+        let m = self.open();
+        self.advance();
+        if let Ok(level) = self.enter() {
+            self.parse_expr_unary();
+            self.leave(level);
+        }
+        self.close(m, TreeKind::ExprUnary)
+    }
+
+    // ExprFunCall = Ident ExprPrimary+
+    fn parse_expr_call(&mut self) -> MarkClosed {
+        let at_name = self.at(TokenKind::TokenIdentifier)
+            && !is_deprecated_bool(&self.tokens[self.pos].lexeme);
+        let callee = self.parse_expr_postfix();
+        if !at_name || !self.at_argument_start() {
+            return callee;
+        }
+        let m = self.open_before(callee);
+        while self.at_argument_start() {
+            if self.at(TokenKind::TokenMinus) {
+                let m = self.open();
+                self.advance();
+                self.parse_expr_postfix();
+                self.close(m, TreeKind::ExprUnary);
+            } else {
+                self.parse_expr_postfix();
+            }
+        }
+        self.close(m, TreeKind::ExprCall)
+    }
+
+    // ExprField = ExprPrimary ("." Ident)+
+    //
+    // Field accesses chain to the left, `a.b.c` is `(a.b).c`.
+    fn parse_expr_postfix(&mut self) -> MarkClosed {
+        let mut expr = self.parse_expr_primary();
+        while self.at(TokenKind::TokenDot) {
+            let m = self.open_before(expr);
+            self.advance();
+            self.expext(TokenKind::TokenIdentifier);
+            expr = self.close(m, TreeKind::ExprField);
+        }
+        expr
+    }
+
+    /// Whether the current token starts an argument of a call, see `parse_expr_call`
+    fn at_argument_start(&self) -> bool {
+        if self.at(TokenKind::TokenMinus) {
+            return self.at_negative_argument();
+        }
+        is_primary_start(&self.nth(0))
+    }
+
+    /// Whether the `-` at the cursor is the sign of an argument rather than a
+    /// subtraction: it is separated from the previous token and touches the next one,
+    /// which starts a primary expression, e.g. `f -1` but not `f - 1` nor `f-1`.
+    fn at_negative_argument(&self) -> bool {
+        let (Some(previous), Some(minus), Some(next)) = (
+            self.pos.checked_sub(1).and_then(|pos| self.tokens.get(pos)),
+            self.tokens.get(self.pos),
+            self.tokens.get(self.pos + 1),
+        ) else {
+            return false;
+        };
+        let separated = previous.location.line == minus.location.line
+            && previous.location.column_end < minus.location.column_start;
+        let touching = minus.location.line == next.location.line
+            && minus.location.column_end == next.location.column_start;
+        separated && touching && is_primary_start(&next.kind)
+    }
+
+    /// Whether the current token starts an expression
+    fn at_expr_start(&self) -> bool {
+        is_expr_start(&self.nth(0))
+    }
+
+    fn parse_expr_primary(&mut self) -> MarkClosed {
+        let m = self.open();
+
+        match self.nth(0) {
+            TokenKind::TokenLiteral(Literal::Int)
+            | TokenKind::TokenLiteral(Literal::Float)
+            | TokenKind::TokenLiteral(Literal::Bool)
+            | TokenKind::TokenLiteral(Literal::Str) => {
+                self.advance();
+                self.close(m, TreeKind::ExprLiteral)
+            }
+            // Accepted for compatibility, see `migrate`
+            TokenKind::TokenIdentifier if is_deprecated_bool(&self.tokens[self.pos].lexeme) => {
+                self.advance();
+                self.close(m, TreeKind::ExprLiteral)
+            }
+            TokenKind::TokenIdentifier => {
+                self.advance();
+                self.close(m, TreeKind::ExprName)
+            }
+            // A placeholder for an expression that is not written yet
+            TokenKind::TokenHole => {
+                self.advance();
+                self.close(m, TreeKind::ExprHole)
+            }
+            // ExprParen = "(" Expr ")"
+            // ExprTuple = "(" (Expr ("," Expr)+)? ")"
+            // ExprAscription = "(" Expr ":" Type ")"
+            TokenKind::TokenOpenParen => {
+                self.expext(TokenKind::TokenOpenParen);
+                if self.eat(TokenKind::TokenCloseParen) {
+                    return self.close(m, TreeKind::ExprTuple);
+                }
+                self.parse_expr();
+                let kind = if self.eat(TokenKind::TokenColon) {
+                    self.parse_type();
+                    TreeKind::ExprAscription
+                } else if self.at(TokenKind::TokenComma) {
+                    while self.eat(TokenKind::TokenComma) {
+                        self.parse_expr();
+                    }
+                    TreeKind::ExprTuple
+                } else {
+                    TreeKind::ExprParen
+                };
+                self.expext(TokenKind::TokenCloseParen);
+                self.close(m, kind)
+            }
+            // ExprRecord = "{" (FieldInit Separator)* FieldInit? "}"
+            TokenKind::TokenOpenBrace => {
+                self.parse_fields(Parser::parse_field_init);
+                self.close(m, TreeKind::ExprRecord)
+            }
+            // ExprList = "[" (Expr ("," Expr)*)? "]"
+            TokenKind::TokenOpenBracket => {
+                self.advance();
+                if !self.at(TokenKind::TokenCloseBracket) {
+                    self.parse_expr();
+                    while self.eat(TokenKind::TokenComma) {
+                        self.parse_expr();
+                    }
+                }
+                self.expext(TokenKind::TokenCloseBracket);
+                self.close(m, TreeKind::ExprList)
+            }
+            // ExprIf = "if" Expr "then" Expr "else" Expr
+            //
+            // The `else` branch extends as far as possible, like the right operand of
+            // an operator of the lowest precedence.
+            TokenKind::TokenKeyword(Keyword::If) => {
+                self.advance();
+                self.parse_expr();
+                // Without its keyword a branch is not parsed, only the keyword is reported
+                for keyword in [Keyword::Then, Keyword::Else] {
+                    let keyword = TokenKind::TokenKeyword(keyword);
+                    if !self.at(keyword.clone()) {
+                        self.expext(keyword);
+                        break;
+                    }
+                    self.advance();
+                    self.parse_expr();
+                }
+                self.close(m, TreeKind::ExprIf)
+            }
+            // ExprMatch = "match" Expr "\n"? MatchArm+ ";"
+            TokenKind::TokenKeyword(Keyword::Match) => {
+                self.advance();
+                self.parse_expr();
+                self.eat(TokenKind::TokenNewLine);
+                if self.at(TokenKind::TokenPipe) {
+                    while self.at(TokenKind::TokenPipe) {
+                        self.parse_match_arm();
+                    }
+                    self.expext(TokenKind::TokenSemicolon);
+                } else {
+                    self.expext(TokenKind::TokenPipe);
+                }
+                self.close(m, TreeKind::ExprMatch)
+            }
+            TokenKind::TokenUnknown
+                if has_unknown_numeric_suffix(&self.tokens[self.pos].lexeme) =>
+            {
+                let message =
+                    Message::new(Code::UnknownNumericSuffix).with_arg("found", self.found());
+                self.advance_with_error(message);
+                self.close(m, TreeKind::ErrorTree)
+            }
+            _ => {
+                self.advance_with_error(Message::new(Code::ExpectedExpression));
+                self.close(m, TreeKind::ErrorTree)
+            }
+        }
+    }
+
+    // MatchArm = "|" Pattern "=>" Expr "\n"?
+    fn parse_match_arm(&mut self) {
+        let m = self.open();
+        self.expext(TokenKind::TokenPipe);
+        self.parse_pattern();
+        if self.eat(TokenKind::TokenRightDoubleArrow) {
+            self.parse_expr();
+        } else {
+            self.expext(TokenKind::TokenRightDoubleArrow);
+            self.recover_until(&[TokenKind::TokenPipe]);
+        }
+        self.eat(TokenKind::TokenNewLine);
+        self.close(m, TreeKind::MatchArm);
+    }
+
+    // Pattern = PatLiteral | PatName | PatWildcard | PatTuple | PatConstructor
+    fn parse_pattern(&mut self) {
+        self.parse_pattern_with(true);
+    }
+
+    /// Parses a pattern, where a constructor takes the patterns of its fields only when
+    /// `with_fields`, see the grammar of `PatConstructor`
+    fn parse_pattern_with(&mut self, with_fields: bool) {
+        let Ok(level) = self.enter() else {
+            return;
+        };
+        let m = self.open();
+        let literal = |kind: TokenKind| {
+            matches!(
+                kind,
+                TokenKind::TokenLiteral(Literal::Int) | TokenKind::TokenLiteral(Literal::Float)
+            )
+        };
+        match self.nth(0) {
+            TokenKind::TokenLiteral(_) => {
+                self.advance();
+                self.close(m, TreeKind::PatLiteral);
+            }
+            // A negative number, the `-` is part of the pattern
+            TokenKind::TokenMinus if literal(self.nth(1)) => {
+                self.advance();
+                self.advance();
+                self.close(m, TreeKind::PatLiteral);
+            }
+            // Accepted for compatibility, see `migrate`
+            TokenKind::TokenIdentifier if is_deprecated_bool(&self.tokens[self.pos].lexeme) => {
+                self.advance();
+                self.close(m, TreeKind::PatLiteral);
+            }
+            // PatConstructor = Ident Pattern*
+            TokenKind::TokenIdentifier if is_constructor(&self.tokens[self.pos].lexeme) => {
+                self.advance();
+                while with_fields && self.at_pattern_start() {
+                    self.parse_pattern_with(false);
+                }
+                self.close(m, TreeKind::PatConstructor);
+            }
+            TokenKind::TokenIdentifier => {
+                self.advance();
+                self.close(m, TreeKind::PatName);
+            }
+            TokenKind::TokenUnderscore => {
+                self.advance();
+                self.close(m, TreeKind::PatWildcard);
+            }
+            // PatTuple = "(" (Pattern ("," Pattern)*)? ")"
+            TokenKind::TokenOpenParen => {
+                self.advance();
+                if !self.at(TokenKind::TokenCloseParen) {
+                    self.parse_pattern();
+                    while self.eat(TokenKind::TokenComma) {
+                        self.parse_pattern();
+                    }
+                }
+                self.expext(TokenKind::TokenCloseParen);
+                self.close(m, TreeKind::PatTuple);
+            }
+            _ => {
+                self.error(Message::new(Code::ExpectedPattern).with_arg("found", self.found()));
+                self.recover_until(&[
+                    TokenKind::TokenRightDoubleArrow,
+                    TokenKind::TokenPipe,
+                    TokenKind::TokenComma,
+                    TokenKind::TokenCloseParen,
+                ]);
+                self.close(m, TreeKind::ErrorTree);
+            }
+        }
+        self.leave(level);
+    }
+
+    /// Whether the current token starts a pattern
+    fn at_pattern_start(&self) -> bool {
+        match self.nth(0) {
+            TokenKind::TokenLiteral(_)
+            | TokenKind::TokenIdentifier
+            | TokenKind::TokenUnderscore
+            | TokenKind::TokenOpenParen => true,
+            TokenKind::TokenMinus => matches!(
+                self.nth(1),
+                TokenKind::TokenLiteral(Literal::Int) | TokenKind::TokenLiteral(Literal::Float)
+            ),
+            _ => false,
+        }
+    }
+
+    // Comment = "#" [^\n]*
+    fn parse_comment(&mut self) {
+        assert!(self.at(TokenKind::TokenComment));
+        let m = self.open();
+        self.expext(TokenKind::TokenComment);
+        self.expect_terminator();
+        self.close(m, TreeKind::Comment);
+    }
+
+    // StmtFunDecl = Attribute* Ident ":" Type "=" (ParamList "->" FunBody | ExprHole Terminator)
+    // Attribute = "@" Ident "\n"?
+    fn parse_fun_decl(&mut self) {
+        let m = self.open();
+        while self.at(TokenKind::TokenAt) {
+            self.parse_attribute();
+        }
+        assert!(self.at(TokenKind::TokenIdentifier));
+
+        self.expext(TokenKind::TokenIdentifier);
+        self.expext(TokenKind::TokenColon);
+        self.parse_type();
+        self.expext(TokenKind::TokenAssign);
+        if self.at(TokenKind::TokenHole) {
+            self.parse_stmt_expr();
+        } else {
+            self.parse_param_list();
+            self.expext(TokenKind::TokenRightArrow);
+            self.parse_fun_body();
+        }
+
+        self.close(m, TreeKind::StmtFunDecl);
+    }
+
+    // ParamList = "(" (Ident ("," Ident)*)? ")"
+    fn parse_param_list(&mut self) {
+        let m = self.open();
+
+        self.expext(TokenKind::TokenOpenParen);
+        if self.at(TokenKind::TokenIdentifier) {
+            self.advance();
+            while self.eat(TokenKind::TokenComma) {
+                self.expext(TokenKind::TokenIdentifier);
+            }
+        }
+        self.expext(TokenKind::TokenCloseParen);
+
+        self.close(m, TreeKind::ParamList);
+    }
+
+    // FunBody = Expr ";" "\n"? | Block
+    fn parse_fun_body(&mut self) {
+        if self.at(TokenKind::TokenNewLine) {
+            self.parse_block();
+            return;
+        }
+        let m = self.open();
+        self.parse_expr();
+        if !self.at(TokenKind::TokenSemicolon) {
+            self.expext(TokenKind::TokenSemicolon);
+            self.recover_until(&[]);
+        }
+        self.eat(TokenKind::TokenSemicolon);
+        self.eat(TokenKind::TokenNewLine);
+        self.close(m, TreeKind::StmtExpr);
+    }
+
+    // Block = "\n" (StmtVarDecl | StmtFunDecl | StmtExpr | Comment)* ";" "\n"?
+    fn parse_block(&mut self) {
+        let Ok(level) = self.enter() else {
+            return;
+        };
+        let m = self.open();
+
+        self.expext(TokenKind::TokenNewLine);
+        while !self.eof() && !self.at(TokenKind::TokenSemicolon) && !self.at(TokenKind::TokenEOF) {
+            match self.nth(0) {
+                TokenKind::TokenComment => self.parse_comment(),
+                TokenKind::TokenIdentifier if self.nth(1) == TokenKind::TokenColon => {
+                    self.parse_decl()
+                }
+                TokenKind::TokenOpenParen if self.at_tuple_decl() => self.parse_var_decl(),
+                TokenKind::TokenAt => self.parse_misplaced_attributes(),
+                _ => {
+                    let m = self.open();
+                    self.parse_expr();
+                    // The `;` closing the block can follow the last expression
+                    if !self.at(TokenKind::TokenSemicolon) && !self.eat_terminator() {
+                        self.expext(TokenKind::TokenNewLine);
+                        self.recover_until(&[]);
+                        self.eat(TokenKind::TokenNewLine);
+                    }
+                    self.close(m, TreeKind::StmtExpr);
+                }
+            }
+        }
+        self.expext(TokenKind::TokenSemicolon);
+        self.eat(TokenKind::TokenNewLine);
+
+        self.close(m, TreeKind::Block);
+        self.leave(level);
+    }
+}
+
+/// Whether `kind` starts a primary expression: a literal, a name, a hole, parentheses, a
+/// list or a record
+fn is_primary_start(kind: &TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::TokenLiteral(_)
+            | TokenKind::TokenIdentifier
+            | TokenKind::TokenHole
+            | TokenKind::TokenOpenParen
+            | TokenKind::TokenOpenBracket
+            | TokenKind::TokenOpenBrace
+    )
+}
+
+/// Whether `name` is the one of a constructor, i.e. it starts with an uppercase letter
+pub fn is_constructor(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase())
+}
+
+/// Whether a token of `kind` starts an expression
+fn is_expr_start(kind: &TokenKind) -> bool {
+    is_primary_start(kind)
+        || matches!(
+            kind,
+            TokenKind::TokenMinus
+                | TokenKind::TokenKeyword(Keyword::If)
+                | TokenKind::TokenKeyword(Keyword::Match)
+        )
+}
+
+/// The precedence of `kind` as a binary operator, higher binds tighter:
+/// comparisons, then `:`, then `++`, then `+` and `-`, then `*` and `/`
+fn binary_precedence(kind: &TokenKind) -> Option<u8> {
+    match kind {
+        TokenKind::TokenEqualEqual
+        | TokenKind::TokenLess
+        | TokenKind::TokenLessEqual
+        | TokenKind::TokenGreater
+        | TokenKind::TokenGreaterEqual => Some(1),
+        TokenKind::TokenColon => Some(2),
+        TokenKind::TokenPlusPlus => Some(3),
+        TokenKind::TokenPlus | TokenKind::TokenMinus => Some(4),
+        TokenKind::TokenStar | TokenKind::TokenSlash => Some(5),
+        _ => None,
+    }
+}
 
 #[cfg(test)]
 pub mod tests {
     use crate::{
-        lexer::Lexer, parser::Parser, source::Source, utils::file_handler::collect_fs_files,
+        diagnostics::messages::{Code, Locale},
+        lexer::{
+            token::{Keyword, Literal, Token, TokenKind, TokenLocation},
+            Lexer,
+        },
+        parser::{lower::lower, Child, Parser, Partial, Tree, TreeKind, MAX_NESTING},
+        source::Source,
+        utils::file_handler::collect_fs_files,
     };
+    use std::path::PathBuf;
     use tracing::info;
 
+    #[test]
+    fn test_parser_generic_types() {
+        for input in [
+            "x: option<int> = 1\n",
+            "x: map<str, (int, bool)> = 1\n",
+            "x: option<option<int>> = 1\n",
+            "x: option<int>= 1\n",
+            "x: [int] = 1\n",
+            "x: map<str, [option<int>]> = 1\n",
+        ] {
+            let source = Source::from(input.to_string());
+            let (tree, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+            assert_eq!(errors, Vec::<String>::new(), "{:?}", input);
+            let kinds = tree
+                .tokens()
+                .iter()
+                .map(|token| token.kind.clone())
+                .collect::<Vec<_>>();
+            assert!(!kinds.contains(&TokenKind::TokenGreaterEqual));
+        }
+    }
+
+    #[test]
+    fn test_parser_type_recovery() {
+        let cases = [
+            ("x: = 1\n", vec!["Expected a type, found `=`"]),
+            ("x: 1 2 = 3\n", vec!["Expected a type, found `1`"]),
+            ("x: int ~ = 0\n", vec!["Expected `=`, found `~`"]),
+            (
+                "x: option<int, 1> = 0\n",
+                vec!["Expected a type, found `1`"],
+            ),
+            (
+                "x: ~ = ~\n",
+                vec!["Expected a type, found `~`", "Expected expression"],
+            ),
+            (
+                "x:\ny: int = 1\n",
+                vec!["Expected a type, found end of line"],
+            ),
+            (
+                "x: int\ny: int = 1\n",
+                vec!["Expected `=`, found end of line"],
+            ),
+        ];
+
+        for (input, expected_errors) in cases {
+            let source = Source::from(input.to_string());
+            let (tree, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+            assert_eq!(errors, expected_errors, "{:?}", input);
+            // The initializer is still parsed
+            assert!(tree.tokens().iter().any(|token| matches!(
+                token.kind,
+                TokenKind::TokenLiteral(_) | TokenKind::TokenUnknown
+            )));
+        }
+    }
+
+    #[test]
+    fn test_parser_numeric_suffixes() {
+        let cases = [
+            ("x: int = 3i\n", vec![]),
+            ("x: float = 3f\n", vec![]),
+            ("x: float = 3.0_float\n", vec![]),
+            (
+                "x: int = 3u8\n",
+                vec!["Unknown numeric suffix in `3u8`, expected one of `i`, `_int`, `f` or `_float`"],
+            ),
+            (
+                "x: int = 3.0i\n",
+                vec!["Unknown numeric suffix in `3.0i`, expected one of `i`, `_int`, `f` or `_float`"],
+            ),
+        ];
+
+        for (input, expected_errors) in cases {
+            let source = Source::from(input.to_string());
+            let (_tree, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+            assert_eq!(errors, expected_errors, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_parser_multi_declarations() {
+        let names = |tree: &Tree| {
+            tree.child_trees()
+                .filter(|tree| *tree.kind() == TreeKind::StmtVarDecl)
+                .map(|decl| decl.tokens()[0].lexeme.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let source =
+            Source::from("x: int = 1; y: float = 2.0\nz: int = 3;\nw: int = 4\n".to_string());
+        let (tree, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(names(&tree), vec!["x", "y", "z", "w"]);
+
+        // An error stops at the `;`, the next declaration is still parsed
+        let source = Source::from("x: int ~; y: int = 2\n".to_string());
+        let (tree, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+        assert_eq!(errors, vec!["Expected `=`, found `~`"]);
+        assert_eq!(names(&tree), vec!["x", "y"]);
+    }
+
+    #[test]
+    fn test_parser_diagnostics() {
+        let source = Source::from("x: int = 1\ny: int ~ 2\n".to_string());
+        let (_tree, diagnostics) = Parser::new(Lexer::new(&source)).parse_with_diagnostics();
+        let located = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic.render(Locale::En),
+                    diagnostic.location.line,
+                    diagnostic.location.column_start,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(located, vec![("Expected `=`, found `~`".to_string(), 1, 7)]);
+        assert!(diagnostics[0].is_error());
+    }
+
+    #[test]
+    fn test_parser_partial() {
+        let partial = |content: &str| {
+            Parser::new(Lexer::new(&Source::from(content.to_string()))).parse_partial()
+        };
+
+        for incomplete in [
+            "square: (int) -> int = (x) ->\n",
+            "square: (int) -> int = (x) ->\n  x * x\n",
+            "data Point = {\n  x: int\n",
+            "data V =\n| A\n",
+            "p: Point = {\n",
+        ] {
+            assert_eq!(partial(incomplete), Partial::Incomplete, "{:?}", incomplete);
+        }
+
+        let Partial::Complete(tree, diagnostics) = partial("x: int = 1\nx + 1\n") else {
+            panic!("a whole file is complete");
+        };
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        assert_eq!(*tree.kind(), TreeKind::File);
+
+        // A statement ends at the end of its line, the next line can not complete it
+        let Partial::Complete(_, diagnostics) = partial("x: int = 1 +\n") else {
+            panic!("an expression cut at the end of its line is complete");
+        };
+        assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+
+        // An error before the end is not fixed by more input
+        let Partial::Complete(_, diagnostics) = partial("x: int ~ 1\ny: int = 1 +\n") else {
+            panic!("an error before the end completes the input");
+        };
+        assert_eq!(diagnostics.len(), 2, "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn test_parser_fragments() {
+        let parser = |content: &str| Parser::new(Lexer::new(&Source::from(content.to_string())));
+
+        let (tree, errors) = parser("42\n").parse_expr_only();
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(*tree.kind(), TreeKind::ExprLiteral);
+        assert_eq!(tree.tokens().len(), 1);
+
+        let (tree, errors) = parser("?todo").parse_expr_only();
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(*tree.kind(), TreeKind::ExprHole);
+
+        let (tree, errors) = parser("map<str, [option<int>]>").parse_type_only();
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(*tree.kind(), TreeKind::TypeExpr);
+
+        let (_tree, errors) = parser("1 2\n").parse_expr_only();
+        assert_eq!(errors, vec!["Expected end of input, found `2`"]);
+        let (tree, errors) = parser("").parse_type_only();
+        assert_eq!(errors, vec!["Expected a type, found end of file"]);
+        assert_eq!(*tree.kind(), TreeKind::TypeExpr);
+    }
+
+    #[test]
+    fn test_parser_ascriptions() {
+        let parser = |content: &str| Parser::new(Lexer::new(&Source::from(content.to_string())));
+
+        let (tree, errors) = parser("(1 : int)").parse_expr_only();
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(*tree.kind(), TreeKind::ExprAscription);
+        let kinds = tree.child_trees().map(|tree| tree.kind().clone());
+        assert_eq!(
+            kinds.collect::<Vec<_>>(),
+            vec![TreeKind::ExprLiteral, TreeKind::TypeExpr]
+        );
+
+        let (tree, errors) = parser("((?x : option<int>))").parse_expr_only();
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(*tree.kind(), TreeKind::ExprParen);
+
+        let source = Source::from(
+            "x: float = (1 : float); y: int = (2)
+"
+            .to_string(),
+        );
+        let (_tree, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let (_tree, errors) = parser("(1 : )").parse_expr_only();
+        assert_eq!(errors, vec!["Expected a type, found `)`"]);
+        let (_tree, errors) = parser(
+            "(1 : int
+",
+        )
+        .parse_expr_only();
+        assert_eq!(errors, vec!["Expected TokenCloseParen"]);
+    }
+
+    #[test]
+    fn test_parser_casts() {
+        let parser = |content: &str| Parser::new(Lexer::new(&Source::from(content.to_string())));
+
+        let (tree, errors) = parser("1 as float").parse_expr_only();
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(*tree.kind(), TreeKind::ExprCast);
+        let kinds = tree.child_trees().map(|tree| tree.kind().clone());
+        assert_eq!(
+            kinds.collect::<Vec<_>>(),
+            vec![TreeKind::ExprLiteral, TreeKind::TypeExpr]
+        );
+
+        // Casts chain to the left, `(1 as float) as int`
+        let (tree, errors) = parser("1 as float as int").parse_expr_only();
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(*tree.kind(), TreeKind::ExprCast);
+        let inner = tree.child_trees().next().unwrap();
+        assert_eq!(*inner.kind(), TreeKind::ExprCast);
+
+        let source = Source::from(
+            "x: float = (?n as float)
+"
+            .to_string(),
+        );
+        let (_tree, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let (_tree, errors) = parser("1 as\n").parse_expr_only();
+        assert_eq!(errors, vec!["Expected a type, found end of line"]);
+    }
+
+    /// The shape of an expression tree, e.g. `(1 + (2 * 3))`, to check precedence
+    fn shape(tree: &Tree) -> String {
+        match tree.kind() {
+            TreeKind::ExprBinary
+            | TreeKind::ExprCons
+            | TreeKind::ExprConcat
+            | TreeKind::ExprUnary
+            | TreeKind::ExprCast => {
+                let mut trees = tree.child_trees();
+                let operator = tree
+                    .children()
+                    .iter()
+                    .find_map(|child| match child {
+                        Child::Token(token) => Some(token.lexeme.clone()),
+                        Child::Tree(_) => None,
+                    })
+                    .unwrap();
+                match tree.kind() {
+                    TreeKind::ExprUnary => {
+                        format!("({}{})", operator, shape(trees.next().unwrap()))
+                    }
+                    TreeKind::ExprCast => format!("({} as _)", shape(trees.next().unwrap())),
+                    _ => format!(
+                        "({} {} {})",
+                        shape(trees.next().unwrap()),
+                        operator,
+                        shape(trees.next().unwrap())
+                    ),
+                }
+            }
+            TreeKind::ExprParen => shape(tree.child_trees().next().unwrap()),
+            TreeKind::ExprCall => {
+                let trees = tree.child_trees().map(shape).collect::<Vec<_>>();
+                format!("({})", trees.join(" "))
+            }
+            TreeKind::ExprIf => {
+                let mut trees = tree.child_trees().map(shape);
+                format!(
+                    "(if {} then {} else {})",
+                    trees.next().unwrap(),
+                    trees.next().unwrap(),
+                    trees.next().unwrap()
+                )
+            }
+            TreeKind::ExprMatch => {
+                let trees = tree.child_trees().map(shape).collect::<Vec<_>>();
+                format!("(match {})", trees.join(" "))
+            }
+            TreeKind::ExprList => {
+                let trees = tree.child_trees().map(shape).collect::<Vec<_>>();
+                format!("[{}]", trees.join(", "))
+            }
+            TreeKind::ExprTuple | TreeKind::PatTuple => {
+                let trees = tree.child_trees().map(shape).collect::<Vec<_>>();
+                format!("<{}>", trees.join(", "))
+            }
+            TreeKind::MatchArm => {
+                let mut trees = tree.child_trees().map(shape);
+                format!("| {} => {}", trees.next().unwrap(), trees.next().unwrap())
+            }
+            TreeKind::ExprRecord => {
+                let trees = tree.child_trees().map(shape).collect::<Vec<_>>();
+                format!("{{{}}}", trees.join(", "))
+            }
+            TreeKind::FieldInit => format!(
+                "{} = {}",
+                tree.declared_name().unwrap().lexeme,
+                shape(tree.child_trees().next().unwrap())
+            ),
+            TreeKind::PatConstructor => {
+                let mut trees = vec![tree.declared_name().unwrap().lexeme.clone()];
+                trees.extend(tree.child_trees().map(shape));
+                match trees.len() {
+                    1 => trees.join(" "),
+                    _ => format!("({})", trees.join(" ")),
+                }
+            }
+            TreeKind::ExprField => format!(
+                "({}.{})",
+                shape(tree.child_trees().next().unwrap()),
+                tree.declared_name().unwrap().lexeme
+            ),
+            _ => tree
+                .tokens()
+                .iter()
+                .map(|token| token.lexeme.clone())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_parser_binary_expressions() {
+        let parse = |content: &str| {
+            let (tree, errors) =
+                Parser::new(Lexer::new(&Source::from(content.to_string()))).parse_expr_only();
+            assert!(errors.is_empty(), "{}: {:?}", content, errors);
+            shape(&tree)
+        };
+
+        assert_eq!(parse("1 + 2 * 3"), "(1 + (2 * 3))");
+        assert_eq!(parse("1 * 2 + 3"), "((1 * 2) + 3)");
+        assert_eq!(parse("1 - 2 - 3"), "((1 - 2) - 3)");
+        assert_eq!(parse("8 / 4 / 2"), "((8 / 4) / 2)");
+        assert_eq!(parse("(1 + 2) * 3"), "((1 + 2) * 3)");
+        assert_eq!(parse("a + 1 <= b * 2"), "((a + 1) <= (b * 2))");
+        assert_eq!(parse("a == b > c"), "((a == b) > c)");
+        assert_eq!(parse("-1 - -x"), "((-1) - (-x))");
+        assert_eq!(parse("--x"), "(-(-x))");
+        assert_eq!(parse("-x as float * 2"), "(((-x) as _) * 2)");
+        assert_eq!(parse("a >= b"), "(a >= b)");
+
+        let (_tree, errors) =
+            Parser::new(Lexer::new(&Source::from("1 +\n".to_string()))).parse_expr_only();
+        assert_eq!(errors, vec!["Expected expression"]);
+    }
+
+    #[test]
+    fn test_parser_calls_and_ifs() {
+        let parse = |content: &str| {
+            let (tree, errors) =
+                Parser::new(Lexer::new(&Source::from(content.to_string()))).parse_expr_only();
+            assert!(errors.is_empty(), "{}: {:?}", content, errors);
+            shape(&tree)
+        };
+
+        assert_eq!(parse("f x"), "(f x)");
+        assert_eq!(parse("f x y + 1"), "((f x y) + 1)");
+        assert_eq!(parse("f (x + 1) ?todo \"s\""), "(f (x + 1) ?todo \"s\")");
+        assert_eq!(parse("-f x as float"), "((-(f x)) as _)");
+        assert_eq!(parse("f - 1"), "(f - 1)");
+        assert_eq!(parse("if a then 1 else 2"), "(if a then 1 else 2)");
+        assert_eq!(
+            parse("if a < b then f a else if b then 2 else 3 + 4"),
+            "(if (a < b) then (f a) else (if b then 2 else (3 + 4)))"
+        );
+        assert_eq!(
+            parse("1 + if a then 1 else 2"),
+            "(1 + (if a then 1 else 2))"
+        );
+
+        // A file can hold expressions that do not start with a name
+        let source = Source::from("1 + 2\nif c then 1 else 2\n-x\n(f)\n".to_string());
+        let (tree, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(tree.child_trees().count(), 4);
+
+        let (_tree, errors) =
+            Parser::new(Lexer::new(&Source::from("if a then 1\n".to_string()))).parse_expr_only();
+        assert_eq!(errors, vec!["Expected TokenKeyword(Else)"]);
+    }
+
+    #[test]
+    fn test_parser_lists() {
+        let parse = |content: &str| {
+            let (tree, errors) =
+                Parser::new(Lexer::new(&Source::from(content.to_string()))).parse_expr_only();
+            assert!(errors.is_empty(), "{}: {:?}", content, errors);
+            shape(&tree)
+        };
+
+        assert_eq!(parse("[]"), "[]");
+        assert_eq!(parse("[1, x + 1, [f x]]"), "[1, (x + 1), [(f x)]]");
+        assert_eq!(parse("0 : 1 : xs"), "(0 : (1 : xs))");
+        assert_eq!(parse("a ++ b ++ c"), "((a ++ b) ++ c)");
+        assert_eq!(parse("x + 1 : a ++ b"), "((x + 1) : (a ++ b))");
+        assert_eq!(parse("x : xs == ys"), "((x : xs) == ys)");
+        assert_eq!(parse("f [1] : xs"), "((f [1]) : xs)");
+        // A `:` before a type name is the one of an ascription
+        assert_eq!(parse("(x : xs)"), "(x : xs)");
+        assert_eq!(parse("(x : [y])"), "(x : [y])");
+        for content in [
+            "(x : int)",
+            "(x : [float])",
+            "(x : Point)",
+            "(x : option<a>)",
+        ] {
+            let (tree, errors) =
+                Parser::new(Lexer::new(&Source::from(content.to_string()))).parse_expr_only();
+            assert!(errors.is_empty(), "{}: {:?}", content, errors);
+            assert_eq!(*tree.kind(), TreeKind::ExprAscription, "{}", content);
+        }
+
+        let (_tree, errors) =
+            Parser::new(Lexer::new(&Source::from("[1, 2\n".to_string()))).parse_expr_only();
+        assert_eq!(errors, vec!["Expected TokenCloseBracket"]);
+    }
+
+    #[test]
+    fn test_parser_list_fixtures() {
+        let fs_files = collect_fs_files("./testdata/lists", true);
+        assert_eq!(fs_files.len(), 3);
+
+        for path in &fs_files {
+            let source = Source::new(path).unwrap();
+            let (output_ast, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+            assert!(errors.is_empty(), "{:?}: {:?}", path, errors);
+            let ast_file = path.to_str().unwrap().replace(".fs", ".ast.json");
+            let json_ast = std::fs::File::open(ast_file).unwrap();
+            let expected_ast: Tree = serde_json::from_reader(json_ast).unwrap();
+            assert!(
+                output_ast.eq_ignoring_locations(&expected_ast),
+                "{:#?}\n!=\n{:#?}",
+                output_ast,
+                expected_ast
+            );
+        }
+    }
+
+    #[test]
+    fn test_parser_tuples() {
+        let parse = |content: &str| {
+            let (tree, errors) =
+                Parser::new(Lexer::new(&Source::from(content.to_string()))).parse_expr_only();
+            assert!(errors.is_empty(), "{}: {:?}", content, errors);
+            shape(&tree)
+        };
+
+        // Tuples are shown between `<` and `>` to tell them from grouping parentheses
+        assert_eq!(parse("()"), "<>");
+        assert_eq!(parse("(1)"), "1");
+        assert_eq!(parse("(1, \"a\")"), "<1, \"a\">");
+        assert_eq!(parse("(x + 1, (y, z), [])"), "<(x + 1), <y, z>, []>");
+        assert_eq!(parse("f (1, 2) x"), "(f <1, 2> x)");
+        assert_eq!(
+            parse("match p | (0, _) => 0 | ((a, b), ()) => a ;"),
+            "(match p | <0, _> => 0 | <<a, b>, <>> => a)"
+        );
+
+        // A declaration whose pattern is a tuple, only parentheses holding a `,` are one
+        let (tree, errors) = Parser::new(Lexer::new(&Source::from(
+            "(a, (b, _)): (int, (str, bool)) = t\n(x) : xs\n".to_string(),
+        )))
+        .parse_with_errors();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let stmts = tree.child_trees().collect::<Vec<_>>();
+        assert_eq!(*stmts[0].kind(), TreeKind::StmtVarDecl);
+        let pattern = stmts[0].child_trees().next().unwrap();
+        assert_eq!(shape(pattern), "<a, <b, _>>");
+        assert_eq!(
+            pattern
+                .pattern_names()
+                .iter()
+                .map(|token| token.lexeme.as_str())
+                .collect::<Vec<_>>(),
+            vec!["a", "b"]
+        );
+        assert_eq!(*stmts[1].kind(), TreeKind::StmtExpr);
+
+        let (_tree, errors) =
+            Parser::new(Lexer::new(&Source::from("(1, 2\n".to_string()))).parse_expr_only();
+        assert_eq!(errors, vec!["Expected TokenCloseParen"]);
+        let (_tree, errors) = Parser::new(Lexer::new(&Source::from(
+            "match p | (1, +) => 1 ;\n".to_string(),
+        )))
+        .parse_expr_only();
+        assert_eq!(errors, vec!["Expected a pattern, found `+`"]);
+    }
+
+    #[test]
+    fn test_parser_tuple_fixtures() {
+        let fs_files = collect_fs_files("./testdata/tuples", true);
+        assert_eq!(fs_files.len(), 3);
+
+        // The others need field accesses, or declare names without parentheses
+        for path in fs_files
+            .iter()
+            .filter(|p| p.ends_with("id_tuple_assign.fs"))
+        {
+            let source = Source::new(path).unwrap();
+            let (output_ast, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+            assert!(errors.is_empty(), "{:?}", errors);
+            let ast_file = path.to_str().unwrap().replace(".fs", ".ast.json");
+            let json_ast = std::fs::File::open(ast_file).unwrap();
+            let expected_ast: Tree = serde_json::from_reader(json_ast).unwrap();
+            assert!(
+                output_ast.eq_ignoring_locations(&expected_ast),
+                "{:#?}\n!=\n{:#?}",
+                output_ast,
+                expected_ast
+            );
+        }
+    }
+
+    #[test]
+    fn test_parser_records() {
+        let parse = |content: &str| {
+            let (tree, errors) =
+                Parser::new(Lexer::new(&Source::from(content.to_string()))).parse_expr_only();
+            assert!(errors.is_empty(), "{}: {:?}", content, errors);
+            shape(&tree)
+        };
+
+        assert_eq!(parse("{}"), "{}");
+        assert_eq!(parse("{ a = 1, b = x + 1 }"), "{a = 1, b = (x + 1)}");
+        assert_eq!(
+            parse("{\n  a = 1\n  b = { c = f x },\n}"),
+            "{a = 1, b = {c = (f x)}}"
+        );
+        // A field access binds tighter than a call and chains to the left
+        assert_eq!(parse("r.a.b + 1"), "(((r.a).b) + 1)");
+        assert_eq!(parse("f r.a -r.b"), "(f (r.a) (-(r.b)))");
+        assert_eq!(parse("{ a = 1 }.a"), "({a = 1}.a)");
+
+        let source = Source::from(
+            "data Point = { x: int, y: int }\ndata Line = {\n  # the ends\n  from: Point\n  to: Point\n}\n"
+                .to_string(),
+        );
+        let (tree, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let fields = tree
+            .child_trees()
+            .map(|decl| {
+                assert_eq!(*decl.kind(), TreeKind::StmtData);
+                let fields = decl
+                    .child_trees()
+                    .map(|field| field.declared_name().unwrap().lexeme.clone());
+                (
+                    decl.declared_name().unwrap().lexeme.clone(),
+                    fields.collect(),
+                )
+            })
+            .collect::<Vec<(String, Vec<String>)>>();
+        assert_eq!(
+            fields,
+            vec![
+                ("Point".to_string(), vec!["x".to_string(), "y".to_string()]),
+                (
+                    "Line".to_string(),
+                    vec!["from".to_string(), "to".to_string()]
+                ),
+            ]
+        );
+
+        let errors = |content: &str| {
+            Parser::new(Lexer::new(&Source::from(content.to_string())))
+                .parse_with_errors()
+                .1
+        };
+        assert_eq!(
+            errors("r: R = { a: 1, b = 2 }\n"),
+            vec!["Expected TokenAssign"]
+        );
+        assert_eq!(
+            errors("r: R = { a = 1 b = 2 }\n"),
+            vec!["Expected TokenCloseBrace"]
+        );
+        assert_eq!(errors("x: int = r.1\n"), vec!["Expected TokenIdentifier"]);
+        assert_eq!(
+            errors("data R = { 1: int }\ny: int = 1\n"),
+            vec!["Expected TokenIdentifier"]
+        );
+        // Without `{` it is a variant, see `test_parser_variants`
+        assert_eq!(errors("data R = 1\n"), vec!["Expected TokenIdentifier"]);
+    }
+
+    #[test]
+    fn test_parser_record_fixtures() {
+        let fs_files = collect_fs_files("./testdata/records", true);
+        assert_eq!(fs_files.len(), 3);
+
+        // The others build records with the name of their type and `:`
+        for path in fs_files.iter().filter(|p| p.ends_with("init.fs")) {
+            let source = Source::new(path).unwrap();
+            let (output_ast, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+            assert!(errors.is_empty(), "{:?}", errors);
+            let ast_file = path.to_str().unwrap().replace(".fs", ".ast.json");
+            let json_ast = std::fs::File::open(ast_file).unwrap();
+            let expected_ast: Tree = serde_json::from_reader(json_ast).unwrap();
+            assert!(
+                output_ast.eq_ignoring_locations(&expected_ast),
+                "{:#?}\n!=\n{:#?}",
+                output_ast,
+                expected_ast
+            );
+        }
+    }
+
+    #[test]
+    fn test_parser_variants() {
+        let cases = |content: &str| {
+            let source = Source::from(content.to_string());
+            let (tree, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+            assert!(errors.is_empty(), "{}: {:?}", content, errors);
+            tree.child_trees()
+                .map(|decl| {
+                    assert_eq!(*decl.kind(), TreeKind::DeclVariant);
+                    decl.child_trees()
+                        .map(|case| {
+                            let name = case.declared_name().unwrap().lexeme.clone();
+                            (name, case.child_trees().count())
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        let color = vec![
+            ("Red".to_string(), 0),
+            ("Green".to_string(), 0),
+            ("Rgb".to_string(), 3),
+        ];
+        assert_eq!(
+            cases("data Color = Red | Green | Rgb int int int\n"),
+            vec![color.clone()]
+        );
+        assert_eq!(
+            cases("data Color = | Red | Green | Rgb int int int ;"),
+            vec![color.clone()]
+        );
+        assert_eq!(
+            cases("data Color =\n| Red\n# no blue\n| Green\n| Rgb int int int\n;\n"),
+            vec![color]
+        );
+        assert_eq!(
+            cases("data T = Pair (int, int) | Many [int] | Other T\n"),
+            vec![vec![
+                ("Pair".to_string(), 1),
+                ("Many".to_string(), 1),
+                ("Other".to_string(), 1),
+            ]]
+        );
+
+        let parse = |content: &str| {
+            let (tree, errors) =
+                Parser::new(Lexer::new(&Source::from(content.to_string()))).parse_expr_only();
+            assert!(errors.is_empty(), "{}: {:?}", content, errors);
+            shape(&tree)
+        };
+        // A constructor is called like a function
+        assert_eq!(parse("Rgb 1 2 (f x)"), "(Rgb 1 2 (f x))");
+        // In a pattern, a capitalized name is a constructor and takes the following
+        // patterns, which need parentheses to have fields themselves
+        assert_eq!(
+            parse("match c | Red => 0 | Rgb r _ 0 => r | x => 1 ;"),
+            "(match c | Red => 0 | (Rgb r _ 0) => r | x => 1)"
+        );
+        assert_eq!(
+            parse("match c | Some (Rgb r g b) => r | Some Red => 0 | None => -1 ;"),
+            "(match c | (Some <(Rgb r g b)>) => r | (Some Red) => 0 | None => (-1))"
+        );
+        assert_eq!(
+            parse("match p | (Some x, None) => x | _ => 0 ;"),
+            "(match p | <(Some x), None> => x | _ => 0)"
+        );
+
+        let errors = |content: &str| {
+            Parser::new(Lexer::new(&Source::from(content.to_string())))
+                .parse_with_errors()
+                .1
+        };
+        assert_eq!(
+            errors("data C = Red | 1 | Green\n"),
+            vec!["Expected TokenIdentifier"]
+        );
+        assert_eq!(errors("data C = Red |\n"), vec!["Expected TokenIdentifier"]);
+        assert_eq!(errors("data C =\n| Red\n"), vec!["Expected TokenSemicolon"]);
+    }
+
+    #[test]
+    fn test_parser_variant_fixtures() {
+        let fs_files = collect_fs_files("./testdata/variants", true);
+        assert_eq!(fs_files.len(), 1);
+
+        for path in fs_files {
+            let source = Source::new(&path).unwrap();
+            let (output_ast, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+            assert!(errors.is_empty(), "{:?}", errors);
+            let ast_file = path.to_str().unwrap().replace(".fs", ".ast.json");
+            let json_ast = std::fs::File::open(ast_file).unwrap();
+            let expected_ast: Tree = serde_json::from_reader(json_ast).unwrap();
+            assert!(
+                output_ast.eq_ignoring_locations(&expected_ast),
+                "{:#?}\n!=\n{:#?}",
+                output_ast,
+                expected_ast
+            );
+        }
+    }
+
+    #[test]
+    fn test_parser_imports() {
+        let paths = |content: &str| {
+            let source = Source::from(content.to_string());
+            let (tree, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+            assert!(errors.is_empty(), "{}: {:?}", content, errors);
+            tree.child_trees()
+                .map(|stmt| {
+                    assert_eq!(*stmt.kind(), TreeKind::StmtImport);
+                    stmt.tokens()
+                        .iter()
+                        .filter(|token| token.kind == TokenKind::TokenIdentifier)
+                        .map(|token| token.lexeme.as_str())
+                        .collect::<Vec<_>>()
+                        .join(".")
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            paths("import math\nimport math.trig; import a.b.c\n"),
+            vec!["math", "math.trig", "a.b.c"]
+        );
+
+        let errors = |content: &str| {
+            Parser::new(Lexer::new(&Source::from(content.to_string())))
+                .parse_with_errors()
+                .1
+        };
+        assert_eq!(errors("import\n"), vec!["Expected TokenIdentifier"]);
+        assert_eq!(errors("import math.\n"), vec!["Expected TokenIdentifier"]);
+        assert_eq!(errors("import math trig\n"), vec!["Expected TokenNewLine"]);
+        // Only the top level imports
+        assert_eq!(errors("x: int = import\n"), vec!["Expected expression"]);
+    }
+
+    #[test]
+    fn test_parser_match() {
+        let parse = |content: &str| {
+            let (tree, errors) =
+                Parser::new(Lexer::new(&Source::from(content.to_string()))).parse_expr_only();
+            assert!(errors.is_empty(), "{}: {:?}", content, errors);
+            shape(&tree)
+        };
+
+        assert_eq!(
+            parse("match n | 0 => \"zero\" | -1 => \"minus one\" | _ => \"other\" ;"),
+            "(match n | 0 => \"zero\" | -1 => \"minus one\" | _ => \"other\")"
+        );
+        assert_eq!(
+            parse("match f x\n| 1.5 => a\n| true => b\n| m => m + 1\n;"),
+            "(match (f x) | 1.5 => a | true => b | m => (m + 1))"
+        );
+        assert_eq!(parse("1 + match x | _ => 2 ;"), "(1 + (match x | _ => 2))");
+
+        let errors = |content: &str| {
+            Parser::new(Lexer::new(&Source::from(content.to_string())))
+                .parse_expr_only()
+                .1
+        };
+        assert_eq!(
+            errors("match x | + => 1 | _ => 2 ;"),
+            vec!["Expected a pattern, found `+`"]
+        );
+        assert_eq!(
+            errors("match x | 1 2 | _ => 2 ;"),
+            vec!["Expected TokenRightDoubleArrow"]
+        );
+        assert_eq!(errors("match x\n"), vec!["Expected TokenPipe"]);
+    }
+
+    /// The cases where a call by juxtaposition could be read another way, see the
+    /// grammar of `ExprFunCall`
+    #[test]
+    fn test_parser_call_disambiguation() {
+        let parse = |content: &str| {
+            let (tree, errors) =
+                Parser::new(Lexer::new(&Source::from(content.to_string()))).parse_expr_only();
+            assert!(errors.is_empty(), "{}: {:?}", content, errors);
+            shape(&tree)
+        };
+        let cases = [
+            // A `-` separated from the name and touching its operand is a sign
+            ("f -1", "(f (-1))"),
+            ("f -x", "(f (-x))"),
+            ("f -(x + 1)", "(f (-(x + 1)))"),
+            ("f x -1", "(f x (-1))"),
+            ("f -1 -2", "(f (-1) (-2))"),
+            ("f (x) -1", "(f x (-1))"),
+            // Otherwise it is a subtraction
+            ("f - 1", "(f - 1)"),
+            ("f-1", "(f - 1)"),
+            ("f- 1", "(f - 1)"),
+            ("f x - 1", "((f x) - 1)"),
+            ("f - -1", "(f - (-1))"),
+            ("f --1", "(f - (-1))"),
+            ("f -- 1", "(f - (-1))"),
+            // Only a name is called, other expressions are subtracted from
+            ("1 -1", "(1 - 1)"),
+            ("(f) -1", "(f - 1)"),
+            ("f x as int -1", "(((f x) as _) - 1)"),
+            // A call binds tighter than any operator, arguments are primary expressions
+            ("f x * g y", "((f x) * (g y))"),
+            ("f (g x)", "(f (g x))"),
+            ("f g x", "(f g x)"),
+            ("-f x", "(-(f x))"),
+            ("f x == g -1", "((f x) == (g (-1)))"),
+            (
+                "if f x then g -1 else h - 1",
+                "(if (f x) then (g (-1)) else (h - 1))",
+            ),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(parse(input), expected, "{:?}", input);
+        }
+
+        // A new line ends a call: consecutive lines are separate statements
+        let statements = |content: &str| {
+            let source = Source::from(content.to_string());
+            let (tree, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+            assert!(errors.is_empty(), "{}: {:?}", content, errors);
+            tree.child_trees()
+                .flat_map(|stmt| stmt.child_trees())
+                .map(shape)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(statements("f\nx\n"), vec!["f", "x"]);
+        assert_eq!(statements("f x\n-1\n"), vec!["(f x)", "(-1)"]);
+        assert_eq!(statements("f x; g y\n"), vec!["(f x)", "(g y)"]);
+        assert_eq!(statements("f\n(x)\n"), vec!["f", "x"]);
+    }
+
+    #[test]
+    fn test_parser_attributes() {
+        let parse = |content: &str| {
+            Parser::new(Lexer::new(&Source::from(content.to_string()))).parse_with_errors()
+        };
+
+        let (tree, errors) = parse("@test\n@bench t: () -> bool = () -> true ;\nx: int = 1\n");
+        assert!(errors.is_empty(), "{:?}", errors);
+        let decls = tree.child_trees().collect::<Vec<_>>();
+        assert_eq!(*decls[0].kind(), TreeKind::StmtFunDecl);
+        let attributes = decls[0]
+            .child_trees()
+            .filter(|child| *child.kind() == TreeKind::Attribute)
+            .map(|attribute| attribute.tokens()[1].lexeme.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(attributes, vec!["test", "bench"]);
+        assert_eq!(decls[0].declared_name().unwrap().lexeme, "t");
+        assert_eq!(*decls[1].kind(), TreeKind::StmtVarDecl);
+
+        // Only top-level functions have attributes, the statement after them still parses
+        for content in [
+            "@test\nx: int = 1\n",
+            "@test\n1 + 2\n",
+            "f: () -> int = () ->\n  @test\n  g: () -> int = () -> 1 ;\n  g\n;\n",
+        ] {
+            let (_tree, errors) = parse(content);
+            assert_eq!(
+                errors,
+                vec!["attributes only apply to top-level functions"],
+                "{:?}",
+                content
+            );
+        }
+    }
+
+    #[test]
+    fn test_parser_holes() {
+        let source = Source::from("x: int = ?todo\ny: int = ?\n".to_string());
+        let (tree, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+        assert_eq!(errors, vec!["Expected expression"]);
+
+        let holes = tree
+            .child_trees()
+            .flat_map(|decl| decl.child_trees())
+            .flat_map(|expr| expr.child_trees())
+            .filter(|expr| *expr.kind() == TreeKind::ExprHole)
+            .flat_map(|hole| hole.tokens())
+            .map(|token| token.lexeme.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(holes, vec!["?todo"]);
+    }
+
+    #[test]
+    fn test_parser_hostile_inputs() {
+        let inputs = [
+            "",
+            "\n\n\n",
+            "x",
+            "x:",
+            "x: int =",
+            "x: int = \n",
+            "x: (int, = 1\n",
+            "x: [int = 1\n",
+            "x: int = y\n",
+            "x: (int) -> int = (x) -> x + 1 ;\n",
+            "= = = =",
+            ")(][}{",
+            "\"unterminated",
+            "x: str = \"👾\"\n",
+            "👾: int = 1\n",
+            "x: int ~ = 0\n",
+            "# comment without newline",
+            "\r\n\r\n",
+            "x: int = 1.2.3\n",
+            "match if then else data\n",
+        ];
+
+        for input in inputs {
+            eprintln!("input -> {:?}", input);
+            let source = Source::from(input.to_string());
+            let _tree = Parser::new(Lexer::new(&source)).parse();
+        }
+
+        for dir in [
+            "./testdata/native_types",
+            "./testdata/functions",
+            "./testdata/lists",
+            "./testdata/tuples",
+            "./testdata/records",
+            "./testdata/variants",
+            "./testdata/errors",
+            "./testdata/identifiers",
+            "./testdata/operators",
+            "./testdata/holes",
+            "./testdata/locations",
+            "./testdata/no_trailing_newline",
+            "./testdata/empty",
+        ] {
+            for path in collect_fs_files(dir, true) {
+                eprintln!("file -> {:?}", path);
+                let source = Source::new(&path).unwrap();
+                let _tree = Parser::new(Lexer::new(&source)).parse();
+            }
+        }
+    }
+
+    /// Tokens of every kind, in any order, as if a broken lexer produced them
+    const ARBITRARY_TOKENS: [(TokenKind, &str); 45] = [
+        (TokenKind::TokenLiteral(Literal::Int), "1"),
+        (TokenKind::TokenLiteral(Literal::Float), "1.5"),
+        (TokenKind::TokenLiteral(Literal::Bool), "true"),
+        (TokenKind::TokenLiteral(Literal::Str), "\"s\""),
+        (TokenKind::TokenKeyword(Keyword::Match), "match"),
+        (TokenKind::TokenKeyword(Keyword::If), "if"),
+        (TokenKind::TokenKeyword(Keyword::Then), "then"),
+        (TokenKind::TokenKeyword(Keyword::Else), "else"),
+        (TokenKind::TokenKeyword(Keyword::Data), "data"),
+        (TokenKind::TokenKeyword(Keyword::As), "as"),
+        (TokenKind::TokenKeyword(Keyword::Import), "import"),
+        (TokenKind::TokenIdentifier, "x"),
+        (TokenKind::TokenIdentifier, "Just"),
+        (TokenKind::TokenIdentifier, "True"),
+        (TokenKind::TokenComment, "# c"),
+        (TokenKind::TokenSpace, " "),
+        (TokenKind::TokenTab, "\t"),
+        (TokenKind::TokenNewLine, "\n"),
+        (TokenKind::TokenDot, "."),
+        (TokenKind::TokenColon, ":"),
+        (TokenKind::TokenSemicolon, ";"),
+        (TokenKind::TokenAssign, "="),
+        (TokenKind::TokenEqualEqual, "=="),
+        (TokenKind::TokenSingleQuote, "'"),
+        (TokenKind::TokenDoubleQuote, "\""),
+        (TokenKind::TokenOpenParen, "("),
+        (TokenKind::TokenCloseParen, ")"),
+        (TokenKind::TokenOpenBrace, "{"),
+        (TokenKind::TokenCloseBrace, "}"),
+        (TokenKind::TokenOpenBracket, "["),
+        (TokenKind::TokenCloseBracket, "]"),
+        (TokenKind::TokenComma, ","),
+        (TokenKind::TokenGreater, ">"),
+        (TokenKind::TokenGreaterEqual, ">="),
+        (TokenKind::TokenLess, "<"),
+        (TokenKind::TokenLessEqual, "<="),
+        (TokenKind::TokenRightArrow, "->"),
+        (TokenKind::TokenRightDoubleArrow, "=>"),
+        (TokenKind::TokenPlusPlus, "++"),
+        (TokenKind::TokenUnderscore, "_"),
+        (TokenKind::TokenPipe, "|"),
+        (TokenKind::TokenPipeForward, "|>"),
+        (TokenKind::TokenHole, "?x"),
+        (TokenKind::TokenAt, "@"),
+        (TokenKind::TokenEOF, ""),
+    ];
+
+    /// A stream of `len` tokens drawn from `ARBITRARY_TOKENS` and the operators, the
+    /// same for the same `seed`
+    fn arbitrary_tokens(seed: u64, len: usize) -> Vec<Token> {
+        let operators = [
+            (TokenKind::TokenPlus, "+"),
+            (TokenKind::TokenMinus, "-"),
+            (TokenKind::TokenStar, "*"),
+            (TokenKind::TokenSlash, "/"),
+            (TokenKind::TokenUnknown, "~"),
+            (TokenKind::TokenUnknown, "1xyz"),
+        ];
+        let vocabulary = ARBITRARY_TOKENS.iter().chain(operators.iter());
+        let vocabulary = vocabulary.collect::<Vec<_>>();
+        // xorshift, enough to spread the tokens without a dependency
+        let mut state = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+        (0..len)
+            .map(|column| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                let (kind, lexeme) = vocabulary[state as usize % vocabulary.len()];
+                let location = TokenLocation::new(PathBuf::new(), 0, column, column + 1);
+                Token::new(kind.clone(), lexeme.to_string(), location)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parser_arbitrary_tokens() {
+        // Every entry point of the parser recovers from any input, and so does the lowering
+        // of the tree: a panic would take down `funs check` and the REPL
+        for seed in 0..2000 {
+            let tokens = arbitrary_tokens(seed, seed as usize % 60);
+            let input = tokens
+                .iter()
+                .map(|token| token.lexeme.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            eprintln!("seed {} -> {:?}", seed, input);
+            let (tree, _) = Parser::new(tokens.clone()).parse_with_diagnostics();
+            let _ = lower(&tree);
+            let _ = Parser::new(tokens.clone()).parse_partial();
+            let _ = Parser::new(tokens.clone()).parse_expr_only();
+            let _ = Parser::new(tokens).parse_type_only();
+        }
+    }
+
+    #[test]
+    fn test_parser_recovers_bad_expressions() {
+        // A bad expression is an `ErrorTree`, the statements after it are still parsed
+        let source =
+            Source::from("x: int = )\ny: int = 1\nz: int = ] + 2\nw: int = 3\n".to_string());
+        let (tree, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        let names = tree
+            .child_trees()
+            .filter(|tree| tree.kind == TreeKind::StmtVarDecl)
+            .filter_map(|tree| tree.declared_name())
+            .map(|name| name.lexeme.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["x", "y", "z", "w"]);
+    }
+
+    #[test]
+    fn test_parser_recovers_deep_nesting() {
+        let deep = 20 * MAX_NESTING;
+        let nested = [
+            format!("{}1{}", "(".repeat(deep), ")".repeat(deep)),
+            format!("{}1", "- ".repeat(deep)),
+            format!("1{}", " + 1".repeat(deep)),
+            format!("1{}", " : 1".repeat(deep)),
+            format!("{}1{}", "[".repeat(deep), "]".repeat(deep)),
+            format!("{}1", "if true then ".repeat(deep)),
+            format!("{}1", "(".repeat(deep)),
+        ];
+        for expr in nested {
+            // Each one is a single diagnostic, the statement after it is still parsed
+            let source = Source::from(format!("x: int = {}\ny: int = 2\n", expr));
+            let (tree, diagnostics) = Parser::new(Lexer::new(&source)).parse_with_diagnostics();
+            assert!(!diagnostics.is_empty());
+            assert!(
+                diagnostics.len() <= MAX_NESTING + 1,
+                "{}",
+                diagnostics.len()
+            );
+            assert_eq!(diagnostics[0].message.code, Code::NestingTooDeep);
+            assert_eq!(
+                diagnostics[0].render(Locale::En),
+                format!(
+                    "this is nested too deeply, the limit is {} levels",
+                    MAX_NESTING
+                )
+            );
+            let names = tree
+                .child_trees()
+                .filter(|tree| tree.kind == TreeKind::StmtVarDecl)
+                .filter_map(|tree| tree.declared_name())
+                .map(|name| name.lexeme.as_str())
+                .collect::<Vec<_>>();
+            assert_eq!(names, vec!["x", "y"]);
+        }
+
+        // Types and patterns have the same limit
+        let source = Source::from(format!(
+            "x: {}int{} = []\ny: int = match x | {}_{} => 1 ;\nz: int = 2\n",
+            "List<".repeat(deep),
+            ">".repeat(deep),
+            "(".repeat(deep),
+            ")".repeat(deep)
+        ));
+        let (_tree, diagnostics) = Parser::new(Lexer::new(&source)).parse_with_diagnostics();
+        let codes = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.code)
+            .collect::<Vec<_>>();
+        assert_eq!(codes, vec![Code::NestingTooDeep, Code::NestingTooDeep]);
+
+        // Just under the limit is fine
+        let depth = MAX_NESTING - 2;
+        let source = Source::from(format!(
+            "x: int = {}1{}\n",
+            "(".repeat(depth),
+            ")".repeat(depth)
+        ));
+        let (_tree, diagnostics) = Parser::new(Lexer::new(&source)).parse_with_diagnostics();
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn test_parser_functions() {
+        let fs_files = collect_fs_files("./testdata/functions", true);
+        assert_eq!(fs_files.len(), 13);
+
+        // The others need calls, `if` or comments after an expression
+        let fs_files = fs_files.iter().filter(|p| {
+            p.ends_with("id_function_assign_int.fs")
+                || p.ends_with("id_fun_sum.fs")
+                || p.ends_with("id_fun_sub.fs")
+                || p.ends_with("id_fun_mul.fs")
+                || p.ends_with("id_fun_div.fs")
+                || p.ends_with("id_fun_no_params.fs")
+                || p.ends_with("id_fun_two_params.fs")
+                || p.ends_with("id_fun_block.fs")
+                || p.ends_with("id_fun_hole.fs")
+                || p.ends_with("id_fun_with_match.fs")
+        });
+
+        for path in fs_files {
+            eprintln!("file -> {:?}", path);
+            let source = Source::new(path).unwrap();
+            let (output_ast, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+            assert!(errors.is_empty(), "{:?}", errors);
+            let ast_file = path.to_str().unwrap().replace(".fs", ".ast.json");
+            let json_ast = std::fs::File::open(ast_file).unwrap();
+            let expected_ast: Tree = serde_json::from_reader(json_ast).unwrap();
+            assert!(
+                output_ast.eq_ignoring_locations(&expected_ast),
+                "{:#?}\n!=\n{:#?}",
+                output_ast,
+                expected_ast
+            );
+        }
+    }
+
+    #[test]
+    fn test_parser_fun_decl_recovery() {
+        let parse = |content: &str| {
+            let source = Source::from(content.to_string());
+            Parser::new(Lexer::new(&source)).parse_with_errors()
+        };
+
+        // A tuple type is not a function type
+        let (tree, errors) = parse("x: (int, str) = ?todo\n");
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(
+            *tree.child_trees().next().unwrap().kind(),
+            TreeKind::StmtVarDecl
+        );
+
+        // A missing `;` is reported once, the next declaration is still parsed
+        let (tree, errors) = parse("f: (int) -> int = (x) -> x ~ 1\ny: int = 2\n");
+        assert_eq!(errors, vec!["Expected TokenSemicolon"]);
+        let kinds = tree.child_trees().map(|tree| tree.kind().clone());
+        assert_eq!(
+            kinds.collect::<Vec<_>>(),
+            vec![TreeKind::StmtFunDecl, TreeKind::StmtVarDecl]
+        );
+
+        // The `;` closing a block can follow its last expression
+        let (_tree, errors) = parse("f: () -> int = () ->\n  1 ;\ny: int = 2\n");
+        assert!(errors.is_empty(), "{:?}", errors);
+        let (_tree, errors) = parse("f: () -> int = () ->\n  1\n");
+        assert_eq!(errors, vec!["Expected TokenSemicolon"]);
+    }
+
     #[test]
     fn test_parser_native_types() {
         let fs_files = collect_fs_files("./testdata/native_types", true);
-        assert_eq!(fs_files.len(), 15);
+        assert_eq!(fs_files.len(), 17);
 
         let fs_files = fs_files.iter().filter(|p| {
             p.ends_with("id_int_assign.fs")
@@ -370,6 +2907,8 @@ pub mod tests {
                 || p.ends_with("id_float_assign.fs")
                 || p.ends_with("id_bool_true_assign.fs")
                 || p.ends_with("id_bool_false_assign.fs")
+                || p.ends_with("id_int_suffix_assign.fs")
+                || p.ends_with("id_float_suffix_assign.fs")
         });
 
         for path in fs_files {
@@ -386,8 +2925,107 @@ pub mod tests {
             let ast_file = fs_file.to_string().replace(".fs", ".ast.json");
             let json_ast = std::fs::File::open(ast_file).unwrap();
             println!("{}", serde_json::to_string(&output_ast).unwrap());
-            let expected_ast = serde_json::from_reader(json_ast).unwrap();
+            let expected_ast: Tree = serde_json::from_reader(json_ast).unwrap();
+            assert!(
+                output_ast.eq_ignoring_locations(&expected_ast),
+                "{:#?}\n!=\n{:#?}",
+                output_ast,
+                expected_ast
+            );
+        }
+    }
+
+    /// The spans of the tokens are only checked here, so that the other fixtures do not
+    /// have to change when they do
+    #[test]
+    fn test_parser_locations() {
+        let fs_files = collect_fs_files("./testdata/locations", true);
+        assert_eq!(fs_files.len(), 3);
+
+        for path in fs_files {
+            eprintln!("file -> {:?}", path);
+            let content = std::fs::read_to_string(&path).unwrap();
+            #[cfg(target_os = "windows")]
+            let content = content.replace("\r\n", "\n");
+            let source = Source::from(content);
+
+            let output_ast = Parser::new(Lexer::new(&source)).parse();
+            let ast_file = path.to_str().unwrap().replace(".fs", ".ast.json");
+            let json_ast = std::fs::File::open(ast_file).unwrap();
+            let expected_ast: Tree = serde_json::from_reader(json_ast).unwrap();
             assert_eq!(output_ast, expected_ast);
         }
     }
+
+    #[test]
+    fn test_parser_no_trailing_newline() {
+        let fs_files = collect_fs_files("./testdata/no_trailing_newline", true);
+        assert_eq!(fs_files.len(), 3);
+
+        for path in fs_files {
+            eprintln!("file -> {:?}", path);
+            let content = std::fs::read_to_string(&path).unwrap();
+            let source = Source::from(content);
+
+            let (output_ast, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+            assert!(errors.is_empty(), "{:?}: {:?}", path, errors);
+            let ast_file = path.to_str().unwrap().replace(".fs", ".ast.json");
+            let json_ast = std::fs::File::open(ast_file).unwrap();
+            let expected_ast: Tree = serde_json::from_reader(json_ast).unwrap();
+            assert_eq!(output_ast, expected_ast);
+        }
+    }
+
+    #[test]
+    fn test_parser_empty_files() {
+        let fs_files = collect_fs_files("./testdata/empty", true);
+        assert_eq!(fs_files.len(), 3);
+
+        for path in fs_files {
+            eprintln!("file -> {:?}", path);
+            let content = std::fs::read_to_string(&path).unwrap();
+            let source = Source::from(content);
+
+            let (output_ast, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+            assert!(errors.is_empty(), "{:?}: {:?}", path, errors);
+            // Comments are kept in the tree, but no statement
+            assert!(
+                output_ast
+                    .child_trees()
+                    .all(|child| *child.kind() == TreeKind::Comment),
+                "{:?}",
+                path
+            );
+            let ast_file = path.to_str().unwrap().replace(".fs", ".ast.json");
+            let json_ast = std::fs::File::open(ast_file).unwrap();
+            let expected_ast: Tree = serde_json::from_reader(json_ast).unwrap();
+            assert_eq!(output_ast, expected_ast);
+        }
+
+        // Blank lines and comments around statements are not statements either
+        let source = Source::from("\n\n# a\n\nx: int = 1\n\n\n# b\n\n".to_string());
+        let (tree, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let kinds = tree.child_trees().map(Tree::kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                &TreeKind::Comment,
+                &TreeKind::StmtVarDecl,
+                &TreeKind::Comment
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parser_eq_ignoring_locations() {
+        let parse =
+            |content: &str| Parser::new(Lexer::new(&Source::from(content.to_string()))).parse();
+        let tree = parse("x: int = 1\n");
+        assert!(tree.eq_ignoring_locations(&parse("x  :int=   1\n")));
+        assert!(tree.eq_ignoring_locations(&parse("\tx: int = 1\n")));
+        assert_ne!(tree, parse("x  :int=   1\n"));
+        assert!(!tree.eq_ignoring_locations(&parse("x: int = 2\n")));
+        assert!(!tree.eq_ignoring_locations(&parse("x: float = 1\n")));
+    }
 }
@@ -0,0 +1,145 @@
+//! Ids for the trees of a file, so that later phases (resolution, type checking, lints)
+//! can attach facts to a node in a table keyed by its id instead of storing them in the
+//! tree.
+
+use super::Tree;
+use crate::lexer::token::TokenLocation;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// The id of a tree of a file, its index in a preorder walk: the file itself is
+/// `NodeId(0)`.
+///
+/// Ids only depend on the shape of the tree, so parsing the same source again gives the
+/// same ids.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub struct NodeId(pub u32);
+
+/// The source covered by a tree, from its first token to its last one
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Span {
+    pub start: TokenLocation,
+    pub end: TokenLocation,
+}
+
+impl Span {
+    /// The location a diagnostic on the span points at: the whole span when it is on a
+    /// single line, its start otherwise
+    pub fn location(&self) -> TokenLocation {
+        if self.start.line == self.end.line {
+            self.start.with_column_end(self.end.column_end)
+        } else {
+            self.start.clone()
+        }
+    }
+}
+
+struct Node<'a> {
+    tree: &'a Tree,
+    parent: Option<NodeId>,
+    /// `None` for a tree without tokens
+    span: Option<Span>,
+}
+
+/// The ids of every tree of a file, along with their span and parent
+pub struct NodeMap<'a> {
+    nodes: Vec<Node<'a>>,
+    /// The id of each tree, by address
+    ids: BTreeMap<*const Tree, NodeId>,
+}
+
+impl<'a> NodeMap<'a> {
+    pub fn new(file: &'a Tree) -> NodeMap<'a> {
+        let mut map = NodeMap {
+            nodes: Vec::new(),
+            ids: BTreeMap::new(),
+        };
+        map.visit(file, None);
+        map
+    }
+
+    fn visit(&mut self, tree: &'a Tree, parent: Option<NodeId>) {
+        let id = NodeId(self.nodes.len() as u32);
+        let tokens = tree.tokens();
+        let span = tokens.first().zip(tokens.last()).map(|(first, last)| Span {
+            start: first.location.clone(),
+            end: last.location.clone(),
+        });
+        self.nodes.push(Node { tree, parent, span });
+        self.ids.insert(tree, id);
+        for child in tree.child_trees() {
+            self.visit(child, Some(id));
+        }
+    }
+
+    /// Every id, in preorder
+    pub fn ids(&self) -> impl Iterator<Item = NodeId> {
+        (0..self.nodes.len() as u32).map(NodeId)
+    }
+
+    /// The id of `tree`, which must be a tree of the file the map was built from
+    pub fn id(&self, tree: &Tree) -> Option<NodeId> {
+        self.ids.get(&(tree as *const Tree)).copied()
+    }
+
+    pub fn tree(&self, id: NodeId) -> &'a Tree {
+        self.nodes[id.0 as usize].tree
+    }
+
+    /// The span of the tree, `None` if it has no tokens (e.g. an empty `ErrorTree`)
+    pub fn span(&self, id: NodeId) -> Option<&Span> {
+        self.nodes[id.0 as usize].span.as_ref()
+    }
+
+    /// The parent of the tree, `None` for the file
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id.0 as usize].parent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::snippet::parse_snippet;
+    use crate::parser::TreeKind;
+
+    #[test]
+    fn test_node_ids() {
+        let tree = parse_snippet("x: int = 1\ny: str = ?todo\n").unwrap();
+        let nodes = NodeMap::new(&tree);
+        let kinds = nodes
+            .ids()
+            .map(|id| nodes.tree(id).kind().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                TreeKind::File,
+                TreeKind::StmtVarDecl,
+                TreeKind::TypeExpr,
+                TreeKind::StmtExpr,
+                TreeKind::ExprLiteral,
+                TreeKind::StmtVarDecl,
+                TreeKind::TypeExpr,
+                TreeKind::StmtExpr,
+                TreeKind::ExprHole,
+            ]
+        );
+
+        let hole = NodeId(8);
+        assert_eq!(nodes.parent(hole), Some(NodeId(7)));
+        assert_eq!(nodes.parent(NodeId(7)), Some(NodeId(5)));
+        assert_eq!(nodes.parent(NodeId(0)), None);
+        let span = nodes.span(NodeId(5)).unwrap();
+        assert_eq!((span.start.line, span.start.column_start), (1, 0));
+        assert_eq!((span.end.line, span.end.column_end), (1, 14));
+
+        let declaration = tree.child_trees().nth(1).unwrap();
+        assert_eq!(nodes.id(declaration), Some(NodeId(5)));
+
+        // The same source gets the same ids
+        let again = parse_snippet("x: int = 1\ny: str = ?todo\n").unwrap();
+        let again = NodeMap::new(&again);
+        assert_eq!(again.tree(hole).kind(), nodes.tree(hole).kind());
+    }
+}
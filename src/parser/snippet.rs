@@ -0,0 +1,67 @@
+use super::{Parser, Tree};
+use crate::lexer::Lexer;
+use crate::source::Source;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+thread_local! {
+    static CACHE: RefCell<BTreeMap<String, Rc<Tree>>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+/// Parses a piece of funs code, e.g. `parse_snippet("x: int = 3\n")`.
+///
+/// Meant for tests and tools that need a tree without writing a file: the snippet is
+/// a virtual source named `<snippet>`, and the trees are cached per thread so the same
+/// snippet is parsed only once.
+pub fn parse_snippet(content: &str) -> Result<Rc<Tree>, Vec<String>> {
+    if let Some(tree) = CACHE.with(|cache| cache.borrow().get(content).cloned()) {
+        return Ok(tree);
+    }
+
+    let source = Source::from_snippet("snippet", content.to_string());
+    let (tree, errors) = Parser::new(Lexer::new(&source)).parse_with_errors();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let tree = Rc::new(tree);
+    CACHE.with(|cache| {
+        cache
+            .borrow_mut()
+            .insert(content.to_string(), Rc::clone(&tree))
+    });
+    Ok(tree)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::builder::{file, int, ty_int};
+    use crate::parser::TreeKind;
+
+    #[test]
+    fn test_snippet_parse() {
+        let tree = parse_snippet("x: int = 3\n").unwrap();
+        assert_eq!(*tree.kind(), TreeKind::File);
+        let expected = file()
+            .with_file_path("<snippet>")
+            .var_decl("x", ty_int(), int(3))
+            .build();
+        assert_eq!(*tree, expected);
+    }
+
+    #[test]
+    fn test_snippet_cache() {
+        let first = parse_snippet("y: int = 4\n").unwrap();
+        let second = parse_snippet("y: int = 4\n").unwrap();
+        assert!(Rc::ptr_eq(&first, &second));
+    }
+
+    #[test]
+    fn test_snippet_errors() {
+        assert_eq!(
+            parse_snippet("x: = 1\n"),
+            Err(vec!["Expected a type, found `=`".to_string()])
+        );
+    }
+}
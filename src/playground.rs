@@ -0,0 +1,58 @@
+use crate::diagnostics::messages::Locale;
+use crate::lexer::Lexer;
+use crate::parser::{Parser, Tree};
+use crate::source::Source;
+use serde::Serialize;
+
+/// The result of compiling a playground program
+#[derive(Debug, Serialize)]
+pub struct Output {
+    pub diagnostics: Vec<String>,
+    /// The CST of the program, even if it has errors
+    pub ast: Tree,
+}
+
+impl Output {
+    pub fn to_json(&self) -> String {
+        // A tree only holds strings and numbers, it always serializes
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// Compiles a program held in memory.
+///
+/// This is the entry point for embedding the frontend (e.g. in a browser playground):
+/// it never touches the file system nor sets up the logger.
+pub fn compile(content: &str) -> Output {
+    let source = Source::from_snippet("playground", content.to_string());
+    let mut lexer = Lexer::new(&source);
+    let tokens = lexer.by_ref().collect::<Vec<_>>();
+    let (ast, parse_errors) = Parser::new(tokens).parse_with_errors();
+    let diagnostics = lexer
+        .diagnostics()
+        .iter()
+        .map(|diagnostic| diagnostic.render(Locale::default()))
+        .chain(parse_errors)
+        .collect();
+    Output { diagnostics, ast }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_playground_compile() {
+        let output = compile("x: int = 1\n");
+        assert!(output.diagnostics.is_empty());
+        let json = serde_json::from_str::<serde_json::Value>(&output.to_json()).unwrap();
+        assert_eq!(json["ast"]["kind"], "File");
+        assert_eq!(json["diagnostics"], serde_json::json!([]));
+
+        let output = compile("x: = 1\n");
+        assert_eq!(output.diagnostics, vec!["Expected a type, found `=`"]);
+
+        let output = compile("x: int = 1\n#| never closed\n");
+        assert_eq!(output.diagnostics, vec!["unterminated block comment"]);
+    }
+}
@@ -0,0 +1,466 @@
+//! The read-eval-print loop of `funs repl`.
+//!
+//! Lines are buffered until they form whole statements, then the statements run in an
+//! `Interpreter` that keeps the declarations of the previous inputs. An input that ends in
+//! the middle of a statement (e.g. a function body waiting for its `;`) waits for more
+//! lines, an empty line gives up on it and reports its errors.
+//!
+//! `:save <file>` writes the syntax trees of the declarations that ran to a session file,
+//! and `:restore <file>` runs them again, e.g. in a new REPL. The expressions are not
+//! saved, a restored session does not print again what it printed.
+use crate::diagnostics::messages::{Code, Locale};
+use crate::diagnostics::renderer::Renderer;
+use crate::diagnostics::Diagnostic;
+use crate::interpreter::{Interpreter, Value};
+use crate::lexer::token::Token;
+use crate::lexer::Lexer;
+use crate::parser::lower::lower;
+use crate::parser::{Parser, Partial, Tree};
+use crate::source::Source;
+use crate::tailcall::non_tail_calls;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+
+/// The version of the session files written by `:save`.
+///
+/// It changes whenever a session saved before could not be restored.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The declarations of the inputs that ran, saved by `:save` and run again by `:restore`
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct Session {
+    pub version: u32,
+    /// A `File` tree for each input, with its declarations only
+    pub inputs: Vec<Tree>,
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Session {
+            version: FORMAT_VERSION,
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        // A tree only holds strings and numbers, it always serializes
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    pub fn from_json(json: &str) -> Result<Session, SessionError> {
+        let session: Session =
+            serde_json::from_str(json).map_err(|e| SessionError::Parse(e.to_string()))?;
+        if session.version != FORMAT_VERSION {
+            return Err(SessionError::Version(session.version));
+        }
+        Ok(session)
+    }
+}
+
+impl Default for Session {
+    fn default() -> Session {
+        Session::new()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SessionError {
+    Parse(String),
+    Version(u32),
+}
+
+impl std::fmt::Display for SessionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SessionError::Parse(message) => write!(f, "Invalid session: {}", message),
+            SessionError::Version(version) => write!(
+                f,
+                "Session version {} is not supported, expected {}",
+                version, FORMAT_VERSION
+            ),
+        }
+    }
+}
+
+/// What the REPL expects after a line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prompt {
+    /// A new input
+    Input,
+    /// The rest of an input cut short
+    Continuation,
+}
+
+impl Prompt {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Prompt::Input => "funs> ",
+            Prompt::Continuation => "....> ",
+        }
+    }
+}
+
+/// Runs the inputs fed to it, printing the results and the program output to `out`
+/// and the diagnostics to `errors`.
+pub struct Repl<W: Write, E: Write> {
+    interpreter: Interpreter<W>,
+    errors: E,
+    renderer: Renderer,
+    locale: Locale,
+    /// The lines of the input being typed
+    buffer: String,
+    /// How many inputs were read, to name their sources `<repl-1>`, `<repl-2>`, ...
+    inputs: usize,
+    /// The declarations that ran, for `:save`
+    session: Session,
+}
+
+impl<W: Write, E: Write> Repl<W, E> {
+    pub fn new(out: W, errors: E) -> Repl<W, E> {
+        Repl {
+            interpreter: Interpreter::new(out),
+            errors,
+            renderer: Renderer::new(),
+            locale: Locale::default(),
+            buffer: String::new(),
+            inputs: 0,
+            session: Session::new(),
+        }
+    }
+
+    pub fn with_locale(mut self, locale: Locale) -> Repl<W, E> {
+        self.locale = locale;
+        self
+    }
+
+    pub fn with_renderer(mut self, renderer: Renderer) -> Repl<W, E> {
+        self.renderer = renderer;
+        self
+    }
+
+    /// Reads a line, and runs the input if the line completes it.
+    ///
+    /// A line starting with `:` outside of an input is a command, e.g. `:save <file>`.
+    pub fn feed(&mut self, line: &str) -> std::io::Result<Prompt> {
+        let line = line.trim_end_matches(['\n', '\r']);
+        if let Some(command) = line.trim().strip_prefix(':') {
+            if self.buffer.trim().is_empty() {
+                self.command(command)?;
+                return Ok(Prompt::Input);
+            }
+        }
+        if line.trim().is_empty() {
+            // An empty line gives up on the input being typed
+            if !self.buffer.trim().is_empty() {
+                self.submit(true)?;
+            }
+            self.buffer.clear();
+            return Ok(Prompt::Input);
+        }
+        self.buffer.push_str(line);
+        self.buffer.push('\n');
+        self.submit(false)
+    }
+
+    /// Runs what is left of the input, reporting its errors if it is incomplete, and
+    /// returns the outputs.
+    pub fn finish(mut self) -> std::io::Result<(W, E)> {
+        if !self.buffer.trim().is_empty() {
+            self.submit(true)?;
+        }
+        Ok((self.interpreter.into_output(), self.errors))
+    }
+
+    /// Runs the buffered input, unless it is incomplete and not `forced`.
+    fn submit(&mut self, forced: bool) -> std::io::Result<Prompt> {
+        let name = format!("repl-{}", self.inputs + 1);
+        let source = Source::from_snippet(&name, self.buffer.clone());
+        let mut lexer = Lexer::new(&source);
+        let tokens = lexer.by_ref().collect::<Vec<Token>>();
+        let mut diagnostics = lexer.diagnostics().to_vec();
+        let unterminated = diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.message.code == Code::UnterminatedBlockComment);
+        if unterminated && !forced {
+            return Ok(Prompt::Continuation);
+        }
+        let parser = Parser::new(tokens).with_locale(self.locale);
+        let (tree, parse_diagnostics) = match (forced, parser.parse_partial()) {
+            (false, Partial::Incomplete) => return Ok(Prompt::Continuation),
+            (_, Partial::Complete(tree, diagnostics)) => (tree, diagnostics),
+            // The tree is built again as a whole file, to report its errors
+            (true, Partial::Incomplete) => {
+                let lexer = Lexer::new(&source);
+                Parser::new(lexer)
+                    .with_locale(self.locale)
+                    .parse_with_diagnostics()
+            }
+        };
+        self.inputs += 1;
+        self.buffer.clear();
+
+        // Unlike `funs check`, duplicates are not reported: an input can declare again
+        // a name of a previous one, like shadowing
+        let ast = match parse_diagnostics.is_empty() {
+            true => {
+                let (ast, lower_diagnostics) = lower(&tree);
+                diagnostics.extend(lower_diagnostics);
+                diagnostics.extend(non_tail_calls(&ast));
+                Some(ast)
+            }
+            false => {
+                diagnostics.extend(parse_diagnostics);
+                None
+            }
+        };
+        self.report(&diagnostics, &source)?;
+        let Some(ast) = ast else {
+            return Ok(Prompt::Input);
+        };
+        if diagnostics.iter().any(Diagnostic::is_error) {
+            return Ok(Prompt::Input);
+        }
+        match self.interpreter.run(&ast) {
+            Ok(value) => {
+                self.record(tree);
+                if let Some(value) = value.filter(|value| *value != Value::Unit) {
+                    writeln!(self.interpreter.output(), "{}", value)?;
+                }
+            }
+            Err(diagnostic) => self.report(&[diagnostic], &source)?,
+        }
+        Ok(Prompt::Input)
+    }
+
+    /// Adds the declarations of `file`, an input that ran, to the session
+    fn record(&mut self, file: Tree) {
+        let declarations = file.into_declarations();
+        if declarations.child_trees().next().is_some() {
+            self.session.inputs.push(declarations);
+        }
+    }
+
+    /// Runs `command`, a line after its `:`, reporting its errors
+    fn command(&mut self, command: &str) -> std::io::Result<()> {
+        let (name, argument) = command
+            .trim()
+            .split_once(char::is_whitespace)
+            .map_or((command.trim(), ""), |(name, argument)| {
+                (name, argument.trim())
+            });
+        let result = match (name, argument) {
+            ("save" | "restore", "") => Err(format!("Missing file for `:{}`", name)),
+            ("save", path) => self.save(Path::new(path)),
+            ("restore", path) => self.restore(Path::new(path)),
+            _ => Err(format!(
+                "Unknown command `:{}`, expected `:save <file>` or `:restore <file>`",
+                name
+            )),
+        };
+        match result {
+            Ok(message) => writeln!(self.interpreter.output(), "{}", message),
+            Err(message) => writeln!(self.errors, "{}", message),
+        }
+    }
+
+    /// Writes the session to `path`, returning what to tell
+    fn save(&mut self, path: &Path) -> Result<String, String> {
+        std::fs::write(path, self.session.to_json())
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(format!(
+            "Saved {} input(s) to {}",
+            self.session.inputs.len(),
+            path.display()
+        ))
+    }
+
+    /// Runs the declarations of the session saved at `path`, in order, returning what to
+    /// tell. It stops at the first one failing, the ones after it could depend on it.
+    fn restore(&mut self, path: &Path) -> Result<String, String> {
+        let error = |e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+        let json = std::fs::read_to_string(path).map_err(|e| error(&e))?;
+        let session = Session::from_json(&json).map_err(|e| error(&e))?;
+        let mut restored = 0;
+        for file in session.inputs {
+            let (ast, diagnostics) = lower(&file);
+            if let Some(diagnostic) = diagnostics.iter().find(|d| d.is_error()) {
+                return Err(error(diagnostic));
+            }
+            self.interpreter.run(&ast).map_err(|e| error(&e))?;
+            self.record(file);
+            restored += 1;
+        }
+        Ok(format!(
+            "Restored {} input(s) from {}",
+            restored,
+            path.display()
+        ))
+    }
+
+    fn report(&mut self, diagnostics: &[Diagnostic], source: &Source) -> std::io::Result<()> {
+        for diagnostic in diagnostics {
+            let rendered = self
+                .renderer
+                .render_diagnostic(diagnostic, source, self.locale);
+            write!(self.errors, "{}", rendered)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::file_handler::create_tmp_dir;
+
+    /// Feeds `lines` to a REPL, returns the prompts after each line and the outputs
+    fn session(lines: &[&str]) -> (Vec<Prompt>, String, String) {
+        let mut repl = Repl::new(Vec::new(), Vec::new());
+        let prompts = lines.iter().map(|line| repl.feed(line).unwrap()).collect();
+        let (out, errors) = repl.finish().unwrap();
+        (
+            prompts,
+            String::from_utf8(out).unwrap(),
+            String::from_utf8(errors).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_repl_keeps_bindings() {
+        let (prompts, out, errors) = session(&[
+            "x: int = 20",
+            "square: (int) -> int = (n) ->",
+            "  n * n ;",
+            "square x + 1",
+            "print \"hi\"",
+            "",
+        ]);
+        assert_eq!(
+            prompts,
+            vec![
+                Prompt::Input,
+                Prompt::Continuation,
+                Prompt::Input,
+                Prompt::Input,
+                Prompt::Input,
+                Prompt::Input,
+            ]
+        );
+        assert_eq!(out, "401\nhi\n");
+        assert_eq!(errors, "");
+
+        // A name can be declared again
+        let (_, out, errors) = session(&["x: int = 1", "x: int = x + 1", "x"]);
+        assert_eq!(out, "2\n");
+        assert_eq!(errors, "");
+    }
+
+    #[test]
+    fn test_repl_sessions() {
+        let dir = create_tmp_dir("repl_sessions");
+        let path = dir.join("session.funsrepl");
+        let path = path.display();
+
+        // Only the declarations of the inputs that ran are saved
+        let (_, out, errors) = session(&[
+            "x: int = 20",
+            "print \"hi\"",
+            "square: (int) -> int = (n) -> n * n ;",
+            "y: int = x / 0",
+            "data Shape = Circle int | Square int",
+            &format!(":save {}", path),
+        ]);
+        assert_eq!(out, format!("hi\nSaved 3 input(s) to {}\n", path));
+        assert!(errors.contains("<repl-4>:1:"), "{}", errors);
+        let saved = std::fs::read_to_string(dir.join("session.funsrepl")).unwrap();
+        let restored = Session::from_json(&saved).unwrap();
+        assert_eq!(restored.inputs.len(), 3);
+
+        // A restored session runs the declarations again, without printing
+        let (_, out, errors) = session(&[&format!(":restore {}", path), "square x"]);
+        assert_eq!(out, format!("Restored 3 input(s) from {}\n400\n", path));
+        assert_eq!(errors, "");
+
+        // It is saved again with the inputs after it, the same way
+        let again = dir.join("again.funsrepl");
+        session(&[
+            &format!(":restore {}", path),
+            &format!(":save {}", again.display()),
+        ]);
+        assert_eq!(std::fs::read_to_string(&again).unwrap(), saved);
+
+        let (_, out, errors) = session(&[
+            ":restore",
+            ":load x",
+            &format!(":restore {}", dir.join("missing").display()),
+            "f: (int) -> int = (n) ->",
+            ":save",
+            "  n ;",
+        ]);
+        assert_eq!(out, "");
+        assert!(errors.contains("Missing file for `:restore`"), "{}", errors);
+        assert!(errors.contains("Unknown command `:load`"), "{}", errors);
+        assert!(errors.contains("missing: "), "{}", errors);
+
+        std::fs::write(
+            &again,
+            saved.replacen("\"version\": 1", "\"version\": 9", 1),
+        )
+        .unwrap();
+        let (_, _, errors) = session(&[&format!(":restore {}", again.display())]);
+        assert!(
+            errors.contains("Session version 9 is not supported"),
+            "{}",
+            errors
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_repl_errors() {
+        // A parse error is reported at once, and the next inputs still run
+        let (prompts, out, errors) = session(&["x: int ~ 1", "1 + 1"]);
+        assert_eq!(prompts, vec![Prompt::Input, Prompt::Input]);
+        assert_eq!(out, "2\n");
+        assert!(errors.contains("Expected `=`, found `~`"), "{}", errors);
+        assert!(errors.contains("<repl-1>:1:"), "{}", errors);
+
+        // A runtime error does not lose the bindings
+        let (_, out, errors) = session(&["x: int = 4", "x / 0", "x"]);
+        assert_eq!(out, "4\n");
+        assert!(errors.contains("<repl-2>:1:"), "{}", errors);
+
+        // An incomplete input is reported on an empty line, or at the end
+        let (prompts, out, errors) =
+            session(&["f: (int) -> int = (n) ->", "", "g: (int) -> int = (n) ->"]);
+        assert_eq!(
+            prompts,
+            vec![Prompt::Continuation, Prompt::Input, Prompt::Continuation]
+        );
+        assert_eq!(out, "");
+        assert!(errors.contains("<repl-1>:"), "{}", errors);
+        assert!(errors.contains("<repl-2>:"), "{}", errors);
+
+        // A warning is reported, and the input still runs
+        let (_, out, errors) = session(&[
+            "f: (int) -> int = (n) -> if n == 0 then 0 else 1 + f (n - 1) ;",
+            "f 3",
+        ]);
+        assert_eq!(out, "3\n");
+        assert!(errors.contains("not a tail call"), "{}", errors);
+
+        // A statement ends with its line
+        let (prompts, _, errors) = session(&["1 +"]);
+        assert_eq!(prompts, vec![Prompt::Input]);
+        assert!(errors.contains("Expected expression"), "{}", errors);
+
+        // A block comment spans lines
+        let (prompts, out, _) = session(&["#| a", "b |#", "3"]);
+        assert_eq!(
+            prompts,
+            vec![Prompt::Continuation, Prompt::Input, Prompt::Input]
+        );
+        assert_eq!(out, "3\n");
+    }
+}
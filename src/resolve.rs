@@ -0,0 +1,645 @@
+//! Finds the declaration each name refers to.
+//!
+//! `duplicates` works on the CST, so that the names declared twice are reported even in
+//! a file with parse errors. `resolve` works on the AST: it gives every declared name a
+//! `SymbolId` and records the one each name expression and constructor pattern refers
+//! to, so that later phases can look a name up by the id of its node.
+use crate::diagnostics::messages::{Code, Message};
+use crate::diagnostics::Diagnostic;
+use crate::lexer::token::{Token, TokenLocation};
+use crate::parser::ast::{Expr, ExprKind, File, FunDecl, Pattern, PatternKind, Stmt};
+use crate::parser::node_id::{NodeId, Span};
+use crate::parser::{Tree, TreeKind};
+use std::collections::BTreeMap;
+
+/// A top-level name declared more than once in a module.
+///
+/// Top-level declarations never shadow each other: a module is a flat namespace, so a
+/// second declaration of a name is always an error, whether it is a function or a
+/// variable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Duplicate {
+    pub name: String,
+    /// Where the name is declared first
+    pub first: TokenLocation,
+    /// Where the name is declared again
+    pub second: TokenLocation,
+}
+
+impl Duplicate {
+    pub fn message(&self) -> Message {
+        Message::new(Code::DuplicateDeclaration)
+            .with_arg("name", &self.name)
+            .with_arg("first", &self.first)
+            .with_arg("second", &self.second)
+    }
+}
+
+impl std::fmt::Display for Duplicate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+/// Finds the top-level names of `file` that are declared more than once, in source
+/// order. A name declared three times is reported twice, each time against its first
+/// declaration.
+pub fn duplicates(file: &Tree) -> Vec<Duplicate> {
+    let mut declared = BTreeMap::<&str, &Token>::new();
+    let mut duplicates = Vec::new();
+    for name in file.child_trees().flat_map(declared_names) {
+        match declared.get(name.lexeme.as_str()) {
+            Some(first) => duplicates.push(Duplicate {
+                name: name.lexeme.clone(),
+                first: first.location.clone(),
+                second: name.location.clone(),
+            }),
+            None => {
+                declared.insert(&name.lexeme, name);
+            }
+        }
+    }
+    duplicates
+}
+
+/// The identifiers introduced by a top-level declaration, the constructors of a variant
+/// are values like functions and variables
+fn declared_names(tree: &Tree) -> Vec<&Token> {
+    match tree.kind() {
+        TreeKind::StmtVarDecl | TreeKind::StmtFunDecl => tree.declared_names(),
+        TreeKind::DeclVariant => tree
+            .child_trees()
+            .filter(|child| *child.kind() == TreeKind::VariantCase)
+            .filter_map(Tree::declared_name)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The id of a declared name, its index in the order `resolve` declares the names, so
+/// resolving the same file again gives the same ids
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SymbolId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefinitionKind {
+    /// A function every program can call, e.g. `print`
+    Builtin,
+    /// A name of a variable or pattern declaration
+    Variable,
+    Function,
+    /// A case of a variant
+    Constructor,
+    Parameter,
+    /// A name of the pattern of a `match` arm
+    Binding,
+}
+
+/// A declared name
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definition {
+    pub name: String,
+    pub kind: DefinitionKind,
+    /// Where the name is declared, `None` for a builtin
+    pub span: Option<Span>,
+}
+
+/// The names declared in a file and the declaration each use of a name refers to
+#[derive(Debug, Default)]
+pub struct Resolution {
+    definitions: Vec<Definition>,
+    /// The declaration of each name expression and constructor pattern, by id
+    uses: BTreeMap<NodeId, SymbolId>,
+}
+
+impl Resolution {
+    pub fn definition(&self, id: SymbolId) -> &Definition {
+        &self.definitions[id.0 as usize]
+    }
+
+    /// The declaration the name expression or the constructor pattern `node` refers to,
+    /// `None` for any other node or for a name that is not defined
+    pub fn symbol(&self, node: NodeId) -> Option<SymbolId> {
+        self.uses.get(&node).copied()
+    }
+
+    /// Every declared name, in the order of their ids
+    pub fn definitions(&self) -> impl Iterator<Item = (SymbolId, &Definition)> {
+        (0..).map(SymbolId).zip(self.definitions.iter())
+    }
+}
+
+/// The names every program can use without declaring them
+const BUILTINS: [&str; 1] = ["print"];
+
+/// Resolves the names of `file`, returning the declaration of each use along with the
+/// names that are not defined and the names declared twice in a function or in the
+/// pattern of a `match` arm, in source order.
+///
+/// Like in the interpreter, constructors and functions can be used anywhere in the
+/// statements declaring them, variables only after their declaration. The body of a
+/// function runs when it is called, so it is resolved where the function is first used,
+/// with the variables declared before that use, or once all the statements around it
+/// are if it is not used there. A function body is a flat namespace like the module: its
+/// parameters and its declarations are declared once.
+pub fn resolve(file: &File) -> (Resolution, Vec<Diagnostic>) {
+    resolve_with_imports(file, &[])
+}
+
+/// Resolves the names of `file` like `resolve`, with the top-level declarations of the
+/// modules it imports in scope, see `modules::imported`. The declarations of `file`
+/// shadow them.
+pub fn resolve_with_imports(file: &File, imports: &[&File]) -> (Resolution, Vec<Diagnostic>) {
+    let mut resolver = Resolver {
+        resolution: Resolution::default(),
+        scopes: vec![BTreeMap::new()],
+        pending: BTreeMap::new(),
+        diagnostics: Vec::new(),
+    };
+    for name in BUILTINS {
+        resolver.declare(name, DefinitionKind::Builtin, None);
+    }
+    for module in imports {
+        resolver.import(module);
+    }
+    resolver.scopes.push(BTreeMap::new());
+    resolver.stmts(&file.stmts);
+
+    let mut diagnostics = resolver.diagnostics;
+    diagnostics
+        .sort_by_key(|diagnostic| (diagnostic.location.line, diagnostic.location.column_start));
+    (resolver.resolution, diagnostics)
+}
+
+struct Resolver<'a> {
+    resolution: Resolution,
+    /// The names in scope, the builtins and the imported names first, then the module,
+    /// the innermost scope last
+    scopes: Vec<BTreeMap<String, SymbolId>>,
+    /// The functions declared whose body is not resolved yet, since they are not used yet
+    pending: BTreeMap<SymbolId, &'a FunDecl>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Resolver<'a> {
+    /// Declares `name` in the innermost scope, shadowing any previous declaration
+    fn declare(&mut self, name: &str, kind: DefinitionKind, span: Option<&Span>) -> SymbolId {
+        let id = SymbolId(self.resolution.definitions.len() as u32);
+        // The duplicates of the module are found on the CST, see `duplicates`
+        if let (Some(first), Some(second), true) = (
+            self.declared_here(name)
+                .and_then(|first| first.span.as_ref()),
+            span,
+            self.scopes.len() > 2,
+        ) {
+            let message = Message::new(Code::DuplicateDeclaration)
+                .with_arg("name", name)
+                .with_arg("first", &first.start)
+                .with_arg("second", &second.start);
+            self.diagnostics
+                .push(Diagnostic::error(message, second.location()));
+        }
+        self.resolution.definitions.push(Definition {
+            name: name.to_string(),
+            kind,
+            span: span.cloned(),
+        });
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), id);
+        }
+        id
+    }
+
+    /// The declaration of `name` in the innermost scope
+    fn declared_here(&self, name: &str) -> Option<&Definition> {
+        let id = self.scopes.last()?.get(name)?;
+        Some(self.resolution.definition(*id))
+    }
+
+    /// Records the declaration `name` refers to at `node`, or reports that it is not
+    /// defined. A function used for the first time has its body resolved here, in the
+    /// scopes it is declared in.
+    fn refer(&mut self, node: NodeId, name: &str, span: &Span) {
+        let found = self
+            .scopes
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(depth, scope)| Some((depth, *scope.get(name)?)));
+        match found {
+            Some((depth, id)) => {
+                self.resolution.uses.insert(node, id);
+                if let Some(decl) = self.pending.remove(&id) {
+                    let inner = self.scopes.split_off(depth + 1);
+                    self.fun_decl(decl);
+                    self.scopes.extend(inner);
+                }
+            }
+            None => {
+                let message = Message::new(Code::UnknownName).with_arg("name", name);
+                self.diagnostics
+                    .push(Diagnostic::error(message, span.location()));
+            }
+        }
+    }
+
+    /// Declares the top-level names of the imported `module`
+    fn import(&mut self, module: &File) {
+        for stmt in &module.stmts {
+            match stmt {
+                Stmt::VarDecl(decl) => {
+                    let kind = DefinitionKind::Variable;
+                    self.declare(&decl.name.name, kind, Some(&decl.name.span));
+                }
+                Stmt::PatternDecl(decl) => {
+                    for (name, span) in pattern_names(&decl.pattern) {
+                        self.declare(name, DefinitionKind::Variable, Some(span));
+                    }
+                }
+                Stmt::FunDecl(decl) => {
+                    let kind = DefinitionKind::Function;
+                    self.declare(&decl.name.name, kind, Some(&decl.name.span));
+                }
+                Stmt::Variant(decl) => {
+                    for case in &decl.cases {
+                        let kind = DefinitionKind::Constructor;
+                        self.declare(&case.name.name, kind, Some(&case.name.span));
+                    }
+                }
+                Stmt::Import(_) | Stmt::Data(_) | Stmt::Expr(_) => {}
+            }
+        }
+    }
+
+    fn stmts(&mut self, stmts: &'a [Stmt]) {
+        let mut functions = Vec::new();
+        for stmt in stmts {
+            match stmt {
+                Stmt::Variant(decl) => {
+                    for case in &decl.cases {
+                        let kind = DefinitionKind::Constructor;
+                        self.declare(&case.name.name, kind, Some(&case.name.span));
+                    }
+                }
+                Stmt::FunDecl(decl) => {
+                    let kind = DefinitionKind::Function;
+                    let id = self.declare(&decl.name.name, kind, Some(&decl.name.span));
+                    self.pending.insert(id, decl);
+                    functions.push(id);
+                }
+                _ => {}
+            }
+        }
+        for stmt in stmts {
+            match stmt {
+                Stmt::VarDecl(decl) => {
+                    self.expr(&decl.value);
+                    let kind = DefinitionKind::Variable;
+                    self.declare(&decl.name.name, kind, Some(&decl.name.span));
+                }
+                Stmt::PatternDecl(decl) => {
+                    self.expr(&decl.value);
+                    self.pattern(&decl.pattern, DefinitionKind::Variable);
+                }
+                Stmt::Import(_) | Stmt::Data(_) | Stmt::Variant(_) | Stmt::FunDecl(_) => {}
+                Stmt::Expr(expr) => self.expr(expr),
+            }
+        }
+        // The functions not used in the statements see all their variables
+        for id in functions {
+            if let Some(decl) = self.pending.remove(&id) {
+                self.fun_decl(decl);
+            }
+        }
+    }
+
+    fn fun_decl(&mut self, decl: &'a FunDecl) {
+        self.scopes.push(BTreeMap::new());
+        for param in &decl.params {
+            self.declare(&param.name, DefinitionKind::Parameter, Some(&param.span));
+        }
+        self.stmts(&decl.body);
+        self.scopes.pop();
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Int(_)
+            | ExprKind::Float(_)
+            | ExprKind::Bool(_)
+            | ExprKind::Str(_)
+            | ExprKind::Hole(_) => {}
+            ExprKind::Name(name) => self.refer(expr.id, name, &expr.span),
+            ExprKind::Unary { expr, .. }
+            | ExprKind::Cast { expr, .. }
+            | ExprKind::Ascription { expr, .. }
+            | ExprKind::Field { expr, .. } => self.expr(expr),
+            ExprKind::Binary { lhs, rhs, .. } => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            ExprKind::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                self.expr(cond);
+                self.expr(then_branch);
+                self.expr(else_branch);
+            }
+            ExprKind::List(exprs) | ExprKind::Tuple(exprs) => {
+                exprs.iter().for_each(|expr| self.expr(expr))
+            }
+            ExprKind::Record(fields) => fields.iter().for_each(|field| self.expr(&field.value)),
+            ExprKind::Call { callee, args } => {
+                self.expr(callee);
+                args.iter().for_each(|arg| self.expr(arg));
+            }
+            ExprKind::Match { scrutinee, arms } => {
+                self.expr(scrutinee);
+                for arm in arms {
+                    self.scopes.push(BTreeMap::new());
+                    self.pattern(&arm.pattern, DefinitionKind::Binding);
+                    self.expr(&arm.body);
+                    self.scopes.pop();
+                }
+            }
+        }
+    }
+
+    /// Resolves the constructors of `pattern` and declares its names as `kind`
+    fn pattern(&mut self, pattern: &Pattern, kind: DefinitionKind) {
+        match &pattern.kind {
+            PatternKind::Int(_)
+            | PatternKind::Float(_)
+            | PatternKind::Bool(_)
+            | PatternKind::Str(_)
+            | PatternKind::Wildcard => {}
+            PatternKind::Name(name) => {
+                self.declare(name, kind, Some(&pattern.span));
+            }
+            PatternKind::Tuple(patterns) => patterns
+                .iter()
+                .for_each(|pattern| self.pattern(pattern, kind)),
+            PatternKind::Constructor { name, fields } => {
+                self.refer(pattern.id, name, &pattern.span);
+                fields
+                    .iter()
+                    .for_each(|pattern| self.pattern(pattern, kind));
+            }
+        }
+    }
+}
+
+/// The names bound by `pattern` along with their span, in source order
+fn pattern_names(pattern: &Pattern) -> Vec<(&str, &Span)> {
+    match &pattern.kind {
+        PatternKind::Name(name) => vec![(name, &pattern.span)],
+        PatternKind::Tuple(patterns)
+        | PatternKind::Constructor {
+            fields: patterns, ..
+        } => patterns.iter().flat_map(pattern_names).collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::lower::lower;
+    use crate::parser::snippet::parse_snippet;
+
+    fn resolve_snippet(content: &str) -> (File, Resolution, Vec<String>) {
+        let (file, errors) = lower(&parse_snippet(content).unwrap());
+        assert!(errors.is_empty(), "{:?}", errors);
+        let (resolution, diagnostics) = resolve(&file);
+        let messages = diagnostics
+            .iter()
+            .map(|diagnostic| format!("{}: {}", diagnostic.location, diagnostic.message))
+            .collect();
+        (file, resolution, messages)
+    }
+
+    #[test]
+    fn test_resolve_duplicates() {
+        let tree =
+            parse_snippet("x: int = 1\ny: int = 2\nx: float = 3.0\nx: str = \"\"\n").unwrap();
+        let messages = duplicates(&tree)
+            .iter()
+            .map(|duplicate| duplicate.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "`x` is declared more than once, first at <snippet>:1:1, again at <snippet>:3:1",
+                "`x` is declared more than once, first at <snippet>:1:1, again at <snippet>:4:1",
+            ]
+        );
+
+        // The names of a tuple pattern are declared too
+        let tree = parse_snippet("(a, (b, _)): (int, (int, int)) = ?t\nb: int = 1\n").unwrap();
+        let names = duplicates(&tree)
+            .iter()
+            .map(|duplicate| duplicate.name.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["b"]);
+    }
+
+    #[test]
+    fn test_resolve_duplicate_constructors() {
+        let tree = parse_snippet("data A = X | Y int\ndata B = Y\nX: int = 1\n").unwrap();
+        let names = duplicates(&tree)
+            .iter()
+            .map(|duplicate| duplicate.name.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["Y", "X"]);
+    }
+
+    #[test]
+    fn test_resolve_no_duplicates() {
+        let tree = parse_snippet("# x: int = 1\nx: int = 1; y: int = 2\n").unwrap();
+        assert!(duplicates(&tree).is_empty());
+
+        // A function and a variable share the namespace of the module
+        let tree = parse_snippet("f: int = 1\nf: () -> int = () -> 1 ;\n").unwrap();
+        assert_eq!(duplicates(&tree).len(), 1);
+    }
+
+    #[test]
+    fn test_resolve_symbols() {
+        let content = "\
+f: (int) -> int = (n) -> n + g n ;
+g: (int) -> int = (n) -> n * x ;
+x: int = 1
+print x
+";
+        let (file, resolution, messages) = resolve_snippet(content);
+        assert!(messages.is_empty(), "{:?}", messages);
+        let definitions = resolution
+            .definitions()
+            .map(|(id, definition)| (id.0, definition.name.as_str(), definition.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            definitions,
+            vec![
+                (0, "print", DefinitionKind::Builtin),
+                (1, "f", DefinitionKind::Function),
+                (2, "g", DefinitionKind::Function),
+                (3, "x", DefinitionKind::Variable),
+                (4, "n", DefinitionKind::Parameter),
+                (5, "n", DefinitionKind::Parameter),
+            ]
+        );
+
+        // `print x` refers to the builtin and to the variable
+        let Stmt::Expr(expr) = &file.stmts[3] else {
+            panic!("expected an expression, found {:?}", file.stmts[3]);
+        };
+        let ExprKind::Call { callee, args } = &expr.kind else {
+            panic!("expected a call, found {:?}", expr.kind);
+        };
+        assert_eq!(resolution.symbol(callee.id), Some(SymbolId(0)));
+        assert_eq!(resolution.symbol(args[0].id), Some(SymbolId(3)));
+        assert_eq!(resolution.symbol(expr.id), None);
+
+        // Resolving again gives the same ids
+        let (_, again, _) = resolve_snippet(content);
+        assert_eq!(again.uses, resolution.uses);
+    }
+
+    #[test]
+    fn test_resolve_function_use() {
+        // A function called before a variable its body uses is declared sees it undefined,
+        // like in the interpreter
+        let content = "\
+f: (int) -> int = (x) -> x + y ;
+z: int = f 1
+y: int = 2
+";
+        let (_, _, messages) = resolve_snippet(content);
+        assert_eq!(messages, vec!["<snippet>:1:30: `y` is not defined"]);
+
+        // Through the functions it calls too, but not when it is called after
+        let content = "\
+f: (int) -> int = (x) -> g x ;
+g: (int) -> int = (x) -> x + y ;
+y: int = 2
+z: int = f 1
+";
+        let (_, _, messages) = resolve_snippet(content);
+        assert!(messages.is_empty(), "{:?}", messages);
+        let (_, _, messages) = resolve_snippet(&format!("a: int = f 0\n{}", content));
+        assert_eq!(messages, vec!["<snippet>:3:30: `y` is not defined"]);
+
+        // A recursive function resolves its own body once
+        let content = "\
+n: int = 3
+count: (int) -> int = (x) -> if x == n then x else count (x + 1) ;
+z: int = count 0
+";
+        let (_, _, messages) = resolve_snippet(content);
+        assert!(messages.is_empty(), "{:?}", messages);
+    }
+
+    #[test]
+    fn test_resolve_scopes() {
+        let content = "\
+data Shape = Circle float | Square float
+area: (Shape) -> float = (s) ->
+  match s
+  | Circle r => r * r
+  | Square side => side * r
+  ;
+;
+y: int = y
+z: int = n
+";
+        let (_, resolution, messages) = resolve_snippet(content);
+        // The names of an arm, the parameters and the variable being declared are not in
+        // scope elsewhere
+        assert_eq!(
+            messages,
+            vec![
+                "<snippet>:5:27: `r` is not defined",
+                "<snippet>:8:10: `y` is not defined",
+                "<snippet>:9:10: `n` is not defined",
+            ]
+        );
+        let kinds = resolution
+            .definitions()
+            .filter(|(_, definition)| definition.name == "r")
+            .map(|(_, definition)| definition.kind)
+            .collect::<Vec<_>>();
+        assert_eq!(kinds, vec![DefinitionKind::Binding]);
+
+        let (_, _, messages) = resolve_snippet("n: int = match 1 | Some x => x ;\n");
+        assert_eq!(messages, vec!["<snippet>:1:20: `Some` is not defined"]);
+    }
+
+    #[test]
+    fn test_resolve_duplicate_names() {
+        let content = "\
+f: (int, int) -> int = (a, a) -> a ;
+g: (int) -> int = (a) ->
+  a: int = 1
+  match (a, a)
+  | (b, b) => b
+  ;
+;
+";
+        let (_, _, messages) = resolve_snippet(content);
+        assert_eq!(
+            messages,
+            vec![
+                "<snippet>:1:28: `a` is declared more than once, first at <snippet>:1:25, again at <snippet>:1:28",
+                "<snippet>:3:3: `a` is declared more than once, first at <snippet>:2:20, again at <snippet>:3:3",
+                "<snippet>:5:9: `b` is declared more than once, first at <snippet>:5:6, again at <snippet>:5:9",
+            ]
+        );
+
+        // The duplicates of the module are left to `duplicates`
+        let (_, _, messages) =
+            resolve_snippet("x: int = 1\nx: int = 2\n(y, y): (int, int) = (1, 2)\n");
+        assert!(messages.is_empty(), "{:?}", messages);
+    }
+
+    #[test]
+    fn test_resolve_imports() {
+        let module = "data D = A | B int\n(x, y): (int, int) = (1, 2)\nsquare: (int) -> int = (n) -> n * n ;\n";
+        let (module, errors) = lower(&parse_snippet(module).unwrap());
+        assert!(errors.is_empty(), "{:?}", errors);
+        let content = "import m\nz: int = square x + y\nd: D = B z\ny: int = 3\nw: int = y + n\n";
+        let (file, errors) = lower(&parse_snippet(content).unwrap());
+        assert!(errors.is_empty(), "{:?}", errors);
+        let (resolution, diagnostics) = resolve_with_imports(&file, &[&module]);
+
+        // The parameters of an imported function are not in scope
+        let messages = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(messages, vec!["`n` is not defined"]);
+        // Each use with the kind of its declaration and its line, the imported `y` until
+        // the file declares its own
+        let uses = resolution
+            .uses
+            .values()
+            .map(|id| {
+                let definition = resolution.definition(*id);
+                let line = definition.span.as_ref().map(|span| span.start.line);
+                (definition.name.as_str(), definition.kind, line)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            uses,
+            vec![
+                ("square", DefinitionKind::Function, Some(2)),
+                ("x", DefinitionKind::Variable, Some(1)),
+                ("y", DefinitionKind::Variable, Some(1)),
+                ("B", DefinitionKind::Constructor, Some(0)),
+                ("z", DefinitionKind::Variable, Some(1)),
+                ("y", DefinitionKind::Variable, Some(3)),
+            ]
+        );
+    }
+}
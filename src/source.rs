@@ -1,22 +1,191 @@
+use crate::config::Config;
+use crate::diagnostics::messages::{Code, Message};
+use crate::lexer::token::TokenLocation;
 use serde::{Deserialize, Serialize};
-use std::fs;
+use std::fs::File;
+use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use tracing::info;
 
+/// The size in bytes above which a file is not read, unless `funs.toml` sets another
+/// one with `max_file_size`
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// The bytes shown on each side of a sequence that is not UTF-8
+const UTF8_CONTEXT: usize = 4;
+
+#[derive(Debug)]
+pub enum SourceError {
+    Io {
+        file_path: PathBuf,
+        error: std::io::Error,
+    },
+    /// The file is larger than the limit, it is not read
+    TooLarge {
+        file_path: PathBuf,
+        size: u64,
+        limit: u64,
+    },
+    /// The file is not UTF-8, `error` is its first invalid sequence
+    InvalidUtf8 {
+        file_path: PathBuf,
+        error: InvalidUtf8,
+    },
+}
+
+impl std::fmt::Display for SourceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SourceError::Io { file_path, error } => {
+                write!(
+                    f,
+                    "Error reading file \"{}\": {}",
+                    file_path.display(),
+                    error
+                )
+            }
+            SourceError::TooLarge {
+                file_path,
+                size,
+                limit,
+            } => {
+                write!(
+                    f,
+                    "File \"{}\" is too large: {} bytes, the limit is {} bytes (`max_file_size` in funs.toml)",
+                    file_path.display(),
+                    size,
+                    limit
+                )
+            }
+            SourceError::InvalidUtf8 { file_path, error } => {
+                write!(
+                    f,
+                    "File \"{}\" is not valid UTF-8, at byte {}: {} (`lossy_utf8` in funs.toml checks the rest of it)",
+                    file_path.display(),
+                    error.offset,
+                    error.bytes
+                )
+            }
+        }
+    }
+}
+
+/// A sequence of bytes of a source file that is not UTF-8
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidUtf8 {
+    /// Where the sequence starts in the file, in bytes
+    pub offset: usize,
+    /// The bytes around the sequence in hexadecimal, the sequence in brackets, e.g.
+    /// `61 62 [ff] 63`
+    pub bytes: String,
+    /// The location of the `U+FFFD` replacing the sequence in the content
+    pub location: TokenLocation,
+}
+
+impl InvalidUtf8 {
+    pub fn message(&self) -> Message {
+        Message::new(Code::InvalidUtf8)
+            .with_arg("offset", self.offset)
+            .with_arg("bytes", &self.bytes)
+    }
+}
+
+impl std::fmt::Display for InvalidUtf8 {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+/// A source file and its content.
+///
+/// Cloning a source shares its content instead of copying it, e.g. the lexer keeps its
+/// own clone.
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct Source {
     file_path: PathBuf,
-    content: String,
+    content: Rc<str>,
+    /// Whether the source lives in memory (e.g. `<repl-3>`) instead of on disk
+    #[serde(default)]
+    is_virtual: bool,
+    /// The sequences that were not UTF-8 in the file, see `lossy`
+    #[serde(skip)]
+    invalid_utf8: Vec<InvalidUtf8>,
 }
 
 impl Source {
-    pub fn new(file_path: impl AsRef<Path>) -> Source {
+    pub fn new(file_path: impl AsRef<Path>) -> Result<Source, SourceError> {
+        Source::with_limit(file_path, DEFAULT_MAX_FILE_SIZE)
+    }
+
+    /// Reads the file at `file_path` if it is at most `limit` bytes long and UTF-8.
+    ///
+    /// The size on disk is checked before reading, and the file is read only up to the
+    /// limit in case it grows in the meantime or has no size, e.g. a pipe.
+    pub fn with_limit(file_path: impl AsRef<Path>, limit: u64) -> Result<Source, SourceError> {
+        let source = Source::lossy(file_path, limit)?;
+        match source.invalid_utf8.first() {
+            Some(error) => Err(SourceError::InvalidUtf8 {
+                file_path: source.file_path.clone(),
+                error: error.clone(),
+            }),
+            None => Ok(source),
+        }
+    }
+
+    /// Reads the file at `file_path` like `with_limit`, but replaces each sequence that
+    /// is not UTF-8 with `U+FFFD` instead of failing, so that the rest of the file can
+    /// still be checked. The sequences are kept in `invalid_utf8`.
+    pub fn lossy(file_path: impl AsRef<Path>, limit: u64) -> Result<Source, SourceError> {
         let file_path = file_path.as_ref().to_path_buf();
-        let content = fs::read_to_string(&file_path).unwrap_or_else(|e| {
-            panic!("Error reading file \"{}\": {}", file_path.display(), e);
-        });
+        let io_error = |error| SourceError::Io {
+            file_path: file_path.clone(),
+            error,
+        };
+        let file = File::open(&file_path).map_err(io_error)?;
+        let size = file.metadata().map_err(io_error)?.len();
+        if size > limit {
+            return Err(SourceError::TooLarge {
+                file_path,
+                size,
+                limit,
+            });
+        }
+        let mut bytes = Vec::with_capacity(size as usize);
+        file.take(limit.saturating_add(1))
+            .read_to_end(&mut bytes)
+            .map_err(io_error)?;
+        if bytes.len() as u64 > limit {
+            return Err(SourceError::TooLarge {
+                file_path,
+                size: bytes.len() as u64,
+                limit,
+            });
+        }
+        let (content, invalid_utf8) = decode(&file_path, &bytes);
         info!("Created Source from file \"{}\"", file_path.display());
-        Source { file_path, content }
+        Ok(Source {
+            file_path,
+            content: content.into(),
+            is_virtual: false,
+            invalid_utf8,
+        })
+    }
+
+    /// Creates an in-memory source named `<name>`, e.g. `<repl-3>` or `<doctest>`.
+    pub fn from_snippet(name: &str, content: String) -> Source {
+        let file_path = PathBuf::from(format!("<{}>", name));
+        info!("Created Source from snippet \"{}\"", file_path.display());
+        Source {
+            file_path,
+            content: content.into(),
+            is_virtual: true,
+            invalid_utf8: Vec::new(),
+        }
+    }
+
+    pub fn is_virtual(&self) -> bool {
+        self.is_virtual
     }
 
     pub fn file_path(&self) -> &PathBuf {
@@ -27,16 +196,241 @@ impl Source {
         &self.content
     }
 
-    pub fn content_mut(&mut self) -> &mut String {
-        &mut self.content
+    /// The sequences replaced by `lossy`, in the order of the file
+    pub fn invalid_utf8(&self) -> &[InvalidUtf8] {
+        &self.invalid_utf8
+    }
+}
+
+/// Reads the source at `path`, up to the `max_file_size` of the `funs.toml` that applies
+/// to it and replacing the bytes that are not UTF-8 if it sets `lossy_utf8`. A
+/// configuration that can not be read falls back to the defaults.
+///
+/// Every source file of a program is read this way, the entry file and its modules.
+pub fn read_source(path: &Path) -> Result<Source, SourceError> {
+    let config = Config::discover(path)
+        .map(|config| config.compiler)
+        .unwrap_or_default();
+    match config.lossy_utf8 {
+        true => Source::lossy(path, config.max_file_size),
+        false => Source::with_limit(path, config.max_file_size),
+    }
+}
+
+/// Decodes `bytes` like `String::from_utf8_lossy`, returning the sequences it replaced
+/// along with the content.
+fn decode(file_path: &Path, bytes: &[u8]) -> (String, Vec<InvalidUtf8>) {
+    let hex = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let mut content = String::with_capacity(bytes.len());
+    let mut invalid_utf8 = Vec::new();
+    let mut offset = 0;
+    let (mut line, mut line_start) = (0, 0);
+    for chunk in bytes.utf8_chunks() {
+        let valid = chunk.valid();
+        if let Some(last) = valid.rfind('\n') {
+            line += valid.matches('\n').count();
+            line_start = content.len() + last + 1;
+        }
+        content.push_str(valid);
+        offset += valid.len();
+        let invalid = chunk.invalid();
+        if invalid.is_empty() {
+            continue;
+        }
+        let before = &bytes[offset.saturating_sub(UTF8_CONTEXT)..offset];
+        let end = (offset + invalid.len() + UTF8_CONTEXT).min(bytes.len());
+        let after = &bytes[offset + invalid.len()..end];
+        let bytes = [hex(before), format!("[{}]", hex(invalid)), hex(after)]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let column = content[line_start..].chars().count();
+        invalid_utf8.push(InvalidUtf8 {
+            offset,
+            bytes,
+            location: TokenLocation::new(file_path.to_path_buf(), line, column, column + 1),
+        });
+        content.push(char::REPLACEMENT_CHARACTER);
+        offset += invalid.len();
     }
+    (content, invalid_utf8)
 }
 
 impl From<String> for Source {
     fn from(content: String) -> Source {
         Source {
             file_path: PathBuf::new(),
-            content,
+            content: content.into(),
+            is_virtual: true,
+            invalid_utf8: Vec::new(),
+        }
+    }
+}
+
+/// The sources known to a compilation, both read from disk and virtual.
+///
+/// Token locations only carry a file path, so diagnostics use the map to get back to the
+/// text of the source they point into.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    sources: Vec<Source>,
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap::default()
+    }
+
+    /// Adds a source to the map, replacing a previous source with the same path.
+    pub fn add(&mut self, source: Source) {
+        match self
+            .sources
+            .iter_mut()
+            .find(|known| known.file_path == source.file_path)
+        {
+            Some(known) => *known = source,
+            None => self.sources.push(source),
         }
     }
+
+    pub fn get(&self, file_path: &Path) -> Option<&Source> {
+        self.sources
+            .iter()
+            .find(|source| source.file_path == file_path)
+    }
+
+    /// Returns the 0-based `line` of the source at `file_path`, without the line terminator.
+    pub fn line(&self, file_path: &Path, line: usize) -> Option<&str> {
+        self.get(file_path)?.content.lines().nth(line)
+    }
+
+    pub fn sources(&self) -> &[Source] {
+        &self.sources
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_new_missing_file() {
+        let result = Source::new("./testdata/does_not_exist.fs");
+        assert!(matches!(result, Err(SourceError::Io { .. })));
+    }
+
+    #[test]
+    fn test_source_with_limit() {
+        let path = "./testdata/native_types/id_int_assign.fs";
+        let size = std::fs::metadata(path).unwrap().len();
+        assert!(Source::with_limit(path, size).is_ok());
+        let Err(SourceError::TooLarge {
+            size: found, limit, ..
+        }) = Source::with_limit(path, size - 1)
+        else {
+            panic!("{} bytes read with a limit of {}", size, size - 1);
+        };
+        assert_eq!((found, limit), (size, size - 1));
+        assert!(matches!(
+            Source::with_limit("./testdata/does_not_exist.fs", 0),
+            Err(SourceError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn test_source_invalid_utf8() {
+        let path = "./testdata/encoding/invalid_utf8.fs";
+        let Err(SourceError::InvalidUtf8 { error, .. }) = Source::new(path) else {
+            panic!("{} read as UTF-8", path);
+        };
+        assert_eq!(error.offset, 22);
+        assert_eq!(error.bytes, "3d 20 22 61 [ff] 62 22 0a 7a");
+
+        // The rest of the file is kept
+        let source = Source::lossy(path, DEFAULT_MAX_FILE_SIZE).unwrap();
+        assert_eq!(
+            source.content(),
+            "x: int = 1\ny: str = \"a\u{fffd}b\"\nz: int = 2\n"
+        );
+        assert_eq!(source.invalid_utf8(), &[error]);
+        let location = &source.invalid_utf8()[0].location;
+        assert_eq!((location.line, location.column_start), (1, 11));
+    }
+
+    #[test]
+    fn test_source_decode() {
+        let file_path = Path::new("a.fs");
+        let (content, invalid) = decode(file_path, "é\n".as_bytes());
+        assert_eq!(content, "é\n");
+        assert!(invalid.is_empty());
+
+        // A truncated sequence at the end, and an invalid one after a multi-byte character
+        let (content, invalid) = decode(file_path, b"\xc3\xa9\xff\n\xe2\x82");
+        assert_eq!(content, "é\u{fffd}\n\u{fffd}");
+        let found = invalid
+            .iter()
+            .map(|invalid| {
+                let location = &invalid.location;
+                (
+                    invalid.offset,
+                    invalid.bytes.as_str(),
+                    location.line,
+                    location.column_start,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                (2, "c3 a9 [ff] 0a e2 82", 0, 1),
+                (4, "c3 a9 ff 0a [e2 82]", 1, 0)
+            ]
+        );
+        assert_eq!(
+            invalid[0].to_string(),
+            "invalid UTF-8 at byte 2: c3 a9 [ff] 0a e2 82"
+        );
+    }
+
+    #[test]
+    fn test_source_clone_shares_content() {
+        let source = Source::new("./testdata/native_types/id_int_assign.fs").unwrap();
+        let clone = source.clone();
+        assert!(std::ptr::eq(source.content(), clone.content()));
+    }
+
+    #[test]
+    fn test_source_from_snippet() {
+        let source = Source::from_snippet("repl-3", "x: int = 1\n".to_string());
+        assert!(source.is_virtual());
+        assert_eq!(source.file_path(), &PathBuf::from("<repl-3>"));
+    }
+
+    #[test]
+    fn test_source_map_mixed_sources() {
+        let mut source_map = SourceMap::new();
+        let file = Source::new("./testdata/native_types/id_int_assign.fs").unwrap();
+        let file_path = file.file_path().clone();
+        source_map.add(file);
+        source_map.add(Source::from_snippet(
+            "doctest",
+            "a: int = 1\nb: int = 2\n".to_string(),
+        ));
+
+        assert_eq!(source_map.sources().len(), 2);
+        assert_eq!(source_map.line(&file_path, 0), Some("_x_int: int = 0"));
+        assert_eq!(
+            source_map.line(Path::new("<doctest>"), 1),
+            Some("b: int = 2")
+        );
+        assert_eq!(source_map.line(Path::new("<doctest>"), 2), None);
+        assert!(!source_map.get(&file_path).unwrap().is_virtual());
+    }
 }
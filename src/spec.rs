@@ -0,0 +1,224 @@
+//! The spec suite, run by `funs test --spec`.
+//!
+//! A spec is a small program whose leading comment lines state what compiling and running
+//! it does, so that the files under `spec/` read as an executable description of the
+//! language:
+//!
+//! ```text
+//! # A type is required after `:`
+//! # @exit: 1
+//! # @error: Expected a type, found `=`
+//! x: = 1
+//! ```
+//!
+//! - `@exit` -- the exit code of `funs run`: `1` when it does not compile, `3` when it
+//!   stops with an error, `0` otherwise
+//! - `@error` -- an error, the errors are listed in the order they are reported
+//! - `@warning` -- a warning, the warnings are listed in the order they are reported
+//! - `@stdout` -- a line the program prints, the lines are listed in order
+//!
+//! Any other comment line of the header is a description. A spec that compiles is run as
+//! `funs run` does, and what it prints is compared with its `@stdout` lines.
+
+use crate::cli::ExitStatus;
+use crate::diagnostics::messages::Locale;
+use crate::diagnostics::Diagnostic;
+use crate::interpreter::{Interpreter, Value};
+use crate::modules::{self, Module};
+use crate::parser::ast::File;
+use crate::source::Source;
+use std::io::Write;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spec {
+    pub file_path: PathBuf,
+    /// The expected exit code, if given
+    pub exit: Option<u8>,
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+    /// The lines the program prints, its result included
+    pub stdout: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpecFailure {
+    pub file_path: PathBuf,
+    pub message: String,
+}
+
+impl std::fmt::Display for SpecFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: spec failed: {}",
+            self.file_path.display(),
+            self.message
+        )
+    }
+}
+
+impl Spec {
+    /// Reads the expectations in the header of `source`, the comment lines it starts with.
+    pub fn parse(source: &Source) -> Result<Spec, SpecFailure> {
+        let mut spec = Spec {
+            file_path: source.file_path().clone(),
+            exit: None,
+            errors: Vec::new(),
+            warnings: Vec::new(),
+            stdout: Vec::new(),
+        };
+        let header = source
+            .content()
+            .lines()
+            .map_while(|line| line.trim_start().strip_prefix('#'));
+        for comment in header {
+            let Some(expectation) = comment.trim().strip_prefix('@') else {
+                continue;
+            };
+            let (key, value) = expectation.split_once(':').unwrap_or((expectation, ""));
+            let value = value.trim().to_string();
+            match key.trim() {
+                "exit" => match value.parse() {
+                    Ok(code) => spec.exit = Some(code),
+                    Err(_) => return Err(spec.failure(format!("invalid exit code `{}`", value))),
+                },
+                "error" => spec.errors.push(value),
+                "warning" => spec.warnings.push(value),
+                "stdout" => spec.stdout.push(value),
+                key => return Err(spec.failure(format!("unknown expectation `@{}`", key))),
+            }
+        }
+        Ok(spec)
+    }
+
+    /// The exit code of `funs run` on the spec, when it is not given the errors tell
+    /// whether it compiles
+    fn expected_exit(&self) -> u8 {
+        match self.exit {
+            Some(code) => code,
+            None if self.errors.is_empty() => ExitStatus::Success.code(),
+            None => ExitStatus::CompileError.code(),
+        }
+    }
+
+    fn failure(&self, message: String) -> SpecFailure {
+        SpecFailure {
+            file_path: self.file_path.clone(),
+            message,
+        }
+    }
+}
+
+/// Compiles and runs the program of `spec` as `funs run` does, and compares what is
+/// reported with the expectations. Deep calls need the stack of `interpreter::with_stack`.
+pub fn run(spec: &Spec) -> Result<(), SpecFailure> {
+    let report = crate::compile(&spec.file_path, None, Locale::En);
+    let mut errors = report.error_messages(Locale::En);
+    let warnings = report.warning_messages(Locale::En);
+    let mut stdout = Vec::new();
+    let exit = match &report.ast {
+        Some(ast) if errors.is_empty() => {
+            let (output, result) = run_program(ast, &report.modules);
+            stdout = output;
+            match result {
+                Ok(()) => ExitStatus::Success.code(),
+                Err(diagnostic) => {
+                    errors.push(diagnostic.render(Locale::En));
+                    ExitStatus::RuntimeError.code()
+                }
+            }
+        }
+        _ => ExitStatus::CompileError.code(),
+    };
+
+    if errors != spec.errors {
+        return Err(spec.failure(format!(
+            "expected errors {:?}, found {:?}",
+            spec.errors, errors
+        )));
+    }
+    if warnings != spec.warnings {
+        return Err(spec.failure(format!(
+            "expected warnings {:?}, found {:?}",
+            spec.warnings, warnings
+        )));
+    }
+    if stdout != spec.stdout {
+        return Err(spec.failure(format!(
+            "expected stdout {:?}, found {:?}",
+            spec.stdout, stdout
+        )));
+    }
+    if exit != spec.expected_exit() {
+        return Err(spec.failure(format!(
+            "expected exit code {}, found {}",
+            spec.expected_exit(),
+            exit
+        )));
+    }
+    Ok(())
+}
+
+/// Runs `ast` after its `modules`, and returns the lines it printed, its result last
+/// unless it is `()`.
+fn run_program(ast: &File, modules: &[Module]) -> (Vec<String>, Result<(), Diagnostic>) {
+    let mut interpreter = Interpreter::new(Vec::new());
+    let result = modules::run(&mut interpreter, modules).and_then(|()| interpreter.run(ast));
+    let result = match result {
+        Ok(None | Some(Value::Unit)) => Ok(()),
+        Ok(Some(value)) => {
+            // Writing to a `Vec` does not fail
+            let _ = writeln!(interpreter.output(), "{}", value);
+            Ok(())
+        }
+        Err(diagnostic) => Err(diagnostic),
+    };
+    let output = String::from_utf8_lossy(&interpreter.into_output()).into_owned();
+    (output.lines().map(str::to_string).collect(), result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::file_handler::collect_fs_files;
+
+    #[test]
+    fn test_spec_parse() {
+        let source = Source::from(
+            "# Not an expectation: x\n\
+             # @exit: 1\n\
+             # @error: Expected a type, found `=`\n\
+             x: = 1\n\
+             # @error: not in the header\n"
+                .to_string(),
+        );
+        let spec = Spec::parse(&source).unwrap();
+        assert_eq!(spec.exit, Some(1));
+        assert_eq!(spec.errors, vec!["Expected a type, found `=`"]);
+        assert!(spec.warnings.is_empty());
+
+        let source = Source::from("# @stdout: 1\n# @stdout: two words\n".to_string());
+        assert_eq!(Spec::parse(&source).unwrap().stdout, vec!["1", "two words"]);
+
+        let source = Source::from("# @stderr: 1\n".to_string());
+        assert_eq!(
+            Spec::parse(&source).unwrap_err().message,
+            "unknown expectation `@stderr`"
+        );
+    }
+
+    #[test]
+    fn test_spec_suite() {
+        let files = collect_fs_files("spec", false);
+        assert!(!files.is_empty());
+        crate::interpreter::with_stack(|| {
+            for file in files {
+                let source = Source::new(&file).unwrap();
+                let result = Spec::parse(&source).and_then(|spec| run(&spec));
+                assert_eq!(result, Ok(()), "{}", file.display());
+            }
+        })
+        .unwrap();
+    }
+}
@@ -0,0 +1,174 @@
+use crate::ice::{self, Phase};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// The version of the statistics written by `funs check --stats-file`.
+///
+/// It changes whenever the runs recorded before could not be read, see
+/// `dev_doc/stats_json.md`.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The runs of `funs check` recorded in a stats file, oldest first. The file stays where
+/// it is written, nothing is sent anywhere.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct StatsJson {
+    pub version: u32,
+    pub runs: Vec<Run>,
+}
+
+/// A run of `funs check`
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct Run {
+    /// The version of `funs` that ran
+    pub funs: String,
+    /// When the run started, in seconds since the Unix epoch
+    pub started_at: u64,
+    pub total_ns: u64,
+    pub files: usize,
+    pub files_with_errors: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    /// How many diagnostics of each code were reported
+    pub diagnostics: BTreeMap<String, usize>,
+    /// The time spent in each phase, over all the files
+    pub phases_ns: BTreeMap<Phase, u64>,
+}
+
+/// The time spent in each phase of the compiler
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Timings {
+    phases: BTreeMap<Phase, Duration>,
+}
+
+impl Timings {
+    /// Runs `f` like `ice::with_phase`, adding the time it takes to `phase`
+    pub fn time<T>(&mut self, phase: Phase, file_path: &Path, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = ice::with_phase(phase, file_path, f);
+        *self.phases.entry(phase).or_default() += start.elapsed();
+        result
+    }
+
+    pub fn add(&mut self, other: &Timings) {
+        for (phase, duration) in &other.phases {
+            *self.phases.entry(*phase).or_default() += *duration;
+        }
+    }
+
+    pub fn get(&self, phase: Phase) -> Duration {
+        self.phases.get(&phase).copied().unwrap_or_default()
+    }
+
+    pub fn to_nanos(&self) -> BTreeMap<Phase, u64> {
+        self.phases
+            .iter()
+            .map(|(phase, duration)| (*phase, nanos(*duration)))
+            .collect()
+    }
+}
+
+pub fn nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum StatsError {
+    Parse(String),
+    Version(u32),
+}
+
+impl std::fmt::Display for StatsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            StatsError::Parse(message) => write!(f, "Invalid stats file: {}", message),
+            StatsError::Version(version) => write!(
+                f,
+                "Stats file version {} is not supported, expected {}",
+                version, FORMAT_VERSION
+            ),
+        }
+    }
+}
+
+impl StatsJson {
+    pub fn new(runs: Vec<Run>) -> StatsJson {
+        StatsJson {
+            version: FORMAT_VERSION,
+            runs,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        // The runs only hold strings and numbers, they always serialize
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    pub fn from_json(json: &str) -> Result<StatsJson, StatsError> {
+        let stats: StatsJson =
+            serde_json::from_str(json).map_err(|e| StatsError::Parse(e.to_string()))?;
+        if stats.version != FORMAT_VERSION {
+            return Err(StatsError::Version(stats.version));
+        }
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn run() -> Run {
+        Run {
+            funs: "0.1.0".to_string(),
+            started_at: 1_700_000_000,
+            total_ns: 1500,
+            files: 2,
+            files_with_errors: 1,
+            errors: 1,
+            warnings: 0,
+            diagnostics: BTreeMap::from([("E0004".to_string(), 1)]),
+            phases_ns: BTreeMap::from([(Phase::Lexing, 200), (Phase::TypeChecking, 300)]),
+        }
+    }
+
+    #[test]
+    fn test_stats_json_round_trip() {
+        let stats = StatsJson::new(vec![run(), run()]);
+        let json = stats.to_json();
+        assert!(json.contains("\"type_checking\": 300"), "{}", json);
+        assert_eq!(StatsJson::from_json(&json), Ok(stats));
+
+        let json = json.replacen("\"version\": 1", "\"version\": 99", 1);
+        assert_eq!(StatsJson::from_json(&json), Err(StatsError::Version(99)));
+        assert!(matches!(
+            StatsJson::from_json("[]"),
+            Err(StatsError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_stats_json_timings() {
+        let path = PathBuf::from("main.fs");
+        let mut timings = Timings::default();
+        let value = timings.time(Phase::Parsing, &path, || {
+            std::thread::sleep(Duration::from_millis(1));
+            42
+        });
+        assert_eq!(value, 42);
+        let parsing = timings.get(Phase::Parsing);
+        assert!(parsing >= Duration::from_millis(1));
+
+        let mut total = Timings::default();
+        total.add(&timings);
+        total.add(&timings);
+        assert_eq!(total.get(Phase::Parsing), parsing * 2);
+        assert_eq!(total.get(Phase::Lexing), Duration::ZERO);
+        assert_eq!(
+            total.to_nanos().keys().collect::<Vec<_>>(),
+            [&Phase::Parsing]
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use crate::diff::declarations;
+use crate::lexer::token::TokenLocation;
+use crate::parser::{Tree, TreeKind};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolKind {
+    Variable,
+    Function,
+}
+
+impl std::fmt::Display for SymbolKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SymbolKind::Variable => write!(f, "variable"),
+            SymbolKind::Function => write!(f, "function"),
+        }
+    }
+}
+
+/// A top-level declaration, as listed by `funs symbols`.
+///
+/// The JSON form keeps the 0-based location of the name, so that editors can use it as
+/// is (e.g. for document symbols).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// The declared type, e.g. `option<int>`
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub location: TokenLocation,
+}
+
+/// Lists the top-level declarations of a file, in source order.
+pub fn symbols(file: &Tree) -> Vec<Symbol> {
+    declarations(file)
+        .into_iter()
+        .map(|declaration| Symbol {
+            name: declaration.name,
+            kind: match declaration.kind {
+                TreeKind::StmtFunDecl => SymbolKind::Function,
+                _ => SymbolKind::Variable,
+            },
+            ty: declaration.signature,
+            location: declaration.location,
+        })
+        .collect()
+}
+
+/// Renders symbols as a table with one symbol per line, locations are 1-based.
+///
+/// ```text
+/// NAME  KIND      TYPE         LOCATION
+/// x     variable  option<int>  main.fs:1:1
+/// ```
+pub fn table(symbols: &[Symbol]) -> String {
+    let header = ["NAME", "KIND", "TYPE", "LOCATION"].map(String::from);
+    let rows = symbols
+        .iter()
+        .map(|symbol| {
+            [
+                symbol.name.clone(),
+                symbol.kind.to_string(),
+                symbol.ty.clone(),
+                symbol.location.to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let mut widths = [0; 4];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut table = String::new();
+    for row in std::iter::once(&header).chain(&rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        table.push_str(line.trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::snippet::parse_snippet;
+
+    #[test]
+    fn test_symbols_table() {
+        let tree = parse_snippet(
+            "# a comment\nx: int = 1\nanswer: option<(int, str)> = ?todo\nid: (int) -> int = (x) -> x ;\n",
+        )
+        .unwrap();
+        assert_eq!(
+            table(&symbols(&tree)),
+            "NAME    KIND      TYPE                LOCATION\n\
+             x       variable  int                 <snippet>:2:1\n\
+             answer  variable  option<(int, str)>  <snippet>:3:1\n\
+             id      function  (int) -> int        <snippet>:4:1\n"
+        );
+    }
+
+    #[test]
+    fn test_symbols_json() {
+        let tree = parse_snippet("x: int = 1\n").unwrap();
+        let json = serde_json::to_value(symbols(&tree)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([{
+                "name": "x",
+                "kind": "variable",
+                "type": "int",
+                "location": {
+                    "file_path": "<snippet>",
+                    "line": 0,
+                    "column_start": 0,
+                    "column_end": 1
+                }
+            }])
+        );
+    }
+}
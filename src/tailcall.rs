@@ -0,0 +1,240 @@
+//! Finds the recursive calls that are not tail calls.
+//!
+//! A call is a tail call when its value is the value of the function: it is the last
+//! statement of the body, or a branch of an `if` or the body of a `match` arm that is
+//! itself in tail position. Any other recursive call, e.g. `n * fact (n - 1)`, keeps its
+//! caller waiting, so the depth of the recursion grows with the input.
+//!
+//! Such calls are warnings, and errors in a function marked `@tailrec`. Only the calls of
+//! a function by its own name count, mutual recursion is not followed.
+use crate::diagnostics::messages::{Code, Message};
+use crate::diagnostics::Diagnostic;
+use crate::parser::ast::{Attribute, Expr, ExprKind, File, FunDecl, Stmt};
+
+/// The recursive calls of the functions of `file` that are not tail calls, in source
+/// order. Nested functions are checked on their own.
+pub fn non_tail_calls(file: &File) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for stmt in &file.stmts {
+        if let Stmt::FunDecl(decl) = stmt {
+            check(decl, &mut diagnostics);
+        }
+    }
+    diagnostics
+        .sort_by_key(|diagnostic| (diagnostic.location.line, diagnostic.location.column_start));
+    diagnostics
+}
+
+fn check(decl: &FunDecl, diagnostics: &mut Vec<Diagnostic>) {
+    let mut finder = Finder {
+        name: &decl.name.name,
+        calls: Vec::new(),
+    };
+    // A parameter named like the function hides it from the body
+    if !decl.params.iter().any(|param| param.name == decl.name.name) {
+        finder.stmts(&decl.body);
+    }
+    let tailrec = decl.attributes.contains(&Attribute::TailRec);
+    for call in finder.calls {
+        let location = call.span.start.clone();
+        diagnostics.push(match tailrec {
+            true => Diagnostic::error(
+                Message::new(Code::TailRecNonTailCall).with_arg("name", &decl.name.name),
+                location,
+            ),
+            false => Diagnostic::warning(Message::new(Code::NonTailCall), location),
+        });
+    }
+    for stmt in &decl.body {
+        if let Stmt::FunDecl(nested) = stmt {
+            check(nested, diagnostics);
+        }
+    }
+}
+
+/// Collects the calls of the function `name` that are not in tail position
+struct Finder<'a> {
+    name: &'a str,
+    calls: Vec<&'a Expr>,
+}
+
+impl<'a> Finder<'a> {
+    /// Visits a body, whose last statement is in tail position
+    fn stmts(&mut self, stmts: &'a [Stmt]) {
+        // Functions are declared before the statements of their block run
+        let hidden = stmts
+            .iter()
+            .any(|stmt| matches!(stmt, Stmt::FunDecl(decl) if decl.name.name == self.name));
+        if hidden {
+            return;
+        }
+        for (index, stmt) in stmts.iter().enumerate() {
+            match stmt {
+                Stmt::VarDecl(decl) => {
+                    self.expr(&decl.value, false);
+                    // The next statements see the variable
+                    if decl.name.name == self.name {
+                        return;
+                    }
+                }
+                Stmt::PatternDecl(decl) => {
+                    self.expr(&decl.value, false);
+                    if decl.pattern.names().contains(&self.name) {
+                        return;
+                    }
+                }
+                // Checked on their own
+                Stmt::FunDecl(_) => {}
+                Stmt::Import(_) | Stmt::Data(_) | Stmt::Variant(_) => {}
+                Stmt::Expr(expr) => self.expr(expr, index == stmts.len() - 1),
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &'a Expr, tail: bool) {
+        match &expr.kind {
+            ExprKind::Int(_)
+            | ExprKind::Float(_)
+            | ExprKind::Bool(_)
+            | ExprKind::Str(_)
+            | ExprKind::Name(_)
+            | ExprKind::Hole(_) => {}
+            ExprKind::Unary { expr, .. }
+            | ExprKind::Field { expr, .. }
+            | ExprKind::Cast { expr, .. }
+            | ExprKind::Ascription { expr, .. } => self.expr(expr, false),
+            ExprKind::Binary { lhs, rhs, .. } => {
+                self.expr(lhs, false);
+                self.expr(rhs, false);
+            }
+            ExprKind::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                self.expr(cond, false);
+                self.expr(then_branch, tail);
+                self.expr(else_branch, tail);
+            }
+            ExprKind::List(elements) | ExprKind::Tuple(elements) => {
+                for element in elements {
+                    self.expr(element, false);
+                }
+            }
+            ExprKind::Record(fields) => {
+                for field in fields {
+                    self.expr(&field.value, false);
+                }
+            }
+            ExprKind::Call { callee, args } => {
+                if !tail && matches!(&callee.kind, ExprKind::Name(name) if name == self.name) {
+                    self.calls.push(expr);
+                }
+                self.expr(callee, false);
+                for arg in args {
+                    self.expr(arg, false);
+                }
+            }
+            ExprKind::Match { scrutinee, arms } => {
+                self.expr(scrutinee, false);
+                for arm in arms {
+                    // A pattern binding the name of the function hides it from the arm
+                    if !arm.pattern.names().contains(&self.name) {
+                        self.expr(&arm.body, tail);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::lower::lower;
+    use crate::parser::snippet::parse_snippet;
+
+    fn diagnostics(content: &str) -> Vec<String> {
+        let (file, errors) = lower(&parse_snippet(content).unwrap());
+        assert!(errors.is_empty(), "{:?}", errors);
+        non_tail_calls(&file)
+            .iter()
+            .map(Diagnostic::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_tailcall_positions() {
+        // The branches of `if` and the arms of `match` are tail positions
+        assert_eq!(
+            diagnostics(
+            "count: (int, int) -> int = (n, acc) ->\n\
+             \x20 if n == 0 then acc else count (n - 1) (acc + 1)\n\
+             ;\n\
+             down: (int) -> int = (n) ->\n\
+             \x20 match n\n\
+             \x20 | 0 => 0\n\
+             \x20 | _ => down (n - 1)\n\
+             \x20 ;\n\
+             ;\n\
+             last: (int) -> int = (n) ->\n\
+             \x20 x: int = n - 1\n\
+             \x20 (last x : int)\n\
+             ;\n"
+            ),
+            // An ascription checks the value once the call returned
+            vec!["<snippet>:12:4: warning: this recursive call is not a tail call and may overflow for large inputs"]
+        );
+
+        assert_eq!(
+            diagnostics(
+                "fact: (int) -> int = (n) -> if n < 2 then 1 else n * fact (n - 1) ;\n\
+                 fib: (int) -> int = (n) -> if n < 2 then n else fib (n - 1) + fib (n - 2) ;\n\
+                 twice: (int) -> int = (n) ->\n\
+                 \x20 twice (twice n)\n\
+                 ;\n"
+            ),
+            vec![
+                "<snippet>:1:54: warning: this recursive call is not a tail call and may overflow for large inputs",
+                "<snippet>:2:49: warning: this recursive call is not a tail call and may overflow for large inputs",
+                "<snippet>:2:63: warning: this recursive call is not a tail call and may overflow for large inputs",
+                "<snippet>:4:10: warning: this recursive call is not a tail call and may overflow for large inputs",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tailcall_tailrec() {
+        assert_eq!(
+            diagnostics(
+                "@tailrec\n\
+                 fact: (int) -> int = (n) -> if n < 2 then 1 else n * fact (n - 1) ;\n\
+                 @tailrec\n\
+                 loop: (int) -> int = (n) -> if n == 0 then 0 else loop (n - 1) ;\n"
+            ),
+            vec!["<snippet>:2:54: error: `fact` is marked `@tailrec`, but this recursive call is not a tail call"]
+        );
+    }
+
+    #[test]
+    fn test_tailcall_shadowing() {
+        // Neither the calls of another function named the same, nor of a parameter, are
+        // recursive, while a nested function is checked on its own
+        assert_eq!(
+            diagnostics(
+                "f: (int) -> int = (f) -> 1 + f 2 ;\n\
+                 g: (int) -> int = (n) ->\n\
+                 \x20 g: (int) -> int = (m) -> m ;\n\
+                 \x20 1 + g n\n\
+                 ;\n\
+                 h: (int) -> int = (n) ->\n\
+                 \x20 inner: (int) -> int = (m) -> 1 + inner m ;\n\
+                 \x20 match n\n\
+                 \x20 | h => 1 + h n\n\
+                 \x20 ;\n\
+                 ;\n"
+            ),
+            vec!["<snippet>:7:36: warning: this recursive call is not a tail call and may overflow for large inputs"]
+        );
+    }
+}
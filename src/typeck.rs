@@ -0,0 +1,1210 @@
+//! Infers the types of the expressions of a file and checks them against the declared
+//! types, Hindley–Milner style.
+//!
+//! Every declaration states its type, so inference only fills in what is left open: the
+//! type of an empty list, of a hole, of the arguments a generic function is called with.
+//! An open type is a variable that unification binds. A generic name in a declared type,
+//! e.g. `T` in `(T) -> T`, stays fixed inside the body of its declaration and stands for
+//! any type where the declaration is used.
+//!
+//! A record literal has the type it is expected to have, or else the one record declared
+//! with the same fields; a field read from a value of an open type picks the one record
+//! declaring it. Each hole is reported as a warning with the type it stands for.
+use crate::diagnostics::messages::{Code, Message};
+use crate::diagnostics::Diagnostic;
+use crate::parser::ast::{
+    BinaryOp, Expr, ExprKind, FieldInit, File, FunDecl, Ident, Pattern, PatternKind, Stmt, Type,
+    TypeKind,
+};
+use crate::parser::node_id::{NodeId, Span};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A type found by the checker
+#[derive(Debug, Clone, PartialEq)]
+pub enum Ty {
+    Int,
+    Float,
+    Bool,
+    Str,
+    List(Box<Ty>),
+    /// `()` is the empty tuple, the type of `unit`
+    Tuple(Vec<Ty>),
+    Function {
+        params: Vec<Ty>,
+        ret: Box<Ty>,
+    },
+    /// A record, a variant or a type with arguments, e.g. `option<int>`
+    Named {
+        name: String,
+        args: Vec<Ty>,
+    },
+    /// A generic name of a declared type, e.g. `T`
+    Param(String),
+    /// An open type, bound by unification
+    Var(u32),
+}
+
+/// The type as written in a declaration, an open type is `_`
+impl std::fmt::Display for Ty {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let join = |types: &[Ty]| {
+            types
+                .iter()
+                .map(Ty::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self {
+            Ty::Int => write!(f, "int"),
+            Ty::Float => write!(f, "float"),
+            Ty::Bool => write!(f, "bool"),
+            Ty::Str => write!(f, "str"),
+            Ty::List(ty) => write!(f, "[{}]", ty),
+            Ty::Tuple(types) => write!(f, "({})", join(types)),
+            Ty::Function { params, ret } => write!(f, "({}) -> {}", join(params), ret),
+            Ty::Named { name, args } if args.is_empty() => write!(f, "{}", name),
+            Ty::Named { name, args } => write!(f, "{}<{}>", name, join(args)),
+            Ty::Param(name) => write!(f, "{}", name),
+            Ty::Var(_) => write!(f, "_"),
+        }
+    }
+}
+
+impl Ty {
+    fn unit() -> Ty {
+        Ty::Tuple(Vec::new())
+    }
+
+    /// Whether the type can still be any type: an operator accepts it, the interpreter
+    /// checks its values
+    fn is_open(&self) -> bool {
+        matches!(self, Ty::Var(_) | Ty::Param(_))
+    }
+}
+
+/// Checks the statements of `file`, returning the type of every expression by id and
+/// the mismatches found, in source order.
+pub fn check(file: &File) -> (BTreeMap<NodeId, Ty>, Vec<Diagnostic>) {
+    check_with_imports(file, &[])
+}
+
+/// Checks `file` like `check`, with the top-level declarations of the modules it imports
+/// in scope, see `modules::imported`. The declarations of `file` shadow them.
+pub fn check_with_imports(
+    file: &File,
+    imports: &[&File],
+) -> (BTreeMap<NodeId, Ty>, Vec<Diagnostic>) {
+    let mut checker = Checker {
+        vars: Vec::new(),
+        scopes: vec![BTreeMap::new()],
+        named: BTreeSet::new(),
+        records: BTreeMap::new(),
+        constructors: BTreeMap::new(),
+        exprs: BTreeMap::new(),
+        holes: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    // `print` writes any value
+    let print = Ty::Function {
+        params: vec![Ty::Param("T".to_string())],
+        ret: Box::new(Ty::unit()),
+    };
+    checker.define("print", print, true);
+    for module in imports {
+        checker.import(module);
+    }
+    checker.scopes.push(BTreeMap::new());
+    checker.stmts(&file.stmts);
+
+    // The types of the holes are only known once the whole file is checked
+    for hole in std::mem::take(&mut checker.holes) {
+        checker.report_hole(hole);
+    }
+    let exprs = std::mem::take(&mut checker.exprs)
+        .into_iter()
+        .map(|(id, ty)| (id, checker.zonk(&ty)))
+        .collect();
+    let mut diagnostics = checker.diagnostics;
+    diagnostics
+        .sort_by_key(|diagnostic| (diagnostic.location.line, diagnostic.location.column_start));
+    (exprs, diagnostics)
+}
+
+/// The type of a name in scope
+struct Binding {
+    ty: Ty,
+    /// Whether the generic names of `ty` stand for any type, as for a declaration, or
+    /// for the ones fixed by the enclosing declaration, as for a parameter
+    generic: bool,
+}
+
+/// A hole, with the names in scope where it is written
+struct Hole {
+    name: String,
+    ty: Ty,
+    span: Span,
+    scope: Vec<(String, Ty)>,
+}
+
+struct Checker {
+    /// The type each variable is bound to, `None` while it is open
+    vars: Vec<Option<Ty>>,
+    /// The names in scope, the innermost scope last
+    scopes: Vec<BTreeMap<String, Binding>>,
+    /// The records and variants declared so far
+    named: BTreeSet<String>,
+    /// The fields of each record declared so far, in order
+    records: BTreeMap<String, Vec<(String, Ty)>>,
+    /// The variant and the field types of each constructor declared so far
+    constructors: BTreeMap<String, (String, Vec<Ty>)>,
+    exprs: BTreeMap<NodeId, Ty>,
+    holes: Vec<Hole>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    fn fresh(&mut self) -> Ty {
+        self.vars.push(None);
+        Ty::Var(self.vars.len() as u32 - 1)
+    }
+
+    /// Follows the bound variables at the top of `ty`
+    fn resolve(&self, ty: &Ty) -> Ty {
+        match ty {
+            Ty::Var(var) => match &self.vars[*var as usize] {
+                Some(bound) => self.resolve(bound),
+                None => ty.clone(),
+            },
+            _ => ty.clone(),
+        }
+    }
+
+    /// Replaces every bound variable of `ty` with its type
+    fn zonk(&self, ty: &Ty) -> Ty {
+        match self.resolve(ty) {
+            Ty::List(ty) => Ty::List(Box::new(self.zonk(&ty))),
+            Ty::Tuple(types) => Ty::Tuple(types.iter().map(|ty| self.zonk(ty)).collect()),
+            Ty::Function { params, ret } => Ty::Function {
+                params: params.iter().map(|ty| self.zonk(ty)).collect(),
+                ret: Box::new(self.zonk(&ret)),
+            },
+            Ty::Named { name, args } => Ty::Named {
+                name,
+                args: args.iter().map(|ty| self.zonk(ty)).collect(),
+            },
+            ty => ty,
+        }
+    }
+
+    fn occurs(&self, var: u32, ty: &Ty) -> bool {
+        match self.resolve(ty) {
+            Ty::Var(other) => var == other,
+            Ty::List(ty) => self.occurs(var, &ty),
+            Ty::Tuple(types) | Ty::Named { args: types, .. } => {
+                types.iter().any(|ty| self.occurs(var, ty))
+            }
+            Ty::Function { params, ret } => {
+                params.iter().any(|ty| self.occurs(var, ty)) || self.occurs(var, &ret)
+            }
+            _ => false,
+        }
+    }
+
+    /// Makes `a` and `b` the same type by binding their variables, fails if they differ.
+    /// The variables bound before a failure stay bound.
+    fn unify(&mut self, a: &Ty, b: &Ty) -> Result<(), ()> {
+        let (a, b) = (self.resolve(a), self.resolve(b));
+        match (&a, &b) {
+            (Ty::Var(a), Ty::Var(b)) if a == b => Ok(()),
+            (Ty::Var(var), ty) | (ty, Ty::Var(var)) => {
+                // `a = [a]` has no finite solution
+                if self.occurs(*var, ty) {
+                    return Err(());
+                }
+                self.vars[*var as usize] = Some(ty.clone());
+                Ok(())
+            }
+            (Ty::List(a), Ty::List(b)) => self.unify(a, b),
+            (Ty::Tuple(a), Ty::Tuple(b)) if a.len() == b.len() => self.unify_all(a, b),
+            (
+                Ty::Function { params, ret },
+                Ty::Function {
+                    params: other_params,
+                    ret: other_ret,
+                },
+            ) if params.len() == other_params.len() => {
+                self.unify_all(params, other_params)?;
+                self.unify(ret, other_ret)
+            }
+            (
+                Ty::Named { name, args },
+                Ty::Named {
+                    name: other_name,
+                    args: other_args,
+                },
+            ) if name == other_name && args.len() == other_args.len() => {
+                self.unify_all(args, other_args)
+            }
+            _ if a == b => Ok(()),
+            _ => Err(()),
+        }
+    }
+
+    fn unify_all(&mut self, a: &[Ty], b: &[Ty]) -> Result<(), ()> {
+        a.iter().zip(b).try_for_each(|(a, b)| self.unify(a, b))
+    }
+
+    fn report(&mut self, message: Message, span: &Span) {
+        self.diagnostics
+            .push(Diagnostic::error(message, span.location()));
+    }
+
+    fn mismatch(&mut self, expected: &Ty, found: &Ty, span: &Span) {
+        let message = Message::new(Code::TypeMismatch)
+            .with_arg("expected", self.zonk(expected))
+            .with_arg("found", self.zonk(found));
+        self.report(message, span);
+    }
+
+    /// Infers the type of `expr` and unifies it with `expected`, the mismatch is reported
+    /// on `expr`
+    fn expect(&mut self, expr: &Expr, expected: &Ty) {
+        // A record literal is checked against the fields of the record expected
+        if let (ExprKind::Record(fields), Ty::Named { name, .. }) =
+            (&expr.kind, self.resolve(expected))
+        {
+            if self.records.contains_key(&name) {
+                let ty = self.record(expr, fields, &name);
+                self.exprs.insert(expr.id, ty);
+                return;
+            }
+        }
+        let found = self.infer(expr);
+        if self.unify(expected, &found).is_err() {
+            self.mismatch(expected, &found, &expr.span);
+        }
+    }
+
+    fn define(&mut self, name: &str, ty: Ty, generic: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), Binding { ty, generic });
+        }
+    }
+
+    /// The names in scope and their types, the innermost binding of each name
+    fn in_scope(&self) -> Vec<(String, Ty)> {
+        let mut names = BTreeMap::new();
+        for scope in &self.scopes {
+            for (name, binding) in scope {
+                names.insert(name.clone(), binding.ty.clone());
+            }
+        }
+        names.into_iter().collect()
+    }
+
+    fn lookup(&mut self, name: &str) -> Option<Ty> {
+        let binding = self.scopes.iter().rev().find_map(|scope| scope.get(name))?;
+        let (ty, generic) = (binding.ty.clone(), binding.generic);
+        Some(match generic {
+            true => self.instantiate(&ty, &mut BTreeMap::new()),
+            false => ty,
+        })
+    }
+
+    /// Replaces each generic name of `ty` with its own open type
+    fn instantiate(&mut self, ty: &Ty, params: &mut BTreeMap<String, Ty>) -> Ty {
+        match ty {
+            Ty::Param(name) => match params.get(name) {
+                Some(ty) => ty.clone(),
+                None => {
+                    let fresh = self.fresh();
+                    params.insert(name.clone(), fresh.clone());
+                    fresh
+                }
+            },
+            Ty::List(ty) => Ty::List(Box::new(self.instantiate(ty, params))),
+            Ty::Tuple(types) => Ty::Tuple(
+                types
+                    .iter()
+                    .map(|ty| self.instantiate(ty, params))
+                    .collect(),
+            ),
+            Ty::Function { params: types, ret } => Ty::Function {
+                params: types
+                    .iter()
+                    .map(|ty| self.instantiate(ty, params))
+                    .collect(),
+                ret: Box::new(self.instantiate(ret, params)),
+            },
+            Ty::Named { name, args } => Ty::Named {
+                name: name.clone(),
+                args: args.iter().map(|ty| self.instantiate(ty, params)).collect(),
+            },
+            ty => ty.clone(),
+        }
+    }
+
+    /// The type written in a declaration. A name without arguments that is neither native
+    /// nor a record or a variant is generic.
+    fn declared(&self, ty: &Type) -> Ty {
+        match &ty.kind {
+            TypeKind::Named { name, args } if args.is_empty() => match name.as_str() {
+                "int" => Ty::Int,
+                "float" => Ty::Float,
+                "bool" => Ty::Bool,
+                "str" => Ty::Str,
+                "unit" => Ty::unit(),
+                _ if self.named.contains(name) => Ty::Named {
+                    name: name.clone(),
+                    args: Vec::new(),
+                },
+                _ => Ty::Param(name.clone()),
+            },
+            TypeKind::Named { name, args } => Ty::Named {
+                name: name.clone(),
+                args: args.iter().map(|ty| self.declared(ty)).collect(),
+            },
+            TypeKind::List(ty) => Ty::List(Box::new(self.declared(ty))),
+            TypeKind::Tuple(types) => Ty::Tuple(types.iter().map(|ty| self.declared(ty)).collect()),
+            TypeKind::Function { params, ret } => Ty::Function {
+                params: params.iter().map(|ty| self.declared(ty)).collect(),
+                ret: Box::new(self.declared(ret)),
+            },
+        }
+    }
+
+    /// Checks `stmts` in the current scope and returns the type of the last one, `()` if
+    /// it is a declaration
+    fn stmts(&mut self, stmts: &[Stmt]) -> Ty {
+        // Like in the interpreter, types, constructors and functions are declared before
+        // any statement runs
+        self.declare(stmts);
+
+        let mut last = Ty::unit();
+        for stmt in stmts {
+            last = match stmt {
+                Stmt::VarDecl(decl) => {
+                    let ty = self.declared(&decl.ty);
+                    self.expect(&decl.value, &ty);
+                    self.define(&decl.name.name, ty, true);
+                    Ty::unit()
+                }
+                Stmt::PatternDecl(decl) => {
+                    let ty = self.declared(&decl.ty);
+                    self.expect(&decl.value, &ty);
+                    self.pattern(&decl.pattern, &ty);
+                    Ty::unit()
+                }
+                Stmt::FunDecl(decl) => {
+                    self.fun_decl(decl);
+                    Ty::unit()
+                }
+                Stmt::Import(_) | Stmt::Data(_) | Stmt::Variant(_) => Ty::unit(),
+                Stmt::Expr(expr) => self.infer(expr),
+            };
+        }
+        last
+    }
+
+    /// Declares the top-level names of the imported `module` with their declared types,
+    /// its errors are reported when it is checked itself
+    fn import(&mut self, module: &File) {
+        let diagnostics = self.diagnostics.len();
+        self.declare(&module.stmts);
+        for stmt in &module.stmts {
+            match stmt {
+                Stmt::VarDecl(decl) => {
+                    let ty = self.declared(&decl.ty);
+                    self.define(&decl.name.name, ty, true);
+                }
+                Stmt::PatternDecl(decl) => {
+                    let ty = self.declared(&decl.ty);
+                    self.pattern(&decl.pattern, &ty);
+                }
+                _ => {}
+            }
+        }
+        self.diagnostics.truncate(diagnostics);
+    }
+
+    /// Declares the types, the constructors and the functions of `stmts`
+    fn declare(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            match stmt {
+                Stmt::Data(decl) => {
+                    self.named.insert(decl.name.name.clone());
+                }
+                Stmt::Variant(decl) => {
+                    self.named.insert(decl.name.name.clone());
+                }
+                _ => {}
+            }
+        }
+        for stmt in stmts {
+            match stmt {
+                Stmt::Data(decl) => {
+                    let fields = decl
+                        .fields
+                        .iter()
+                        .map(|field| (field.name.name.clone(), self.declared(&field.ty)))
+                        .collect();
+                    self.records.insert(decl.name.name.clone(), fields);
+                }
+                Stmt::Variant(decl) => {
+                    let variant = Ty::Named {
+                        name: decl.name.name.clone(),
+                        args: Vec::new(),
+                    };
+                    for case in &decl.cases {
+                        let fields = case
+                            .fields
+                            .iter()
+                            .map(|ty| self.declared(ty))
+                            .collect::<Vec<_>>();
+                        let ty = match fields.is_empty() {
+                            true => variant.clone(),
+                            false => Ty::Function {
+                                params: fields.clone(),
+                                ret: Box::new(variant.clone()),
+                            },
+                        };
+                        self.define(&case.name.name, ty, true);
+                        self.constructors
+                            .insert(case.name.name.clone(), (decl.name.name.clone(), fields));
+                    }
+                }
+                Stmt::FunDecl(decl) => {
+                    let ty = self.declared(&decl.ty);
+                    self.define(&decl.name.name, ty, true);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn fun_decl(&mut self, decl: &FunDecl) {
+        let Ty::Function { params, ret } = self.declared(&decl.ty) else {
+            return;
+        };
+        // `f: (int) -> int = ?todo` stands for the whole function
+        let hole = matches!(
+            decl.body.as_slice(),
+            [Stmt::Expr(expr)] if matches!(expr.kind, ExprKind::Hole(_))
+        );
+        if decl.params.is_empty() && hole {
+            if let [Stmt::Expr(expr)] = decl.body.as_slice() {
+                let ty = Ty::Function { params, ret };
+                self.hole(expr, ty.clone());
+                self.exprs.insert(expr.id, ty);
+                // The function is not a candidate for itself
+                if let Some(hole) = self.holes.last_mut() {
+                    hole.scope.retain(|(name, _)| *name != decl.name.name);
+                }
+            }
+            return;
+        }
+
+        self.scopes.push(BTreeMap::new());
+        let arity = decl.params.len() == params.len();
+        let types = match arity {
+            true => params.clone(),
+            false => decl.params.iter().map(|_| self.fresh()).collect(),
+        };
+        for (param, ty) in decl.params.iter().zip(&types) {
+            self.define(&param.name, ty.clone(), false);
+        }
+        let body = self.stmts(&decl.body);
+        self.scopes.pop();
+
+        if !arity {
+            let found = Ty::Function {
+                params: types,
+                ret: Box::new(body),
+            };
+            let expected = Ty::Function { params, ret };
+            self.mismatch(&expected, &found, &decl.span);
+            return;
+        }
+        if self.unify(&ret, &body).is_err() {
+            // On the result if there is one, the function otherwise
+            let span = match decl.body.last() {
+                Some(Stmt::Expr(expr)) => &expr.span,
+                _ => &decl.span,
+            };
+            self.mismatch(&ret, &body, span);
+        }
+    }
+
+    /// Binds the names of `pattern`, which matches values of type `ty`
+    fn pattern(&mut self, pattern: &Pattern, ty: &Ty) {
+        // A literal only matches values of its type
+        let literal = |literal: Ty, checker: &mut Checker| {
+            if checker.unify(ty, &literal).is_err() {
+                checker.mismatch(ty, &literal, &pattern.span);
+            }
+        };
+        match &pattern.kind {
+            PatternKind::Int(_) => literal(Ty::Int, self),
+            PatternKind::Float(_) => literal(Ty::Float, self),
+            PatternKind::Bool(_) => literal(Ty::Bool, self),
+            PatternKind::Str(_) => literal(Ty::Str, self),
+            PatternKind::Wildcard => {}
+            PatternKind::Name(name) => self.define(name, ty.clone(), false),
+            PatternKind::Tuple(patterns) => {
+                let types = patterns.iter().map(|_| self.fresh()).collect::<Vec<_>>();
+                let found = Ty::Tuple(types.clone());
+                if self.unify(ty, &found).is_err() {
+                    self.mismatch(ty, &found, &pattern.span);
+                }
+                for (pattern, ty) in patterns.iter().zip(&types) {
+                    self.pattern(pattern, ty);
+                }
+            }
+            PatternKind::Constructor { name, fields } => {
+                let (variant, mut types) = match self.constructors.get(name) {
+                    Some((variant, types)) => {
+                        let variant = Ty::Named {
+                            name: variant.clone(),
+                            args: Vec::new(),
+                        };
+                        (Some(variant), types.clone())
+                    }
+                    None => (None, Vec::new()),
+                };
+                if let Some(variant) = variant {
+                    if types.len() != fields.len() {
+                        let message = Message::new(Code::ConstructorArity)
+                            .with_arg("name", name)
+                            .with_arg("expected", types.len())
+                            .with_arg("found", fields.len());
+                        self.report(message, &pattern.span);
+                    } else if self.unify(ty, &variant).is_err() {
+                        self.mismatch(ty, &variant, &pattern.span);
+                    }
+                }
+                // An unknown constructor binds its names to open types, like the fields
+                // a constructor does not have
+                while types.len() < fields.len() {
+                    types.push(self.fresh());
+                }
+                for (pattern, ty) in fields.iter().zip(&types) {
+                    self.pattern(pattern, ty);
+                }
+            }
+        }
+    }
+
+    fn infer(&mut self, expr: &Expr) -> Ty {
+        let ty = self.infer_kind(expr);
+        self.exprs.insert(expr.id, ty.clone());
+        ty
+    }
+
+    fn infer_kind(&mut self, expr: &Expr) -> Ty {
+        match &expr.kind {
+            ExprKind::Int(_) => Ty::Int,
+            ExprKind::Float(_) => Ty::Float,
+            ExprKind::Bool(_) => Ty::Bool,
+            ExprKind::Str(_) => Ty::Str,
+            // An unknown name is reported by `resolve`
+            ExprKind::Name(name) => match self.lookup(name) {
+                Some(ty) => ty,
+                None => self.fresh(),
+            },
+            ExprKind::Hole(_) => {
+                let ty = self.fresh();
+                self.hole(expr, ty.clone());
+                ty
+            }
+            ExprKind::Unary { op, expr: operand } => {
+                let ty = self.infer(operand);
+                let resolved = self.resolve(&ty);
+                if !matches!(resolved, Ty::Int | Ty::Float) && !resolved.is_open() {
+                    let message = Message::new(Code::InvalidOperand)
+                        .with_arg("op", op)
+                        .with_arg("ty", self.zonk(&ty));
+                    self.report(message, &expr.span);
+                }
+                ty
+            }
+            ExprKind::Binary { op, lhs, rhs } => self.binary(*op, lhs, rhs, &expr.span),
+            ExprKind::Cast { expr: operand, ty } => {
+                let to = self.declared(ty);
+                let from = self.infer(operand);
+                let resolved = self.resolve(&from);
+                let numeric = |ty: &Ty| matches!(ty, Ty::Int | Ty::Float);
+                let converts = numeric(&resolved) && numeric(&to);
+                if resolved.is_open() {
+                    if !numeric(&to) {
+                        let _ = self.unify(&from, &to);
+                    }
+                } else if !converts && self.unify(&from, &to).is_err() {
+                    let message = Message::new(Code::InvalidCast)
+                        .with_arg("from", self.zonk(&from))
+                        .with_arg("to", self.zonk(&to));
+                    self.report(message, &expr.span);
+                }
+                to
+            }
+            ExprKind::Ascription { expr, ty } => {
+                let ty = self.declared(ty);
+                self.expect(expr, &ty);
+                ty
+            }
+            ExprKind::If {
+                cond,
+                then_branch,
+                else_branch,
+            } => {
+                self.expect(cond, &Ty::Bool);
+                let ty = self.infer(then_branch);
+                self.expect(else_branch, &ty);
+                ty
+            }
+            ExprKind::List(elements) => {
+                let element = self.fresh();
+                for expr in elements {
+                    self.expect(expr, &element);
+                }
+                Ty::List(Box::new(element))
+            }
+            ExprKind::Tuple(elements) => {
+                Ty::Tuple(elements.iter().map(|expr| self.infer(expr)).collect())
+            }
+            ExprKind::Record(fields) => {
+                let names = fields
+                    .iter()
+                    .map(|field| field.name.name.as_str())
+                    .collect::<BTreeSet<_>>();
+                let records = self
+                    .records
+                    .iter()
+                    .filter(|(_, declared)| {
+                        declared.len() == names.len()
+                            && declared
+                                .iter()
+                                .all(|(name, _)| names.contains(name.as_str()))
+                    })
+                    .map(|(name, _)| name.clone())
+                    .collect::<Vec<_>>();
+                match records.as_slice() {
+                    [name] => self.record(expr, fields, name),
+                    _ => {
+                        for field in fields {
+                            self.infer(&field.value);
+                        }
+                        self.fresh()
+                    }
+                }
+            }
+            ExprKind::Field { expr, field } => self.field(expr, field),
+            ExprKind::Call { callee, args } => self.call(callee, args),
+            ExprKind::Match { scrutinee, arms } => {
+                let scrutinee = self.infer(scrutinee);
+                let ty = self.fresh();
+                for arm in arms {
+                    self.scopes.push(BTreeMap::new());
+                    self.pattern(&arm.pattern, &scrutinee);
+                    self.expect(&arm.body, &ty);
+                    self.scopes.pop();
+                }
+                ty
+            }
+        }
+    }
+
+    /// The record `name` built by the literal `expr`, whose fields must be the ones
+    /// declared
+    fn record(&mut self, expr: &Expr, fields: &[FieldInit], name: &str) -> Ty {
+        let declared = self.records.get(name).cloned().unwrap_or_default();
+        let field_error = |code: Code, field: &str| {
+            Message::new(code)
+                .with_arg("ty", name)
+                .with_arg("field", field)
+        };
+        for field in fields {
+            match declared.iter().find(|(name, _)| *name == field.name.name) {
+                Some((_, ty)) => self.expect(&field.value, ty),
+                None => {
+                    self.report(
+                        field_error(Code::UnknownField, &field.name.name),
+                        &field.name.span,
+                    );
+                    self.infer(&field.value);
+                }
+            }
+        }
+        for (field, _) in &declared {
+            if !fields.iter().any(|init| init.name.name == *field) {
+                self.report(field_error(Code::MissingField, field), &expr.span);
+            }
+        }
+        Ty::Named {
+            name: name.to_string(),
+            args: Vec::new(),
+        }
+    }
+
+    /// The type of `expr.field`. A value of an open type is the one record declaring
+    /// `field`, if there is a single one.
+    fn field(&mut self, expr: &Expr, field: &Ident) -> Ty {
+        let ty = self.infer(expr);
+        let name = match self.resolve(&ty) {
+            Ty::Named { name, .. } if self.records.contains_key(&name) => name,
+            Ty::Var(_) => {
+                let records = self
+                    .records
+                    .iter()
+                    .filter(|(_, fields)| fields.iter().any(|(name, _)| *name == field.name))
+                    .map(|(name, _)| name.clone())
+                    .collect::<Vec<_>>();
+                let [name] = records.as_slice() else {
+                    return self.fresh();
+                };
+                let record = Ty::Named {
+                    name: name.clone(),
+                    args: Vec::new(),
+                };
+                let _ = self.unify(&ty, &record);
+                name.clone()
+            }
+            Ty::Param(_) => return self.fresh(),
+            found => {
+                let message = Message::new(Code::TypeMismatch)
+                    .with_arg("expected", "record")
+                    .with_arg("found", self.zonk(&found));
+                self.report(message, &expr.span);
+                return self.fresh();
+            }
+        };
+        let fields = &self.records[&name];
+        match fields.iter().find(|(name, _)| *name == field.name) {
+            Some((_, ty)) => ty.clone(),
+            None => {
+                let message = Message::new(Code::UnknownField)
+                    .with_arg("ty", &name)
+                    .with_arg("field", &field.name);
+                self.report(message, &field.span);
+                self.fresh()
+            }
+        }
+    }
+
+    /// Keeps the hole `expr` of type `ty` to report once its type is known
+    fn hole(&mut self, expr: &Expr, ty: Ty) {
+        let ExprKind::Hole(name) = &expr.kind else {
+            return;
+        };
+        let scope = self.in_scope();
+        self.holes.push(Hole {
+            name: name.clone(),
+            ty,
+            span: expr.span.clone(),
+            scope,
+        });
+    }
+
+    /// Warns of the type `hole` stands for, with the names in scope of that type
+    fn report_hole(&mut self, hole: Hole) {
+        let ty = self.zonk(&hole.ty);
+        let candidates = hole
+            .scope
+            .iter()
+            .filter(|(_, candidate)| self.zonk(candidate) == ty)
+            .map(|(name, _)| format!("`{}`", name))
+            .collect::<Vec<_>>();
+        let message = Message::new(Code::HoleType)
+            .with_arg("hole", &hole.name)
+            .with_arg("ty", &ty);
+        let mut diagnostic = Diagnostic::warning(message, hole.span.location());
+        if !candidates.is_empty() {
+            diagnostic = diagnostic.with_note(format!(
+                "in scope with this type: {}",
+                candidates.join(", ")
+            ));
+        }
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// The type of `callee args...`, calls are curried: the arguments fill the parameters
+    /// in order, the ones left over are passed to the result
+    fn call(&mut self, callee: &Expr, args: &[Expr]) -> Ty {
+        let mut ty = self.infer(callee);
+        let mut args = args;
+        while !args.is_empty() {
+            match self.resolve(&ty) {
+                // A function without parameters takes `()`, e.g. `f ()`
+                Ty::Function { params, ret } if params.is_empty() => {
+                    self.expect(&args[0], &Ty::unit());
+                    ty = *ret;
+                    args = &args[1..];
+                }
+                Ty::Function { params, ret } => {
+                    let count = params.len().min(args.len());
+                    for (arg, param) in args.iter().zip(&params) {
+                        self.expect(arg, param);
+                    }
+                    ty = match params.len() > count {
+                        true => Ty::Function {
+                            params: params[count..].to_vec(),
+                            ret,
+                        },
+                        false => *ret,
+                    };
+                    args = &args[count..];
+                }
+                Ty::Var(_) => {
+                    let ret = self.fresh();
+                    let params = args.iter().map(|arg| self.infer(arg)).collect();
+                    let function = Ty::Function {
+                        params,
+                        ret: Box::new(ret.clone()),
+                    };
+                    // Fails when the callee is among its arguments, e.g. `f f`
+                    if self.unify(&ty, &function).is_err() {
+                        self.mismatch(&function, &ty, &callee.span);
+                    }
+                    return ret;
+                }
+                found => {
+                    let message = Message::new(Code::TypeMismatch)
+                        .with_arg("expected", "function")
+                        .with_arg("found", self.zonk(&found));
+                    self.report(message, &callee.span);
+                    for arg in args {
+                        self.infer(arg);
+                    }
+                    return self.fresh();
+                }
+            }
+        }
+        ty
+    }
+
+    fn binary(&mut self, op: BinaryOp, lhs: &Expr, rhs: &Expr, span: &Span) -> Ty {
+        use BinaryOp::*;
+        let (left, right) = (self.infer(lhs), self.infer(rhs));
+        // `:` puts an element in front of a list of its type, the other operators take two
+        // operands of the same type
+        let (same, result) = match op {
+            Cons => {
+                let list = Ty::List(Box::new(left.clone()));
+                (self.unify(&list, &right), list)
+            }
+            Eq | Lt | Le | Gt | Ge => (self.unify(&left, &right), Ty::Bool),
+            Add | Sub | Mul | Div | Concat => (self.unify(&left, &right), left.clone()),
+        };
+        let operand = self.resolve(&left);
+        let valid = operand.is_open()
+            || match op {
+                Add | Lt | Le | Gt | Ge => matches!(operand, Ty::Int | Ty::Float | Ty::Str),
+                Sub | Mul | Div => matches!(operand, Ty::Int | Ty::Float),
+                Concat => matches!(operand, Ty::List(_)),
+                // Functions can not be compared, like in the interpreter
+                Eq => !matches!(operand, Ty::Function { .. }),
+                Cons => true,
+            };
+        if same.is_err() || !valid {
+            let message = Message::new(Code::InvalidOperands)
+                .with_arg("op", op)
+                .with_arg("lhs", self.zonk(&left))
+                .with_arg("rhs", self.zonk(&right));
+            self.report(message, span);
+            // Reported once, the result can be anything
+            return self.fresh();
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::messages::Locale;
+    use crate::interpreter::Interpreter;
+    use crate::parser::lower::lower;
+    use crate::parser::snippet::parse_snippet;
+
+    /// The messages of the errors found in `content`
+    fn errors(content: &str) -> Vec<String> {
+        diagnostics(content)
+            .iter()
+            .filter(|diagnostic| diagnostic.is_error())
+            .map(|diagnostic| diagnostic.render(Locale::En))
+            .collect()
+    }
+
+    /// The messages of the warnings found in `content`, with their notes
+    fn warnings(content: &str) -> Vec<String> {
+        diagnostics(content)
+            .iter()
+            .filter(|diagnostic| !diagnostic.is_error())
+            .map(|diagnostic| {
+                let mut message = diagnostic.render(Locale::En);
+                for note in &diagnostic.notes {
+                    message = format!("{} ({})", message, note);
+                }
+                message
+            })
+            .collect()
+    }
+
+    fn diagnostics(content: &str) -> Vec<Diagnostic> {
+        let (file, errors) = lower(&parse_snippet(content).unwrap());
+        assert!(errors.is_empty(), "{:?}", errors);
+        check(&file).1
+    }
+
+    /// The type of the last statement of `content`, an expression
+    fn type_of(content: &str) -> String {
+        let (file, _) = lower(&parse_snippet(content).unwrap());
+        let (types, diagnostics) = check(&file);
+        let errors = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.is_error());
+        assert_eq!(errors.count(), 0, "{}: {:?}", content, diagnostics);
+        let Some(Stmt::Expr(expr)) = file.stmts.last() else {
+            panic!("{:?}", file.stmts.last());
+        };
+        types[&expr.id].to_string()
+    }
+
+    #[test]
+    fn test_typeck_infer() {
+        assert_eq!(type_of("1 + 2 * 3\n"), "int");
+        assert_eq!(type_of("1.5 / 2.0\n"), "float");
+        assert_eq!(type_of("\"a\" + \"b\"\n"), "str");
+        assert_eq!(type_of("1 < 2\n"), "bool");
+        assert_eq!(type_of("-1.5\n"), "float");
+        assert_eq!(type_of("2 as float\n"), "float");
+        assert_eq!(type_of("[1, 2]\n"), "[int]");
+        assert_eq!(type_of("[]\n"), "[_]");
+        assert_eq!(type_of("[[], [1]]\n"), "[[int]]");
+        assert_eq!(type_of("0 : [] ++ [1]\n"), "[int]");
+        assert_eq!(type_of("(1, (\"a\", [true]))\n"), "(int, (str, [bool]))");
+        assert_eq!(type_of("()\n"), "()");
+        assert_eq!(type_of("if true then [] else [1.5]\n"), "[float]");
+        assert_eq!(
+            type_of("match (1, \"a\") | (0, s) => s | _ => \"b\" ;\n"),
+            "str"
+        );
+        assert_eq!(type_of("xs: [str] = []\nxs\n"), "[str]");
+        assert_eq!(type_of("?todo\n"), "_");
+        assert_eq!(type_of("print 1\n"), "()");
+
+        // Calls are curried, a generic function takes the types of its arguments
+        let add = "add: (int, int) -> int = (a, b) -> a + b ;\n";
+        assert_eq!(type_of(&format!("{}add 1\n", add)), "(int) -> int");
+        assert_eq!(type_of(&format!("{}add 1 2\n", add)), "int");
+        let apply = "apply: ((T) -> U, T) -> U = (f, x) -> f x ;\n";
+        assert_eq!(
+            type_of(&format!("{}{}apply (add 1) 2\n", add, apply)),
+            "int"
+        );
+        let id = "id: (T) -> T = (x) -> x ;\n";
+        assert_eq!(type_of(&format!("{}(id 1, id \"a\")\n", id)), "(int, str)");
+
+        // Functions can be used before they are declared
+        assert_eq!(
+            type_of("twice 1\ntwice: (int) -> int = (x) -> x * 2 ;\ntwice 1\n"),
+            "int"
+        );
+
+        let color = "data Color = Red | Rgb int int int\n";
+        assert_eq!(type_of(&format!("{}Rgb 1 2\n", color)), "(int) -> Color");
+        assert_eq!(
+            type_of(&format!(
+                "{}match Red | Rgb r _ _ => r | Red => 0 ;\n",
+                color
+            )),
+            "int"
+        );
+    }
+
+    #[test]
+    fn test_typeck_mismatches() {
+        assert_eq!(
+            errors("x: int = \"a\"\n"),
+            vec!["expected `int`, found `str`"]
+        );
+        assert_eq!(
+            errors("x: [int] = [1, 2.0]\n"),
+            vec!["expected `int`, found `float`"]
+        );
+        assert_eq!(
+            errors("t: (int, str) = (1, 2)\n"),
+            vec!["expected `(int, str)`, found `(int, int)`"]
+        );
+        assert_eq!(
+            errors("x: int = if true then 1 else \"a\"\n"),
+            vec!["expected `int`, found `str`"]
+        );
+        assert_eq!(
+            errors("x: int = if 1 then 2 else 3\n"),
+            vec!["expected `bool`, found `int`"]
+        );
+        assert_eq!(
+            errors("1 + \"a\"\n"),
+            vec!["`+` can not be applied to `int` and `str`"]
+        );
+        assert_eq!(
+            errors("true * false\n"),
+            vec!["`*` can not be applied to `bool` and `bool`"]
+        );
+        assert_eq!(
+            errors("[1] ++ 2\n"),
+            vec!["`++` can not be applied to `[int]` and `int`"]
+        );
+        assert_eq!(
+            errors("1 : [\"a\"]\n"),
+            vec!["`:` can not be applied to `int` and `[str]`"]
+        );
+        assert_eq!(errors("-true\n"), vec!["`-` can not be applied to `bool`"]);
+        assert_eq!(
+            errors("\"1\" as int\n"),
+            vec!["`str` can not be cast to `int`"]
+        );
+        assert_eq!(
+            errors("x: int = 1\nx 2\n"),
+            vec!["expected `function`, found `int`"]
+        );
+        assert_eq!(
+            errors("f: (int) -> str = (x) -> x ;\n"),
+            vec!["expected `str`, found `int`"]
+        );
+        assert_eq!(
+            errors("f: (int, int) -> int = (x) -> x ;\n"),
+            vec!["expected `(int, int) -> int`, found `(_) -> _`"]
+        );
+        assert_eq!(
+            errors("f: (int) -> int = (x) -> x ;\ny: str = f 1\nz: int = f \"a\"\n"),
+            vec!["expected `str`, found `int`", "expected `int`, found `str`",]
+        );
+        assert_eq!(
+            errors("x: int = match (1, 2) | (a, b, c) => a ;\n"),
+            vec!["expected `(int, int)`, found `(_, _, _)`"]
+        );
+        // A generic name is fixed inside its declaration
+        assert_eq!(
+            errors("f: (T) -> T = (x) -> 1 ;\n"),
+            vec!["expected `T`, found `int`"]
+        );
+        assert_eq!(
+            errors("g: (U) -> U = (y) -> y 1 ;\n"),
+            vec!["expected `function`, found `U`"]
+        );
+        // A function without parameters takes `()`
+        assert_eq!(
+            errors("f: () -> int = () -> 1 ;\nx: int = f 2\n"),
+            vec!["expected `()`, found `int`"]
+        );
+        assert_eq!(
+            errors("f: (int) -> int = (x) -> x ;\nf == f\n"),
+            vec!["`==` can not be applied to `(int) -> int` and `(int) -> int`"]
+        );
+        // Literal patterns, constructor patterns and records follow their declarations
+        assert_eq!(
+            errors("x: str = match 2 | 2.5 => \"float\" | _ => \"other\" ;\n"),
+            vec!["expected `int`, found `float`"]
+        );
+        let color = "data Color = Red | Rgb int int int\n";
+        assert_eq!(
+            errors(&format!(
+                "{}x: int = match Red | Rgb r g => r | _ => 0 ;\n",
+                color
+            )),
+            vec!["the constructor `Rgb` has 3 field(s), the pattern has 2"]
+        );
+        let point = "data P = { x: int, y: int }\n";
+        assert_eq!(
+            errors(&format!("{}p: P = {{ x = 1 }}\n", point)),
+            vec!["`P` needs a value for its field `y`"]
+        );
+        assert_eq!(
+            errors(&format!("{}p: P = {{ x = 1, y = 2, z = 3 }}\n", point)),
+            vec!["`P` has no field `z`"]
+        );
+        assert_eq!(
+            errors(&format!("{}p: P = {{ x = 1, y = \"2\" }}\n", point)),
+            vec!["expected `int`, found `str`"]
+        );
+        assert_eq!(
+            errors(&format!(
+                "{}p: P = {{ x = 1, y = 2 }}\nz: int = p.z\n",
+                point
+            )),
+            vec!["`P` has no field `z`"]
+        );
+        assert_eq!(
+            errors(&format!("{}s: str = {{ x = 1, y = 2 }}.x\n", point)),
+            vec!["expected `str`, found `int`"]
+        );
+        assert_eq!(
+            errors("x: int = 1\ny: int = x.a\n"),
+            vec!["expected `record`, found `int`"]
+        );
+        // A type that can not be finite
+        assert_eq!(
+            errors("match ?t | f => f f ;\n"),
+            vec!["expected `(_) -> _`, found `_`"]
+        );
+    }
+
+    #[test]
+    fn test_typeck_accepts() {
+        for content in [
+            // A hole stands for a value of any type
+            "x: int = ?todo\nf: (int) -> str = ?todo\n",
+            "data P = { x: int }\np: P = { x = 1 }\ny: int = p.x\n",
+            "data P = { x: int }\nget: (T) -> int = (p) -> p.x ;\nz: int = { x = 1 }.x\n",
+            "x: str = match 2 | 2 => \"two\" | _ => \"other\" ;\n",
+            // Operators take generic operands, the interpreter checks their values
+            "add: (T, T) -> T = (a, b) -> a + b ;\n",
+            "(a, b): (int, str) = (1, \"b\")\nc: str = b + \"c\"\n",
+            "f: () -> int = () -> 1 ;\n",
+            "f: () -> int = () -> 1 ;\nx: int = f () + 1\n",
+            "@test\nt: () -> bool = () -> 1 + 1 == 2 ;\n",
+            "x: unit = print \"a\"\n",
+        ] {
+            assert_eq!(errors(content), Vec::<String>::new(), "{}", content);
+        }
+    }
+
+    #[test]
+    fn test_typeck_holes() {
+        assert_eq!(
+            warnings("a: int = 1\nb: str = \"b\"\nc: int = 2\nx: int = ?todo\n"),
+            vec!["`?todo` stands for a value of type `int` (in scope with this type: `a`, `c`)"]
+        );
+        assert_eq!(
+            warnings("f: (int) -> str = ?todo\n"),
+            vec!["`?todo` stands for a value of type `(int) -> str`"]
+        );
+        // The type is the one the rest of the file gives the hole
+        assert_eq!(
+            warnings("f: (int) -> int = (n) -> n + ?rest ;\n"),
+            vec!["`?rest` stands for a value of type `int` (in scope with this type: `n`)"]
+        );
+        assert_eq!(
+            warnings("xs: [str] = [?first]\n"),
+            vec!["`?first` stands for a value of type `str`"]
+        );
+    }
+
+    #[test]
+    fn test_typeck_agrees_with_interpreter() {
+        // The operators the checker accepts are the ones the interpreter can apply
+        for (content, valid) in [
+            ("\"a\" + \"b\"\n", true),
+            ("[1] ++ [2]\n", true),
+            ("(1, \"a\") == (1, \"a\")\n", true),
+            ("1 + \"a\"\n", false),
+            ("f: (int) -> int = (x) -> x ;\nf == f\n", false),
+        ] {
+            assert_eq!(errors(content).is_empty(), valid, "{}", content);
+            let (file, _) = lower(&parse_snippet(content).unwrap());
+            let result = Interpreter::new(Vec::new()).run(&file);
+            assert_eq!(result.is_ok(), valid, "{}", content);
+        }
+    }
+}
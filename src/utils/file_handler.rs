@@ -1,6 +1,225 @@
 use std::fs::File;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_EXTENSION: &str = "fs";
+const IGNORE_FILE: &str = ".funsignore";
+
+#[derive(Debug)]
+pub enum FileHandlerError {
+    ReadDir {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    ReadIgnoreFile {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+}
+
+impl std::fmt::Display for FileHandlerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FileHandlerError::ReadDir { path, error } => {
+                write!(
+                    f,
+                    "Error reading directory \"{}\": {}",
+                    path.display(),
+                    error
+                )
+            }
+            FileHandlerError::ReadIgnoreFile { path, error } => {
+                write!(
+                    f,
+                    "Error reading ignore file \"{}\": {}",
+                    path.display(),
+                    error
+                )
+            }
+        }
+    }
+}
+
+/// Walks directories looking for fs files.
+///
+/// By default a fs file is a file with the `.fs` extension; other extensions can be
+/// accepted with `with_extensions`.
+///
+/// The files are returned sorted by path, so the order does not depend on the file system.
+/// Exclude patterns are globs (`*`, `?` and `**`) matched against the path relative to the
+/// searched directory; a pattern without `/` is matched against the file or directory name
+/// only. Patterns are also read from a `.funsignore` file (one per line, `#` for comments)
+/// in the searched directory.
+///
+/// Symbolic links to directories are not followed unless `with_follow_symlinks(true)` is
+/// used, in which case each directory is visited at most once.
+#[derive(Debug, Clone)]
+pub struct FsFileFinder {
+    extensions: Vec<String>,
+    excludes: Vec<String>,
+    follow_symlinks: bool,
+}
+
+impl Default for FsFileFinder {
+    fn default() -> FsFileFinder {
+        FsFileFinder {
+            extensions: vec![DEFAULT_EXTENSION.to_string()],
+            excludes: Vec::new(),
+            follow_symlinks: false,
+        }
+    }
+}
+
+impl FsFileFinder {
+    pub fn new() -> FsFileFinder {
+        FsFileFinder::default()
+    }
+
+    /// Replaces the accepted extensions (given without the leading dot)
+    pub fn with_extensions(mut self, extensions: &[String]) -> FsFileFinder {
+        self.extensions = extensions.to_vec();
+        self
+    }
+
+    pub fn with_exclude(mut self, pattern: &str) -> FsFileFinder {
+        self.excludes.push(pattern.to_string());
+        self
+    }
+
+    pub fn with_follow_symlinks(mut self, follow_symlinks: bool) -> FsFileFinder {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    pub fn find(&self, root: &Path) -> Result<Vec<PathBuf>, FileHandlerError> {
+        let mut excludes = self.excludes.clone();
+        excludes.extend(read_ignore_file(&root.join(IGNORE_FILE))?);
+
+        let mut files = Vec::new();
+        let mut visited = Vec::new();
+        self.walk(root, root, &excludes, &mut visited, &mut files)?;
+        files.sort();
+        Ok(files)
+    }
+
+    fn walk(
+        &self,
+        root: &Path,
+        dir: &Path,
+        excludes: &[String],
+        visited: &mut Vec<PathBuf>,
+        files: &mut Vec<PathBuf>,
+    ) -> Result<(), FileHandlerError> {
+        if let Ok(canonical) = dir.canonicalize() {
+            if visited.contains(&canonical) {
+                return Ok(());
+            }
+            visited.push(canonical);
+        }
+
+        let read_dir_error = |error| FileHandlerError::ReadDir {
+            path: dir.to_path_buf(),
+            error,
+        };
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(dir).map_err(read_dir_error)? {
+            entries.push(entry.map_err(read_dir_error)?.path());
+        }
+        entries.sort();
+
+        for path in entries {
+            if is_excluded(root, &path, excludes) {
+                continue;
+            }
+            let is_symlink = path.symlink_metadata().is_ok_and(|m| m.is_symlink());
+            if path.is_dir() {
+                if !is_symlink || self.follow_symlinks {
+                    self.walk(root, &path, excludes, visited, files)?;
+                }
+            } else if self.is_fs_file(&path) {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    fn is_fs_file(&self, path: &Path) -> bool {
+        path.extension().is_some_and(|extension| {
+            self.extensions
+                .iter()
+                .any(|accepted| extension == accepted.as_str())
+        })
+    }
+}
+
+/// Collect all fs files in the given directory and in its subdirectories.
+pub fn find_fs_files(path: &Path) -> Result<Vec<PathBuf>, FileHandlerError> {
+    FsFileFinder::new().find(path)
+}
+
+fn read_ignore_file(path: &Path) -> Result<Vec<String>, FileHandlerError> {
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    let content =
+        std::fs::read_to_string(path).map_err(|error| FileHandlerError::ReadIgnoreFile {
+            path: path.to_path_buf(),
+            error,
+        })?;
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.trim_end_matches('/').to_string())
+        .collect())
+}
+
+fn is_excluded(root: &Path, path: &Path, excludes: &[String]) -> bool {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let relative = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    excludes.iter().any(|pattern| {
+        if pattern.contains('/') {
+            glob_match(pattern.trim_start_matches('/'), &relative)
+        } else {
+            glob_match(pattern, &name)
+        }
+    })
+}
+
+/// Matches `text` against a glob `pattern`.
+///
+/// `?` matches one character and `*` any sequence of characters, both except `/`,
+/// while `**` matches any sequence of characters including `/`.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    glob_match_from(&pattern, &text)
+}
+
+fn glob_match_from(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', rest @ ..] => {
+            let rest = rest.strip_prefix(&['/']).unwrap_or(rest);
+            (0..=text.len()).any(|i| glob_match_from(rest, &text[i..]))
+        }
+        ['*', rest @ ..] => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != '/')
+            .any(|i| glob_match_from(rest, &text[i..])),
+        ['?', rest @ ..] => {
+            matches!(text, [c, ..] if *c != '/') && glob_match_from(rest, &text[1..])
+        }
+        [c, rest @ ..] => matches!(text, [t, ..] if t == c) && glob_match_from(rest, &text[1..]),
+    }
+}
 
 #[cfg(test)]
 /// Collect all fs files in the given path.
@@ -17,18 +236,18 @@ pub fn collect_fs_files(path: &str, set_logger: bool) -> Vec<std::path::PathBuf>
             .map_err(|_err| eprintln!("Unable to set global default subscriber"));
     }
 
-    std::fs::read_dir(path)
-        .expect("Failed to read directory")
-        .filter_map(|entry| {
-            let path = entry.ok()?.path();
-            if let Some(extension) = path.extension() {
-                if extension == "fs" {
-                    return Some(path);
-                }
-            }
-            None
-        })
-        .collect()
+    find_fs_files(Path::new(path)).expect("Failed to read directory")
+}
+
+#[cfg(test)]
+/// Creates an empty directory for the test `name` in the temporary directory of the
+/// system. The id of the process is in its name, so that the tests of two runs at once
+/// do not share it; the test removes it once done.
+pub fn create_tmp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("funs_{}_{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("Failed to create directory");
+    dir
 }
 
 pub fn create_tmp_file(file_path: &str, content: &str) {
@@ -42,3 +261,102 @@ pub fn remove_tmp_file(file_path: &str) {
     let path = Path::new(file_path);
     std::fs::remove_file(path).expect("Failed to remove file");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch(dir: &Path, relative: &str) {
+        let path = dir.join(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create directory");
+        create_tmp_file(path.to_str().unwrap(), "");
+    }
+
+    #[test]
+    fn test_file_handler_glob_match() {
+        assert!(glob_match("*.fs", "main.fs"));
+        assert!(!glob_match("*.fs", "src/main.fs"));
+        assert!(glob_match("src/*.fs", "src/main.fs"));
+        assert!(glob_match("**/gen/*.fs", "a/b/gen/x.fs"));
+        assert!(glob_match("**/gen/*.fs", "gen/x.fs"));
+        assert!(glob_match("ma?n.fs", "main.fs"));
+        assert!(!glob_match("ma?n.fs", "maiin.fs"));
+        assert!(glob_match("target", "target"));
+    }
+
+    #[test]
+    fn test_file_handler_find_recursive_sorted() {
+        let dir = create_tmp_dir("file_handler_recursive");
+        touch(&dir, "b.fs");
+        touch(&dir, "a.fs");
+        touch(&dir, "notes.txt");
+        touch(&dir, "sub/c.fs");
+        touch(&dir, "sub/deeper/d.fs");
+
+        let files = FsFileFinder::new().find(&dir).unwrap();
+        let expected = ["a.fs", "b.fs", "sub/c.fs", "sub/deeper/d.fs"]
+            .iter()
+            .map(|relative| dir.join(relative))
+            .collect::<Vec<_>>();
+        assert_eq!(files, expected);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_handler_find_extensions() {
+        let dir = create_tmp_dir("file_handler_extensions");
+        touch(&dir, "a.fs");
+        touch(&dir, "b.funs");
+        touch(&dir, "c.txt");
+
+        let files = FsFileFinder::new()
+            .with_extensions(&["fs".to_string(), "funs".to_string()])
+            .find(&dir)
+            .unwrap();
+        assert_eq!(files, vec![dir.join("a.fs"), dir.join("b.funs")]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_handler_find_excludes() {
+        let dir = create_tmp_dir("file_handler_excludes");
+        touch(&dir, "main.fs");
+        touch(&dir, "generated/out.fs");
+        touch(&dir, "sub/skip_me.fs");
+        touch(&dir, "sub/keep.fs");
+        create_tmp_file(
+            dir.join(IGNORE_FILE).to_str().unwrap(),
+            "# generated code\ngenerated/\n",
+        );
+
+        let files = FsFileFinder::new()
+            .with_exclude("skip_*.fs")
+            .find(&dir)
+            .unwrap();
+        assert_eq!(files, vec![dir.join("main.fs"), dir.join("sub/keep.fs")]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_file_handler_find_symlink_cycle() {
+        let dir = create_tmp_dir("file_handler_symlinks");
+        touch(&dir, "sub/a.fs");
+        std::os::unix::fs::symlink(&dir, dir.join("sub/loop")).unwrap();
+
+        let files = FsFileFinder::new().find(&dir).unwrap();
+        assert_eq!(files, vec![dir.join("sub/a.fs")]);
+        let files = FsFileFinder::new()
+            .with_follow_symlinks(true)
+            .find(&dir)
+            .unwrap();
+        assert_eq!(files, vec![dir.join("sub/a.fs")]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_handler_find_missing_directory() {
+        let result = FsFileFinder::new().find(Path::new("./testdata/does_not_exist"));
+        assert!(matches!(result, Err(FileHandlerError::ReadDir { .. })));
+    }
+}
@@ -0,0 +1,5 @@
+# a file with comments only
+
+#| and a block
+   comment |#
+  # indented
@@ -0,0 +1,3 @@
+x: int = 1
+y: str = "ab"
+z: int = 2
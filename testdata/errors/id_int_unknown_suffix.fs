@@ -0,0 +1 @@
+_x_int: int = 42u8
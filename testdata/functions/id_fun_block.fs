@@ -0,0 +1,5 @@
+f_block: (int) -> int = (a) ->
+  b: int = a
+  # the result
+  b
+;
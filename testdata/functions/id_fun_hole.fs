@@ -0,0 +1 @@
+f_todo: (int) -> option<int> = ?todo
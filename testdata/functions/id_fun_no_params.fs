@@ -0,0 +1 @@
+answer: () -> int = () -> 42 ;
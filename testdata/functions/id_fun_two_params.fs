@@ -0,0 +1 @@
+first: (int, str) -> int = (a, b) -> a ;
@@ -0,0 +1,2 @@
+x: int = ?todo
+y: option<str> = ?name_2
@@ -0,0 +1,2 @@
+c: str = 'a'
+x'y
@@ -0,0 +1,3 @@
+# header
+first: int   =  1
+  second: str = "two"
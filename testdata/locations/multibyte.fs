@@ -0,0 +1,2 @@
+s: str = "👾 é"
+t: int = 2
@@ -0,0 +1 @@
+x: int = 1
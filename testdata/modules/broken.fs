@@ -0,0 +1,2 @@
+import missing
+import cycle.a
@@ -0,0 +1,8 @@
+import util
+data Shape = Circle float | Square float
+area: (Shape) -> float = (s) ->
+  match s
+  | Circle r => 3.0 * square r
+  | Square side => square side
+  ;
+;
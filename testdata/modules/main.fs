@@ -0,0 +1,4 @@
+import geometry.shapes
+import util
+s: Shape = Square 2.0
+print (area s as int)
@@ -0,0 +1 @@
+square: (float) -> float = (x) -> x * x ;
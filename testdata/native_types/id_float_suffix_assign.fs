@@ -0,0 +1,2 @@
+_x_float: float = 0.5f
+_y_float: float = 3_float
@@ -0,0 +1,2 @@
+_x_int: int = 42i
+_y_int: int = 1_int
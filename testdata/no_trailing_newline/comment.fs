@@ -0,0 +1,2 @@
+x: int = 1
+# the end
\ No newline at end of file
@@ -0,0 +1 @@
+x: int = 1; y: str = "a"
\ No newline at end of file
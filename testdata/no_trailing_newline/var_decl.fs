@@ -0,0 +1 @@
+x: int = 1
\ No newline at end of file
@@ -0,0 +1,8 @@
+a: bool = x == y
+b: int = x |> f
+c: int = x -> y => z
+d: [int] = xs ++ ys
+e: int = x-
+f: int = x+
+g: bool = x ===y
+h: int = x||>y
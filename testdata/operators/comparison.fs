@@ -0,0 +1,6 @@
+a: bool = x < y
+b: bool = x <= y
+c: bool = x > y
+d: bool = x >= y
+e: bool = x<-1
+f: option<int>= 1